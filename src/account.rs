@@ -0,0 +1,470 @@
+//! Account summary, position and alias types.
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::contract::Contract;
+use crate::wire::FieldIterator;
+
+/// Account summary tags that can be requested from TWS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountSummaryTag {
+    AccountType,
+    NetLiquidation,
+    TotalCashValue,
+    SettledCash,
+    AccruedCash,
+    BuyingPower,
+    EquityWithLoanValue,
+    PreviousEquityWithLoanValue,
+    GrossPositionValue,
+    RegTEquity,
+    RegTMargin,
+    Sma,
+    InitMarginReq,
+    MaintMarginReq,
+    AvailableFunds,
+    ExcessLiquidity,
+    Cushion,
+    FullInitMarginReq,
+    FullMaintMarginReq,
+    FullAvailableFunds,
+    FullExcessLiquidity,
+    LookAheadNextChange,
+    LookAheadInitMarginReq,
+    LookAheadMaintMarginReq,
+    LookAheadAvailableFunds,
+    LookAheadExcessLiquidity,
+    HighestSeverity,
+    DayTradesRemaining,
+    Leverage,
+}
+
+impl AccountSummaryTag {
+    /// Every tag TWS supports, in the order listed by the API documentation.
+    pub const ALL: &'static [Self] = &[
+        Self::AccountType,
+        Self::NetLiquidation,
+        Self::TotalCashValue,
+        Self::SettledCash,
+        Self::AccruedCash,
+        Self::BuyingPower,
+        Self::EquityWithLoanValue,
+        Self::PreviousEquityWithLoanValue,
+        Self::GrossPositionValue,
+        Self::RegTEquity,
+        Self::RegTMargin,
+        Self::Sma,
+        Self::InitMarginReq,
+        Self::MaintMarginReq,
+        Self::AvailableFunds,
+        Self::ExcessLiquidity,
+        Self::Cushion,
+        Self::FullInitMarginReq,
+        Self::FullMaintMarginReq,
+        Self::FullAvailableFunds,
+        Self::FullExcessLiquidity,
+        Self::LookAheadNextChange,
+        Self::LookAheadInitMarginReq,
+        Self::LookAheadMaintMarginReq,
+        Self::LookAheadAvailableFunds,
+        Self::LookAheadExcessLiquidity,
+        Self::HighestSeverity,
+        Self::DayTradesRemaining,
+        Self::Leverage,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccountType => "AccountType",
+            Self::NetLiquidation => "NetLiquidation",
+            Self::TotalCashValue => "TotalCashValue",
+            Self::SettledCash => "SettledCash",
+            Self::AccruedCash => "AccruedCash",
+            Self::BuyingPower => "BuyingPower",
+            Self::EquityWithLoanValue => "EquityWithLoanValue",
+            Self::PreviousEquityWithLoanValue => "PreviousEquityWithLoanValue",
+            Self::GrossPositionValue => "GrossPositionValue",
+            Self::RegTEquity => "RegTEquity",
+            Self::RegTMargin => "RegTMargin",
+            Self::Sma => "SMA",
+            Self::InitMarginReq => "InitMarginReq",
+            Self::MaintMarginReq => "MaintMarginReq",
+            Self::AvailableFunds => "AvailableFunds",
+            Self::ExcessLiquidity => "ExcessLiquidity",
+            Self::Cushion => "Cushion",
+            Self::FullInitMarginReq => "FullInitMarginReq",
+            Self::FullMaintMarginReq => "FullMaintMarginReq",
+            Self::FullAvailableFunds => "FullAvailableFunds",
+            Self::FullExcessLiquidity => "FullExcessLiquidity",
+            Self::LookAheadNextChange => "LookAheadNextChange",
+            Self::LookAheadInitMarginReq => "LookAheadInitMarginReq",
+            Self::LookAheadMaintMarginReq => "LookAheadMaintMarginReq",
+            Self::LookAheadAvailableFunds => "LookAheadAvailableFunds",
+            Self::LookAheadExcessLiquidity => "LookAheadExcessLiquidity",
+            Self::HighestSeverity => "HighestSeverity",
+            Self::DayTradesRemaining => "DayTradesRemaining",
+            Self::Leverage => "Leverage",
+        }
+    }
+
+    /// Join tags into the comma-separated list sent with the request.
+    pub fn join(tags: &[Self]) -> String {
+        tags.iter()
+            .map(|tag| tag.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl std::fmt::Display for AccountSummaryTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Account value update.
+#[derive(Debug, Clone)]
+pub struct AccountValue {
+    pub key: String,
+    pub value: String,
+    pub currency: String,
+    pub account: String,
+}
+
+impl AccountValue {
+    /// Parse a value from message fields (after the version).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            key: fields.next_string()?.to_string(),
+            value: fields.next_string().unwrap_or("").to_string(),
+            currency: fields.next_string().unwrap_or("").to_string(),
+            account: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// A single account summary value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountSummaryValue {
+    /// Account code (e.g., "U1234567")
+    pub account: String,
+    /// Tag name (e.g., "NetLiquidation")
+    pub tag: String,
+    /// Value as sent by TWS
+    pub value: String,
+    /// Currency of the value (empty for non-monetary tags)
+    pub currency: String,
+}
+
+impl AccountSummaryValue {
+    /// Parse a value from message fields (after the request ID).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            account: fields.next_string()?.to_string(),
+            tag: fields.next_string()?.to_string(),
+            value: fields.next_string()?.to_string(),
+            currency: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// Margin and buying power figures for one account.
+///
+/// Monetary values are in `currency`; values TWS didn't report are zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarginSummary {
+    pub account: String,
+    /// Account base currency
+    pub currency: String,
+    pub net_liquidation: f64,
+    pub buying_power: f64,
+    pub available_funds: f64,
+    pub init_margin_req: f64,
+    pub maint_margin_req: f64,
+    pub excess_liquidity: f64,
+    /// Gross position value divided by net liquidation
+    pub leverage: f64,
+    /// Excess liquidity as a fraction of net liquidation
+    pub cushion: f64,
+}
+
+impl MarginSummary {
+    /// Tags needed to build a summary.
+    pub const TAGS: &'static [AccountSummaryTag] = &[
+        AccountSummaryTag::NetLiquidation,
+        AccountSummaryTag::BuyingPower,
+        AccountSummaryTag::AvailableFunds,
+        AccountSummaryTag::InitMarginReq,
+        AccountSummaryTag::MaintMarginReq,
+        AccountSummaryTag::ExcessLiquidity,
+        AccountSummaryTag::Leverage,
+        AccountSummaryTag::Cushion,
+    ];
+
+    /// Build a summary for `account` from account summary values.
+    ///
+    /// Returns `None` if no value belongs to the account.
+    pub fn from_values(account: &str, values: &[AccountSummaryValue]) -> Option<Self> {
+        let mut summary = Self {
+            account: account.to_string(),
+            ..Default::default()
+        };
+        let mut found = false;
+
+        for value in values.iter().filter(|value| value.account == account) {
+            found = true;
+            let number = value.value.parse().unwrap_or(0.0);
+            // Account updates suffix segment values (e.g. "Leverage-S").
+            let field = match value.tag.trim_end_matches("-S") {
+                "NetLiquidation" => {
+                    summary.currency = value.currency.clone();
+                    &mut summary.net_liquidation
+                }
+                "BuyingPower" => &mut summary.buying_power,
+                "AvailableFunds" => &mut summary.available_funds,
+                "InitMarginReq" => &mut summary.init_margin_req,
+                "MaintMarginReq" => &mut summary.maint_margin_req,
+                "ExcessLiquidity" => &mut summary.excess_liquidity,
+                "Leverage" => &mut summary.leverage,
+                "Cushion" => &mut summary.cushion,
+                _ => continue,
+            };
+            *field = number;
+        }
+
+        found.then_some(summary)
+    }
+}
+
+/// A position held in an account.
+#[derive(Debug, Clone)]
+pub struct Position {
+    /// Account holding the position
+    pub account: String,
+    /// The instrument held
+    pub contract: Contract,
+    /// Number of shares/contracts (negative for short positions)
+    pub position: Decimal,
+    /// Average cost per unit, including the multiplier for derivatives
+    pub avg_cost: f64,
+}
+
+impl Position {
+    /// Parse a position from message fields (after the version).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let account = fields.next_string()?.to_string();
+        let contract = Contract::parse(fields)?;
+
+        Some(Self {
+            account,
+            contract,
+            position: fields.next_decimal(),
+            avg_cost: fields.next_f64(),
+        })
+    }
+}
+
+/// A portfolio entry from an account updates subscription.
+#[derive(Debug, Clone)]
+pub struct PortfolioValue {
+    /// The instrument held, with its primary exchange set
+    pub contract: Contract,
+    /// Number of shares/contracts (negative for short positions)
+    pub position: Decimal,
+    pub market_price: f64,
+    pub market_value: f64,
+    /// Average cost per unit, including the multiplier for derivatives
+    pub average_cost: f64,
+    pub unrealized_pnl: f64,
+    pub realized_pnl: f64,
+    /// Account holding the position
+    pub account: String,
+}
+
+impl PortfolioValue {
+    /// Parse a portfolio value from message fields (after the version).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let mut contract = Contract::parse(fields)?;
+        // Portfolio updates send the primary exchange in the exchange slot
+        contract.primary_exchange = std::mem::take(&mut contract.exchange);
+
+        Some(Self {
+            contract,
+            position: fields.next_decimal(),
+            market_price: fields.next_f64(),
+            market_value: fields.next_f64(),
+            average_cost: fields.next_f64(),
+            unrealized_pnl: fields.next_f64(),
+            realized_pnl: fields.next_f64(),
+            account: fields.next_string()?.to_string(),
+        })
+    }
+}
+
+/// Maps account codes to human-readable aliases and back.
+///
+/// Built from the FA "ALIASES" configuration; accounts without an alias
+/// (or every account, for non-FA logins) display as their code.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl AccountAliases {
+    /// Parse the XML returned for an FA aliases request.
+    pub fn from_xml(xml: &str) -> Self {
+        let mut aliases = HashMap::new();
+        for entry in xml.split("<AccountAlias>").skip(1) {
+            if let (Some(account), Some(alias)) = (xml_value(entry, "account"), xml_value(entry, "alias"))
+                && !account.is_empty()
+            {
+                aliases.insert(account.to_string(), alias.to_string());
+            }
+        }
+        Self { aliases }
+    }
+
+    /// Aliases for accounts that have none: every account maps to itself.
+    pub fn from_accounts(accounts: &[String]) -> Self {
+        Self {
+            aliases: accounts
+                .iter()
+                .map(|account| (account.clone(), account.clone()))
+                .collect(),
+        }
+    }
+
+    /// Alias for an account code, or the code itself if it has none.
+    pub fn alias<'a>(&'a self, account: &'a str) -> &'a str {
+        match self.aliases.get(account) {
+            Some(alias) if !alias.is_empty() => alias,
+            _ => account,
+        }
+    }
+
+    /// Account code for an alias (or for an account code).
+    pub fn account<'a>(&'a self, alias: &'a str) -> Option<&'a str> {
+        if self.aliases.contains_key(alias) {
+            return Some(alias);
+        }
+        self.aliases
+            .iter()
+            .find(|(_, a)| a.eq_ignore_ascii_case(alias))
+            .map(|(account, _)| account.as_str())
+    }
+
+    /// Every known account code.
+    pub fn accounts(&self) -> impl Iterator<Item = &str> {
+        self.aliases.keys().map(String::as_str)
+    }
+}
+
+/// Text of the first `<tag>...</tag>` element in `xml`.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_tags() {
+        let tags = [AccountSummaryTag::NetLiquidation, AccountSummaryTag::Sma];
+        assert_eq!(AccountSummaryTag::join(&tags), "NetLiquidation,SMA");
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let buf = b"U1234567\0NetLiquidation\x00100000.00\0USD\0";
+        let mut fields = FieldIterator::new(buf);
+        let value = AccountSummaryValue::parse(&mut fields).unwrap();
+
+        assert_eq!(value.account, "U1234567");
+        assert_eq!(value.tag, "NetLiquidation");
+        assert_eq!(value.value, "100000.00");
+        assert_eq!(value.currency, "USD");
+    }
+
+    #[test]
+    fn test_margin_summary_from_values() {
+        let value = |account: &str, tag: &str, value: &str| AccountSummaryValue {
+            account: account.to_string(),
+            tag: tag.to_string(),
+            value: value.to_string(),
+            currency: "USD".to_string(),
+        };
+        let values = [
+            value("DU1", "NetLiquidation", "100000.50"),
+            value("DU1", "BuyingPower", "400000"),
+            value("DU1", "Leverage-S", "1.25"),
+            value("DU1", "AccountType", "INDIVIDUAL"),
+            value("DU2", "NetLiquidation", "5"),
+        ];
+
+        let summary = MarginSummary::from_values("DU1", &values).unwrap();
+        assert_eq!(summary.net_liquidation, 100000.5);
+        assert_eq!(summary.buying_power, 400000.0);
+        assert_eq!(summary.leverage, 1.25);
+        assert_eq!(summary.currency, "USD");
+        assert!(MarginSummary::from_values("DU3", &values).is_none());
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let buf = b"DU123\x00265598\0AAPL\0STK\0\x000\0\0\0NASDAQ\0USD\0AAPL\0NMS\x00100\x00150.25\0";
+        let mut fields = FieldIterator::new(buf);
+        let position = Position::parse(&mut fields).unwrap();
+
+        assert_eq!(position.account, "DU123");
+        assert_eq!(position.contract.con_id, 265598);
+        assert_eq!(position.contract.symbol, "AAPL");
+        assert_eq!(position.contract.exchange, "NASDAQ");
+        assert_eq!(position.contract.trading_class, "NMS");
+        assert_eq!(position.position, Decimal::from(100));
+        assert_eq!(position.avg_cost, 150.25);
+    }
+
+    #[test]
+    fn test_parse_portfolio_value() {
+        let raw = [
+            "8", "ESZ4", "FUT", "20241220", "0", "", "50", "CME", "USD", "ESZ4", "ES", "-2",
+            "5910.25", "-591025", "295400.5", "-224.5", "0", "DU123",
+        ];
+        let buf = raw.join("\0") + "\0";
+        let mut fields = FieldIterator::new(buf.as_bytes());
+        let value = PortfolioValue::parse(&mut fields).unwrap();
+
+        assert_eq!(value.contract.con_id, 8);
+        assert_eq!(value.contract.multiplier, "50");
+        assert_eq!(value.contract.primary_exchange, "CME");
+        assert!(value.contract.exchange.is_empty());
+        assert_eq!(value.position, Decimal::from(-2));
+        assert_eq!(value.market_value, -591025.0);
+        assert_eq!(value.unrealized_pnl, -224.5);
+        assert_eq!(value.account, "DU123");
+    }
+
+    #[test]
+    fn test_account_aliases() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListOfAccountAliases>
+    <AccountAlias><account>U111</account><alias>Main</alias></AccountAlias>
+    <AccountAlias><account>U222</account><alias></alias></AccountAlias>
+</ListOfAccountAliases>"#;
+        let aliases = AccountAliases::from_xml(xml);
+
+        assert_eq!(aliases.alias("U111"), "Main");
+        assert_eq!(aliases.alias("U222"), "U222");
+        assert_eq!(aliases.alias("U333"), "U333");
+        assert_eq!(aliases.account("main"), Some("U111"));
+        assert_eq!(aliases.account("U222"), Some("U222"));
+        assert_eq!(aliases.account("Other"), None);
+    }
+}
@@ -1,51 +1,354 @@
 //! Async client for TWS/IB Gateway.
 
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
 use std::time::Duration;
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, WriteHalf};
 use tokio::net::TcpStream;
-use tokio::sync::{oneshot, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 use tokio::time::timeout;
 
-use crate::contract::Contract;
-use crate::error::{Error, Result};
-use crate::historical::{BarData, BarSize, Duration as HistDuration, HistoricalDataRequest, WhatToShow};
-use crate::message::{IncomingMessageId, OutgoingMessageId};
-use crate::wire::{make_field, FieldIterator};
-
-/// Account value update.
-#[derive(Debug, Clone)]
-pub struct AccountValue {
-    pub key: String,
-    pub value: String,
-    pub currency: String,
-    pub account: String,
+use rust_decimal::Decimal;
+
+use crate::account::{
+    AccountAliases, AccountSummaryTag, AccountSummaryValue, MarginSummary, Position,
+};
+use crate::codec::{self, FrameReader, Incoming};
+use crate::conid_cache::ConIdCache;
+use crate::contract::{Contract, SecurityType};
+use crate::contract_details::ContractDetails;
+use crate::datetime::DateTime;
+use crate::environment::Environment;
+use crate::error::{Error, RequestContext, Result};
+use crate::event::Event;
+use crate::execution::{ExecutionFilter, Fill, FillEvent};
+use crate::futures;
+use crate::fx::{idealpro_contract, FxRate};
+use crate::historical::{
+    bar_in_range, split_range, BarData, BarSize, DateFormat, Duration as HistDuration,
+    BackfillProgress, HistoricalDataRequest, HistoricalDownloader, WhatToShow,
+};
+use crate::historical_ticks::{
+    append_page, HistoricalTickData, HistoricalTicks, HistoricalTicksRequest,
+    MAX_TICKS_PER_REQUEST,
+};
+use crate::market_data::{
+    GenericTick, MarketDataRequest, MarketDataType, Quote, SmartComponent, Tick, TickAttrib,
+};
+use crate::market_depth::{DepthExchange, DepthUpdate};
+use crate::market_rule::MarketRule;
+use crate::option_chain::{OptionChainRow, OptionParams};
+use crate::order::{
+    ExerciseAction, MarginImpact, OcaType, OpenOrder, Order, OrderAction, OrderBuilder,
+    OrderDefaults, OrderEvent, OrderStatus, OrderType,
+};
+use crate::pnl::PnlSingle;
+use crate::scanner::{ScannerData, ScannerRow, ScannerSubscription};
+use crate::server_versions;
+use crate::subscription::{
+    Fills, OrderTracker, SharedFeed, SharedTicks, StreamMessage, Subscription,
+};
+
+pub use crate::account::AccountValue;
+pub use crate::codec::{ConnectOptions, ConnectionInfo};
+pub use crate::event::UnroutableError;
+pub use crate::historical::HistoricalDataResponse;
+
+/// TWS error code for an unknown contract.
+const NO_SECURITY_DEFINITION: i32 = 200;
+
+/// TWS error code for request validation errors, including FA requests by non-FA logins.
+const VALIDATION_ERROR: i32 = 321;
+
+/// FA data type requesting account aliases.
+const FA_ALIASES: i32 = 3;
+
+/// TWS error code for historical data service errors, including pacing violations.
+const HISTORICAL_DATA_SERVICE_ERROR: i32 = 162;
+
+/// How long to back off after a historical data pacing violation.
+const PACING_BACKOFF: Duration = Duration::from_secs(15);
+
+/// Pacing violations tolerated per page before giving up.
+const MAX_PACING_RETRIES: u32 = 4;
+
+/// TWS error code for a request without the required market data subscription.
+const NOT_SUBSCRIBED: i32 = 354;
+
+/// TWS error code for market data blocked by a competing live session.
+const COMPETING_LIVE_SESSION: i32 = 10197;
+
+/// TWS notices sent against a market data request that don't end it
+/// (partially subscribed data, delayed data substituted for live).
+const MARKET_DATA_NOTICES: [i32; 2] = [10090, 10167];
+
+/// TWS error code for an order warning (e.g. held until the market opens).
+const ORDER_WARNING: i32 = 399;
+
+/// TWS error code confirming an order was cancelled.
+const ORDER_CANCELLED: i32 = 202;
+
+/// TWS error code for an order ID that was already used.
+const DUPLICATE_ORDER_ID: i32 = 103;
+
+/// How long to wait for a snapshot; TWS gives up on missing ticks after 11s.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Snapshots kept in flight by `quote_many`, leaving market data lines
+/// (100 by default) for other requests.
+const SNAPSHOT_LINES: usize = 50;
+
+/// Ticks buffered per shared market data request before slow consumers
+/// start missing them.
+const SHARED_TICK_CAPACITY: usize = 1024;
+
+/// Minimum spacing between requests to stay under TWS's 50 messages/second.
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// First request ID. TWS reports errors for requests and orders by the
+/// same ID field, so request IDs start far above any order ID and order
+/// IDs are never allocated from here on.
+const FIRST_REQ_ID: i32 = 1_000_000_000;
+
+/// Whether a request failed for breaking the historical data pacing rules.
+///
+/// Error 162 also covers queries that returned no data, so the message is
+/// checked too.
+fn is_pacing_violation(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Tws { code: HISTORICAL_DATA_SERVICE_ERROR, message, .. }
+            if message.to_lowercase().contains("pacing")
+    )
+}
+
+/// Kind of request awaiting a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestKind {
+    AccountValues,
+    HistoricalData,
+    BarUpdates,
+    HeadTimestamp,
+    HistoricalTicks,
+    CurrentTime,
+    NextValidId,
+    AccountSummary,
+    AccountAliases,
+    Positions,
+    OpenOrders,
+    Executions,
+    PnlSingle,
+    ContractDetails,
+    OptionParams,
+    ScannerParameters,
+    Scanner,
+    MarketData,
+    MarketDepth,
+    MarketDepthExchanges,
+    MarketRule,
+    SmartComponents,
+}
+
+impl RequestKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccountValues => "account_values",
+            Self::HistoricalData => "historical_data",
+            Self::BarUpdates => "bar_updates",
+            Self::HeadTimestamp => "head_timestamp",
+            Self::HistoricalTicks => "historical_ticks",
+            Self::CurrentTime => "current_time",
+            Self::NextValidId => "next_valid_id",
+            Self::AccountSummary => "account_summary",
+            Self::AccountAliases => "account_aliases",
+            Self::Positions => "positions",
+            Self::OpenOrders => "open_orders",
+            Self::Executions => "executions",
+            Self::PnlSingle => "pnl_single",
+            Self::ContractDetails => "contract_details",
+            Self::OptionParams => "option_params",
+            Self::ScannerParameters => "scanner_parameters",
+            Self::Scanner => "scanner",
+            Self::MarketData => "market_data",
+            Self::MarketDepth => "market_depth",
+            Self::MarketDepthExchanges => "market_depth_exchanges",
+            Self::MarketRule => "market_rule",
+            Self::SmartComponents => "smart_components",
+        }
+    }
+}
+
+/// What was requested, kept for routing and diagnostics.
+struct RequestInfo {
+    kind: RequestKind,
+    /// Human-readable request parameters
+    params: String,
+    /// Encoded request, kept only when payload capture is enabled
+    payload: Option<String>,
+}
+
+impl RequestInfo {
+    fn new(kind: RequestKind, params: String) -> Self {
+        Self {
+            kind,
+            params,
+            payload: None,
+        }
+    }
+
+    fn context(&self, req_id: i32) -> RequestContext {
+        RequestContext {
+            req_id,
+            kind: self.kind.as_str(),
+            params: self.params.clone(),
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+/// A request waiting for its response.
+struct PendingRequest {
+    info: RequestInfo,
+    tx: oneshot::Sender<ResponseMessage>,
+}
+
+/// A subscription receiving streamed messages.
+struct ActiveSubscription {
+    info: RequestInfo,
+    tx: mpsc::UnboundedSender<StreamMessage>,
+    /// Re-sends a market data request for delayed data when live data is
+    /// refused, keeping the route open
+    delayed_fallback: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+/// Where status updates for an order placed on this connection go.
+struct TrackedOrder {
+    latest: watch::Sender<Option<OrderStatus>>,
+    updates: mpsc::UnboundedSender<OrderStatus>,
+    rejection: oneshot::Sender<Error>,
+}
+
+/// A broadcast channel that closes with the connection, so receivers see
+/// `RecvError::Closed` instead of waiting forever.
+struct ConnectionBroadcast<T> {
+    sender: std::sync::Mutex<Option<broadcast::Sender<T>>>,
+}
+
+impl<T: Clone> ConnectionBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            sender: std::sync::Mutex::new(Some(broadcast::channel(capacity).0)),
+        }
+    }
+
+    fn send(&self, value: T) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            let _ = sender.send(value);
+        }
+    }
+
+    /// A receiver for values sent from now on; already closed once the
+    /// connection is gone.
+    fn subscribe(&self) -> broadcast::Receiver<T> {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.subscribe(),
+            None => broadcast::channel(1).1,
+        }
+    }
+
+    fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
 }
 
-/// Historical data response.
-#[derive(Debug, Clone)]
-pub struct HistoricalDataResponse {
-    pub start: String,
-    pub end: String,
-    pub bars: Vec<BarData>,
+/// State shared between the client and its reader task.
+struct Shared {
+    pending: Mutex<HashMap<i32, PendingRequest>>,
+    subscriptions: Mutex<HashMap<i32, ActiveSubscription>>,
+    unroutable_errors: broadcast::Sender<UnroutableError>,
+    events: broadcast::Sender<Event>,
+    fills: ConnectionBroadcast<FillEvent>,
+    order_events: ConnectionBroadcast<OrderEvent>,
+    /// Live fills waiting for their commission report, by execution ID
+    uncommissioned: std::sync::Mutex<HashMap<String, Fill>>,
+    /// Ticks of historical ticks responses split over several messages,
+    /// until the last one arrives
+    partial_ticks: std::sync::Mutex<HashMap<i32, HistoricalTicks>>,
+    /// Request ID of the executions query each queried fill belongs to, until
+    /// its commission report arrives or the query ends
+    queried_executions: std::sync::Mutex<HashMap<String, i32>>,
+    connected: AtomicBool,
+    client_id: i32,
+    server_version: u32,
+    /// Connection time TWS sent with its server version
+    connection_time: String,
+    /// Whether TWS paces requests for this session (PACEAPI)
+    pace_api: bool,
+    /// Accounts from the most recent MANAGED_ACCTS message
+    managed_accounts: std::sync::Mutex<Vec<String>>,
+    /// First NEXT_VALID_ID of the session
+    initial_order_id: OnceLock<i32>,
+    /// Next order ID to use; 0 until TWS reports one
+    next_order_id: AtomicI32,
+    /// Orders sent on this connection, as last sent, by order ID
+    orders: std::sync::Mutex<HashMap<i32, (Contract, Order)>>,
+    /// IDs of orders placed on this connection or reported open for this
+    /// client, which can't be placed again
+    used_order_ids: std::sync::Mutex<HashSet<i32>>,
+    /// Status routes for orders placed on this connection, until they're done
+    tracked_orders: std::sync::Mutex<HashMap<i32, TrackedOrder>>,
+    /// Shared market data requests, by contract and generic tick list
+    market_data_feeds: Mutex<HashMap<String, Weak<SharedFeed>>>,
+    /// Contract IDs resolved by `Client::resolve_conid`
+    con_ids: ConIdCache,
 }
 
 /// Internal message for request/response correlation.
 enum ResponseMessage {
     AccountValues(Vec<AccountValue>),
     HistoricalData(HistoricalDataResponse),
-    Error { code: i32, message: String },
+    HeadTimestamp(String),
+    HistoricalTicks(HistoricalTicks),
+    CurrentTime(i64),
+    NextValidId(i32),
+    FaConfiguration(String),
+    ScannerParameters(String),
+    MarketDepthExchanges(Vec<DepthExchange>),
+    MarketRule(MarketRule),
+    SmartComponents(HashMap<i32, SmartComponent>),
+    Error {
+        code: i32,
+        message: String,
+        context: Box<RequestContext>,
+    },
 }
 
+/// A byte stream the client can talk to TWS over.
+///
+/// Implemented for every `AsyncRead + AsyncWrite` stream, e.g. `TcpStream`,
+/// `UnixStream`, TLS wrappers or `tokio::io::DuplexStream` in tests.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static> Transport for T {}
+
 /// Async client for Interactive Brokers TWS/Gateway.
-pub struct Client {
-    writer: Arc<Mutex<tokio::io::WriteHalf<TcpStream>>>,
-    pending: Arc<Mutex<HashMap<i32, oneshot::Sender<ResponseMessage>>>>,
+///
+/// Generic over the [`Transport`] it runs on; `Client::connect` uses TCP.
+pub struct Client<S: Transport = TcpStream> {
+    writer: Arc<Mutex<WriteHalf<S>>>,
+    shared: Arc<Shared>,
+    capture_payloads: AtomicBool,
+    /// Whether `market_data` streams switch to delayed data when live data
+    /// is refused
+    delayed_fallback: AtomicBool,
+    /// Defaults for builders from `Client::order_builder`
+    order_defaults: std::sync::Mutex<OrderDefaults>,
     next_req_id: AtomicI32,
-    server_version: u32,
+    /// Remote address, when connected over TCP
+    peer: Option<SocketAddr>,
     #[allow(dead_code)]
     reader_handle: tokio::task::JoinHandle<()>,
 }
@@ -55,267 +358,3498 @@ impl Client {
     ///
     /// # Arguments
     /// * `addr` - Address to connect to (e.g., "127.0.0.1:7496" for TWS, "127.0.0.1:4002" for Gateway)
-    /// * `client_id` - Unique client identifier (use different IDs for multiple connections)
-    pub async fn connect(addr: &str, client_id: i32) -> Result<Self> {
+    /// * `options` - Client ID, or [`ConnectOptions`] for optional capabilities
+    pub async fn connect(addr: &str, options: impl Into<ConnectOptions>) -> Result<Self> {
         let stream = TcpStream::connect(addr).await?;
-        let (mut reader, mut writer) = tokio::io::split(stream);
+        let peer = stream.peer_addr()?;
+        let mut client = Self::connect_with(stream, options).await?;
+        client.peer = Some(peer);
+        Ok(client)
+    }
+}
 
-        // Send handshake: "API\0" + length-prefixed version string
-        let version_str = b"v100..176";
-        let mut handshake = b"API\0".to_vec();
-        handshake.extend((version_str.len() as u32).to_be_bytes());
-        handshake.extend(version_str);
-        writer.write_all(&handshake).await?;
+impl<S: Transport> Client<S> {
+    /// Connect over an already established stream.
+    ///
+    /// Use this for SSH tunnels, Unix sockets, TLS wrappers, or in-memory
+    /// pipes in tests. The handshake is the same as for [`Client::connect`].
+    pub async fn connect_with(stream: S, options: impl Into<ConnectOptions>) -> Result<Self> {
+        let options = options.into();
+        let (mut reader, mut writer) = tokio::io::split(stream);
 
-        // Read server version response
-        let mut buf = [0u8; 4096];
-        let n = reader.read(&mut buf).await?;
-        if n < 4 {
-            return Err(Error::Protocol("Invalid handshake response".into()));
-        }
+        writer.write_all(&codec::handshake()).await?;
 
-        let fields: Vec<&str> = std::str::from_utf8(&buf[4..n])
-            .map_err(|_| Error::Protocol("Invalid UTF-8 in handshake".into()))?
-            .split('\0')
-            .filter(|s| !s.is_empty())
-            .collect();
+        // Read the server version response. Only this frame is consumed;
+        // anything after it is left for the reader task.
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len).await?;
+        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut buf).await?;
+        let (server_version, connection_time) = codec::parse_handshake(&buf)?;
 
-        let server_version: u32 = fields
-            .first()
-            .and_then(|s| s.parse().ok())
-            .ok_or_else(|| Error::Protocol("Failed to parse server version".into()))?;
-
-        // Send START_API
-        let start_api = format!(
-            "{}{}{}{}",
-            make_field(OutgoingMessageId::StartApi.as_u32()),
-            make_field(2),
-            make_field(client_id),
-            make_field(""),
-        );
-        Self::send_raw(&mut writer, &start_api).await?;
+        Self::send_raw(&mut writer, &codec::start_api(&options)).await?;
 
         // Wait briefly for initial messages
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         let writer = Arc::new(Mutex::new(writer));
-        let pending: Arc<Mutex<HashMap<i32, oneshot::Sender<ResponseMessage>>>> =
-            Arc::new(Mutex::new(HashMap::new()));
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(HashMap::new()),
+            unroutable_errors: broadcast::channel(64).0,
+            events: broadcast::channel(256).0,
+            fills: ConnectionBroadcast::new(256),
+            order_events: ConnectionBroadcast::new(256),
+            uncommissioned: std::sync::Mutex::new(HashMap::new()),
+            partial_ticks: std::sync::Mutex::new(HashMap::new()),
+            queried_executions: std::sync::Mutex::new(HashMap::new()),
+            connected: AtomicBool::new(true),
+            client_id: options.client_id,
+            server_version,
+            connection_time,
+            pace_api: options.pace_api,
+            managed_accounts: std::sync::Mutex::new(Vec::new()),
+            initial_order_id: OnceLock::new(),
+            next_order_id: AtomicI32::new(0),
+            orders: std::sync::Mutex::new(HashMap::new()),
+            tracked_orders: std::sync::Mutex::new(HashMap::new()),
+            used_order_ids: std::sync::Mutex::new(HashSet::new()),
+            market_data_feeds: Mutex::new(HashMap::new()),
+            con_ids: ConIdCache::new(),
+        });
 
         // Spawn reader task
-        let pending_clone = pending.clone();
+        let reader_shared = shared.clone();
         let reader_handle = tokio::spawn(async move {
-            let mut recv_buf = Vec::new();
+            let mut frames = FrameReader::new();
             let mut buf = [0u8; 8192];
 
             loop {
                 match reader.read(&mut buf).await {
                     Ok(0) => break,
                     Ok(n) => {
-                        recv_buf.extend_from_slice(&buf[..n]);
-                        while let Some((msg, rest)) = Self::extract_message(&recv_buf) {
-                            Self::dispatch_message(&msg, &pending_clone).await;
-                            recv_buf = rest;
+                        frames.push(&buf[..n]);
+                        while let Some(frame) = frames.next_frame() {
+                            Self::dispatch_message(&frame, &reader_shared).await;
                         }
                     }
                     Err(_) => break,
                 }
             }
+
+            // Connection is gone: fail sends fast and release every waiter.
+            // Dropping the senders surfaces as Error::NotConnected.
+            reader_shared.connected.store(false, Ordering::SeqCst);
+            let failed = reader_shared.pending.lock().await.drain().count();
+            let ended = reader_shared.subscriptions.lock().await.drain().count();
+            reader_shared.fills.close();
+            reader_shared.order_events.close();
+            let orders: Vec<_> = reader_shared.tracked_orders.lock().unwrap().drain().collect();
+            let orphaned = orders.len();
+            for (_, tracked) in orders {
+                let _ = tracked.rejection.send(Error::NotConnected);
+            }
+            tracing::debug!(failed, ended, orphaned, "connection closed, failing pending requests");
         });
 
         Ok(Self {
             writer,
-            pending,
-            next_req_id: AtomicI32::new(1000),
-            server_version,
+            shared,
+            capture_payloads: AtomicBool::new(false),
+            delayed_fallback: AtomicBool::new(false),
+            order_defaults: std::sync::Mutex::new(OrderDefaults::default()),
+            next_req_id: AtomicI32::new(FIRST_REQ_ID),
+            peer: None,
             reader_handle,
         })
     }
 
     /// Get the TWS/Gateway server version.
     pub fn server_version(&self) -> u32 {
-        self.server_version
+        self.shared.server_version
+    }
+
+    /// What TWS negotiated and reported when this session started.
+    ///
+    /// `next_valid_id` is `None` until TWS sends it, normally right after
+    /// the handshake.
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            client_id: self.shared.client_id,
+            server_version: self.shared.server_version,
+            connection_time: self.shared.connection_time.clone(),
+            managed_accounts: self.managed_accounts(),
+            next_valid_id: self.shared.initial_order_id.get().copied(),
+            endpoint: self.peer,
+        }
+    }
+
+    /// Whether the session was started with the PACEAPI capability.
+    ///
+    /// When true, TWS queues requests that exceed its rate limits instead of
+    /// rejecting them, so callers can skip their own throttling.
+    pub fn pace_api(&self) -> bool {
+        self.shared.pace_api
+    }
+
+    /// Check whether the connection to TWS is still open.
+    ///
+    /// Becomes `false` as soon as the reader task sees the socket close.
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    /// Accounts accessible by this login, as reported on connect.
+    pub fn managed_accounts(&self) -> Vec<String> {
+        self.shared.managed_accounts.lock().unwrap().clone()
+    }
+
+    /// Whether this is a paper or live session.
+    ///
+    /// Based on the managed account codes once TWS has sent them, falling
+    /// back to the default paper/live ports. Callers guarding live order
+    /// flow should treat `Environment::Unknown` as live.
+    pub fn environment(&self) -> Environment {
+        Environment::detect(&self.managed_accounts(), self.peer.map(|peer| peer.port()))
+    }
+
+    /// Request the current TWS server time (seconds since the Unix epoch).
+    pub async fn current_time(&self) -> Result<i64> {
+        self.current_time_within(Duration::from_secs(10)).await
+    }
+
+    /// Verify the session with a lightweight server time round trip.
+    ///
+    /// Returns the round-trip time, or `Error::Timeout` if TWS doesn't answer
+    /// within `deadline`. Useful for supervisors and health checks.
+    pub async fn ping(&self, deadline: Duration) -> Result<Duration> {
+        let started = tokio::time::Instant::now();
+        self.current_time_within(deadline).await?;
+        Ok(started.elapsed())
+    }
+
+    async fn current_time_within(&self, limit: Duration) -> Result<i64> {
+        let (req_id, rx) = self
+            .register(RequestKind::CurrentTime, String::new())
+            .await;
+
+        self.send_request(req_id, &codec::req_current_time()).await?;
+
+        match self.wait_response(req_id, rx, limit).await? {
+            ResponseMessage::CurrentTime(time) => Ok(time),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Subscribe to TWS errors that could not be routed to a pending request.
+    ///
+    /// Only errors received after subscribing are delivered.
+    pub fn unroutable_errors(&self) -> broadcast::Receiver<UnroutableError> {
+        self.shared.unroutable_errors.subscribe()
+    }
+
+    /// Subscribe to messages not claimed by any request or subscription.
+    ///
+    /// Covers connection notices, news bulletins, order binds and anything
+    /// the crate doesn't decode yet. Only events received after subscribing
+    /// are delivered; a receiver that falls behind skips the oldest events.
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.shared.events.subscribe()
+    }
+
+    /// Subscribe to order lifecycle messages (open orders and status updates).
+    ///
+    /// Covers every order TWS reports to this client, including answers
+    /// to open order queries. Only events received after subscribing are
+    /// delivered; a receiver that falls behind skips the oldest events.
+    /// The receiver closes once the connection does.
+    pub fn order_events(&self) -> broadcast::Receiver<OrderEvent> {
+        self.shared.order_events.subscribe()
+    }
+
+    /// Stream fills on this connection as they happen.
+    ///
+    /// Each execution is delivered immediately as [`FillEvent::Executed`],
+    /// and again as [`FillEvent::Commissioned`] once TWS reports its
+    /// commission. Use the filter to narrow by account or client ID.
+    pub fn fills(&self, filter: ExecutionFilter) -> Fills {
+        Fills::new(self.shared.fills.subscribe(), filter)
+    }
+
+    /// Switch the market data type for subsequent requests.
+    ///
+    /// Typically used with [`MarketDataType::Delayed`] after an
+    /// [`Event::MarketDataUnavailable`], then resubscribing.
+    pub async fn set_market_data_type(&self, data_type: MarketDataType) -> Result<()> {
+        self.send(&codec::req_market_data_type(data_type)).await
+    }
+
+    /// Keep [`Client::market_data`] streams alive on delayed data when live
+    /// data is refused.
+    ///
+    /// Off by default. When enabled, a stream failing with error 354 (not
+    /// subscribed) or 10197 (competing live session) switches the session to
+    /// [`MarketDataType::Delayed`] and re-sends its request under the same
+    /// ID, instead of ending. Delayed data is still live wherever the
+    /// account is subscribed. [`Event::MarketDataUnavailable`] is published
+    /// either way.
+    pub fn fall_back_to_delayed(&self, enabled: bool) {
+        self.delayed_fallback.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Record encoded request payloads so they can be attached to TWS errors.
+    ///
+    /// Off by default. When enabled, `Error::Tws` carries the exact message
+    /// that triggered the error in its request context.
+    pub fn capture_request_payloads(&self, enabled: bool) {
+        self.capture_payloads.store(enabled, Ordering::SeqCst);
     }
 
     /// Request account values.
     ///
     /// Returns all account values for the connected account.
     pub async fn account_values(&self) -> Result<Vec<AccountValue>> {
-        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
-        let (tx, rx) = oneshot::channel();
+        let (req_id, rx) = self
+            .register(RequestKind::AccountValues, String::new())
+            .await;
 
-        {
-            let mut pending = self.pending.lock().await;
-            pending.insert(req_id, tx);
+        self.send_request(req_id, &codec::req_account_data(true, "")).await?;
+
+        // Wait for response
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await? {
+            ResponseMessage::AccountValues(values) => Ok(values),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
         }
+    }
 
-        // Send REQ_ACCT_DATA
-        let msg = format!(
-            "{}{}{}{}",
-            make_field(OutgoingMessageId::ReqAccountData.as_u32()),
-            make_field(2),
-            make_field(1), // subscribe
-            make_field(""),
-        );
-        self.send(&msg).await?;
+    /// Request a one-shot account summary.
+    ///
+    /// # Arguments
+    /// * `group` - Account group ("All" for every account)
+    /// * `tags` - Which values to return
+    pub async fn account_summary(
+        &self,
+        group: &str,
+        tags: &[AccountSummaryTag],
+    ) -> Result<Vec<AccountSummaryValue>> {
+        // Values are streamed until the end marker; None marks the end.
+        let mut subscription = self
+            .request_account_summary(group, tags, |message| match message {
+                StreamMessage::AccountSummary(value) => Some(Some(value)),
+                StreamMessage::AccountSummaryEnd => Some(None),
+                _ => None,
+            })
+            .await?;
 
-        // Wait for response
-        match timeout(Duration::from_secs(10), rx).await {
-            Ok(Ok(ResponseMessage::AccountValues(values))) => Ok(values),
-            Ok(Ok(ResponseMessage::Error { code, message })) => {
-                Err(Error::Tws { code, message })
+        let collect = async {
+            let mut values = Vec::new();
+            while let Some(Some(value)) = subscription.next().await {
+                values.push(value);
             }
-            Ok(Ok(_)) => Err(Error::Protocol("Unexpected response type".into())),
-            Ok(Err(_)) => Err(Error::Protocol("Response channel closed".into())),
-            Err(_) => Err(Error::Timeout),
+            values
+        };
+        let values = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(values),
         }
     }
 
-    /// Request historical market data.
+    /// Get margin and buying power figures for an account.
+    ///
+    /// A typed view over the margin-related account summary tags, for risk
+    /// checks that shouldn't match tag names.
+    pub async fn margin_summary(&self, account: &str) -> Result<MarginSummary> {
+        let values = self.account_summary("All", MarginSummary::TAGS).await?;
+        MarginSummary::from_values(account, &values)
+            .ok_or_else(|| Error::Protocol(format!("No account summary for {account}")))
+    }
+
+    /// Subscribe to account summary updates.
+    ///
+    /// TWS sends every requested value once, then pushes changes roughly
+    /// every three minutes. The subscription stays open until dropped.
     ///
     /// # Arguments
-    /// * `contract` - The contract to request data for
-    /// * `duration` - How far back to request data
-    /// * `bar_size` - The size of each bar
-    /// * `what_to_show` - The type of data to return
-    /// * `use_rth` - Only return data from regular trading hours
-    pub async fn historical_data(
+    /// * `group` - Account group ("All" for every account)
+    /// * `tags` - Which values to monitor
+    pub async fn account_summary_updates(
         &self,
-        contract: Contract,
-        duration: HistDuration,
-        bar_size: BarSize,
-        what_to_show: WhatToShow,
-        use_rth: bool,
-    ) -> Result<Vec<BarData>> {
+        group: &str,
+        tags: &[AccountSummaryTag],
+    ) -> Result<Subscription<AccountSummaryValue>> {
+        self.request_account_summary(group, tags, |message| match message {
+            StreamMessage::AccountSummary(value) => Some(value),
+            _ => None,
+        })
+        .await
+    }
+
+    async fn request_account_summary<T>(
+        &self,
+        group: &str,
+        tags: &[AccountSummaryTag],
+        decode: fn(StreamMessage) -> Option<T>,
+    ) -> Result<Subscription<T>> {
+        let params = format!("group={group} tags={}", AccountSummaryTag::join(tags));
         let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
-        let (tx, rx) = oneshot::channel();
 
-        {
-            let mut pending = self.pending.lock().await;
-            pending.insert(req_id, tx);
+        let cancel = codec::cancel_account_summary(req_id);
+        let subscription = self
+            .subscribe(req_id, RequestKind::AccountSummary, params, decode, cancel)
+            .await;
+
+        let msg = codec::req_account_summary(req_id, group, tags);
+        self.send_request(req_id, &msg).await?;
+
+        Ok(subscription)
+    }
+
+    /// Look up aliases for the accounts accessible by this login.
+    ///
+    /// Uses the FA alias configuration for advisor logins. Other logins
+    /// (which TWS refuses FA requests for) get their managed accounts, each
+    /// aliased to its own code.
+    pub async fn account_aliases(&self) -> Result<AccountAliases> {
+        let (req_id, rx) = self
+            .register(RequestKind::AccountAliases, String::new())
+            .await;
+
+        self.send_request(req_id, &codec::req_fa(FA_ALIASES)).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await {
+            Ok(ResponseMessage::FaConfiguration(xml)) => Ok(AccountAliases::from_xml(&xml)),
+            Ok(_) => Err(Error::Protocol("Unexpected response type".into())),
+            Err(Error::Tws {
+                code: VALIDATION_ERROR,
+                ..
+            }) => Ok(AccountAliases::from_accounts(&self.managed_accounts())),
+            Err(error) => Err(error),
         }
+    }
 
-        let request = HistoricalDataRequest::new(req_id, contract)
-            .duration(duration)
-            .bar_size(bar_size)
-            .what_to_show(what_to_show)
-            .use_rth(use_rth);
+    /// Request all positions held across accounts.
+    pub async fn positions(&self) -> Result<Vec<Position>> {
+        // TWS allows only one position subscription, so it's keyed by kind
+        // and cancelled as soon as the end marker arrives.
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let mut subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::Positions,
+                String::new(),
+                |message| match message {
+                    StreamMessage::Position(position) => Some(Some(*position)),
+                    StreamMessage::PositionEnd => Some(None),
+                    _ => None,
+                },
+                codec::cancel_positions(),
+            )
+            .await;
 
-        self.send(&request.encode()).await?;
+        self.send_request(req_id, &codec::req_positions()).await?;
 
-        match timeout(Duration::from_secs(30), rx).await {
-            Ok(Ok(ResponseMessage::HistoricalData(response))) => Ok(response.bars),
-            Ok(Ok(ResponseMessage::Error { code, message })) => {
-                Err(Error::Tws { code, message })
+        let collect = async {
+            let mut positions = Vec::new();
+            while let Some(Some(position)) = subscription.next().await {
+                positions.push(position);
             }
-            Ok(Ok(_)) => Err(Error::Protocol("Unexpected response type".into())),
-            Ok(Err(_)) => Err(Error::Protocol("Response channel closed".into())),
-            Err(_) => Err(Error::Timeout),
+            positions
+        };
+        let positions = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(positions),
         }
     }
 
-    async fn send(&self, payload: &str) -> Result<()> {
-        let mut writer = self.writer.lock().await;
-        Self::send_raw(&mut writer, payload).await
+    /// Get today's executions matching `filter`.
+    ///
+    /// Unlike [`Client::fills`], this also returns executions from before
+    /// the connection was opened. TWS follows each execution with its
+    /// commission report, which is attached to the returned fill.
+    pub async fn executions(&self, filter: &ExecutionFilter) -> Result<Vec<Fill>> {
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let mut subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::Executions,
+                String::new(),
+                |message| match message {
+                    StreamMessage::Execution(_)
+                    | StreamMessage::Commission(_)
+                    | StreamMessage::ExecutionEnd => Some(message),
+                    _ => None,
+                },
+                String::new(),
+            )
+            .await;
+
+        self.send_request(req_id, &filter.encode(req_id)).await?;
+
+        let collect = async {
+            let mut fills: Vec<Fill> = Vec::new();
+            while let Some(message) = subscription.next().await {
+                match message {
+                    StreamMessage::Execution(fill) => fills.push(*fill),
+                    StreamMessage::Commission(report) => {
+                        let fill = fills
+                            .iter_mut()
+                            .find(|fill| fill.execution.exec_id == report.exec_id);
+                        if let Some(fill) = fill {
+                            fill.commission = Some(report);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            fills
+        };
+        let fills = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(fills),
+        }
     }
 
-    async fn send_raw(writer: &mut tokio::io::WriteHalf<TcpStream>, payload: &str) -> Result<()> {
-        let bytes = payload.as_bytes();
-        writer.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
-        writer.write_all(bytes).await?;
-        writer.flush().await?;
-        Ok(())
+    /// Get the open orders placed by this client.
+    ///
+    /// With client ID 0 this also includes orders entered in TWS.
+    pub async fn open_orders(&self) -> Result<Vec<OpenOrder>> {
+        self.collect_open_orders(codec::req_open_orders()).await
+    }
+
+    /// Get the open orders of every API client and TWS.
+    pub async fn all_open_orders(&self) -> Result<Vec<OpenOrder>> {
+        self.collect_open_orders(codec::req_all_open_orders()).await
+    }
+
+    /// Bind orders entered in TWS to this client as they are placed.
+    ///
+    /// Only valid for client ID 0. TWS reports each bound order with an
+    /// `Event::OrderBound` carrying the API order ID it was given.
+    pub async fn auto_open_orders(&self, auto_bind: bool) -> Result<()> {
+        self.send(&codec::req_auto_open_orders(auto_bind)).await
     }
 
-    fn extract_message(buf: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
-        if buf.len() < 4 {
-            return None;
+    async fn collect_open_orders(&self, request: String) -> Result<Vec<OpenOrder>> {
+        // Open orders carry no request ID, so the query is keyed by kind.
+        // There is no cancel message; the route is dropped with the stream.
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let mut subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::OpenOrders,
+                String::new(),
+                |message| match message {
+                    StreamMessage::OpenOrder(open) => Some(Some(*open)),
+                    StreamMessage::OpenOrderEnd => Some(None),
+                    _ => None,
+                },
+                String::new(),
+            )
+            .await;
+
+        self.send_request(req_id, &request).await?;
+
+        let collect = async {
+            let mut orders = Vec::new();
+            while let Some(Some(open)) = subscription.next().await {
+                orders.push(open);
+            }
+            orders
+        };
+        let orders = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(orders),
         }
-        let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
-        if buf.len() >= 4 + len {
-            let msg = buf[4..4 + len].to_vec();
-            let rest = buf[4 + len..].to_vec();
-            Some((msg, rest))
-        } else {
-            None
+    }
+
+    /// Subscribe to daily P&L updates for a single position.
+    ///
+    /// # Arguments
+    /// * `account` - Account holding the position
+    /// * `model_code` - Model code ("" for none)
+    /// * `con_id` - Contract ID of the position
+    pub async fn pnl_single(
+        &self,
+        account: &str,
+        model_code: &str,
+        con_id: i32,
+    ) -> Result<Subscription<PnlSingle>> {
+        let params = format!("account={account} model_code={model_code} con_id={con_id}");
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+
+        let subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::PnlSingle,
+                params,
+                |message| match message {
+                    StreamMessage::PnlSingle(pnl) => Some(pnl),
+                    _ => None,
+                },
+                codec::cancel_pnl_single(req_id),
+            )
+            .await;
+
+        let msg = codec::req_pnl_single(req_id, account, model_code, con_id);
+        self.send_request(req_id, &msg).await?;
+
+        Ok(subscription)
+    }
+
+    /// Request the XML document describing valid scanner parameters.
+    ///
+    /// Lists instruments, location codes, scan codes and the filter tags
+    /// accepted by [`ScannerSubscription::filter`].
+    pub async fn scanner_parameters(&self) -> Result<String> {
+        let (req_id, rx) = self
+            .register(RequestKind::ScannerParameters, String::new())
+            .await;
+
+        self.send_request(req_id, &codec::req_scanner_parameters()).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(30)).await? {
+            ResponseMessage::ScannerParameters(xml) => Ok(xml),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
         }
     }
 
-    async fn dispatch_message(
-        buf: &[u8],
-        pending: &Arc<Mutex<HashMap<i32, oneshot::Sender<ResponseMessage>>>>,
-    ) {
-        let mut fields = FieldIterator::new(buf);
-        let Some(msg_id) = fields.next_parsed::<u32>() else {
-            return;
-        };
+    /// Subscribe to a market scanner.
+    ///
+    /// Each item is the full result list; TWS resends it as the scan
+    /// changes. The subscription stays open until dropped.
+    pub async fn scanner_subscription(
+        &self,
+        subscription: &ScannerSubscription,
+    ) -> Result<Subscription<Vec<ScannerData>>> {
+        let params = format!(
+            "instrument={} location={} scan_code={} filters={}",
+            subscription.instrument,
+            subscription.location_code,
+            subscription.scan_code,
+            subscription.filters.len()
+        );
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
 
-        match IncomingMessageId::from_u32(msg_id) {
-            Some(IncomingMessageId::AccountValue) => {
-                // Collect in a static for account updates
-                // For now, we'll handle this differently
-                let _version = fields.next_i32();
-                let key = fields.next_string().unwrap_or("").to_string();
-                let value = fields.next_string().unwrap_or("").to_string();
-                let currency = fields.next_string().unwrap_or("").to_string();
-                let account = fields.next_string().unwrap_or("").to_string();
-
-                // Account values are streaming - we need a different pattern
-                // For now, log them
-                let _ = AccountValue {
-                    key,
-                    value,
-                    currency,
-                    account,
-                };
-            }
-            Some(IncomingMessageId::AccountDownloadEnd) => {
-                // Signal completion - for now, find any pending account request
-                let mut pending = pending.lock().await;
-                // Find first pending request (simplified - should match by type)
-                if let Some((req_id, tx)) = pending.iter().next().map(|(k, _)| *k).and_then(|k| {
-                    pending.remove(&k).map(|tx| (k, tx))
-                }) {
-                    let _ = tx.send(ResponseMessage::AccountValues(vec![]));
-                    let _ = req_id;
-                }
-            }
-            Some(IncomingMessageId::HistoricalData) => {
-                let req_id = fields.next_i32();
-                let start = fields.next_string().unwrap_or("").to_string();
-                let end = fields.next_string().unwrap_or("").to_string();
-                let bar_count = fields.next_i32();
-
-                let mut bars = Vec::with_capacity(bar_count as usize);
-                for _ in 0..bar_count {
-                    if let Some(bar) = BarData::parse(&mut fields) {
-                        bars.push(bar);
-                    }
-                }
+        let stream = self
+            .subscribe(
+                req_id,
+                RequestKind::Scanner,
+                params,
+                |message| match message {
+                    StreamMessage::ScannerData(rows) => Some(rows),
+                    _ => None,
+                },
+                codec::cancel_scanner_subscription(req_id),
+            )
+            .await;
 
-                let mut pending = pending.lock().await;
-                if let Some(tx) = pending.remove(&req_id) {
-                    let _ = tx.send(ResponseMessage::HistoricalData(HistoricalDataResponse {
-                        start,
-                        end,
-                        bars,
-                    }));
-                }
+        let msg = subscription.encode(req_id, self.server_version());
+        self.send_request(req_id, &msg).await?;
+
+        Ok(stream)
+    }
+
+    /// Run a scan once and attach a snapshot quote to every row.
+    ///
+    /// Takes the first result list, cancels the scan, then quotes the
+    /// returned contracts with [`Client::quote_many`]. Rows whose quote
+    /// fails keep `quote: None`.
+    pub async fn scan_with_quotes(
+        &self,
+        subscription: &ScannerSubscription,
+    ) -> Result<Vec<ScannerRow>> {
+        let rows = {
+            let mut scan = self.scanner_subscription(subscription).await?;
+            match timeout(Duration::from_secs(30), scan.next()).await {
+                Ok(Some(rows)) => rows,
+                Ok(None) => return Err(scan.take_error().unwrap_or(Error::NotConnected)),
+                Err(_) => return Err(Error::Timeout),
             }
-            Some(IncomingMessageId::Error) => {
-                let _version = fields.next_i32();
-                let req_id = fields.next_i32();
-                let code = fields.next_i32();
-                let message = fields.next_string().unwrap_or("").to_string();
+        };
 
-                if req_id > 0 {
-                    let mut pending = pending.lock().await;
-                    if let Some(tx) = pending.remove(&req_id) {
-                        let _ = tx.send(ResponseMessage::Error { code, message });
-                    }
+        let contracts: Vec<Contract> = rows.iter().map(ScannerData::quote_contract).collect();
+        let quotes = self.quote_many(&contracts).await;
+
+        Ok(rows
+            .into_iter()
+            .zip(quotes)
+            .map(|(data, quote)| {
+                if let Err(error) = &quote {
+                    tracing::debug!(symbol = %data.contract.symbol, %error, "scanner row quote failed");
+                }
+                ScannerRow {
+                    data,
+                    quote: quote.ok(),
                 }
+            })
+            .collect())
+    }
+
+    /// Request a one-off quote snapshot for a contract.
+    ///
+    /// Collects the ticks TWS sends until it marks the snapshot complete.
+    /// Uses a market data line while in flight.
+    pub async fn quote(&self, contract: Contract) -> Result<Quote> {
+        let subscription = self.request_snapshot(contract).await?;
+        self.collect_quote(subscription).await
+    }
+
+    /// Request quote snapshots for a watchlist.
+    ///
+    /// Keeps at most 50 snapshots in flight and spaces requests to respect
+    /// the TWS message rate (unless the session uses PACEAPI). Results are
+    /// returned in the order of `contracts`; one contract failing doesn't
+    /// affect the others.
+    pub async fn quote_many(&self, contracts: &[Contract]) -> Vec<Result<Quote>> {
+        let mut results: Vec<Option<Result<Quote>>> = contracts.iter().map(|_| None).collect();
+        let mut in_flight = VecDeque::new();
+
+        for (index, contract) in contracts.iter().enumerate() {
+            if in_flight.len() >= SNAPSHOT_LINES
+                && let Some((done, subscription)) = in_flight.pop_front()
+            {
+                results[done] = Some(self.collect_quote(subscription).await);
+            }
+            if index > 0 && !self.pace_api() {
+                tokio::time::sleep(MESSAGE_INTERVAL).await;
             }
-            _ => {}
+            match self.request_snapshot(contract.clone()).await {
+                Ok(subscription) => in_flight.push_back((index, subscription)),
+                Err(error) => results[index] = Some(Err(error)),
+            }
+        }
+        for (done, subscription) in in_flight {
+            results[done] = Some(self.collect_quote(subscription).await);
         }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or(Err(Error::NotConnected)))
+            .collect()
+    }
+
+    async fn request_snapshot(&self, contract: Contract) -> Result<Subscription<StreamMessage>> {
+        let params = format!(
+            "symbol={} sec_type={} exchange={} snapshot=true",
+            contract.symbol, contract.sec_type, contract.exchange
+        );
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::MarketData,
+                params,
+                Some,
+                codec::cancel_market_data(req_id),
+            )
+            .await;
+
+        let request = MarketDataRequest::new(req_id, contract).snapshot(true);
+        self.send_request(req_id, &request.encode()).await?;
+        Ok(subscription)
+    }
+
+    /// Stream market data ticks for a contract.
+    ///
+    /// `generic_ticks` adds optional tick groups to the default bid, ask
+    /// and last ticks. With `snapshot`, TWS sends the current values
+    /// followed by [`Tick::SnapshotEnd`] and ends the stream. Dropping the
+    /// subscription cancels the request.
+    pub async fn market_data(
+        &self,
+        contract: Contract,
+        generic_ticks: &[GenericTick],
+        snapshot: bool,
+    ) -> Result<Subscription<Tick>> {
+        let params = format!(
+            "symbol={} sec_type={} exchange={} generic_ticks={} snapshot={snapshot}",
+            contract.symbol,
+            contract.sec_type,
+            contract.exchange,
+            GenericTick::list(generic_ticks)
+        );
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::MarketData,
+                params,
+                |message| match message {
+                    StreamMessage::TickPrice {
+                        tick_type,
+                        price,
+                        attr_mask,
+                    } => Some(Tick::Price {
+                        tick_type,
+                        price,
+                        attrib: TickAttrib::from_mask(attr_mask),
+                    }),
+                    StreamMessage::TickSize { tick_type, size } => {
+                        Some(Tick::Size { tick_type, size })
+                    }
+                    StreamMessage::TickString { tick_type, value } => {
+                        Some(Tick::String { tick_type, value })
+                    }
+                    StreamMessage::TickGeneric { tick_type, value } => {
+                        Some(Tick::Generic { tick_type, value })
+                    }
+                    StreamMessage::MarketDataType(data_type) => Some(Tick::DataType(data_type)),
+                    StreamMessage::TickSnapshotEnd => Some(Tick::SnapshotEnd),
+                    _ => None,
+                },
+                codec::cancel_market_data(req_id),
+            )
+            .await;
+
+        let request = MarketDataRequest::new(req_id, contract)
+            .generic_ticks(generic_ticks)
+            .snapshot(snapshot)
+            .encode();
+        if !snapshot && self.delayed_fallback.load(Ordering::SeqCst) {
+            self.set_delayed_fallback(req_id, request.clone()).await;
+        }
+        self.send_request(req_id, &request).await?;
+        Ok(subscription)
+    }
+
+    /// Stream market data ticks for a contract over a request shared with
+    /// other consumers.
+    ///
+    /// Consumers asking for the same contract and generic ticks share one
+    /// market data line; the request is cancelled when the last
+    /// [`SharedTicks`] is dropped. Errors end the stream for every consumer;
+    /// use [`Client::market_data`] to see them.
+    pub async fn shared_market_data(
+        &self,
+        contract: Contract,
+        generic_ticks: &[GenericTick],
+    ) -> Result<SharedTicks> {
+        let key = format!(
+            "{}{}{}",
+            contract.encode(),
+            contract.encode_combo_legs(),
+            GenericTick::list(generic_ticks)
+        );
+        // Held across the request so concurrent callers can't both open one.
+        let mut feeds = self.shared.market_data_feeds.lock().await;
+        if let Some(feed) = feeds.get(&key).and_then(Weak::upgrade) {
+            return Ok(feed.subscribe());
+        }
+
+        let mut ticks = self.market_data(contract, generic_ticks, false).await?;
+        let req_id = ticks.req_id();
+        let (sender, receiver) = broadcast::channel(SHARED_TICK_CAPACITY);
+        let shared = self.shared.clone();
+        let forward_key = key.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(tick) = ticks.next().await {
+                let _ = sender.send(tick);
+            }
+            if let Some(error) = ticks.take_error() {
+                tracing::warn!(req_id, %error, "shared market data request ended");
+            }
+            // Let the next caller open a fresh request.
+            let mut feeds = shared.market_data_feeds.lock().await;
+            let current = feeds.get(&forward_key).and_then(Weak::upgrade);
+            if current.is_none_or(|feed| feed.req_id == req_id) {
+                feeds.remove(&forward_key);
+            }
+        });
+
+        let feed = Arc::new(SharedFeed::new(req_id, receiver, forward.abort_handle()));
+        feeds.insert(key, Arc::downgrade(&feed));
+        Ok(feed.subscribe())
+    }
+
+    /// Stream order book changes for a contract, up to `num_rows` rows on
+    /// each side.
+    ///
+    /// With `is_smart_depth`, depth from all exchanges listed by
+    /// [`Client::market_depth_exchanges`] is aggregated into one book.
+    /// TWS limits how many depth requests can be open at once (3 by
+    /// default). Dropping the subscription cancels the request.
+    pub async fn market_depth(
+        &self,
+        contract: Contract,
+        num_rows: i32,
+        is_smart_depth: bool,
+    ) -> Result<Subscription<DepthUpdate>> {
+        let params = format!(
+            "symbol={} sec_type={} exchange={} num_rows={num_rows} smart={is_smart_depth}",
+            contract.symbol, contract.sec_type, contract.exchange
+        );
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let server_version = self.server_version();
+        let subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::MarketDepth,
+                params,
+                |message| match message {
+                    StreamMessage::MarketDepth(update) => Some(update),
+                    _ => None,
+                },
+                codec::cancel_market_depth(req_id, is_smart_depth, server_version),
+            )
+            .await;
+
+        let request =
+            codec::req_market_depth(req_id, &contract, num_rows, is_smart_depth, server_version);
+        self.send_request(req_id, &request).await?;
+        Ok(subscription)
+    }
+
+    /// List the exchanges that offer market depth, and which of them take
+    /// part in SMART depth.
+    pub async fn market_depth_exchanges(&self) -> Result<Vec<DepthExchange>> {
+        let (req_id, rx) = self
+            .register(RequestKind::MarketDepthExchanges, String::new())
+            .await;
+
+        self.send_request(req_id, &codec::req_market_depth_exchanges()).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await? {
+            ResponseMessage::MarketDepthExchanges(exchanges) => Ok(exchanges),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Look up a market rule's price increments, e.g. for one of
+    /// [`ContractDetails::market_rule_ids`].
+    ///
+    /// Use [`round_to_tick`](crate::market_rule::round_to_tick) to keep
+    /// limit prices on a valid tick.
+    pub async fn market_rule(&self, market_rule_id: i32) -> Result<MarketRule> {
+        // The reply carries the rule ID rather than a request ID
+        let params = format!("market_rule_id={market_rule_id}");
+        let (req_id, rx) = self.register(RequestKind::MarketRule, params).await;
+
+        self.send_request(req_id, &codec::req_market_rule(market_rule_id)).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await? {
+            ResponseMessage::MarketRule(rule) => Ok(rule),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Map the single-letter exchange codes in SMART exchange ticks to
+    /// exchanges, by bit number.
+    ///
+    /// `bbo_exchange` comes from the
+    /// [`TickReqParams`](crate::market_data::TickReqParams) TWS sends at the
+    /// start of a market data request.
+    pub async fn smart_components(
+        &self,
+        bbo_exchange: &str,
+    ) -> Result<HashMap<i32, SmartComponent>> {
+        let params = format!("bbo_exchange={bbo_exchange}");
+        let (req_id, rx) = self.register(RequestKind::SmartComponents, params).await;
+
+        self.send_request(req_id, &codec::req_smart_components(req_id, bbo_exchange)).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await? {
+            ResponseMessage::SmartComponents(components) => Ok(components),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Build a quote from snapshot ticks until TWS ends the snapshot.
+    async fn collect_quote(&self, mut subscription: Subscription<StreamMessage>) -> Result<Quote> {
+        let collect = async {
+            let mut quote = Quote::default();
+            while let Some(message) = subscription.next().await {
+                match message {
+                    StreamMessage::TickPrice {
+                        tick_type, price, ..
+                    } => quote.apply_price(tick_type, price),
+                    StreamMessage::TickSize { tick_type, size } => quote.apply_size(tick_type, size),
+                    StreamMessage::MarketDataType(data_type) => quote.data_type = data_type,
+                    StreamMessage::TickSnapshotEnd => {
+                        subscription.complete();
+                        return Some(quote);
+                    }
+                    _ => {}
+                }
+            }
+            None
+        };
+
+        // On timeout the subscription is dropped, cancelling the request.
+        match timeout(SNAPSHOT_TIMEOUT, collect).await {
+            Ok(Some(quote)) => Ok(quote),
+            Ok(None) => Err(subscription.take_error().unwrap_or(Error::NotConnected)),
+            Err(_) => Err(Error::Timeout),
+        }
+    }
+
+    /// Set the defaults that [`Client::order_builder`] starts orders from,
+    /// e.g. the account and time in force for this session.
+    pub fn set_order_defaults(&self, defaults: OrderDefaults) {
+        *self.order_defaults.lock().unwrap() = defaults;
+    }
+
+    /// Start building an order from the client's [`OrderDefaults`].
+    pub fn order_builder(
+        &self,
+        action: OrderAction,
+        quantity: Decimal,
+        order_type: OrderType,
+    ) -> OrderBuilder {
+        self.order_defaults.lock().unwrap().builder(action, quantity, order_type)
+    }
+
+    /// Place an order and return a tracker for its status.
+    ///
+    /// Order IDs are taken from the next valid ID TWS reports on connect.
+    /// TWS reports rejections as errors against the order ID; mistakes
+    /// caught by [`Order::validate`] fail before anything is sent.
+    pub async fn place_order(&self, contract: &Contract, order: &Order) -> Result<OrderTracker> {
+        self.check_order_support()?;
+        order.validate()?;
+        let order_id = self.next_order_id()?;
+        self.place(order_id, contract, order).await
+    }
+
+    /// Place an order under an ID reserved with [`Client::next_order_id`].
+    ///
+    /// Fails with [`Error::DuplicateOrderId`] without sending anything if
+    /// the ID was already used on this connection; amend a working order
+    /// with [`Client::modify_order`] instead. IDs must be positive and
+    /// below the request ID range. The allocator moves past the ID, so
+    /// [`Client::place_order`] won't reuse it.
+    pub async fn place_order_with_id(
+        &self,
+        order_id: i32,
+        contract: &Contract,
+        order: &Order,
+    ) -> Result<OrderTracker> {
+        self.check_order_support()?;
+        if !(1..FIRST_REQ_ID).contains(&order_id) {
+            return Err(Error::InvalidOrder(format!(
+                "order ID {order_id} is outside 1..{FIRST_REQ_ID}"
+            )));
+        }
+        order.validate()?;
+        self.shared.next_order_id.fetch_max(order_id + 1, Ordering::SeqCst);
+        self.place(order_id, contract, order).await
+    }
+
+    /// Fail early on servers older than the order layout this crate sends.
+    fn check_order_support(&self) -> Result<()> {
+        let required = server_versions::PRICE_MGMT_ALGO;
+        if self.shared.server_version < required {
+            return Err(Error::Protocol(format!(
+                "placing orders requires server version {required}, connected to {}",
+                self.shared.server_version
+            )));
+        }
+        Ok(())
+    }
+
+    async fn place(
+        &self,
+        order_id: i32,
+        contract: &Contract,
+        order: &Order,
+    ) -> Result<OrderTracker> {
+        if !self.shared.used_order_ids.lock().unwrap().insert(order_id) {
+            return Err(Error::DuplicateOrderId(order_id));
+        }
+        tracing::info!(
+            order_id,
+            symbol = %contract.symbol,
+            action = %order.action,
+            quantity = %order.total_quantity,
+            order_type = %order.order_type,
+            "placing order"
+        );
+
+        // Register before sending so the first status can't be missed.
+        let (latest_tx, latest_rx) = watch::channel(None);
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel();
+        let (rejection_tx, rejection_rx) = oneshot::channel();
+        let tracked = TrackedOrder {
+            latest: latest_tx,
+            updates: updates_tx,
+            rejection: rejection_tx,
+        };
+        self.shared.tracked_orders.lock().unwrap().insert(order_id, tracked);
+        if let Err(e) = self.send_order(order_id, contract, order).await {
+            self.shared.tracked_orders.lock().unwrap().remove(&order_id);
+            return Err(e);
+        }
+        Ok(OrderTracker::new(order_id, latest_rx, updates_rx, rejection_rx))
+    }
+
+    /// Preview an order's margin and commission impact without placing it.
+    ///
+    /// Sends the order as a what-if order under a fresh order ID and waits
+    /// for TWS to report the resulting order state.
+    pub async fn preview_order(&self, contract: &Contract, order: &Order) -> Result<MarginImpact> {
+        self.check_order_support()?;
+        order.validate()?;
+        let order_id = self.next_order_id()?;
+        let preview = Order {
+            what_if: true,
+            ..order.clone()
+        };
+
+        // Subscribe before sending so the reply can't be missed.
+        let mut events = self.order_events();
+        let mut errors = self.unroutable_errors();
+        self.send(&preview.encode(order_id, contract, self.server_version())).await?;
+
+        let wait = async {
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(OrderEvent::Open(open)) if open.order_id == order_id => {
+                            return Ok(MarginImpact::from_state(open.state));
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return Err(Error::NotConnected),
+                        _ => {}
+                    },
+                    error = errors.recv() => match error {
+                        Ok(error) if error.req_id == order_id && error.code != ORDER_WARNING => {
+                            return Err(Self::order_error(order_id, error.code, error.message));
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return Err(Error::NotConnected),
+                        _ => {}
+                    },
+                }
+            }
+        };
+        timeout(Duration::from_secs(10), wait)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    /// Amend a working order placed on this connection.
+    ///
+    /// Re-sends the order under its ID with the contract it was placed
+    /// for, so only the order's fields (price, quantity, time in force...)
+    /// can change. Start from [`Client::placed_order`] to keep the rest.
+    pub async fn modify_order(&self, order_id: i32, order: &Order) -> Result<()> {
+        let (contract, previous) = self.placed(order_id).ok_or_else(|| {
+            Error::InvalidOrder(format!("order {order_id} wasn't placed on this connection"))
+        })?;
+        if order.action != previous.action {
+            return Err(Error::InvalidOrder("a working order's action can't change".into()));
+        }
+        order.validate()?;
+        tracing::info!(
+            order_id,
+            quantity = %order.total_quantity,
+            lmt_price = ?order.lmt_price,
+            aux_price = ?order.aux_price,
+            "modifying order"
+        );
+        self.send_order(order_id, &contract, order).await
+    }
+
+    /// The order as last sent under `order_id` from this connection.
+    pub fn placed_order(&self, order_id: i32) -> Option<Order> {
+        self.placed(order_id).map(|(_, order)| order)
+    }
+
+    fn placed(&self, order_id: i32) -> Option<(Contract, Order)> {
+        self.shared.orders.lock().unwrap().get(&order_id).cloned()
+    }
+
+    async fn send_order(&self, order_id: i32, contract: &Contract, order: &Order) -> Result<()> {
+        // Older servers parse the quantity as an integer
+        let fractional = !order.total_quantity.fract().is_zero();
+        if fractional && self.server_version() < server_versions::FRACTIONAL_SIZE_SUPPORT {
+            return Err(Error::InvalidOrder(format!(
+                "fractional quantity {} needs server version {} or later",
+                order.total_quantity,
+                server_versions::FRACTIONAL_SIZE_SUPPORT
+            )));
+        }
+        self.send(&order.encode(order_id, contract, self.server_version())).await?;
+        self.shared
+            .orders
+            .lock()
+            .unwrap()
+            .insert(order_id, (contract.clone(), order.clone()));
+        Ok(())
+    }
+
+    /// Exercise an option position or let it lapse.
+    ///
+    /// Set `override_` to ignore IB's automatic exercise rules, e.g. to
+    /// exercise out of the money. TWS reports the result through order
+    /// events; rejections arrive as errors against the returned request ID
+    /// on [`Client::unroutable_errors`].
+    pub async fn exercise_options(
+        &self,
+        contract: &Contract,
+        action: ExerciseAction,
+        quantity: i32,
+        account: &str,
+        override_: bool,
+    ) -> Result<i32> {
+        if quantity <= 0 {
+            return Err(Error::InvalidOrder("exercise quantity must be positive".into()));
+        }
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        tracing::info!(
+            req_id,
+            symbol = %contract.symbol,
+            action = ?action,
+            quantity,
+            "exercising options"
+        );
+        let request = codec::exercise_options(
+            req_id,
+            contract,
+            action,
+            quantity,
+            account,
+            override_,
+            self.server_version(),
+        );
+        self.send_request(req_id, &request).await?;
+        Ok(req_id)
+    }
+
+    /// Place orders as one one-cancels-all group and return their IDs.
+    ///
+    /// Every order gets `group` and `oca_type`, replacing any OCA settings
+    /// it had. All orders are validated before the first one is sent.
+    ///
+    /// If an order after the first can't be placed, fails with
+    /// [`Error::PartialOcaGroup`] carrying the IDs of the orders already
+    /// working, so they can be cancelled or kept.
+    pub async fn place_oca_group(
+        &self,
+        group: &str,
+        oca_type: OcaType,
+        orders: &[(Contract, Order)],
+    ) -> Result<Vec<i32>> {
+        let orders: Vec<_> = orders
+            .iter()
+            .map(|(contract, order)| (contract, order.clone().oca(group, oca_type)))
+            .collect();
+        for (_, order) in &orders {
+            order.validate()?;
+        }
+
+        let mut order_ids = Vec::with_capacity(orders.len());
+        for (contract, order) in &orders {
+            match self.place_order(contract, order).await {
+                Ok(tracker) => order_ids.push(tracker.order_id()),
+                Err(error) if order_ids.is_empty() => return Err(error),
+                Err(error) => {
+                    return Err(Error::PartialOcaGroup {
+                        placed: order_ids,
+                        source: Box::new(error),
+                    });
+                }
+            }
+        }
+        Ok(order_ids)
+    }
+
+    /// Reserve the next order ID.
+    ///
+    /// IDs start from the next valid ID TWS sent on connect and are never
+    /// handed out twice on a connection. Use this to place orders through
+    /// other means under IDs that won't collide with [`Client::place_order`].
+    ///
+    /// Fails once order IDs would reach the range used for request IDs;
+    /// reset the API order ID sequence in TWS to continue.
+    pub fn next_order_id(&self) -> Result<i32> {
+        self.shared
+            .next_order_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| {
+                (id > 0 && id < FIRST_REQ_ID).then_some(id + 1)
+            })
+            .map_err(|id| match id {
+                FIRST_REQ_ID.. => Error::Protocol(format!("Order ID {id} would overlap request IDs")),
+                _ => Error::Protocol("No valid order ID received from TWS".into()),
+            })
+    }
+
+    /// Ask TWS for the next valid order ID and re-sync the allocator.
+    ///
+    /// The allocator only moves forward, so IDs already handed out are
+    /// never reissued. Returns the ID TWS reported.
+    pub async fn request_ids(&self) -> Result<i32> {
+        let (req_id, rx) = self
+            .register(RequestKind::NextValidId, String::new())
+            .await;
+
+        self.send_request(req_id, &codec::req_ids()).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(10)).await? {
+            ResponseMessage::NextValidId(order_id) => Ok(order_id),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Look up the contracts matching `contract`, with their details.
+    ///
+    /// A partially specified contract (e.g. only a symbol and security
+    /// type) returns every match; an unknown one fails with TWS error 200.
+    pub async fn contract_details(&self, contract: &Contract) -> Result<Vec<ContractDetails>> {
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let params = format!(
+            "symbol={} sec_type={} exchange={} currency={}",
+            contract.symbol, contract.sec_type, contract.exchange, contract.currency
+        );
+        let mut subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::ContractDetails,
+                params,
+                |message| match message {
+                    StreamMessage::ContractDetails(_) | StreamMessage::ContractDetailsEnd => {
+                        Some(message)
+                    }
+                    _ => None,
+                },
+                String::new(),
+            )
+            .await;
+
+        let request = codec::req_contract_details(req_id, contract, self.server_version());
+        self.send_request(req_id, &request).await?;
+
+        let collect = async {
+            let mut details = Vec::new();
+            while let Some(StreamMessage::ContractDetails(item)) = subscription.next().await {
+                details.push(*item);
+            }
+            details
+        };
+        let details = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(details),
+        }
+    }
+
+    /// Resolve `contract` to its contract ID.
+    ///
+    /// Looks the contract up with contract details the first time and
+    /// caches the ID, so later calls for the same contract don't hit TWS.
+    /// Fails with [`Error::AmbiguousContract`] unless exactly one contract
+    /// matches. See [`Client::conid_cache`] to persist the cache.
+    pub async fn resolve_conid(&self, contract: &Contract) -> Result<i32> {
+        if contract.con_id != 0 {
+            return Ok(contract.con_id);
+        }
+        if let Some(con_id) = self.shared.con_ids.get(contract) {
+            return Ok(con_id);
+        }
+        let con_id = match self.contract_details(contract).await?.as_slice() {
+            [details] => details.contract.con_id,
+            matches => {
+                return Err(Error::AmbiguousContract {
+                    contract: contract.key(),
+                    matches: matches.len(),
+                });
+            }
+        };
+        self.shared.con_ids.insert(contract, con_id)?;
+        Ok(con_id)
+    }
+
+    /// The contract IDs cached by [`Client::resolve_conid`].
+    ///
+    /// Call [`ConIdCache::persist_to`] on it to keep them across sessions.
+    pub fn conid_cache(&self) -> &ConIdCache {
+        &self.shared.con_ids
+    }
+
+    /// The `symbol` futures contract to trade today, rolling to the next
+    /// expiry `roll_days` calendar days before the front month's last
+    /// trade date.
+    ///
+    /// Lists the expiries with contract details; see
+    /// [`futures::front_month`] for the selection. Returns `None` if no
+    /// listed expiry is far enough out.
+    pub async fn front_month(
+        &self,
+        symbol: &str,
+        exchange: &str,
+        currency: &str,
+        roll_days: u32,
+    ) -> Result<Option<Contract>> {
+        let lookup = Contract::futures(symbol, exchange, "", currency);
+        let futures: Vec<Contract> = self
+            .contract_details(&lookup)
+            .await?
+            .into_iter()
+            .map(|details| details.contract)
+            .collect();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let today = DateTime::from_timestamp(now.as_secs() as i64).date_string();
+        Ok(futures::front_month(&futures, &today, roll_days).cloned())
+    }
+
+    /// List the expiries and strikes of `underlying`'s options, one entry
+    /// per exchange and trading class.
+    ///
+    /// The underlying is resolved to its contract ID first (see
+    /// [`Client::resolve_conid`]). For futures, only options on the
+    /// underlying's exchange are listed.
+    pub async fn option_params(&self, underlying: &Contract) -> Result<Vec<OptionParams>> {
+        let underlying = Contract {
+            con_id: self.resolve_conid(underlying).await?,
+            ..underlying.clone()
+        };
+        let fut_fop_exchange = match underlying.sec_type {
+            SecurityType::Future => underlying.exchange.as_str(),
+            _ => "",
+        };
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let params = format!(
+            "symbol={} sec_type={} con_id={}",
+            underlying.symbol, underlying.sec_type, underlying.con_id
+        );
+        let mut subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::OptionParams,
+                params,
+                |message| match message {
+                    StreamMessage::OptionParams(_) | StreamMessage::OptionParamsEnd => {
+                        Some(message)
+                    }
+                    _ => None,
+                },
+                String::new(),
+            )
+            .await;
+
+        let request = codec::req_sec_def_opt_params(req_id, &underlying, fut_fop_exchange);
+        self.send_request(req_id, &request).await?;
+
+        let collect = async {
+            let mut params = Vec::new();
+            while let Some(StreamMessage::OptionParams(item)) = subscription.next().await {
+                params.push(*item);
+            }
+            params
+        };
+        let params = timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match subscription.take_error() {
+            Some(error) => Err(error),
+            None => Ok(params),
+        }
+    }
+
+    /// Snapshot the SMART-routed options on `underlying` expiring on the
+    /// dates `expiry_filter` accepts (YYYYMMDD) with strikes in
+    /// `strike_range`.
+    ///
+    /// Finds the expiries with [`Client::option_params`], looks up each
+    /// expiry's contracts with one contract details request, then quotes
+    /// the options in range with [`Client::quote_many`], which paces the
+    /// snapshots. Rows are sorted by expiry, strike and right; rows whose
+    /// quote fails keep `quote: None`.
+    pub async fn option_chain(
+        &self,
+        underlying: &Contract,
+        expiry_filter: impl Fn(&str) -> bool,
+        strike_range: std::ops::RangeInclusive<f64>,
+    ) -> Result<Vec<OptionChainRow>> {
+        let sec_type = match underlying.sec_type {
+            SecurityType::Future => SecurityType::FutureOption,
+            _ => SecurityType::Option,
+        };
+        let mut contracts = Vec::new();
+        let mut first = true;
+        for params in self.option_params(underlying).await? {
+            if params.exchange != "SMART" {
+                continue;
+            }
+            for expiry in params.expirations.iter().filter(|expiry| expiry_filter(expiry)) {
+                if !first && !self.pace_api() {
+                    tokio::time::sleep(MESSAGE_INTERVAL).await;
+                }
+                first = false;
+                let lookup = Contract {
+                    symbol: underlying.symbol.clone(),
+                    sec_type: sec_type.clone(),
+                    last_trade_date: expiry.clone(),
+                    exchange: params.exchange.clone(),
+                    currency: underlying.currency.clone(),
+                    trading_class: params.trading_class.clone(),
+                    multiplier: params.multiplier.clone(),
+                    ..Default::default()
+                };
+                let details = self.contract_details(&lookup).await?;
+                contracts.extend(
+                    details
+                        .into_iter()
+                        .map(|details| details.contract)
+                        .filter(|contract| strike_range.contains(&contract.strike)),
+                );
+            }
+        }
+        contracts.sort_by(|a, b| {
+            a.last_trade_date
+                .cmp(&b.last_trade_date)
+                .then(a.strike.total_cmp(&b.strike))
+                .then(a.right.as_str().cmp(b.right.as_str()))
+        });
+
+        let quotes = self.quote_many(&contracts).await;
+        Ok(contracts
+            .into_iter()
+            .zip(quotes)
+            .map(|(contract, quote)| {
+                if let Err(error) = &quote {
+                    tracing::debug!(symbol = %contract.local_symbol, %error, "option quote failed");
+                }
+                OptionChainRow {
+                    contract,
+                    quote: quote.ok(),
+                }
+            })
+            .collect())
+    }
+
+    /// Request historical market data.
+    ///
+    /// Fails with [`Error::InvalidHistoricalRequest`] if `duration` is
+    /// longer than TWS allows for `bar_size` in one request.
+    ///
+    /// # Arguments
+    /// * `contract` - The contract to request data for
+    /// * `duration` - How far back to request data
+    /// * `bar_size` - The size of each bar
+    /// * `what_to_show` - The type of data to return
+    /// * `use_rth` - Only return data from regular trading hours
+    pub async fn historical_data(
+        &self,
+        contract: Contract,
+        duration: HistDuration,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Vec<BarData>> {
+        self.check_what_to_show(what_to_show)?;
+        bar_size.check_duration(duration)?;
+        let params = format!(
+            "symbol={} sec_type={} duration={} bar_size={} what_to_show={} use_rth={}",
+            contract.symbol, contract.sec_type, duration, bar_size, what_to_show, use_rth
+        );
+        self.request_bars(params, |req_id| {
+            HistoricalDataRequest::new(req_id, contract)
+                .duration(duration)
+                .bar_size(bar_size)
+                .what_to_show(what_to_show)
+                .use_rth(use_rth)
+        })
+        .await
+    }
+
+    /// Request historical bars and keep receiving the latest bar as it
+    /// forms (keepUpToDate).
+    ///
+    /// The subscription first yields the bars covering `duration`, then
+    /// updates as trades come in. TWS repeats the latest bar with each
+    /// update, so a bar dated the same as the one before it replaces it.
+    /// Bars are at least 5 seconds long and intraday bars are dated in
+    /// epoch seconds. Dropping the subscription cancels the request; see
+    /// [`GaplessBars`](crate::historical::GaplessBars) to carry on across
+    /// reconnects.
+    ///
+    /// # Arguments
+    /// * `contract` - The contract to request data for
+    /// * `duration` - How far back the initial bars go
+    /// * `bar_size` - The size of each bar
+    /// * `what_to_show` - The type of data to return
+    /// * `use_rth` - Only return data from regular trading hours
+    pub async fn bar_updates(
+        &self,
+        contract: Contract,
+        duration: HistDuration,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Subscription<BarData>> {
+        let server_version = self.server_version();
+        if server_version < server_versions::SYNT_REALTIME_BARS {
+            return Err(Error::Protocol(format!(
+                "Bar updates require server version {}, connected to {server_version}",
+                server_versions::SYNT_REALTIME_BARS
+            )));
+        }
+        if bar_size == BarSize::Sec1 {
+            return Err(Error::InvalidHistoricalRequest(
+                "bar updates need bars of 5 secs or longer".into(),
+            ));
+        }
+        self.check_what_to_show(what_to_show)?;
+        bar_size.check_duration(duration)?;
+        let params = format!(
+            "symbol={} sec_type={} duration={} bar_size={} what_to_show={} use_rth={}",
+            contract.symbol, contract.sec_type, duration, bar_size, what_to_show, use_rth
+        );
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let subscription = self
+            .subscribe(
+                req_id,
+                RequestKind::BarUpdates,
+                params,
+                |message| match message {
+                    StreamMessage::Bar(bar) => Some(bar),
+                    _ => None,
+                },
+                codec::cancel_historical_data(req_id),
+            )
+            .await;
+
+        let request = HistoricalDataRequest::new(req_id, contract)
+            .duration(duration)
+            .bar_size(bar_size)
+            .what_to_show(what_to_show)
+            .use_rth(use_rth)
+            .format_date(DateFormat::Unix)
+            .keep_up_to_date(true)
+            .encode(server_version);
+        self.send_request(req_id, &request).await?;
+        Ok(subscription)
+    }
+
+    /// Request historical bars between `start` and `end`.
+    ///
+    /// The range is converted to IB durations and split into several
+    /// requests when it's longer than TWS allows for `bar_size`; pacing
+    /// violations are retried after a back-off. Bars are returned in order
+    /// without duplicates. Both times must be UTC (see
+    /// [`DateTime::utc`]); intraday bars are dated in epoch seconds.
+    ///
+    /// # Arguments
+    /// * `contract` - The contract to request data for
+    /// * `start` - Start of the range
+    /// * `end` - End of the range
+    /// * `bar_size` - The size of each bar
+    /// * `what_to_show` - The type of data to return
+    /// * `use_rth` - Only return data from regular trading hours
+    pub async fn historical_data_range(
+        &self,
+        contract: Contract,
+        start: &DateTime,
+        end: &DateTime,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Vec<BarData>> {
+        let (Some(start), Some(end)) = (start.timestamp(), end.timestamp()) else {
+            return Err(Error::Protocol("Bar range bounds must be UTC".into()));
+        };
+        self.check_what_to_show(what_to_show)?;
+        // TWS only serves adjusted bars up to the present.
+        if what_to_show == WhatToShow::AdjustedLast {
+            return Err(Error::Protocol("ADJUSTED_LAST bars can't have an end time".into()));
+        }
+
+        let mut bars: Vec<BarData> = Vec::new();
+        for (chunk_end, duration) in split_range(start, end, bar_size) {
+            let chunk = self
+                .historical_data_chunk_paced(
+                    &contract,
+                    chunk_end,
+                    duration,
+                    bar_size,
+                    what_to_show,
+                    use_rth,
+                )
+                .await?;
+
+            // Chunks rounded up to whole days overlap the previous one.
+            let last = bars.last().and_then(|bar| bar.time().ok());
+            bars.extend(
+                chunk
+                    .into_iter()
+                    .filter(|bar| last.is_none() || bar.time().ok() > last),
+            );
+        }
+
+        bars.retain(|bar| bar_in_range(bar, start, end));
+        Ok(bars)
+    }
+
+    /// Timestamp of the earliest historical data TWS has for a contract.
+    ///
+    /// # Arguments
+    /// * `contract` - The contract to look up
+    /// * `what_to_show` - The type of data
+    /// * `use_rth` - Only consider regular trading hours
+    pub async fn head_timestamp(
+        &self,
+        contract: &Contract,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<DateTime> {
+        self.check_what_to_show(what_to_show)?;
+        let params = format!(
+            "symbol={} sec_type={} what_to_show={} use_rth={}",
+            contract.symbol, contract.sec_type, what_to_show, use_rth
+        );
+        let (req_id, rx) = self.register(RequestKind::HeadTimestamp, params).await;
+        let request = codec::req_head_timestamp(req_id, contract, what_to_show, use_rth);
+        self.send_request(req_id, &request).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(30)).await? {
+            ResponseMessage::HeadTimestamp(timestamp) => DateTime::parse(&timestamp),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Download every bar from the earliest data TWS has up to now.
+    ///
+    /// Looks up the start with [`Client::head_timestamp`] and downloads
+    /// with a [`HistoricalDownloader`], calling `progress` after each
+    /// request. Long backfills at small bar sizes take many paced
+    /// requests; use the downloader directly to process bars as they
+    /// arrive.
+    pub async fn backfill_all(
+        self: &Arc<Self>,
+        contract: Contract,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+        mut progress: impl FnMut(&BackfillProgress),
+    ) -> Result<Vec<BarData>> {
+        let start = self.head_timestamp(&contract, what_to_show, use_rth).await?;
+        let Some(from) = start.timestamp() else {
+            return Err(Error::Protocol(format!("Head timestamp {start} isn't UTC")));
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let end = DateTime::from_timestamp(now.as_secs() as i64);
+        let total = split_range(from, now.as_secs() as i64, bar_size).len();
+
+        let mut download = HistoricalDownloader::new(self.clone(), contract, bar_size, &start, &end)
+            .what_to_show(what_to_show)
+            .use_rth(use_rth);
+        let mut bars = Vec::new();
+        while let Some(chunk) = download.next_chunk().await? {
+            bars.extend(chunk);
+            progress(&BackfillProgress {
+                requests_done: total - download.remaining().unwrap_or(0),
+                requests_total: total,
+                bars: bars.len(),
+                through: bars.last().and_then(|bar| bar.time().ok()),
+            });
+        }
+        Ok(bars)
+    }
+
+    /// Request one chunk of a bar range, ending at `end` (epoch seconds).
+    async fn historical_data_chunk(
+        &self,
+        contract: &Contract,
+        end: i64,
+        duration: HistDuration,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Vec<BarData>> {
+        if contract.sec_type == SecurityType::ContinuousFuture {
+            return Err(Error::InvalidHistoricalRequest(
+                "continuous futures bars can't have an end time".into(),
+            ));
+        }
+        let end_time = DateTime::from_timestamp(end);
+        let params = format!(
+            "symbol={} sec_type={} end={} duration={} bar_size={} what_to_show={} use_rth={}",
+            contract.symbol, contract.sec_type, end_time, duration, bar_size, what_to_show, use_rth
+        );
+        self.request_bars(params, |req_id| {
+            HistoricalDataRequest::new(req_id, contract.clone())
+                .end_at_for(&end_time, None, self.server_version())
+                .duration(duration)
+                .bar_size(bar_size)
+                .what_to_show(what_to_show)
+                .use_rth(use_rth)
+                .format_date(DateFormat::Unix)
+        })
+        .await
+    }
+
+    /// One chunk of a bar range, retrying after pacing violations.
+    pub(crate) async fn historical_data_chunk_paced(
+        &self,
+        contract: &Contract,
+        end: i64,
+        duration: HistDuration,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Vec<BarData>> {
+        let mut retries = 0;
+        loop {
+            let result = self
+                .historical_data_chunk(contract, end, duration, bar_size, what_to_show, use_rth)
+                .await;
+            match result {
+                Err(ref error) if is_pacing_violation(error) && retries < MAX_PACING_RETRIES => {
+                    retries += 1;
+                    tracing::debug!(retries, "historical bars pacing violation, backing off");
+                    tokio::time::sleep(PACING_BACKOFF).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Fail early for bar data types the connected server doesn't know.
+    pub(crate) fn check_what_to_show(&self, what_to_show: WhatToShow) -> Result<()> {
+        let required = what_to_show.min_server_version();
+        if self.shared.server_version < required {
+            return Err(Error::Protocol(format!(
+                "{what_to_show} requires server version {required}, connected to {}",
+                self.shared.server_version
+            )));
+        }
+        Ok(())
+    }
+
+    /// Cancel a historical data request.
+    ///
+    /// A caller still waiting for the bars fails with
+    /// [`Error::NotConnected`].
+    pub async fn cancel_historical_data(&self, req_id: i32) -> Result<()> {
+        self.shared.pending.lock().await.remove(&req_id);
+        self.send(&codec::cancel_historical_data(req_id)).await
+    }
+
+    /// Send a historical data request built for a fresh request ID.
+    ///
+    /// Requests that time out are cancelled, since TWS keeps working on
+    /// them and they count against the pacing limits.
+    async fn request_bars(
+        &self,
+        params: String,
+        build: impl FnOnce(i32) -> HistoricalDataRequest,
+    ) -> Result<Vec<BarData>> {
+        let (req_id, rx) = self.register(RequestKind::HistoricalData, params).await;
+        let request = build(req_id).encode(self.server_version());
+        self.send_request(req_id, &request).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(30)).await {
+            Ok(ResponseMessage::HistoricalData(response)) => Ok(response.bars),
+            Ok(_) => Err(Error::Protocol("Unexpected response type".into())),
+            Err(Error::Timeout) => {
+                let _ = self.send(&codec::cancel_historical_data(req_id)).await;
+                Err(Error::Timeout)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Request up to 1000 historical ticks starting at `start`.
+    ///
+    /// The tick kind is picked by the return type: [`HistoricalTickLast`]
+    /// for trades, [`HistoricalTickBidAsk`] for quotes and [`HistoricalTick`]
+    /// for midpoints.
+    ///
+    /// # Arguments
+    /// * `contract` - The contract to request ticks for
+    /// * `start` - Time of the first tick
+    /// * `number_of_ticks` - How many ticks to return (at most 1000)
+    /// * `use_rth` - Only return ticks from regular trading hours
+    pub async fn historical_ticks<T: HistoricalTickData>(
+        &self,
+        contract: Contract,
+        start: &DateTime,
+        number_of_ticks: i32,
+        use_rth: bool,
+    ) -> Result<Vec<T>> {
+        let params = format!(
+            "symbol={} sec_type={} start={} count={} what_to_show={} use_rth={}",
+            contract.symbol, contract.sec_type, start, number_of_ticks, T::WHAT_TO_SHOW, use_rth
+        );
+        let (req_id, rx) = self.register(RequestKind::HistoricalTicks, params).await;
+
+        let request = HistoricalTicksRequest::new::<T>(req_id, contract)
+            .start(start.clone())
+            .number_of_ticks(number_of_ticks)
+            .use_rth(use_rth);
+        self.send_request(req_id, &request.encode(self.server_version())).await?;
+
+        match self.wait_response(req_id, rx, Duration::from_secs(30)).await? {
+            ResponseMessage::HistoricalTicks(ticks) => T::from_ticks(ticks)
+                .ok_or_else(|| Error::Protocol("Unexpected historical tick type".into())),
+            _ => Err(Error::Protocol("Unexpected response type".into())),
+        }
+    }
+
+    /// Request every historical tick between `start` and `end` (inclusive).
+    ///
+    /// Pages through the range 1000 ticks at a time, starting each page at
+    /// the last tick returned and dropping the repeated boundary ticks.
+    /// Pacing violations are retried after a back-off. Both times must be
+    /// UTC (see [`DateTime::utc`]).
+    pub async fn historical_ticks_range<T: HistoricalTickData>(
+        &self,
+        contract: Contract,
+        start: &DateTime,
+        end: &DateTime,
+        use_rth: bool,
+    ) -> Result<Vec<T>> {
+        let (Some(mut cursor), Some(end)) = (start.timestamp(), end.timestamp()) else {
+            return Err(Error::Protocol("Tick range bounds must be UTC".into()));
+        };
+
+        let mut ticks: Vec<T> = Vec::new();
+        while cursor <= end {
+            let page = self.historical_ticks_paced(&contract, cursor, use_rth).await?;
+            let full = page.len() >= MAX_TICKS_PER_REQUEST as usize;
+            append_page(&mut ticks, page, cursor);
+
+            let last = match ticks.last() {
+                Some(tick) if full => tick.time(),
+                _ => break,
+            };
+            if last == cursor {
+                // More than a full page within one second; TWS can't page
+                // inside a second, so move on to the next one.
+                tracing::warn!(time = cursor, "historical ticks truncated within one second");
+                cursor += 1;
+            } else {
+                cursor = last;
+            }
+        }
+
+        ticks.retain(|tick| tick.time() <= end);
+        Ok(ticks)
+    }
+
+    /// One page of ticks, retrying after pacing violations.
+    async fn historical_ticks_paced<T: HistoricalTickData>(
+        &self,
+        contract: &Contract,
+        start: i64,
+        use_rth: bool,
+    ) -> Result<Vec<T>> {
+        let start = DateTime::from_timestamp(start);
+        let mut retries = 0;
+        loop {
+            let result = self
+                .historical_ticks(contract.clone(), &start, MAX_TICKS_PER_REQUEST, use_rth)
+                .await;
+            match result {
+                Err(ref error) if is_pacing_violation(error) && retries < MAX_PACING_RETRIES => {
+                    retries += 1;
+                    tracing::debug!(retries, "historical ticks pacing violation, backing off");
+                    tokio::time::sleep(PACING_BACKOFF).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get the exchange rate between two currencies.
+    ///
+    /// Uses the midpoint of an IDEALPRO bid/ask snapshot for the pair,
+    /// trying the inverse pair if TWS doesn't list it in the requested
+    /// direction. If neither exists, the rate is crossed through USD. Fails
+    /// if TWS has no bid or ask, e.g. while the FX market is closed.
+    ///
+    /// # Arguments
+    /// * `base` - Base currency (e.g., "EUR")
+    /// * `quote` - Quote currency (e.g., "JPY")
+    pub async fn fx_rate(&self, base: &str, quote: &str) -> Result<FxRate> {
+        let base = base.to_uppercase();
+        let quote = quote.to_uppercase();
+
+        if base == quote {
+            return Ok(FxRate {
+                base,
+                quote,
+                rate: 1.0,
+                time: String::new(),
+            });
+        }
+
+        match self.fx_pair_rate(&base, &quote).await {
+            Err(Error::Tws { code: NO_SECURITY_DEFINITION, .. })
+                if base != "USD" && quote != "USD" =>
+            {
+                let to_usd = self.fx_pair_rate(&base, "USD").await?;
+                let from_usd = self.fx_pair_rate("USD", &quote).await?;
+                Ok(to_usd.cross(&from_usd))
+            }
+            result => result,
+        }
+    }
+
+    /// Rate for a directly quoted pair, in either direction.
+    async fn fx_pair_rate(&self, base: &str, quote: &str) -> Result<FxRate> {
+        match self.fx_midpoint(base, quote).await {
+            Err(Error::Tws { code: NO_SECURITY_DEFINITION, .. }) => {
+                Ok(self.fx_midpoint(quote, base).await?.invert())
+            }
+            result => result,
+        }
+    }
+
+    /// Current IDEALPRO midpoint for a pair as listed by TWS.
+    async fn fx_midpoint(&self, base: &str, quote: &str) -> Result<FxRate> {
+        let snapshot = self.quote(idealpro_contract(base, quote)).await?;
+        let rate = snapshot
+            .midpoint()
+            .ok_or_else(|| Error::Protocol(format!("No bid/ask for {base}.{quote}")))?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(FxRate {
+            base: base.to_string(),
+            quote: quote.to_string(),
+            rate,
+            time: DateTime::from_timestamp(now.as_secs() as i64).to_string(),
+        })
+    }
+
+    /// Allocate a request ID and register a pending response for it.
+    async fn register(
+        &self,
+        kind: RequestKind,
+        params: String,
+    ) -> (i32, oneshot::Receiver<ResponseMessage>) {
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+
+        let mut pending = self.shared.pending.lock().await;
+        pending.insert(
+            req_id,
+            PendingRequest {
+                info: RequestInfo::new(kind, params),
+                tx,
+            },
+        );
+
+        (req_id, rx)
+    }
+
+    /// Register a subscription route for an allocated request ID.
+    ///
+    /// `cancel` is the message sent to TWS when the subscription is dropped.
+    async fn subscribe<T>(
+        &self,
+        req_id: i32,
+        kind: RequestKind,
+        params: String,
+        decode: fn(StreamMessage) -> Option<T>,
+        cancel: String,
+    ) -> Subscription<T> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.shared.subscriptions.lock().await.insert(
+            req_id,
+            ActiveSubscription {
+                info: RequestInfo::new(kind, params),
+                tx,
+                delayed_fallback: None,
+            },
+        );
+
+        let shared = self.shared.clone();
+        let writer = self.writer.clone();
+        let cancel = Box::new(move || {
+            // Drop can't await, so the cancel is sent from a task. Without
+            // a runtime there is no connection left to cancel on anyway.
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    shared.subscriptions.lock().await.remove(&req_id);
+                    // Requests without a cancel message only drop their route.
+                    if !cancel.is_empty() && shared.connected.load(Ordering::SeqCst) {
+                        let mut writer = writer.lock().await;
+                        let _ = Self::send_raw(&mut writer, &cancel).await;
+                    }
+                });
+            }
+        });
+
+        Subscription::new(req_id, rx, decode, cancel)
+    }
+
+    /// Wait for the response to a registered request.
+    ///
+    /// TWS errors, disconnects and timeouts are mapped to their `Error`
+    /// variants; a timed-out request is dropped from the pending map.
+    async fn wait_response(
+        &self,
+        req_id: i32,
+        rx: oneshot::Receiver<ResponseMessage>,
+        limit: Duration,
+    ) -> Result<ResponseMessage> {
+        match timeout(limit, rx).await {
+            Ok(Ok(ResponseMessage::Error {
+                code,
+                message,
+                context,
+            })) => Err(Error::Tws {
+                code,
+                message,
+                context: Some(context),
+            }),
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(Error::NotConnected),
+            Err(_) => {
+                self.shared.pending.lock().await.remove(&req_id);
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Let a market data route re-send `request` for delayed data once.
+    async fn set_delayed_fallback(&self, req_id: i32, request: String) {
+        let shared = self.shared.clone();
+        let writer = self.writer.clone();
+        let fallback = Box::new(move || {
+            // Called from the reader task, which can't wait on the writer.
+            tokio::spawn(async move {
+                if !shared.connected.load(Ordering::SeqCst) {
+                    return;
+                }
+                let mut writer = writer.lock().await;
+                let delayed = codec::req_market_data_type(MarketDataType::Delayed);
+                if Self::send_raw(&mut writer, &delayed).await.is_ok() {
+                    let _ = Self::send_raw(&mut writer, &request).await;
+                }
+            });
+        });
+        if let Some(route) = self.shared.subscriptions.lock().await.get_mut(&req_id) {
+            route.delayed_fallback = Some(fallback);
+        }
+    }
+
+    /// Send a request, dropping its pending entry or route if the send fails.
+    async fn send_request(&self, req_id: i32, payload: &str) -> Result<()> {
+        if self.capture_payloads.load(Ordering::SeqCst) {
+            let payload = Some(payload.to_string());
+            if let Some(request) = self.shared.pending.lock().await.get_mut(&req_id) {
+                request.info.payload = payload;
+            } else if let Some(route) = self.shared.subscriptions.lock().await.get_mut(&req_id) {
+                route.info.payload = payload;
+            }
+        }
+
+        let result = self.send(payload).await;
+        if result.is_err() {
+            self.shared.pending.lock().await.remove(&req_id);
+            self.shared.subscriptions.lock().await.remove(&req_id);
+        }
+        result
+    }
+
+    async fn send(&self, payload: &str) -> Result<()> {
+        if !self.shared.connected.load(Ordering::SeqCst) {
+            return Err(Error::NotConnected);
+        }
+        let mut writer = self.writer.lock().await;
+        Self::send_raw(&mut writer, payload).await
+    }
+
+    async fn send_raw(writer: &mut WriteHalf<S>, payload: &str) -> Result<()> {
+        writer.write_all(&codec::encode_frame(payload)).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Remove the oldest pending request of the given kind.
+    ///
+    /// Used for responses that don't carry a request ID.
+    fn take_pending_by_kind(
+        pending: &mut HashMap<i32, PendingRequest>,
+        kind: RequestKind,
+    ) -> Option<PendingRequest> {
+        let req_id = pending
+            .iter()
+            .filter(|(_, request)| request.info.kind == kind)
+            .map(|(req_id, _)| *req_id)
+            .min()?;
+        pending.remove(&req_id)
+    }
+
+    fn log_request_error(req_id: i32, info: &RequestInfo, code: i32, message: &str) {
+        tracing::warn!(
+            req_id,
+            kind = info.kind.as_str(),
+            params = %info.params,
+            code,
+            message,
+            "TWS error for request"
+        );
+    }
+
+    /// Forward a message to the subscription registered for `req_id`.
+    async fn route_stream(shared: &Shared, req_id: i32, message: StreamMessage) {
+        let subscriptions = shared.subscriptions.lock().await;
+        if let Some(route) = subscriptions.get(&req_id) {
+            let _ = route.tx.send(message);
+        }
+    }
+
+    /// Pass a status update to the tracker of a placed order, dropping the
+    /// route once the order is done.
+    fn track_status(shared: &Shared, status: &OrderStatus) {
+        let mut tracked_orders = shared.tracked_orders.lock().unwrap();
+        let Some(tracked) = tracked_orders.get(&status.order_id) else {
+            return;
+        };
+        tracked.latest.send_replace(Some(status.clone()));
+        let dropped = tracked.updates.send(status.clone()).is_err();
+        if dropped || status.is_done() {
+            tracked_orders.remove(&status.order_id);
+        }
+    }
+
+    /// End the tracker of a placed order that TWS rejected. Warnings and
+    /// cancel confirmations leave it running; its status reports those.
+    fn track_error(shared: &Shared, order_id: i32, code: i32, message: &str) {
+        if matches!(code, ORDER_WARNING | ORDER_CANCELLED | 2100..=2169) {
+            return;
+        }
+        let Some(tracked) = shared.tracked_orders.lock().unwrap().remove(&order_id) else {
+            return;
+        };
+        let _ = tracked.rejection.send(Self::order_error(order_id, code, message.to_string()));
+    }
+
+    /// The error for a TWS error reported against an order.
+    fn order_error(order_id: i32, code: i32, message: String) -> Error {
+        match code {
+            DUPLICATE_ORDER_ID => Error::DuplicateOrderId(order_id),
+            _ => Error::Tws {
+                code,
+                message,
+                context: None,
+            },
+        }
+    }
+
+    /// Forward a message to the oldest subscription of the given kind.
+    ///
+    /// Used for streamed messages that don't carry a request ID.
+    async fn route_stream_by_kind(shared: &Shared, kind: RequestKind, message: StreamMessage) {
+        let subscriptions = shared.subscriptions.lock().await;
+        let route = subscriptions
+            .iter()
+            .filter(|(_, route)| route.info.kind == kind)
+            .min_by_key(|(req_id, _)| **req_id);
+        if let Some((_, route)) = route {
+            let _ = route.tx.send(message);
+        }
+    }
+
+    /// Collect a historical ticks message, completing the request once the
+    /// last message for it arrives.
+    async fn complete_ticks(shared: &Shared, req_id: i32, ticks: HistoricalTicks, done: bool) {
+        let ticks = {
+            let mut partial_ticks = shared.partial_ticks.lock().unwrap();
+            let ticks = match partial_ticks.remove(&req_id) {
+                Some(mut earlier) => {
+                    earlier.extend(ticks);
+                    earlier
+                }
+                None => ticks,
+            };
+            if !done {
+                partial_ticks.insert(req_id, ticks);
+                return;
+            }
+            ticks
+        };
+        if let Some(request) = shared.pending.lock().await.remove(&req_id) {
+            let _ = request.tx.send(ResponseMessage::HistoricalTicks(ticks));
+        }
+    }
+
+    /// Decode a frame and route it to its request, subscription or the events stream.
+    async fn dispatch_message(buf: &[u8], shared: &Shared) {
+        let Some(message) = codec::decode(buf, shared.server_version) else {
+            return;
+        };
+
+        match message {
+            Incoming::AccountValue(value) => {
+                let _ = shared.events.send(Event::AccountValue(value));
+            }
+            Incoming::AccountUpdateTime(time) => {
+                let _ = shared.events.send(Event::AccountUpdateTime(time));
+            }
+            Incoming::PortfolioValue(value) => {
+                let _ = shared.events.send(Event::PortfolioValue(value));
+            }
+            Incoming::NextValidId(order_id) => {
+                let _ = shared.initial_order_id.set(order_id);
+                shared.next_order_id.fetch_max(order_id, Ordering::SeqCst);
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::NextValidId)
+                {
+                    let _ = request.tx.send(ResponseMessage::NextValidId(order_id));
+                }
+                let _ = shared.events.send(Event::NextValidId(order_id));
+            }
+            Incoming::ManagedAccounts(accounts) => {
+                *shared.managed_accounts.lock().unwrap() = accounts.clone();
+                let _ = shared.events.send(Event::ManagedAccounts(accounts));
+            }
+            Incoming::FaConfiguration { xml, .. } => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::AccountAliases)
+                {
+                    let _ = request.tx.send(ResponseMessage::FaConfiguration(xml));
+                }
+            }
+            Incoming::NewsBulletin(bulletin) => {
+                let _ = shared.events.send(Event::NewsBulletin(bulletin));
+            }
+            Incoming::OrderStatus(status) => {
+                tracing::debug!(
+                    order_id = status.order_id,
+                    status = %status.status,
+                    filled = %status.filled,
+                    "order status"
+                );
+                Self::track_status(shared, &status);
+                shared.order_events.send(OrderEvent::Status(status));
+            }
+            Incoming::OpenOrder(open) => {
+                // Open orders of this client (e.g. from an earlier session)
+                // hold their IDs; keep the allocator past them.
+                if open.client_id == shared.client_id && open.order_id > 0 {
+                    shared.used_order_ids.lock().unwrap().insert(open.order_id);
+                    let _ = shared.next_order_id.fetch_update(
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                        |id| (id > 0 && id <= open.order_id).then_some(open.order_id + 1),
+                    );
+                }
+                let message = StreamMessage::OpenOrder(open.clone());
+                Self::route_stream_by_kind(shared, RequestKind::OpenOrders, message).await;
+                shared.order_events.send(OrderEvent::Open(open));
+            }
+            Incoming::OpenOrderEnd => {
+                let message = StreamMessage::OpenOrderEnd;
+                Self::route_stream_by_kind(shared, RequestKind::OpenOrders, message).await;
+            }
+            Incoming::OrderBound {
+                order_id,
+                api_client_id,
+                api_order_id,
+            } => {
+                let _ = shared.events.send(Event::OrderBound {
+                    order_id,
+                    api_client_id,
+                    api_order_id,
+                });
+            }
+            Incoming::AccountDownloadEnd(_) => {
+                // Signal completion to the oldest pending account request
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::AccountValues)
+                {
+                    let _ = request.tx.send(ResponseMessage::AccountValues(vec![]));
+                }
+            }
+            Incoming::HistoricalData { req_id, response } => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) = pending.remove(&req_id) {
+                    let _ = request.tx.send(ResponseMessage::HistoricalData(response));
+                    return;
+                }
+                drop(pending);
+                // The initial bars of a keepUpToDate request
+                let subscriptions = shared.subscriptions.lock().await;
+                if let Some(route) = subscriptions.get(&req_id) {
+                    for bar in response.bars {
+                        let _ = route.tx.send(StreamMessage::Bar(bar));
+                    }
+                }
+            }
+            Incoming::HistoricalDataUpdate { req_id, bar } => {
+                Self::route_stream(shared, req_id, StreamMessage::Bar(bar)).await;
+            }
+            Incoming::ContractDetails { req_id, details } => {
+                Self::route_stream(shared, req_id, StreamMessage::ContractDetails(details)).await;
+            }
+            Incoming::ContractDetailsEnd { req_id } => {
+                Self::route_stream(shared, req_id, StreamMessage::ContractDetailsEnd).await;
+            }
+            Incoming::OptionParams { req_id, params } => {
+                Self::route_stream(shared, req_id, StreamMessage::OptionParams(params)).await;
+            }
+            Incoming::OptionParamsEnd { req_id } => {
+                Self::route_stream(shared, req_id, StreamMessage::OptionParamsEnd).await;
+            }
+            Incoming::HeadTimestamp { req_id, timestamp } => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) = pending.remove(&req_id) {
+                    let _ = request.tx.send(ResponseMessage::HeadTimestamp(timestamp));
+                }
+            }
+            Incoming::HistoricalTicks {
+                req_id,
+                ticks,
+                done,
+            } => {
+                Self::complete_ticks(shared, req_id, ticks, done).await;
+            }
+            Incoming::ScannerParameters(xml) => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::ScannerParameters)
+                {
+                    let _ = request.tx.send(ResponseMessage::ScannerParameters(xml));
+                }
+            }
+            Incoming::ScannerData { req_id, rows } => {
+                Self::route_stream(shared, req_id, StreamMessage::ScannerData(rows)).await;
+            }
+            Incoming::CurrentTime(time) => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::CurrentTime)
+                {
+                    let _ = request.tx.send(ResponseMessage::CurrentTime(time));
+                }
+            }
+            Incoming::AccountSummary { req_id, value } => {
+                Self::route_stream(shared, req_id, StreamMessage::AccountSummary(value)).await;
+            }
+            Incoming::AccountSummaryEnd { req_id } => {
+                Self::route_stream(shared, req_id, StreamMessage::AccountSummaryEnd).await;
+            }
+            Incoming::Position(position) => {
+                let message = StreamMessage::Position(position);
+                Self::route_stream_by_kind(shared, RequestKind::Positions, message).await;
+            }
+            Incoming::PositionEnd => {
+                let message = StreamMessage::PositionEnd;
+                Self::route_stream_by_kind(shared, RequestKind::Positions, message).await;
+            }
+            Incoming::PnlSingle { req_id, pnl } => {
+                Self::route_stream(shared, req_id, StreamMessage::PnlSingle(pnl)).await;
+            }
+            Incoming::TickPrice {
+                req_id,
+                tick_type,
+                price,
+                attr_mask,
+                ..
+            } => {
+                let message = StreamMessage::TickPrice {
+                    tick_type,
+                    price,
+                    attr_mask,
+                };
+                Self::route_stream(shared, req_id, message).await;
+            }
+            Incoming::TickSize {
+                req_id,
+                tick_type,
+                size,
+            } => {
+                let message = StreamMessage::TickSize { tick_type, size };
+                Self::route_stream(shared, req_id, message).await;
+            }
+            Incoming::TickString {
+                req_id,
+                tick_type,
+                value,
+            } => {
+                let message = StreamMessage::TickString { tick_type, value };
+                Self::route_stream(shared, req_id, message).await;
+            }
+            Incoming::TickGeneric {
+                req_id,
+                tick_type,
+                value,
+            } => {
+                let message = StreamMessage::TickGeneric { tick_type, value };
+                Self::route_stream(shared, req_id, message).await;
+            }
+            Incoming::MarketDepth { req_id, update } => {
+                Self::route_stream(shared, req_id, StreamMessage::MarketDepth(update)).await;
+            }
+            Incoming::MarketDepthExchanges(exchanges) => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) =
+                    Self::take_pending_by_kind(&mut pending, RequestKind::MarketDepthExchanges)
+                {
+                    let _ = request.tx.send(ResponseMessage::MarketDepthExchanges(exchanges));
+                }
+            }
+            Incoming::SmartComponents { req_id, components } => {
+                let mut pending = shared.pending.lock().await;
+                if let Some(request) = pending.remove(&req_id) {
+                    let _ = request.tx.send(ResponseMessage::SmartComponents(components));
+                }
+            }
+            Incoming::MarketRule(rule) => {
+                let params = format!("market_rule_id={}", rule.id);
+                let mut pending = shared.pending.lock().await;
+                let req_id = pending
+                    .iter()
+                    .filter(|(_, request)| request.info.kind == RequestKind::MarketRule)
+                    .find(|(_, request)| request.info.params == params)
+                    .map(|(req_id, _)| *req_id);
+                if let Some(request) = req_id.and_then(|req_id| pending.remove(&req_id)) {
+                    let _ = request.tx.send(ResponseMessage::MarketRule(rule));
+                }
+            }
+            Incoming::MarketDataType { req_id, data_type } => {
+                Self::route_stream(shared, req_id, StreamMessage::MarketDataType(data_type)).await;
+            }
+            Incoming::TickSnapshotEnd { req_id } => {
+                // The snapshot is over on the TWS side, so drop its route.
+                if let Some(route) = shared.subscriptions.lock().await.remove(&req_id) {
+                    let _ = route.tx.send(StreamMessage::TickSnapshotEnd);
+                }
+            }
+            Incoming::ExecutionData { req_id: -1, fill } => {
+                shared
+                    .uncommissioned
+                    .lock()
+                    .unwrap()
+                    .insert(fill.execution.exec_id.clone(), (*fill).clone());
+                shared.fills.send(FillEvent::Executed(*fill));
+            }
+            Incoming::ExecutionData { req_id, fill } => {
+                shared
+                    .queried_executions
+                    .lock()
+                    .unwrap()
+                    .insert(fill.execution.exec_id.clone(), req_id);
+                Self::route_stream(shared, req_id, StreamMessage::Execution(fill)).await;
+            }
+            Incoming::ExecutionDataEnd { req_id } => {
+                shared.queried_executions.lock().unwrap().retain(|_, id| *id != req_id);
+                Self::route_stream(shared, req_id, StreamMessage::ExecutionEnd).await;
+            }
+            Incoming::CommissionReport(report) => {
+                let fill = shared.uncommissioned.lock().unwrap().remove(&report.exec_id);
+                let queried = shared.queried_executions.lock().unwrap().remove(&report.exec_id);
+                match (fill, queried) {
+                    (Some(mut fill), _) => {
+                        fill.commission = Some(report);
+                        shared.fills.send(FillEvent::Commissioned(fill));
+                    }
+                    (None, Some(req_id)) => {
+                        Self::route_stream(shared, req_id, StreamMessage::Commission(report)).await;
+                    }
+                    (None, None) => {
+                        tracing::debug!(exec_id = %report.exec_id, "commission report for unknown execution");
+                    }
+                }
+            }
+            Incoming::TickReqParams { req_id, params } => {
+                let subscriptions = shared.subscriptions.lock().await;
+                match subscriptions.get(&req_id) {
+                    Some(route) => {
+                        let _ = route.tx.send(StreamMessage::TickReqParams(params));
+                    }
+                    None => {
+                        let _ = shared.events.send(Event::TickReqParams { req_id, params });
+                    }
+                }
+            }
+            Incoming::Error {
+                req_id,
+                code,
+                message,
+            } => {
+                // Entitlement problems are also announced on the events
+                // stream so callers can fall back to delayed data.
+                if matches!(code, NOT_SUBSCRIBED | COMPETING_LIVE_SESSION) {
+                    let _ = shared.events.send(Event::MarketDataUnavailable {
+                        req_id,
+                        code,
+                        message: message.clone(),
+                        competing_session: code == COMPETING_LIVE_SESSION,
+                    });
+                }
+
+                if MARKET_DATA_NOTICES.contains(&code) {
+                    tracing::debug!(req_id, code, message = %message, "market data notice");
+                    let _ = shared.events.send(Event::Error(UnroutableError {
+                        req_id,
+                        code,
+                        message,
+                    }));
+                    return;
+                }
+
+                // FA requests carry no request ID, so their rejection for
+                // non-FA logins goes to the oldest pending alias lookup.
+                if req_id <= 0 && code == VALIDATION_ERROR && message.contains("FA") {
+                    let mut pending = shared.pending.lock().await;
+                    if let Some(request) =
+                        Self::take_pending_by_kind(&mut pending, RequestKind::AccountAliases)
+                    {
+                        let context = Box::new(request.info.context(req_id));
+                        let _ = request.tx.send(ResponseMessage::Error {
+                            code,
+                            message,
+                            context,
+                        });
+                        return;
+                    }
+                }
+
+                // Errors against an order belong to the order, never to a
+                // request or subscription with the same ID.
+                let order_error = shared.orders.lock().unwrap().contains_key(&req_id);
+                if order_error {
+                    Self::track_error(shared, req_id, code, &message);
+                }
+                if req_id > 0 && !order_error {
+                    if let Some(request) = shared.pending.lock().await.remove(&req_id) {
+                        Self::log_request_error(req_id, &request.info, code, &message);
+                        let context = Box::new(request.info.context(req_id));
+                        let _ = request.tx.send(ResponseMessage::Error {
+                            code,
+                            message,
+                            context,
+                        });
+                        return;
+                    }
+
+                    let mut subscriptions = shared.subscriptions.lock().await;
+                    if matches!(code, NOT_SUBSCRIBED | COMPETING_LIVE_SESSION)
+                        && let Some(route) = subscriptions.get_mut(&req_id)
+                        && let Some(fallback) = route.delayed_fallback.take()
+                    {
+                        tracing::info!(req_id, code, "live market data refused, using delayed");
+                        fallback();
+                        return;
+                    }
+                    if let Some(route) = subscriptions.remove(&req_id) {
+                        Self::log_request_error(req_id, &route.info, code, &message);
+                        let context = Some(Box::new(route.info.context(req_id)));
+                        let _ = route.tx.send(StreamMessage::Error(Error::Tws {
+                            code,
+                            message,
+                            context,
+                        }));
+                        return;
+                    }
+                }
+
+                tracing::debug!(req_id, code, message = %message, "unroutable TWS error");
+                let error = UnroutableError {
+                    req_id,
+                    code,
+                    message,
+                };
+                let _ = shared.unroutable_errors.send(error.clone());
+                let _ = shared.events.send(Event::Error(error));
+            }
+            Incoming::Unhandled { msg_id, fields } => {
+                let _ = shared.events.send(Event::Unhandled { msg_id, fields });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical_ticks::HistoricalTickLast;
+    use crate::market_depth::DepthSide;
+    use crate::order::OrderAction;
+    use crate::testing::{accept, connected_client, read_frame, write_frame};
+
+    #[tokio::test]
+    async fn test_connect_with_duplex() {
+        let (client_side, mut server) = tokio::io::duplex(4096);
+
+        let server_task = tokio::spawn(async move {
+            let mut prefix = [0u8; 4];
+            server.read_exact(&mut prefix).await.unwrap();
+            assert_eq!(&prefix, b"API\0");
+            assert_eq!(read_frame(&mut server).await, b"v100..176");
+
+            // Version frame followed immediately by a second message
+            write_frame(&mut server, "176\x0020240105 09:30:00 EST\0").await;
+            write_frame(&mut server, "9\x001\x0042\0").await;
+
+            let start_api = read_frame(&mut server).await;
+            assert!(start_api.starts_with(b"71\x002\x007\0"));
+
+            let current_time = read_frame(&mut server).await;
+            assert_eq!(current_time, b"49\x001\0");
+            write_frame(&mut server, "49\x001\x001704465000\0").await;
+            server
+        });
+
+        let client = Client::connect_with(client_side, 7).await.unwrap();
+        assert_eq!(client.server_version(), 176);
+        assert_eq!(client.current_time().await.unwrap(), 1_704_465_000);
+
+        let info = client.connection_info();
+        assert_eq!(info.client_id, 7);
+        assert_eq!(info.connection_time, "20240105 09:30:00 EST");
+        assert_eq!(info.next_valid_id, Some(42));
+        assert_eq!(info.endpoint, None);
+
+        drop(server_task.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_fails_in_flight_requests() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut ticks = client.market_data(contract, &[], false).await.unwrap();
+        read_frame(&mut server).await;
+        assert!(client.is_connected());
+
+        let (time, _) = tokio::join!(client.current_time(), async move {
+            assert_eq!(read_frame(&mut server).await, b"49\x001\0");
+            drop(server);
+        });
+        assert!(matches!(time, Err(Error::NotConnected)));
+        assert!(ticks.next().await.is_none());
+        assert!(matches!(ticks.take_error(), Some(Error::NotConnected)));
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let (client, mut server) = connected_client(1).await;
+        let (rtt, _) = tokio::join!(client.ping(Duration::from_secs(1)), async {
+            assert_eq!(read_frame(&mut server).await, b"49\x001\0");
+            write_frame(&mut server, "49\x001\x001704465000\0").await;
+        });
+        assert!(rtt.unwrap() < Duration::from_secs(1));
+        assert!(client.is_connected());
+
+        // TWS not answering is a timeout, not a closed connection
+        let unanswered = client.ping(Duration::from_millis(50)).await;
+        assert!(matches!(unanswered, Err(Error::Timeout)));
+        assert!(client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_entitlement_error_published_as_event() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+
+        write_frame(&mut server, "4\x002\x00-1\x0010197\0No market data during competing live session\0").await;
+
+        loop {
+            match events.recv().await.unwrap() {
+                Event::MarketDataUnavailable {
+                    code,
+                    competing_session,
+                    ..
+                } => {
+                    assert_eq!(code, 10197);
+                    assert!(competing_session);
+                    break;
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quote_snapshot() {
+        let (client, mut server) = connected_client(1).await;
+
+        let server_task = tokio::spawn(async move {
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000000\0"));
+            write_frame(&mut server, "58\x001\x001000000000\x003\0").await;
+            write_frame(&mut server, "1\x006\x001000000000\x001\x00185.5\x00200\x000\0").await;
+            let delayed = "4\x002\x001000000000\x0010167\0Displaying delayed market data\0";
+            write_frame(&mut server, delayed).await;
+            write_frame(&mut server, "2\x006\x001000000000\x000\x00200\0").await;
+            write_frame(&mut server, "57\x001\x001000000000\0").await;
+            server
+        });
+
+        let quote = client.quote(Contract::stock("AAPL", "SMART", "USD")).await.unwrap();
+        assert_eq!(quote.bid, Some(185.5));
+        assert_eq!(quote.bid_size, Some(200.into()));
+        assert_eq!(quote.data_type, MarketDataType::Delayed);
+
+        drop(server_task.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fx_rate_from_inverse_snapshot() {
+        let (client, mut server) = connected_client(1).await;
+        let server_task = tokio::spawn(async move {
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000000\x000\0USD\0CASH\0"));
+            let unknown = "4\x002\x001000000000\x00200\0No security definition\0";
+            write_frame(&mut server, unknown).await;
+
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000001\x000\0EUR\0CASH\0"));
+            write_frame(&mut server, "1\x006\x001000000001\x001\x001.0999\x00100000\x000\0").await;
+            write_frame(&mut server, "1\x006\x001000000001\x002\x001.1001\x00100000\x000\0").await;
+            write_frame(&mut server, "57\x001\x001000000001\0").await;
+            server
+        });
+
+        let rate = client.fx_rate("usd", "eur").await.unwrap();
+        assert_eq!((rate.base.as_str(), rate.quote.as_str()), ("USD", "EUR"));
+        assert!((rate.rate - 1.0 / 1.1).abs() < 1e-9);
+        assert!(rate.time.ends_with(" UTC"));
+        drop(server_task.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_market_data_stream() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let generic_ticks = [GenericTick::RtVolume];
+        let mut ticks = client.market_data(contract, &generic_ticks, false).await.unwrap();
+
+        let request = read_frame(&mut server).await;
+        assert!(request.starts_with(b"1\x0011\x001000000000\x000\0AAPL\0"));
+        assert!(request.ends_with(b"\x00233\x000\x000\0\0"));
+        write_frame(&mut server, "81\x001000000000\x000.01\x009c0001\x003\0").await;
+        write_frame(&mut server, "1\x006\x001000000000\x001\x00185.5\x00200\x001\0").await;
+        let rt_volume = "46\x006\x001000000000\x0048\x00185.5;100;1704465000000;5000;185.4;true\0";
+        write_frame(&mut server, rt_volume).await;
+        write_frame(&mut server, "45\x006\x001000000000\x0049\x000\0").await;
+
+        let attrib = TickAttrib {
+            can_auto_execute: true,
+            ..Default::default()
+        };
+        let price = Tick::Price {
+            tick_type: 1,
+            price: 185.5,
+            attrib,
+        };
+        assert_eq!(ticks.next().await, Some(price));
+        let params = ticks.tick_req_params().unwrap();
+        assert_eq!(params.min_tick, 0.01);
+        assert_eq!(params.bbo_exchange, "9c0001");
+        let trade = ticks.next().await.and_then(|tick| tick.trade()).unwrap();
+        assert_eq!(trade.vwap, 185.4);
+        let halted = Tick::Generic {
+            tick_type: 49,
+            value: 0.0,
+        };
+        assert_eq!(ticks.next().await, Some(halted));
+
+        drop(ticks);
+        assert_eq!(read_frame(&mut server).await, b"2\x002\x001000000000\0");
+    }
+
+    #[tokio::test]
+    async fn test_shared_market_data() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut first = client.shared_market_data(contract.clone(), &[]).await.unwrap();
+        let mut second = client.shared_market_data(contract, &[]).await.unwrap();
+        assert_eq!(first.req_id(), second.req_id());
+
+        read_frame(&mut server).await;
+        write_frame(&mut server, "2\x006\x001000000000\x000\x00300\0").await;
+        let bid_size = Tick::Size {
+            tick_type: 0,
+            size: 300.into(),
+        };
+        assert_eq!(first.next().await, Some(bid_size.clone()));
+        assert_eq!(second.next().await, Some(bid_size));
+
+        // The request stays open while a consumer is left.
+        drop(first);
+        let other = Contract::stock("MSFT", "SMART", "USD");
+        let _other = client.shared_market_data(other, &[]).await.unwrap();
+        assert!(read_frame(&mut server).await.starts_with(b"1\x0011\x001000000001\0"));
+
+        drop(second);
+        assert_eq!(read_frame(&mut server).await, b"2\x002\x001000000000\0");
+    }
+
+    #[tokio::test]
+    async fn test_market_data_type_switch() {
+        let (client, mut server) = connected_client(1).await;
+        client.set_market_data_type(MarketDataType::DelayedFrozen).await.unwrap();
+        assert_eq!(read_frame(&mut server).await, b"59\x001\x004\0");
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut ticks = client.market_data(contract, &[], false).await.unwrap();
+        read_frame(&mut server).await;
+        write_frame(&mut server, "58\x001\x001000000000\x004\0").await;
+        match ticks.next().await {
+            Some(Tick::DataType(data_type)) => {
+                assert!(data_type.is_delayed());
+                assert!(data_type.is_frozen());
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_market_data_falls_back_to_delayed() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        client.fall_back_to_delayed(true);
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut ticks = client.market_data(contract, &[], false).await.unwrap();
+        let request = read_frame(&mut server).await;
+        write_frame(&mut server, "4\x002\x001000000000\x00354\0Not subscribed\0").await;
+
+        // Same request, same ID, after switching the session to delayed data
+        assert_eq!(read_frame(&mut server).await, b"59\x001\x003\0");
+        assert_eq!(read_frame(&mut server).await, request);
+        write_frame(&mut server, "58\x001\x001000000000\x003\0").await;
+        write_frame(&mut server, "1\x006\x001000000000\x0066\x00185.25\x00100\x000\0").await;
+        assert!(matches!(ticks.next().await, Some(Tick::DataType(MarketDataType::Delayed))));
+        assert!(matches!(ticks.next().await, Some(Tick::Price { price: 185.25, .. })));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            Event::MarketDataUnavailable { code: 354, .. }
+        ));
+
+        // Only once: delayed data being refused too ends the stream
+        write_frame(&mut server, "4\x002\x001000000000\x00354\0Not subscribed\0").await;
+        assert!(ticks.next().await.is_none());
+        assert!(matches!(ticks.take_error(), Some(Error::Tws { code: 354, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_market_depth_stream() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut depth = client.market_depth(contract, 5, false).await.unwrap();
+
+        let request = read_frame(&mut server).await;
+        assert!(request.starts_with(b"10\x005\x001000000000\x000\0AAPL\0"));
+        assert!(request.ends_with(b"\0USD\0\0\x005\x000\0\0"));
+        let update = "13\x001\x001000000000\x000\0NSDQ\x000\x001\x00185.5\x00200\x000\0";
+        write_frame(&mut server, update).await;
+
+        let update = depth.next().await.unwrap();
+        assert_eq!(update.market_maker, "NSDQ");
+        assert_eq!(update.side, DepthSide::Bid);
+        assert_eq!(update.size, 200.into());
+
+        drop(depth);
+        assert_eq!(read_frame(&mut server).await, b"11\x001\x001000000000\x000\0");
+
+        let (exchanges, _) = tokio::join!(client.market_depth_exchanges(), async {
+            assert_eq!(read_frame(&mut server).await, b"82\0");
+            write_frame(&mut server, "80\x001\0ISLAND\0STK\0NASDAQ\0Deep2\x002147483647\0").await;
+        });
+        let exchanges = exchanges.unwrap();
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(exchanges[0].listing_exchange, "NASDAQ");
+    }
+
+    #[tokio::test]
+    async fn test_market_rules_by_id() {
+        let (client, mut server) = connected_client(1).await;
+        let (first, second) = (client.market_rule(26), client.market_rule(32));
+        let (first, second, _) = tokio::join!(first, second, async {
+            read_frame(&mut server).await;
+            read_frame(&mut server).await;
+            // Answered out of order
+            write_frame(&mut server, "93\x0032\x001\x000\x000.25\0").await;
+            write_frame(&mut server, "93\x0026\x001\x000\x000.01\0").await;
+        });
+        assert_eq!(first.unwrap().increments[0].increment, 0.01);
+        assert_eq!(second.unwrap().increments[0].increment, 0.25);
+    }
+
+    #[tokio::test]
+    async fn test_smart_components() {
+        let (client, mut server) = connected_client(1).await;
+        let (components, _) = tokio::join!(client.smart_components("9c0001"), async {
+            assert_eq!(read_frame(&mut server).await, b"83\x001000000000\x009c0001\0");
+            write_frame(&mut server, "82\x001000000000\x001\x002\0ARCA\0P\0").await;
+        });
+        assert_eq!(components.unwrap()[&2].exchange, "ARCA");
+    }
+
+    #[tokio::test]
+    async fn test_historical_data_range_splits_requests() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let start = DateTime::utc(2024, 1, 5, 14, 30, 0);
+        let end = DateTime::utc(2024, 1, 5, 15, 20, 0);
+        let (bar_size, what_to_show) = (BarSize::Sec1, WhatToShow::Trades);
+        let request =
+            client.historical_data_range(contract, &start, &end, bar_size, what_to_show, true);
+        let (bars, _) = tokio::join!(request, async {
+            read_frame(&mut server).await;
+            let bars = "17\x001000000000\0\0\x002\0\
+                        1704464999\x001\x001\x001\x001\x001\x001\x001\0\
+                        1704466800\x002\x002\x002\x002\x002\x002\x001\0";
+            write_frame(&mut server, bars).await;
+            read_frame(&mut server).await;
+            let bars = "17\x001000000001\0\0\x001\0\
+                        1704466800\x002\x002\x002\x002\x002\x002\x001\0";
+            write_frame(&mut server, bars).await;
+        });
+
+        // The bar before the range and the repeated boundary bar are dropped.
+        let bars = bars.unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].date, "1704466800");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_historical_data() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let request = client.historical_data(
+            contract,
+            HistDuration::Days(1),
+            BarSize::Min1,
+            WhatToShow::Trades,
+            true,
+        );
+        let (bars, _) = tokio::join!(request, async {
+            read_frame(&mut server).await;
+            client.cancel_historical_data(1_000_000_000).await.unwrap();
+            assert_eq!(read_frame(&mut server).await, b"25\x001\x001000000000\0");
+        });
+        assert!(matches!(bars, Err(Error::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn test_bar_updates() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let (duration, bar_size) = (HistDuration::Seconds(300), BarSize::Min1);
+        let request = client.bar_updates(contract, duration, bar_size, WhatToShow::Trades, true);
+        let (bars, request) = tokio::join!(request, read_frame(&mut server));
+        let mut bars = bars.unwrap();
+        // Empty end time, epoch dates and keepUpToDate
+        assert!(request.ends_with(b"\x00\x001 min\x00300 S\x001\0TRADES\x002\x001\0\0"));
+
+        let initial = "17\x001000000000\0\0\x001\0\
+                       1704466740\x001\x001\x001\x001\x001\x001\x001\0";
+        write_frame(&mut server, initial).await;
+        let update = "90\x001000000000\x002\x001704466740\x001\x002\x002\x001\x001.5\x003\0";
+        write_frame(&mut server, update).await;
+        assert_eq!(bars.next().await.unwrap().close, 1.0);
+        let update = bars.next().await.unwrap();
+        assert_eq!((update.date.as_str(), update.close), ("1704466740", 2.0));
+
+        drop(bars);
+        assert_eq!(read_frame(&mut server).await, b"25\x001\x001000000000\0");
+    }
+
+    #[tokio::test]
+    async fn test_contract_details() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let (details, _) = tokio::join!(client.contract_details(&contract), async {
+            let request = read_frame(&mut server).await;
+            let expected = b"9\x008\x001000000000\x000\0AAPL\0STK\0\0\0\0\0\
+                             SMART\0\0USD\0\0\x000\0\0\0\0";
+            assert_eq!(request, expected);
+            let mut raw = vec!["10", "1000000000", "AAPL", "STK", "", "0", "", "SMART"];
+            raw.extend(["USD", "AAPL"]);
+            raw.extend(["NMS", "NMS", "265598", "0.01", "", "LMT,MKT", "SMART,NASDAQ", "1", "0"]);
+            raw.extend(["APPLE INC"]);
+            raw.extend(["NASDAQ", "", "", "", "", "US/Eastern", "", "", "", "", "0", "1", "", ""]);
+            raw.extend(["26,26", "", "COMMON", "1", "1", "100"]);
+            write_frame(&mut server, &(raw.join("\0") + "\0")).await;
+            write_frame(&mut server, "52\x001\x001000000000\0").await;
+        });
+
+        let details = details.unwrap();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].contract.con_id, 265598);
+        assert_eq!(details[0].long_name, "APPLE INC");
+        assert_eq!(details[0].valid_exchanges, ["SMART", "NASDAQ"]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_conid_caches() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let (con_id, _) = tokio::join!(client.resolve_conid(&contract), async {
+            read_frame(&mut server).await;
+            let mut raw = vec!["10", "1000000000", "AAPL", "STK", "", "0", "", "SMART"];
+            raw.extend(["USD", "AAPL"]);
+            raw.extend(["NMS", "NMS", "265598", "0.01", "", "LMT,MKT", "SMART,NASDAQ", "1", "0"]);
+            raw.extend(["APPLE INC"]);
+            raw.extend(["NASDAQ", "", "", "", "", "US/Eastern", "", "", "", "", "0", "1", "", ""]);
+            raw.extend(["26,26", "", "COMMON", "1", "1", "100"]);
+            write_frame(&mut server, &(raw.join("\0") + "\0")).await;
+            write_frame(&mut server, "52\x001\x001000000000\0").await;
+        });
+        assert_eq!(con_id.unwrap(), 265598);
+
+        // Answered from the cache, without another request
+        assert_eq!(client.resolve_conid(&contract).await.unwrap(), 265598);
+        assert_eq!(client.conid_cache().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_front_month() {
+        let (client, mut server) = connected_client(1).await;
+        let (front, _) = tokio::join!(client.front_month("ES", "CME", "USD", 8), async {
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"9\x008\x001000000000\x000\0ES\0FUT\0\0"));
+            for (expiry, con_id) in [("21000615", "2002"), ("20990320", "2001")] {
+                let mut raw = vec!["10", "1000000000", "ES", "FUT", expiry, "0", "", "CME"];
+                raw.extend(["USD", ""]);
+                raw.extend(["ES", "ES", con_id, "0.25", "50", "LMT", "CME", "1", "0", "", ""]);
+                raw.extend(["", "", "", "", "", "", "", "", "", "", "0", "1", "", "", "", "", ""]);
+                raw.extend(["1", "1", "1"]);
+                write_frame(&mut server, &(raw.join("\0") + "\0")).await;
+            }
+            write_frame(&mut server, "52\x001\x001000000000\0").await;
+        });
+
+        let front = front.unwrap().unwrap();
+        assert_eq!(front.con_id, 2001);
+        assert_eq!(front.last_trade_date, "20990320");
+    }
+
+    #[tokio::test]
+    async fn test_option_chain() {
+        let (client, mut server) = connected_client(1).await;
+        let underlying = Contract {
+            con_id: 265598,
+            ..Contract::stock("AAPL", "SMART", "USD")
+        };
+        let chain = client.option_chain(&underlying, |expiry| expiry == "20240119", 180.0..=200.0);
+        let (chain, _) = tokio::join!(chain, async {
+            assert_eq!(read_frame(&mut server).await, b"78\x001000000000\0AAPL\0\0STK\x00265598\0");
+            let params = "AAPL\x00100\x002\x0020240119\x0020240216\x002\x00190\x00250\0";
+            write_frame(&mut server, &format!("75\x001000000000\0SMART\x00265598\0{params}")).await;
+            write_frame(&mut server, &format!("75\x001000000000\0CBOE\x00265598\0{params}")).await;
+            write_frame(&mut server, "76\x001000000000\0").await;
+
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"9\x008\x001000000001\x000\0AAPL\0OPT\x0020240119\0"));
+            for (strike, con_id) in [("250", "2002"), ("190", "2001")] {
+                let mut raw = vec!["10", "1000000001", "AAPL", "OPT", "20240119", strike, "C"];
+                raw.push("SMART");
+                raw.extend(["USD", "", "AAPL", "AAPL", con_id, "0.01", "100", "LMT", "SMART", "1"]);
+                raw.extend(["265598", "", "", "", "", "", "", "", "", "", "", "", "0", "1", ""]);
+                raw.extend(["", "", "", "", "1", "1", "1"]);
+                write_frame(&mut server, &(raw.join("\0") + "\0")).await;
+            }
+            write_frame(&mut server, "52\x001\x001000000001\0").await;
+
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000002\x002001\0"));
+            write_frame(&mut server, "1\x006\x001000000002\x001\x004.5\x0010\x000\0").await;
+            write_frame(&mut server, "57\x001\x001000000002\0").await;
+        });
+
+        let chain = chain.unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].contract.strike, 190.0);
+        assert_eq!(chain[0].quote.as_ref().unwrap().bid, Some(4.5));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_all_from_head_timestamp() {
+        let (client, mut server) = connected_client(1).await;
+        let client = Arc::new(client);
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+        let head = now.as_secs() - 600;
+        let mut updates = Vec::new();
+        let backfill = client.backfill_all(contract, BarSize::Sec1, WhatToShow::Trades, true, |p| {
+            updates.push(p.clone())
+        });
+        let (bars, _) = tokio::join!(backfill, async {
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"87\x001000000000\x000\0AAPL\0STK\0"));
+            assert!(request.ends_with(b"\x001\0TRADES\x002\0"));
+            write_frame(&mut server, &format!("88\x001000000000\0{head}\0")).await;
+
+            read_frame(&mut server).await;
+            let bar = format!("{head}\x001\x001\x001\x001\x001\x001\x001\0");
+            write_frame(&mut server, &format!("17\x001000000001\0\0\x001\0{bar}")).await;
+        });
+
+        assert_eq!(bars.unwrap().len(), 1);
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].requests_done, updates[0].requests_total), (1, 1));
+        assert_eq!(updates[0].through, Some(DateTime::from_timestamp(head as i64)));
+    }
+
+    #[tokio::test]
+    async fn test_historical_ticks_over_several_messages() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let start = DateTime::utc(2024, 1, 5, 14, 30, 0);
+        let request = client.historical_ticks::<HistoricalTickLast>(contract, &start, 2, true);
+        let (ticks, _) = tokio::join!(request, async {
+            assert!(read_frame(&mut server).await.starts_with(b"96\x001000000000\0"));
+            let first = "98\x001000000000\x001\x001704465000\x000\x00185.5\x00100\0ARCA\0\x000\0";
+            write_frame(&mut server, first).await;
+            let last = "98\x001000000000\x001\x001704465001\x000\x00185.6\x00200\0ARCA\0\x001\0";
+            write_frame(&mut server, last).await;
+        });
+        let ticks = ticks.unwrap();
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks[1].price, 185.6);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_pace_api() {
+        let (client_side, mut server) = tokio::io::duplex(4096);
+
+        let server_task = tokio::spawn(async move {
+            let mut prefix = [0u8; 4];
+            server.read_exact(&mut prefix).await.unwrap();
+            read_frame(&mut server).await;
+            write_frame(&mut server, "176\x0020240105 09:30:00 EST\0").await;
+            let start_api = read_frame(&mut server).await;
+            (server, start_api)
+        });
+
+        let options = ConnectOptions::new(3).pace_api(true);
+        let client = Client::connect_with(client_side, options).await.unwrap();
+        let (_server, start_api) = server_task.await.unwrap();
+
+        assert_eq!(start_api, b"71\x002\x003\0+PACEAPI\0");
+        assert!(client.pace_api());
+    }
+    #[tokio::test]
+    async fn test_place_order_allocates_ids() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+
+        assert!(client.place_order(&contract, &order).await.is_err());
+
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        assert_eq!(client.place_order(&contract, &order).await.unwrap().order_id(), 42);
+        assert!(read_frame(&mut server).await.starts_with(b"3\x0042\x000\0AAPL\0"));
+        assert_eq!(client.place_order(&contract, &order).await.unwrap().order_id(), 43);
+        read_frame(&mut server).await;
+
+        // A re-sync to a lower ID doesn't reissue IDs already handed out.
+        let (synced, _) = tokio::join!(client.request_ids(), async {
+            assert_eq!(read_frame(&mut server).await, b"8\x001\x001\0");
+            write_frame(&mut server, "9\x001\x0040\0").await;
+        });
+        assert_eq!(synced.unwrap(), 40);
+        assert_eq!(client.next_order_id().unwrap(), 44);
+    }
+
+    #[tokio::test]
+    async fn test_order_ids_stay_below_request_ids() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x00999999999\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        assert_eq!(client.next_order_id().unwrap(), FIRST_REQ_ID - 1);
+        assert!(matches!(client.next_order_id(), Err(Error::Protocol(_))));
+    }
+
+    #[tokio::test]
+    async fn test_place_order_with_id_checks_range() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+        for order_id in [FIRST_REQ_ID, FIRST_REQ_ID + 5, 0] {
+            let result = client.place_order_with_id(order_id, &contract, &order).await;
+            assert!(matches!(result, Err(Error::InvalidOrder(_))), "{order_id}");
+        }
+        assert_eq!(client.next_order_id().unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_place_order_with_id_advances_allocator() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+        client.place_order_with_id(50, &contract, &order).await.unwrap();
+        assert!(read_frame(&mut server).await.starts_with(b"3\x0050\0"));
+        assert_eq!(client.place_order(&contract, &order).await.unwrap().order_id(), 51);
+
+        // IDs below the allocator stay available to callers
+        client.place_order_with_id(45, &contract, &order).await.unwrap();
+        assert_eq!(client.next_order_id().unwrap(), 52);
+    }
+
+    #[tokio::test]
+    async fn test_place_order_needs_server_version() {
+        let (client_side, mut server) = tokio::io::duplex(64 * 1024);
+        let connect = Client::connect_with(client_side, 1);
+        let (client, _) = tokio::join!(connect, accept(&mut server, 150));
+        let client = client.unwrap();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+        let result = client.place_order(&contract, &order).await;
+        assert!(matches!(result, Err(Error::Protocol(message)) if message.contains("151")));
+        let result = client.preview_order(&contract, &order).await;
+        assert!(matches!(result, Err(Error::Protocol(_))));
+    }
+
+    #[tokio::test]
+    async fn test_oca_group_reports_placed_orders_on_failure() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x00999999999\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        // The second leg runs out of order IDs after the first was sent
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let legs = [
+            (contract.clone(), Order::limit(OrderAction::Sell, 100.into(), 190.0)),
+            (contract, Order::stop(OrderAction::Sell, 100.into(), 180.0)),
+        ];
+        let result = client.place_oca_group("bracket", OcaType::CancelWithBlock, &legs).await;
+        assert!(read_frame(&mut server).await.starts_with(b"3\x00999999999\0"));
+        match result {
+            Err(Error::PartialOcaGroup { placed, source }) => {
+                assert_eq!(placed, [FIRST_REQ_ID - 1]);
+                assert!(matches!(*source, Error::Protocol(_)));
+            }
+            other => panic!("expected a partial OCA group, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_order_tracker_awaits_fill() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+        let mut tracker = client.place_order(&contract, &order).await.unwrap();
+        read_frame(&mut server).await;
+        assert!(tracker.status().is_none());
+
+        let submitted = "3\x0042\0Submitted\x000\x00100\x000\x00555\x000\x000\x001\0\0\0";
+        let fill = "3\x0042\0Filled\x00100\x000\x00185.5\x00555\x000\x00185.5\x001\0\0\0";
+        write_frame(&mut server, submitted).await;
+        write_frame(&mut server, fill).await;
+        assert_eq!(tracker.next().await.unwrap().status, "Submitted");
+        let filled = tracker.await_filled(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(filled.avg_fill_price, 185.5);
+        assert_eq!(tracker.status().unwrap().status, "Filled");
+
+        let mut cancelled = client.place_order(&contract, &order).await.unwrap();
+        read_frame(&mut server).await;
+        let status = "3\x0043\0Cancelled\x000\x00100\x000\x00556\x000\x000\x001\0\0\0";
+        write_frame(&mut server, status).await;
+        let result = cancelled.await_filled(Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(Error::OrderNotFilled { order_id: 43, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_order_tracker_fails_on_rejection() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::limit(OrderAction::Buy, 100.into(), 185.0);
+        let mut tracker = client.place_order(&contract, &order).await.unwrap();
+        read_frame(&mut server).await;
+
+        write_frame(&mut server, "4\x002\x0042\x00399\0Order held until the open\0").await;
+        write_frame(&mut server, "4\x002\x0042\x00201\0Order rejected\0").await;
+        let result = tracker.await_filled(Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(Error::Tws { code: 201, .. })));
+        assert!(tracker.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_order_tracker_fails_on_disconnect() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::limit(OrderAction::Buy, 100.into(), 185.0);
+        let mut tracker = client.place_order(&contract, &order).await.unwrap();
+        read_frame(&mut server).await;
+
+        let (result, _) = tokio::join!(tracker.await_filled(Duration::from_secs(5)), async {
+            drop(server);
+        });
+        assert!(matches!(result, Err(Error::NotConnected)));
+        assert!(tracker.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_order_ids() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, 100.into());
+        client.place_order(&contract, &order).await.unwrap();
+        read_frame(&mut server).await;
+        let reused = client.place_order_with_id(42, &contract, &order).await;
+        assert!(matches!(reused, Err(Error::DuplicateOrderId(42))));
+
+        // TWS knows IDs this connection doesn't, e.g. from an earlier session
+        let order_id = client.next_order_id().unwrap();
+        let mut tracker = client.place_order_with_id(order_id, &contract, &order).await.unwrap();
+        assert!(read_frame(&mut server).await.starts_with(b"3\x0043\0"));
+        write_frame(&mut server, "4\x002\x0043\x00103\0Duplicate order id\0").await;
+        let result = tracker.await_filled(Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(Error::DuplicateOrderId(43))));
+    }
+
+    #[tokio::test]
+    async fn test_modify_order_keeps_contract() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("MSFT", "SMART", "USD");
+        let order = Order::limit(OrderAction::Buy, 5.into(), 400.0);
+        let order_id = client.place_order(&contract, &order).await.unwrap().order_id();
+        read_frame(&mut server).await;
+
+        let mut order = client.placed_order(order_id).unwrap();
+        order.lmt_price = Some(401.5);
+        client.modify_order(order_id, &order).await.unwrap();
+        let frame = read_frame(&mut server).await;
+        assert!(frame.starts_with(b"3\x0042\x000\0MSFT\0"));
+        assert!(frame.windows(6).any(|field| field == b"401.5\0"));
+
+        order.action = OrderAction::Sell;
+        assert!(matches!(client.modify_order(order_id, &order).await, Err(Error::InvalidOrder(_))));
+        assert!(client.modify_order(99, &order).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_preview_order_fails_on_rejection() {
+        let (client, mut server) = connected_client(1).await;
+        let mut events = client.events();
+        write_frame(&mut server, "9\x001\x0042\0").await;
+        while !matches!(events.recv().await.unwrap(), Event::NextValidId(_)) {}
+
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let preview = tokio::spawn(async move {
+            client.preview_order(&contract, &Order::market(OrderAction::Buy, 10.into())).await
+        });
+
+        assert!(read_frame(&mut server).await.starts_with(b"3\x0042\0"));
+        write_frame(&mut server, "4\x002\x0042\x00201\0Order rejected\0").await;
+        let result = preview.await.unwrap();
+        assert!(matches!(result, Err(Error::Tws { code: 201, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_executions_with_commissions() {
+        let (client, mut server) = connected_client(1).await;
+        let query = tokio::spawn(async move {
+            client.executions(&ExecutionFilter::default()).await
+        });
+
+        let frame = read_frame(&mut server).await;
+        assert!(frame.starts_with(b"7\x003\0"));
+        let req_id = std::str::from_utf8(&frame[4..]).unwrap().split('\0').next().unwrap();
+        write_frame(
+            &mut server,
+            &format!(
+                "11\0{req_id}\x0042\x00265598\0AAPL\0STK\0\x000\0\0\0SMART\0USD\0AAPL\0NMS\0\
+                 0001.01\x0020240105 09:30:01\0DU123\0ISLAND\0BOT\x0010\x00185.25\x00991\x001\x000\
+                 \x0010\x00185.25\0\0\0\0\0\x001\0"
+            ),
+        )
+        .await;
+        write_frame(&mut server, "59\x001\x000001.01\x001.05\0USD\0\0\0\0").await;
+        write_frame(&mut server, &format!("55\x001\0{req_id}\0")).await;
+
+        let fills = query.await.unwrap().unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].execution.order_id, 42);
+        assert_eq!(fills[0].commission.as_ref().unwrap().commission, 1.05);
+    }
+
+    #[tokio::test]
+    async fn test_fills_end_on_disconnect() {
+        let (client, mut server) = connected_client(1).await;
+        let mut fills = client.fills(ExecutionFilter::default());
+        let mut order_events = client.order_events();
+
+        write_frame(
+            &mut server,
+            "11\x00-1\x0042\x00265598\0AAPL\0STK\0\x000\0\0\0SMART\0USD\0AAPL\0NMS\0\
+             0001.01\x0020240105 09:30:01\0DU123\0ISLAND\0BOT\x0010\x00185.25\x00991\x001\x000\
+             \x0010\x00185.25\0\0\0\0\0\x001\0",
+        )
+        .await;
+        write_frame(&mut server, "59\x001\x000001.01\x001.05\0USD\0\0\0\0").await;
+        assert!(matches!(fills.next().await, Some(FillEvent::Executed(_))));
+        assert!(matches!(fills.next().await, Some(FillEvent::Commissioned(_))));
+
+        drop(server);
+        assert!(fills.next().await.is_none());
+        let closed = Err(broadcast::error::RecvError::Closed);
+        assert_eq!(order_events.recv().await.map(|_| ()), closed);
+        assert_eq!(client.order_events().recv().await.map(|_| ()), closed);
+        assert!(client.fills(ExecutionFilter::default()).next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_open_orders_until_end() {
+        let (client, mut server) = connected_client(1).await;
+        let query = tokio::spawn(async move { client.all_open_orders().await });
+
+        assert_eq!(read_frame(&mut server).await, b"16\x001\0");
+        write_frame(&mut server, "53\x001\0").await;
+        assert!(query.await.unwrap().unwrap().is_empty());
     }
 }
@@ -0,0 +1,1061 @@
+//! Sans-io protocol core.
+//!
+//! Everything needed to speak the TWS protocol without doing any I/O:
+//! handshake and framing, request encoding, and decoding incoming frames
+//! into [`Incoming`] messages. The async [`Client`](crate::Client) is one
+//! consumer; other runtimes or event loops can drive a socket themselves:
+//!
+//! ```no_run
+//! use ibkr::codec::{self, ConnectOptions, FrameReader, Incoming};
+//! # fn io(_: &[u8]) -> Vec<u8> { Vec::new() }
+//!
+//! let mut frames = FrameReader::new();
+//! frames.push(&io(&codec::handshake()));
+//! let server_version = codec::parse_server_version(&frames.next_frame().unwrap()).unwrap();
+//! io(&codec::encode_frame(&codec::start_api(&ConnectOptions::new(1))));
+//!
+//! frames.push(&io(&codec::encode_frame(&codec::req_current_time())));
+//! while let Some(frame) = frames.next_frame() {
+//!     if let Some(Incoming::CurrentTime(time)) = codec::decode(&frame, server_version) {
+//!         println!("server time: {time}");
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::account::{
+    AccountSummaryTag, AccountSummaryValue, AccountValue, PortfolioValue, Position,
+};
+use crate::contract::Contract;
+use crate::contract_details::ContractDetails;
+use crate::error::{Error, Result};
+use crate::event::NewsBulletin;
+use crate::execution::{CommissionReport, Execution, Fill};
+use crate::historical::{BarData, DateFormat, HistoricalDataResponse, WhatToShow};
+use crate::historical_ticks::{HistoricalTick, HistoricalTickBidAsk, HistoricalTickLast, HistoricalTicks};
+use crate::market_data::{MarketDataType, SmartComponent, TickReqParams};
+use crate::market_depth::{DepthExchange, DepthUpdate};
+use crate::market_rule::MarketRule;
+use crate::message::{IncomingMessageId, OutgoingMessageId};
+use crate::option_chain::OptionParams;
+use crate::order::{ExerciseAction, OpenOrder, OrderStatus};
+use crate::pnl::PnlSingle;
+use crate::scanner::ScannerData;
+use crate::server_versions;
+use crate::wire::{extract_message, make_field, FieldIterator};
+
+/// Range of API versions this crate speaks, sent in the handshake.
+pub const CLIENT_VERSIONS: &str = "v100..176";
+
+/// Options sent to TWS when starting the API session.
+///
+/// A bare client ID converts into default options, so `Client::connect(addr, 1)`
+/// keeps working.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Unique client identifier (use different IDs for multiple connections)
+    pub client_id: i32,
+    /// Let TWS pace requests itself instead of rejecting bursts ("+PACEAPI")
+    pub pace_api: bool,
+}
+
+impl ConnectOptions {
+    /// Create options for a client ID.
+    pub fn new(client_id: i32) -> Self {
+        Self {
+            client_id,
+            ..Default::default()
+        }
+    }
+
+    /// Enable the PACEAPI capability.
+    ///
+    /// TWS then queues requests beyond its message rate limit rather than
+    /// failing them, so client-side throttling can be relaxed.
+    pub fn pace_api(mut self, enabled: bool) -> Self {
+        self.pace_api = enabled;
+        self
+    }
+
+    /// The optional capabilities field of START_API.
+    fn optional_capabilities(&self) -> &'static str {
+        if self.pace_api { "+PACEAPI" } else { "" }
+    }
+}
+
+impl From<i32> for ConnectOptions {
+    fn from(client_id: i32) -> Self {
+        Self::new(client_id)
+    }
+}
+
+/// What was negotiated and reported when a session started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionInfo {
+    pub client_id: i32,
+    pub server_version: u32,
+    /// TWS connection time from the handshake (e.g. "20240105 09:30:00 EST")
+    pub connection_time: String,
+    /// Accounts accessible by this login
+    pub managed_accounts: Vec<String>,
+    /// First order ID TWS reported as valid, if it has arrived
+    pub next_valid_id: Option<i32>,
+    /// Remote address, when connected over TCP
+    pub endpoint: Option<std::net::SocketAddr>,
+}
+
+/// Length-prefix a message payload for sending.
+pub fn encode_frame(payload: &str) -> Vec<u8> {
+    let bytes = payload.as_bytes();
+    let mut frame = Vec::with_capacity(4 + bytes.len());
+    frame.extend((bytes.len() as u32).to_be_bytes());
+    frame.extend(bytes);
+    frame
+}
+
+/// Bytes opening a connection: "API\0" followed by the supported versions.
+pub fn handshake() -> Vec<u8> {
+    let mut bytes = b"API\0".to_vec();
+    bytes.extend(encode_frame(CLIENT_VERSIONS));
+    bytes
+}
+
+/// Parse the server version from the first frame TWS sends.
+pub fn parse_server_version(frame: &[u8]) -> Result<u32> {
+    parse_handshake(frame).map(|(server_version, _)| server_version)
+}
+
+/// Parse the server version and connection time from the first frame TWS sends.
+pub fn parse_handshake(frame: &[u8]) -> Result<(u32, String)> {
+    let text = std::str::from_utf8(frame)
+        .map_err(|_| Error::Protocol("Invalid UTF-8 in handshake".into()))?;
+    let mut fields = text.split('\0').filter(|field| !field.is_empty());
+    let server_version = fields
+        .next()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| Error::Protocol("Failed to parse server version".into()))?;
+    Ok((server_version, fields.next().unwrap_or("").to_string()))
+}
+
+/// Splits a byte stream into message frames.
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes read from the connection.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Take the next complete frame payload, if one has arrived.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let (frame, rest) = extract_message(&self.buf)?;
+        self.buf = rest;
+        Some(frame)
+    }
+}
+
+// Request encoders for messages without a dedicated request type.
+
+/// START_API
+pub fn start_api(options: &ConnectOptions) -> String {
+    format!(
+        "{}{}{}{}",
+        make_field(OutgoingMessageId::StartApi.as_u32()),
+        make_field(2),
+        make_field(options.client_id),
+        make_field(options.optional_capabilities()),
+    )
+}
+
+/// REQ_CURRENT_TIME
+pub fn req_current_time() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqCurrentTime.as_u32()),
+        make_field(1),
+    )
+}
+
+/// REQ_IDS
+pub fn req_ids() -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::ReqIds.as_u32()),
+        make_field(1),
+        // numIds; TWS ignores it and always sends a single ID
+        make_field(1),
+    )
+}
+
+/// REQ_ACCT_DATA
+pub fn req_account_data(subscribe: bool, account: &str) -> String {
+    format!(
+        "{}{}{}{}",
+        make_field(OutgoingMessageId::ReqAccountData.as_u32()),
+        make_field(2),
+        make_field(if subscribe { 1 } else { 0 }),
+        make_field(account),
+    )
+}
+
+/// REQ_ACCOUNT_SUMMARY
+pub fn req_account_summary(req_id: i32, group: &str, tags: &[AccountSummaryTag]) -> String {
+    format!(
+        "{}{}{}{}{}",
+        make_field(OutgoingMessageId::ReqAccountSummary.as_u32()),
+        make_field(1),
+        make_field(req_id),
+        make_field(group),
+        make_field(AccountSummaryTag::join(tags)),
+    )
+}
+
+/// CANCEL_ACCOUNT_SUMMARY
+pub fn cancel_account_summary(req_id: i32) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::CancelAccountSummary.as_u32()),
+        make_field(1),
+        make_field(req_id),
+    )
+}
+
+/// REQ_POSITIONS
+pub fn req_positions() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqPositions.as_u32()),
+        make_field(1),
+    )
+}
+
+/// CANCEL_POSITIONS
+pub fn cancel_positions() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::CancelPositions.as_u32()),
+        make_field(1),
+    )
+}
+
+/// REQ_PNL_SINGLE
+pub fn req_pnl_single(req_id: i32, account: &str, model_code: &str, con_id: i32) -> String {
+    format!(
+        "{}{}{}{}{}",
+        make_field(OutgoingMessageId::ReqPnlSingle.as_u32()),
+        make_field(req_id),
+        make_field(account),
+        make_field(model_code),
+        make_field(con_id),
+    )
+}
+
+/// CANCEL_PNL_SINGLE
+pub fn cancel_pnl_single(req_id: i32) -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::CancelPnlSingle.as_u32()),
+        make_field(req_id),
+    )
+}
+
+/// REQ_MKT_DEPTH for up to `num_rows` rows on each side of the book.
+///
+/// With `is_smart_depth`, TWS aggregates depth from every exchange that
+/// offers it.
+pub fn req_market_depth(
+    req_id: i32,
+    contract: &Contract,
+    num_rows: i32,
+    is_smart_depth: bool,
+    server_version: u32,
+) -> String {
+    let mut msg = String::new();
+    msg.push_str(&make_field(OutgoingMessageId::ReqMktDepth.as_u32()));
+    msg.push_str(&make_field(5));
+    msg.push_str(&make_field(req_id));
+    msg.push_str(&contract.encode());
+    msg.push_str(&make_field(num_rows));
+    if server_version >= server_versions::SMART_DEPTH {
+        msg.push_str(&make_field(if is_smart_depth { 1 } else { 0 }));
+    }
+    // mktDepthOptions
+    msg.push_str(&make_field(""));
+    msg
+}
+
+/// CANCEL_MKT_DEPTH
+pub fn cancel_market_depth(req_id: i32, is_smart_depth: bool, server_version: u32) -> String {
+    let mut msg = String::new();
+    msg.push_str(&make_field(OutgoingMessageId::CancelMktDepth.as_u32()));
+    msg.push_str(&make_field(1));
+    msg.push_str(&make_field(req_id));
+    if server_version >= server_versions::SMART_DEPTH {
+        msg.push_str(&make_field(if is_smart_depth { 1 } else { 0 }));
+    }
+    msg
+}
+
+/// REQ_MKT_DEPTH_EXCHANGES
+pub fn req_market_depth_exchanges() -> String {
+    make_field(OutgoingMessageId::ReqMktDepthExchanges.as_u32())
+}
+
+/// REQ_SEC_DEF_OPT_PARAMS
+///
+/// `fut_fop_exchange` is only needed for options on futures.
+pub fn req_sec_def_opt_params(
+    req_id: i32,
+    underlying: &Contract,
+    fut_fop_exchange: &str,
+) -> String {
+    let mut msg = String::new();
+    msg.push_str(&make_field(OutgoingMessageId::ReqSecDefOptParams.as_u32()));
+    msg.push_str(&make_field(req_id));
+    msg.push_str(&make_field(&underlying.symbol));
+    msg.push_str(&make_field(fut_fop_exchange));
+    msg.push_str(&make_field(underlying.sec_type.as_str()));
+    msg.push_str(&make_field(underlying.con_id));
+    msg
+}
+
+/// REQ_SMART_COMPONENTS
+pub fn req_smart_components(req_id: i32, bbo_exchange: &str) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::ReqSmartComponents.as_u32()),
+        make_field(req_id),
+        make_field(bbo_exchange)
+    )
+}
+
+/// REQ_MARKET_RULE
+pub fn req_market_rule(market_rule_id: i32) -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqMarketRule.as_u32()),
+        make_field(market_rule_id)
+    )
+}
+
+/// REQ_MARKET_DATA_TYPE
+pub fn req_market_data_type(data_type: MarketDataType) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::ReqMarketDataType.as_u32()),
+        make_field(1),
+        make_field(data_type as i32),
+    )
+}
+
+/// CANCEL_MKT_DATA
+pub fn cancel_market_data(req_id: i32) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::CancelMktData.as_u32()),
+        make_field(2),
+        make_field(req_id),
+    )
+}
+
+/// REQ_CONTRACT_DATA
+pub fn req_contract_details(req_id: i32, contract: &Contract, server_version: u32) -> String {
+    let mut msg = String::new();
+    msg.push_str(&make_field(OutgoingMessageId::ReqContractData.as_u32()));
+    msg.push_str(&make_field(8));
+    msg.push_str(&make_field(req_id));
+    msg.push_str(&contract.encode_for(server_version));
+    msg.push_str(&make_field(if contract.include_expired { 1 } else { 0 }));
+    msg.push_str(&make_field(&contract.sec_id_type));
+    msg.push_str(&make_field(&contract.sec_id));
+    if server_version >= server_versions::BOND_ISSUERID {
+        // issuerId
+        msg.push_str(&make_field(""));
+    }
+    msg
+}
+
+/// REQ_HEAD_TIMESTAMP, asking for the timestamp in epoch seconds.
+pub fn req_head_timestamp(
+    req_id: i32,
+    contract: &Contract,
+    what_to_show: WhatToShow,
+    use_rth: bool,
+) -> String {
+    let mut msg = String::new();
+    msg.push_str(&make_field(OutgoingMessageId::ReqHeadTimestamp.as_u32()));
+    msg.push_str(&make_field(req_id));
+    msg.push_str(&contract.encode());
+    msg.push_str(&make_field(if contract.include_expired { 1 } else { 0 }));
+    msg.push_str(&make_field(if use_rth { 1 } else { 0 }));
+    msg.push_str(&make_field(what_to_show.as_str()));
+    msg.push_str(&make_field(DateFormat::Unix as i32));
+    msg
+}
+
+/// CANCEL_HISTORICAL_DATA
+pub fn cancel_historical_data(req_id: i32) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::CancelHistoricalData.as_u32()),
+        make_field(1),
+        make_field(req_id),
+    )
+}
+
+/// REQ_OPEN_ORDERS
+pub fn req_open_orders() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqOpenOrders.as_u32()),
+        make_field(1),
+    )
+}
+
+/// REQ_ALL_OPEN_ORDERS
+pub fn req_all_open_orders() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqAllOpenOrders.as_u32()),
+        make_field(1),
+    )
+}
+
+/// REQ_AUTO_OPEN_ORDERS
+pub fn req_auto_open_orders(auto_bind: bool) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::ReqAutoOpenOrders.as_u32()),
+        make_field(1),
+        make_field(if auto_bind { 1 } else { 0 }),
+    )
+}
+
+/// REQ_FA
+pub fn req_fa(data_type: i32) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::ReqFa.as_u32()),
+        make_field(1),
+        make_field(data_type),
+    )
+}
+
+/// REQ_SCANNER_PARAMETERS
+pub fn req_scanner_parameters() -> String {
+    format!(
+        "{}{}",
+        make_field(OutgoingMessageId::ReqScannerParameters.as_u32()),
+        make_field(1),
+    )
+}
+
+/// CANCEL_SCANNER_SUBSCRIPTION
+pub fn cancel_scanner_subscription(req_id: i32) -> String {
+    format!(
+        "{}{}{}",
+        make_field(OutgoingMessageId::CancelScannerSubscription.as_u32()),
+        make_field(1),
+        make_field(req_id),
+    )
+}
+
+/// EXERCISE_OPTIONS
+///
+/// `override_` exercises (or lapses) regardless of IB's automatic
+/// exercise rules, e.g. to exercise an out-of-the-money option.
+pub fn exercise_options(
+    req_id: i32,
+    contract: &Contract,
+    action: ExerciseAction,
+    quantity: i32,
+    account: &str,
+    override_: bool,
+    server_version: u32,
+) -> String {
+    let mut msg = String::new();
+
+    msg.push_str(&make_field(OutgoingMessageId::ExerciseOptions.as_u32()));
+    msg.push_str(&make_field(2));
+    msg.push_str(&make_field(req_id));
+    // Contract fields, without primaryExchange
+    msg.push_str(&make_field(contract.con_id));
+    msg.push_str(&make_field(&contract.symbol));
+    msg.push_str(&make_field(contract.sec_type.as_str()));
+    msg.push_str(&make_field(&contract.last_trade_date));
+    msg.push_str(&make_field(contract.strike));
+    msg.push_str(&make_field(contract.right.as_str()));
+    msg.push_str(&make_field(&contract.multiplier));
+    msg.push_str(&make_field(&contract.exchange));
+    msg.push_str(&make_field(&contract.currency));
+    msg.push_str(&make_field(&contract.local_symbol));
+    msg.push_str(&make_field(&contract.trading_class));
+    msg.push_str(&make_field(action as i32));
+    msg.push_str(&make_field(quantity));
+    msg.push_str(&make_field(account));
+    msg.push_str(&make_field(if override_ { 1 } else { 0 }));
+    if server_version >= server_versions::MANUAL_ORDER_TIME_EXERCISE_OPTIONS {
+        msg.push_str(&make_field(""));
+    }
+
+    msg
+}
+
+/// A decoded message from TWS.
+#[derive(Debug, Clone)]
+pub enum Incoming {
+    /// Error or notice (`req_id` is -1 for connection-level notices)
+    Error {
+        req_id: i32,
+        code: i32,
+        message: String,
+    },
+    AccountValue(AccountValue),
+    AccountUpdateTime(String),
+    PortfolioValue(Box<PortfolioValue>),
+    /// End of an account data download, with the account code
+    AccountDownloadEnd(String),
+    NextValidId(i32),
+    ManagedAccounts(Vec<String>),
+    /// FA configuration XML of the given FA data type
+    FaConfiguration {
+        data_type: i32,
+        xml: String,
+    },
+    NewsBulletin(NewsBulletin),
+    OrderStatus(OrderStatus),
+    OpenOrder(Box<OpenOrder>),
+    OpenOrderEnd,
+    OrderBound {
+        order_id: i64,
+        api_client_id: i32,
+        api_order_id: i32,
+    },
+    HistoricalData {
+        req_id: i32,
+        response: HistoricalDataResponse,
+    },
+    /// Latest bar of a keepUpToDate historical data request
+    HistoricalDataUpdate {
+        req_id: i32,
+        bar: BarData,
+    },
+    HeadTimestamp {
+        req_id: i32,
+        timestamp: String,
+    },
+    ContractDetails {
+        req_id: i32,
+        details: Box<ContractDetails>,
+    },
+    ContractDetailsEnd {
+        req_id: i32,
+    },
+    OptionParams {
+        req_id: i32,
+        params: Box<OptionParams>,
+    },
+    OptionParamsEnd {
+        req_id: i32,
+    },
+    HistoricalTicks {
+        req_id: i32,
+        ticks: HistoricalTicks,
+        /// Whether this is the last message for the request
+        done: bool,
+    },
+    ScannerParameters(String),
+    ScannerData {
+        req_id: i32,
+        rows: Vec<ScannerData>,
+    },
+    CurrentTime(i64),
+    AccountSummary {
+        req_id: i32,
+        value: AccountSummaryValue,
+    },
+    AccountSummaryEnd {
+        req_id: i32,
+    },
+    Position(Box<Position>),
+    PositionEnd,
+    PnlSingle {
+        req_id: i32,
+        pnl: PnlSingle,
+    },
+    TickReqParams {
+        req_id: i32,
+        params: TickReqParams,
+    },
+    TickPrice {
+        req_id: i32,
+        tick_type: i32,
+        price: f64,
+        size: Decimal,
+        /// Bit 0: can auto-execute, bit 1: past limit, bit 2: pre-open
+        attr_mask: i32,
+    },
+    TickSize {
+        req_id: i32,
+        tick_type: i32,
+        size: Decimal,
+    },
+    TickString {
+        req_id: i32,
+        tick_type: i32,
+        value: String,
+    },
+    TickGeneric {
+        req_id: i32,
+        tick_type: i32,
+        value: f64,
+    },
+    TickSnapshotEnd {
+        req_id: i32,
+    },
+    MarketDepth {
+        req_id: i32,
+        update: DepthUpdate,
+    },
+    MarketDepthExchanges(Vec<DepthExchange>),
+    MarketRule(MarketRule),
+    /// SMART components by bit number
+    SmartComponents {
+        req_id: i32,
+        components: HashMap<i32, SmartComponent>,
+    },
+    /// Market data type in effect for a request
+    MarketDataType {
+        req_id: i32,
+        data_type: MarketDataType,
+    },
+    /// An execution (without commission); `req_id` is -1 for live executions
+    ExecutionData {
+        req_id: i32,
+        fill: Box<Fill>,
+    },
+    ExecutionDataEnd {
+        req_id: i32,
+    },
+    CommissionReport(CommissionReport),
+    /// A message this crate doesn't decode yet, with its raw fields
+    Unhandled {
+        msg_id: u32,
+        fields: Vec<String>,
+    },
+}
+
+/// Decode one frame payload.
+///
+/// Returns `None` for frames without a message ID or with fields too
+/// malformed to decode.
+pub fn decode(frame: &[u8], server_version: u32) -> Option<Incoming> {
+    let mut fields = FieldIterator::new(frame);
+    let msg_id = fields.next_parsed::<u32>()?;
+
+    let message = match IncomingMessageId::from_u32(msg_id) {
+        Some(IncomingMessageId::Error) => {
+            let _version = fields.next_i32();
+            Incoming::Error {
+                req_id: fields.next_i32(),
+                code: fields.next_i32(),
+                message: fields.next_string().unwrap_or("").to_string(),
+            }
+        }
+        Some(IncomingMessageId::AccountValue) => {
+            let _version = fields.next_i32();
+            Incoming::AccountValue(AccountValue::parse(&mut fields)?)
+        }
+        Some(IncomingMessageId::AccountUpdateTime) => {
+            let _version = fields.next_i32();
+            Incoming::AccountUpdateTime(fields.next_string().unwrap_or("").to_string())
+        }
+        Some(IncomingMessageId::PortfolioValue) => {
+            let _version = fields.next_i32();
+            Incoming::PortfolioValue(Box::new(PortfolioValue::parse(&mut fields)?))
+        }
+        Some(IncomingMessageId::AccountDownloadEnd) => {
+            let _version = fields.next_i32();
+            Incoming::AccountDownloadEnd(fields.next_string().unwrap_or("").to_string())
+        }
+        Some(IncomingMessageId::NextValidId) => {
+            let _version = fields.next_i32();
+            Incoming::NextValidId(fields.next_i32())
+        }
+        Some(IncomingMessageId::ManagedAccounts) => {
+            let _version = fields.next_i32();
+            let accounts = fields
+                .next_string()
+                .unwrap_or("")
+                .split(',')
+                .filter(|account| !account.is_empty())
+                .map(str::to_string)
+                .collect();
+            Incoming::ManagedAccounts(accounts)
+        }
+        Some(IncomingMessageId::ReceiveFa) => {
+            let _version = fields.next_i32();
+            Incoming::FaConfiguration {
+                data_type: fields.next_i32(),
+                xml: fields.next_string().unwrap_or("").to_string(),
+            }
+        }
+        Some(IncomingMessageId::NewsBulletins) => {
+            let _version = fields.next_i32();
+            Incoming::NewsBulletin(NewsBulletin::parse(&mut fields)?)
+        }
+        Some(IncomingMessageId::OrderStatus) => {
+            Incoming::OrderStatus(OrderStatus::parse(&mut fields, server_version)?)
+        }
+        Some(IncomingMessageId::OpenOrder) => Incoming::OpenOrder(Box::new(OpenOrder::parse(&mut fields)?)),
+        Some(IncomingMessageId::OpenOrderEnd) => Incoming::OpenOrderEnd,
+        Some(IncomingMessageId::OrderBound) => Incoming::OrderBound {
+            order_id: fields.next_parsed().unwrap_or(0),
+            api_client_id: fields.next_i32(),
+            api_order_id: fields.next_i32(),
+        },
+        Some(IncomingMessageId::HistoricalData) => {
+            let req_id = fields.next_i32();
+            let start = fields.next_string().unwrap_or("").to_string();
+            let end = fields.next_string().unwrap_or("").to_string();
+            let bars = parse_list(&mut fields, BarData::parse);
+            Incoming::HistoricalData {
+                req_id,
+                response: HistoricalDataResponse { start, end, bars },
+            }
+        }
+        Some(IncomingMessageId::HistoricalDataUpdate) => Incoming::HistoricalDataUpdate {
+            req_id: fields.next_i32(),
+            bar: BarData::parse_update(&mut fields)?,
+        },
+        Some(IncomingMessageId::ContractData) => {
+            let (req_id, details) = ContractDetails::parse(&mut fields, server_version)?;
+            Incoming::ContractDetails {
+                req_id,
+                details: Box::new(details),
+            }
+        }
+        Some(IncomingMessageId::BondContractData) => {
+            let (req_id, details) = ContractDetails::parse_bond(&mut fields, server_version)?;
+            Incoming::ContractDetails {
+                req_id,
+                details: Box::new(details),
+            }
+        }
+        Some(IncomingMessageId::SecurityDefinitionOptionParameter) => {
+            let (req_id, params) = OptionParams::parse(&mut fields)?;
+            Incoming::OptionParams {
+                req_id,
+                params: Box::new(params),
+            }
+        }
+        Some(IncomingMessageId::SecurityDefinitionOptionParameterEnd) => {
+            Incoming::OptionParamsEnd {
+                req_id: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::ContractDataEnd) => {
+            let _version = fields.next_i32();
+            Incoming::ContractDetailsEnd {
+                req_id: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::HeadTimestamp) => Incoming::HeadTimestamp {
+            req_id: fields.next_i32(),
+            timestamp: fields.next_string().unwrap_or("").to_string(),
+        },
+        Some(IncomingMessageId::HistoricalTicks) => Incoming::HistoricalTicks {
+            req_id: fields.next_i32(),
+            ticks: HistoricalTicks::Midpoint(parse_list(&mut fields, HistoricalTick::parse)),
+            done: fields.next_bool(),
+        },
+        Some(IncomingMessageId::HistoricalTicksBidAsk) => Incoming::HistoricalTicks {
+            req_id: fields.next_i32(),
+            ticks: HistoricalTicks::BidAsk(parse_list(&mut fields, HistoricalTickBidAsk::parse)),
+            done: fields.next_bool(),
+        },
+        Some(IncomingMessageId::HistoricalTicksLast) => Incoming::HistoricalTicks {
+            req_id: fields.next_i32(),
+            ticks: HistoricalTicks::Last(parse_list(&mut fields, HistoricalTickLast::parse)),
+            done: fields.next_bool(),
+        },
+        Some(IncomingMessageId::ScannerParameters) => {
+            let _version = fields.next_i32();
+            Incoming::ScannerParameters(fields.next_string().unwrap_or("").to_string())
+        }
+        Some(IncomingMessageId::ScannerData) => {
+            let _version = fields.next_i32();
+            Incoming::ScannerData {
+                req_id: fields.next_i32(),
+                rows: parse_list(&mut fields, ScannerData::parse),
+            }
+        }
+        Some(IncomingMessageId::CurrentTime) => {
+            let _version = fields.next_i32();
+            Incoming::CurrentTime(fields.next_parsed().unwrap_or(0))
+        }
+        Some(IncomingMessageId::AccountSummary) => {
+            let _version = fields.next_i32();
+            let req_id = fields.next_i32();
+            Incoming::AccountSummary {
+                req_id,
+                value: AccountSummaryValue::parse(&mut fields)?,
+            }
+        }
+        Some(IncomingMessageId::AccountSummaryEnd) => {
+            let _version = fields.next_i32();
+            Incoming::AccountSummaryEnd {
+                req_id: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::PositionData) => {
+            let _version = fields.next_i32();
+            Incoming::Position(Box::new(Position::parse(&mut fields)?))
+        }
+        Some(IncomingMessageId::PositionEnd) => Incoming::PositionEnd,
+        Some(IncomingMessageId::PnlSingle) => {
+            let req_id = fields.next_i32();
+            Incoming::PnlSingle {
+                req_id,
+                pnl: PnlSingle::parse(&mut fields, server_version)?,
+            }
+        }
+        Some(IncomingMessageId::TickPrice) => {
+            let _version = fields.next_i32();
+            Incoming::TickPrice {
+                req_id: fields.next_i32(),
+                tick_type: fields.next_i32(),
+                price: fields.next_f64(),
+                size: fields.next_decimal(),
+                attr_mask: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::TickSize) => {
+            let _version = fields.next_i32();
+            Incoming::TickSize {
+                req_id: fields.next_i32(),
+                tick_type: fields.next_i32(),
+                size: fields.next_decimal(),
+            }
+        }
+        Some(IncomingMessageId::TickString) => {
+            let _version = fields.next_i32();
+            Incoming::TickString {
+                req_id: fields.next_i32(),
+                tick_type: fields.next_i32(),
+                value: fields.next_string()?.to_string(),
+            }
+        }
+        Some(IncomingMessageId::TickGeneric) => {
+            let _version = fields.next_i32();
+            Incoming::TickGeneric {
+                req_id: fields.next_i32(),
+                tick_type: fields.next_i32(),
+                value: fields.next_f64(),
+            }
+        }
+        Some(IncomingMessageId::MarketDepth) => {
+            let _version = fields.next_i32();
+            let req_id = fields.next_i32();
+            Incoming::MarketDepth {
+                req_id,
+                update: DepthUpdate::parse(&mut fields)?,
+            }
+        }
+        Some(IncomingMessageId::MarketDepthL2) => {
+            let _version = fields.next_i32();
+            let req_id = fields.next_i32();
+            Incoming::MarketDepth {
+                req_id,
+                update: DepthUpdate::parse_l2(&mut fields, server_version)?,
+            }
+        }
+        Some(IncomingMessageId::MktDepthExchanges) => {
+            let count = fields.next_i32().max(0) as usize;
+            let mut exchanges = Vec::with_capacity(count);
+            for _ in 0..count {
+                exchanges.push(DepthExchange::parse(&mut fields, server_version)?);
+            }
+            Incoming::MarketDepthExchanges(exchanges)
+        }
+        Some(IncomingMessageId::SmartComponents) => Incoming::SmartComponents {
+            req_id: fields.next_i32(),
+            components: SmartComponent::parse_map(&mut fields)?,
+        },
+        Some(IncomingMessageId::MarketRule) => {
+            Incoming::MarketRule(MarketRule::parse(&mut fields)?)
+        }
+        Some(IncomingMessageId::TickSnapshotEnd) => {
+            let _version = fields.next_i32();
+            Incoming::TickSnapshotEnd {
+                req_id: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::MarketDataType) => {
+            let _version = fields.next_i32();
+            let req_id = fields.next_i32();
+            Incoming::MarketDataType {
+                req_id,
+                data_type: MarketDataType::from_i32(fields.next_i32())?,
+            }
+        }
+        Some(IncomingMessageId::ExecutionData) => {
+            let (req_id, contract, execution) = Execution::parse(&mut fields, server_version)?;
+            Incoming::ExecutionData {
+                req_id,
+                fill: Box::new(Fill {
+                    contract,
+                    execution,
+                    commission: None,
+                }),
+            }
+        }
+        Some(IncomingMessageId::ExecutionDataEnd) => {
+            let _version = fields.next_i32();
+            Incoming::ExecutionDataEnd {
+                req_id: fields.next_i32(),
+            }
+        }
+        Some(IncomingMessageId::CommissionReport) => {
+            let _version = fields.next_i32();
+            Incoming::CommissionReport(CommissionReport::parse(&mut fields)?)
+        }
+        Some(IncomingMessageId::TickReqParams) => {
+            let req_id = fields.next_i32();
+            Incoming::TickReqParams {
+                req_id,
+                params: TickReqParams::parse(&mut fields)?,
+            }
+        }
+        _ => Incoming::Unhandled {
+            msg_id,
+            fields: fields.remaining().iter().map(|f| f.to_string()).collect(),
+        },
+    };
+
+    Some(message)
+}
+
+/// Parse a count-prefixed list of items.
+fn parse_list<T>(fields: &mut FieldIterator, parse: fn(&mut FieldIterator) -> Option<T>) -> Vec<T> {
+    let count = fields.next_i32().max(0) as usize;
+    let mut items = Vec::with_capacity(count);
+    for _ in 0..count {
+        if let Some(item) = parse(fields) {
+            items.push(item);
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{OptionRight, SecurityType};
+
+    #[test]
+    fn test_handshake_and_start_api() {
+        assert_eq!(handshake(), b"API\0\0\0\0\tv100..176");
+        let options = ConnectOptions::new(3).pace_api(true);
+        assert_eq!(start_api(&options), "71\x002\x003\0+PACEAPI\0");
+        assert_eq!(parse_server_version(b"176\x0020240105 09:30:00 EST\0").unwrap(), 176);
+        assert!(parse_server_version(b"\0").is_err());
+        let (_, connection_time) = parse_handshake(b"176\x0020240105 09:30:00 EST\0").unwrap();
+        assert_eq!(connection_time, "20240105 09:30:00 EST");
+    }
+
+    #[test]
+    fn test_exercise_options() {
+        let contract = Contract {
+            symbol: "AAPL".into(),
+            sec_type: SecurityType::Option,
+            last_trade_date: "20240119".into(),
+            strike: 190.0,
+            right: OptionRight::Call,
+            multiplier: "100".into(),
+            exchange: "SMART".into(),
+            currency: "USD".into(),
+            ..Default::default()
+        };
+        let action = ExerciseAction::Exercise;
+        let encoded = exercise_options(5, &contract, action, 2, "DU123", false, 176);
+        assert_eq!(
+            encoded,
+            "21\x002\x005\x000\0AAPL\0OPT\x0020240119\x00190\0C\x00100\0SMART\0USD\0\0\0\
+             1\x002\0DU123\x000\0"
+        );
+        let encoded = exercise_options(5, &contract, action, 2, "DU123", false, 177);
+        assert!(encoded.ends_with("\0DU123\x000\0\0"));
+    }
+
+    #[test]
+    fn test_contract_details_by_sec_id() {
+        let contract = Contract::from_isin("US0378331005");
+        let encoded = req_contract_details(3, &contract, 176);
+        assert!(encoded.ends_with("\0SMART\0\0\0\0\x000\0ISIN\0US0378331005\0\0"));
+        let encoded = req_contract_details(3, &contract, 175);
+        assert!(encoded.ends_with("\x000\0ISIN\0US0378331005\0"));
+    }
+
+    #[test]
+    fn test_frame_reader_across_reads() {
+        let mut bytes = encode_frame("49\x001\x001704465000\0");
+        bytes.extend(encode_frame("9\x001\x0042\0"));
+
+        let mut frames = FrameReader::new();
+        frames.push(&bytes[..6]);
+        assert!(frames.next_frame().is_none());
+        frames.push(&bytes[6..]);
+
+        let first = frames.next_frame().unwrap();
+        assert!(matches!(decode(&first, 176), Some(Incoming::CurrentTime(1_704_465_000))));
+        let second = frames.next_frame().unwrap();
+        assert!(matches!(decode(&second, 176), Some(Incoming::NextValidId(42))));
+        assert!(frames.next_frame().is_none());
+    }
+
+    #[test]
+    fn test_decode_historical_data_update() {
+        let frame = b"90\x001000000000\x003\x001704466800\x00185.1\x00185.3\x00185.4\x00185\0\
+                      185.2\x001200\0";
+        match decode(frame, 176) {
+            Some(Incoming::HistoricalDataUpdate { req_id, bar }) => {
+                assert_eq!(req_id, 1_000_000_000);
+                assert_eq!(bar.date, "1704466800");
+                assert_eq!((bar.open, bar.high, bar.low, bar.close), (185.1, 185.4, 185.0, 185.3));
+                assert_eq!(bar.volume, Some(Decimal::from(1200)));
+                assert_eq!(bar.bar_count, 3);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_error_and_unhandled() {
+        match decode(b"4\x002\x00-1\x002104\0Market data farm connection is OK:usfarm\0", 176) {
+            Some(Incoming::Error { req_id, code, .. }) => {
+                assert_eq!(req_id, -1);
+                assert_eq!(code, 2104);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        match decode(b"999\0a\0b\0", 176) {
+            Some(Incoming::Unhandled { msg_id, fields }) => {
+                assert_eq!(msg_id, 999);
+                assert_eq!(fields, ["a", "b"]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
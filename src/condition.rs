@@ -0,0 +1,307 @@
+//! Order conditions: submit or cancel an order when the market, the clock
+//! or the account reaches a threshold.
+
+use crate::wire::{make_field, FieldIterator};
+
+/// How a condition combines with the one after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conjunction {
+    #[default]
+    And,
+    Or,
+}
+
+impl Conjunction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::And => "a",
+            Self::Or => "o",
+        }
+    }
+}
+
+/// What a condition watches.
+///
+/// `is_more` selects the direction: `true` triggers when the value rises
+/// above the threshold, `false` when it falls below.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Price of a contract crosses `price`
+    Price {
+        con_id: i32,
+        exchange: String,
+        is_more: bool,
+        price: f64,
+        /// How the price is read: 0 default, 1 double bid/ask, 2 last,
+        /// 3 double last, 4 bid/ask, 7 last or bid/ask, 8 midpoint
+        trigger_method: i32,
+    },
+    /// Time passes `time` ("yyyymmdd hh:mm:ss" with a time zone)
+    Time { is_more: bool, time: String },
+    /// Account margin cushion crosses `percent`
+    Margin { is_more: bool, percent: i32 },
+    /// A trade of the described contract executes in the account
+    Execution {
+        sec_type: String,
+        exchange: String,
+        symbol: String,
+    },
+    /// Day's volume of a contract crosses `volume`
+    Volume {
+        con_id: i32,
+        exchange: String,
+        is_more: bool,
+        volume: i32,
+    },
+    /// Day's price change of a contract crosses `change_percent`
+    PercentChange {
+        con_id: i32,
+        exchange: String,
+        is_more: bool,
+        change_percent: f64,
+    },
+}
+
+impl Condition {
+    /// Condition type code used on the wire.
+    pub fn type_id(&self) -> i32 {
+        match self {
+            Self::Price { .. } => 1,
+            Self::Time { .. } => 3,
+            Self::Margin { .. } => 4,
+            Self::Execution { .. } => 5,
+            Self::Volume { .. } => 6,
+            Self::PercentChange { .. } => 7,
+        }
+    }
+}
+
+/// A condition attached to an order, with its link to the next condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderCondition {
+    pub condition: Condition,
+    pub conjunction: Conjunction,
+}
+
+impl OrderCondition {
+    /// Wrap a condition, combined with the next one using AND.
+    pub fn new(condition: Condition) -> Self {
+        Self {
+            condition,
+            conjunction: Conjunction::And,
+        }
+    }
+
+    /// Trigger when the last price of `con_id` on `exchange` crosses `price`.
+    pub fn price(con_id: i32, exchange: &str, is_more: bool, price: f64) -> Self {
+        Self::new(Condition::Price {
+            con_id,
+            exchange: exchange.to_string(),
+            is_more,
+            price,
+            trigger_method: 0,
+        })
+    }
+
+    /// Trigger when the clock passes `time`.
+    pub fn time(is_more: bool, time: &str) -> Self {
+        Self::new(Condition::Time {
+            is_more,
+            time: time.to_string(),
+        })
+    }
+
+    /// Trigger when the margin cushion crosses `percent`.
+    pub fn margin(is_more: bool, percent: i32) -> Self {
+        Self::new(Condition::Margin { is_more, percent })
+    }
+
+    /// Trigger when `symbol` executes in the account.
+    pub fn execution(symbol: &str, sec_type: &str, exchange: &str) -> Self {
+        Self::new(Condition::Execution {
+            sec_type: sec_type.to_string(),
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+        })
+    }
+
+    /// Trigger when the day's volume of `con_id` crosses `volume`.
+    pub fn volume(con_id: i32, exchange: &str, is_more: bool, volume: i32) -> Self {
+        Self::new(Condition::Volume {
+            con_id,
+            exchange: exchange.to_string(),
+            is_more,
+            volume,
+        })
+    }
+
+    /// Trigger when the day's change of `con_id` crosses `change_percent`.
+    pub fn percent_change(con_id: i32, exchange: &str, is_more: bool, change_percent: f64) -> Self {
+        Self::new(Condition::PercentChange {
+            con_id,
+            exchange: exchange.to_string(),
+            is_more,
+            change_percent,
+        })
+    }
+
+    /// Combine with the next condition using OR.
+    pub fn or(mut self) -> Self {
+        self.conjunction = Conjunction::Or;
+        self
+    }
+
+    /// Encode the condition's type and fields.
+    pub fn encode(&self) -> String {
+        let mut msg = String::new();
+        let is_more = |is_more: bool| make_field(if is_more { 1 } else { 0 });
+
+        msg.push_str(&make_field(self.condition.type_id()));
+        msg.push_str(&make_field(self.conjunction.as_str()));
+        match &self.condition {
+            Condition::Price {
+                con_id,
+                exchange,
+                is_more: more,
+                price,
+                trigger_method,
+            } => {
+                msg.push_str(&is_more(*more));
+                msg.push_str(&make_field(price));
+                msg.push_str(&make_field(con_id));
+                msg.push_str(&make_field(exchange));
+                msg.push_str(&make_field(trigger_method));
+            }
+            Condition::Time { is_more: more, time } => {
+                msg.push_str(&is_more(*more));
+                msg.push_str(&make_field(time));
+            }
+            Condition::Margin { is_more: more, percent } => {
+                msg.push_str(&is_more(*more));
+                msg.push_str(&make_field(percent));
+            }
+            Condition::Execution {
+                sec_type,
+                exchange,
+                symbol,
+            } => {
+                msg.push_str(&make_field(sec_type));
+                msg.push_str(&make_field(exchange));
+                msg.push_str(&make_field(symbol));
+            }
+            Condition::Volume {
+                con_id,
+                exchange,
+                is_more: more,
+                volume,
+            } => {
+                msg.push_str(&is_more(*more));
+                msg.push_str(&make_field(volume));
+                msg.push_str(&make_field(con_id));
+                msg.push_str(&make_field(exchange));
+            }
+            Condition::PercentChange {
+                con_id,
+                exchange,
+                is_more: more,
+                change_percent,
+            } => {
+                msg.push_str(&is_more(*more));
+                msg.push_str(&make_field(change_percent));
+                msg.push_str(&make_field(con_id));
+                msg.push_str(&make_field(exchange));
+            }
+        }
+
+        msg
+    }
+
+    /// Parse a condition (starting at its type code), as sent in OPEN_ORDER.
+    ///
+    /// Returns `None` for condition types this crate doesn't know.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let type_id = fields.next_i32();
+        let conjunction = match fields.next_string()? {
+            "o" | "O" => Conjunction::Or,
+            _ => Conjunction::And,
+        };
+        let condition = match type_id {
+            1 => {
+                let is_more = fields.next_bool();
+                let price = fields.next_f64();
+                Condition::Price {
+                    is_more,
+                    price,
+                    con_id: fields.next_i32(),
+                    exchange: fields.next_string()?.to_string(),
+                    trigger_method: fields.next_i32(),
+                }
+            }
+            3 => Condition::Time {
+                is_more: fields.next_bool(),
+                time: fields.next_string()?.to_string(),
+            },
+            4 => Condition::Margin {
+                is_more: fields.next_bool(),
+                percent: fields.next_i32(),
+            },
+            5 => Condition::Execution {
+                sec_type: fields.next_string()?.to_string(),
+                exchange: fields.next_string()?.to_string(),
+                symbol: fields.next_string()?.to_string(),
+            },
+            6 => {
+                let is_more = fields.next_bool();
+                let volume = fields.next_i32();
+                Condition::Volume {
+                    is_more,
+                    volume,
+                    con_id: fields.next_i32(),
+                    exchange: fields.next_string()?.to_string(),
+                }
+            }
+            7 => {
+                let is_more = fields.next_bool();
+                let change_percent = fields.next_f64();
+                Condition::PercentChange {
+                    is_more,
+                    change_percent,
+                    con_id: fields.next_i32(),
+                    exchange: fields.next_string()?.to_string(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            condition,
+            conjunction,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_condition_roundtrip() {
+        let condition = OrderCondition::price(756733, "SMART", false, 400.0).or();
+        let encoded = condition.encode();
+        assert_eq!(encoded, "1\0o\x000\x00400\x00756733\0SMART\x000\0");
+
+        let mut fields = FieldIterator::new(encoded.as_bytes());
+        assert_eq!(OrderCondition::parse(&mut fields), Some(condition));
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let buf = b"3\0a\x001\x0020240105 15:30:00 US/Eastern\x005\0a\0STK\0SMART\0AAPL\0";
+        let mut fields = FieldIterator::new(buf);
+
+        let time = OrderCondition::parse(&mut fields).unwrap();
+        assert_eq!(time, OrderCondition::time(true, "20240105 15:30:00 US/Eastern"));
+        let execution = OrderCondition::parse(&mut fields).unwrap();
+        assert_eq!(execution, OrderCondition::execution("AAPL", "STK", "SMART"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Cache of contract IDs resolved through contract details.
+//!
+//! Keys are the contract's identifying fields, so any two requests for the
+//! same instrument share an entry. Entries can be persisted to a file,
+//! one `key<TAB>con_id` line each, which is appended to as contracts are
+//! resolved and reloaded by [`ConIdCache::persist_to`].
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::contract::Contract;
+use crate::error::Result;
+
+/// Contract IDs by contract, optionally backed by a file.
+#[derive(Debug, Default)]
+pub struct ConIdCache {
+    entries: Mutex<HashMap<String, i32>>,
+    file: Mutex<Option<PathBuf>>,
+}
+
+impl ConIdCache {
+    /// Create an empty in-memory cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the entries stored at `path` and append new ones to it from now
+    /// on. A missing file is created on the first insert.
+    pub fn persist_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        for line in text.lines() {
+            // Later lines win, so a re-resolved contract replaces the old ID
+            if let Some((key, con_id)) = line.split_once('\t')
+                && let Ok(con_id) = con_id.parse()
+            {
+                entries.insert(key.to_string(), con_id);
+            }
+        }
+        *self.file.lock().unwrap() = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// The cached contract ID of `contract`, if it has been resolved.
+    pub fn get(&self, contract: &Contract) -> Option<i32> {
+        self.entries.lock().unwrap().get(&contract.key()).copied()
+    }
+
+    /// Cache `con_id` for `contract`, appending it to the backing file if
+    /// there is one.
+    pub fn insert(&self, contract: &Contract, con_id: i32) -> Result<()> {
+        let key = contract.key();
+        self.entries.lock().unwrap().insert(key.clone(), con_id);
+        if let Some(path) = self.file.lock().unwrap().as_ref() {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{key}\t{con_id}")?;
+        }
+        Ok(())
+    }
+
+    /// Number of cached contracts.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persisted_entries_reload() {
+        let path = std::env::temp_dir().join(format!("ibkr-conids-{}.tsv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let aapl = Contract::stock("AAPL", "SMART", "USD");
+
+        let cache = ConIdCache::new();
+        cache.persist_to(&path).unwrap();
+        cache.insert(&aapl, 265598).unwrap();
+        assert_eq!(cache.get(&aapl), Some(265598));
+        assert_eq!(cache.get(&Contract::stock("AAPL", "SMART", "EUR")), None);
+
+        let reloaded = ConIdCache::new();
+        reloaded.persist_to(&path).unwrap();
+        assert_eq!(reloaded.get(&aapl), Some(265598));
+        assert_eq!(reloaded.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
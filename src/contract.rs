@@ -2,10 +2,21 @@
 //!
 //! A Contract uniquely identifies a tradeable instrument.
 
-use crate::wire::make_field;
+use crate::error::{Error, Result};
+use crate::server_versions;
+use crate::wire::{make_field, FieldIterator};
+
+/// Exchange name of IBKR's overnight trading venue for US equities.
+pub const OVERNIGHT_EXCHANGE: &str = "OVERNIGHT";
+
+/// Currencies traded on IDEALPRO.
+const FOREX_CURRENCIES: [&str; 25] = [
+    "AED", "AUD", "CAD", "CHF", "CNH", "CZK", "DKK", "EUR", "GBP", "HKD", "HUF", "ILS", "JPY",
+    "KRW", "MXN", "NOK", "NZD", "PLN", "RUB", "SAR", "SEK", "SGD", "TRY", "USD", "ZAR",
+];
 
 /// Security type identifiers.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum SecurityType {
     /// Stock or ETF
     #[default]
@@ -14,29 +25,96 @@ pub enum SecurityType {
     Option,
     /// Future
     Future,
+    /// Continuous future, for historical data
+    ContinuousFuture,
+    /// Future or continuous future, for contract details lookups
+    FutureOrContinuous,
+    /// Option on a future
+    FutureOption,
     /// Index
     Index,
     /// Forex pair
-    Forex,
-    /// Cash (for forex)
     Cash,
     /// Contract for difference
     Cfd,
     /// Combo/spread
     Bag,
+    /// Cryptocurrency
+    Crypto,
+    /// Bond
+    Bond,
+    /// Treasury bill
+    Bill,
+    /// Warrant
+    Warrant,
+    /// Dutch warrant or structured product
+    DutchWarrant,
+    /// Mutual fund
+    Fund,
+    /// Commodity (e.g. spot metals)
+    Commodity,
+    /// News feed, for news ticks
+    News,
+    /// A type TWS sent that this crate doesn't know, as sent
+    Other(String),
 }
 
 impl SecurityType {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Stock => "STK",
             Self::Option => "OPT",
             Self::Future => "FUT",
+            Self::ContinuousFuture => "CONTFUT",
+            Self::FutureOrContinuous => "FUT+CONTFUT",
+            Self::FutureOption => "FOP",
             Self::Index => "IND",
-            Self::Forex => "FOREX",
             Self::Cash => "CASH",
             Self::Cfd => "CFD",
             Self::Bag => "BAG",
+            Self::Crypto => "CRYPTO",
+            Self::Bond => "BOND",
+            Self::Bill => "BILL",
+            Self::Warrant => "WAR",
+            Self::DutchWarrant => "IOPT",
+            Self::Fund => "FUND",
+            Self::Commodity => "CMDTY",
+            Self::News => "NEWS",
+            Self::Other(sec_type) => sec_type,
+        }
+    }
+
+    /// Decode a security type sent by TWS, keeping unknown ones as
+    /// [`SecurityType::Other`].
+    pub fn from_wire(s: &str) -> Self {
+        s.parse().unwrap_or_else(|_| Self::Other(s.to_string()))
+    }
+}
+
+impl std::str::FromStr for SecurityType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "STK" => Ok(Self::Stock),
+            "OPT" => Ok(Self::Option),
+            "FUT" => Ok(Self::Future),
+            "CONTFUT" => Ok(Self::ContinuousFuture),
+            "FUT+CONTFUT" => Ok(Self::FutureOrContinuous),
+            "FOP" => Ok(Self::FutureOption),
+            "IND" => Ok(Self::Index),
+            "CASH" => Ok(Self::Cash),
+            "CFD" => Ok(Self::Cfd),
+            "BAG" => Ok(Self::Bag),
+            "CRYPTO" => Ok(Self::Crypto),
+            "BOND" => Ok(Self::Bond),
+            "BILL" => Ok(Self::Bill),
+            "WAR" => Ok(Self::Warrant),
+            "IOPT" => Ok(Self::DutchWarrant),
+            "FUND" => Ok(Self::Fund),
+            "CMDTY" => Ok(Self::Commodity),
+            "NEWS" => Ok(Self::News),
+            _ => Err(Error::Protocol(format!("Unknown security type: {s}"))),
         }
     }
 }
@@ -48,20 +126,42 @@ impl std::fmt::Display for SecurityType {
 }
 
 /// Option right (call or put).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum OptionRight {
     #[default]
     None,
     Call,
     Put,
+    /// A right TWS sent that this crate doesn't know, as sent
+    Other(String),
 }
 
 impl OptionRight {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::None => "",
             Self::Call => "C",
             Self::Put => "P",
+            Self::Other(right) => right,
+        }
+    }
+
+    /// Decode a right sent by TWS, keeping unknown ones as
+    /// [`OptionRight::Other`].
+    pub fn from_wire(s: &str) -> Self {
+        s.parse().unwrap_or_else(|_| Self::Other(s.to_string()))
+    }
+}
+
+impl std::str::FromStr for OptionRight {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" | "0" | "?" => Ok(Self::None),
+            "C" | "CALL" => Ok(Self::Call),
+            "P" | "PUT" => Ok(Self::Put),
+            _ => Err(Error::Protocol(format!("Unknown option right: {s}"))),
         }
     }
 }
@@ -72,6 +172,51 @@ impl std::fmt::Display for OptionRight {
     }
 }
 
+/// One leg of a combo (BAG) contract.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ComboLeg {
+    /// Contract ID of the leg
+    pub con_id: i32,
+    /// Units of the leg per unit of the combo
+    pub ratio: i32,
+    /// "BUY" or "SELL"
+    pub action: String,
+    /// Exchange the leg trades on
+    pub exchange: String,
+}
+
+impl ComboLeg {
+    pub fn new(con_id: i32, ratio: i32, action: &str, exchange: &str) -> Self {
+        Self {
+            con_id,
+            ratio,
+            action: action.to_string(),
+            exchange: exchange.to_string(),
+        }
+    }
+}
+
+/// Underlying position a delta-neutral combo or order is hedged with.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DeltaNeutralContract {
+    /// Contract ID of the underlying
+    pub con_id: i32,
+    /// Hedge delta
+    pub delta: f64,
+    /// Underlying price the delta was calculated at
+    pub price: f64,
+}
+
+impl DeltaNeutralContract {
+    pub fn new(con_id: i32, delta: f64, price: f64) -> Self {
+        Self {
+            con_id,
+            delta,
+            price,
+        }
+    }
+}
+
 /// Contract specification for a tradeable instrument.
 #[derive(Debug, Clone, Default)]
 pub struct Contract {
@@ -99,8 +244,16 @@ pub struct Contract {
     pub local_symbol: String,
     /// Trading class
     pub trading_class: String,
+    /// Security ID type (e.g. "CUSIP", "ISIN"), for lookups by `sec_id`
+    pub sec_id_type: String,
+    /// Security ID of type `sec_id_type`
+    pub sec_id: String,
     /// Include expired contracts in searches
     pub include_expired: bool,
+    /// Legs of a combo (BAG) contract
+    pub combo_legs: Vec<ComboLeg>,
+    /// Delta-neutral underlying component, for delta-neutral combos
+    pub delta_neutral_contract: Option<DeltaNeutralContract>,
 }
 
 impl Contract {
@@ -115,27 +268,285 @@ impl Contract {
         }
     }
 
-    /// Create a new forex contract.
-    pub fn forex(pair: &str) -> Self {
-        // Forex pairs are like "EUR.USD" -> symbol=EUR, currency=USD
+    /// Create a US stock contract routed to the OVERNIGHT venue.
+    ///
+    /// IBKR's overnight session trades US equities between the post-market
+    /// close and the pre-market open.
+    pub fn stock_overnight(symbol: &str) -> Self {
+        Self::stock(symbol, OVERNIGHT_EXCHANGE, "USD")
+    }
+
+    /// Create a futures contract expiring in `expiry` (`YYYYMM` or
+    /// `YYYYMMDD`).
+    pub fn futures(symbol: &str, exchange: &str, expiry: &str, currency: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            sec_type: SecurityType::Future,
+            last_trade_date: expiry.to_string(),
+            exchange: exchange.to_string(),
+            currency: currency.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a continuous futures contract, which TWS stitches together
+    /// from consecutive expiries.
+    ///
+    /// Only usable for historical bars without an end time, e.g. with
+    /// `Client::historical_data`. Contract details requests for
+    /// [`SecurityType::FutureOrContinuous`] list the expiries behind it.
+    pub fn continuous_futures(symbol: &str, exchange: &str, currency: &str) -> Self {
         Self {
-            symbol: pair.to_string(),
+            symbol: symbol.to_string(),
+            sec_type: SecurityType::ContinuousFuture,
+            exchange: exchange.to_string(),
+            currency: currency.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create an IDEALPRO forex contract for a pair like "EUR.USD",
+    /// "EUR/GBP" or "USDJPY".
+    ///
+    /// The base currency becomes the symbol and the quote currency the
+    /// contract's currency. Fails for currencies IDEALPRO doesn't trade.
+    pub fn forex(pair: &str) -> Result<Self> {
+        let invalid = || Error::InvalidCurrencyPair(pair.to_string());
+        let pair = pair.trim().to_ascii_uppercase();
+        let (base, quote) = match pair.split_once(['.', '/']) {
+            Some(split) => split,
+            None if pair.len() == 6 && pair.is_char_boundary(3) => pair.split_at(3),
+            None => return Err(invalid()),
+        };
+        let known = |currency: &str| FOREX_CURRENCIES.contains(&currency);
+        if !known(base) || !known(quote) || base == quote {
+            return Err(invalid());
+        }
+        Ok(Self {
+            symbol: base.to_string(),
             sec_type: SecurityType::Cash,
             exchange: "IDEALPRO".to_string(),
-            currency: "USD".to_string(),
+            currency: quote.to_string(),
             ..Default::default()
+        })
+    }
+
+    /// Create a cryptocurrency contract, e.g. `crypto("BTC", "PAXOS", "USD")`.
+    ///
+    /// Crypto trades in fractional quantities (sent as decimals, which needs
+    /// server version 163 or later); buys can also be sized in currency
+    /// with a cash quantity order.
+    pub fn crypto(symbol: &str, exchange: &str, currency: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            sec_type: SecurityType::Crypto,
+            exchange: exchange.to_string(),
+            currency: currency.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a bond contract identified by its CUSIP.
+    ///
+    /// Bonds are looked up by security ID; resolve the contract through
+    /// contract details to get its `con_id` before trading it.
+    pub fn bond_cusip(cusip: &str) -> Self {
+        Self::bond("CUSIP", cusip)
+    }
+
+    /// Create a bond contract identified by its ISIN.
+    pub fn bond_isin(isin: &str) -> Self {
+        Self::bond("ISIN", isin)
+    }
+
+    fn bond(sec_id_type: &str, sec_id: &str) -> Self {
+        Self {
+            sec_type: SecurityType::Bond,
+            ..Self::from_sec_id(sec_id_type, sec_id)
         }
     }
 
+    /// Create a SMART-routed stock contract identified by a security ID
+    /// (`sec_id_type` is "ISIN", "CUSIP", "FIGI", "SEDOL" or "RIC").
+    ///
+    /// TWS only accepts these in contract details requests and orders, so
+    /// resolve it first (e.g. with `Client::resolve_conid`) to use it for
+    /// market data. Set `sec_type` for anything other than a stock.
+    pub fn from_sec_id(sec_id_type: &str, sec_id: &str) -> Self {
+        Self {
+            exchange: "SMART".to_string(),
+            sec_id_type: sec_id_type.to_string(),
+            sec_id: sec_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a stock contract identified by its ISIN, e.g.
+    /// `from_isin("US0378331005")`. See [`Contract::from_sec_id`].
+    pub fn from_isin(isin: &str) -> Self {
+        Self::from_sec_id("ISIN", isin)
+    }
+
+    /// Create a combo (BAG) contract from its legs.
+    ///
+    /// `symbol` is usually the underlying's symbol; legs are identified by
+    /// contract ID.
+    pub fn combo(symbol: &str, exchange: &str, currency: &str, legs: Vec<ComboLeg>) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            sec_type: SecurityType::Bag,
+            exchange: exchange.to_string(),
+            currency: currency.to_string(),
+            combo_legs: legs,
+            ..Default::default()
+        }
+    }
+
+    /// Start building a contract of `sec_type`.
+    pub fn builder(sec_type: SecurityType) -> ContractBuilder {
+        ContractBuilder::new(sec_type)
+    }
+
+    /// Check that the contract has the fields its security type needs.
+    ///
+    /// A contract ID, or a security ID, identifies a contract on its own.
+    /// Otherwise options need an expiry, strike and right, and futures an
+    /// expiry; a local symbol stands in for all of these.
+    pub fn validate(&self) -> Result<()> {
+        if self.con_id != 0 || !self.sec_id.is_empty() {
+            return Ok(());
+        }
+        let mut missing = Vec::new();
+        let by_local_symbol = !self.local_symbol.is_empty();
+        if self.symbol.is_empty() && !by_local_symbol {
+            missing.push("symbol");
+        }
+        if self.exchange.is_empty() {
+            missing.push("exchange");
+        }
+        match self.sec_type {
+            SecurityType::Option | SecurityType::FutureOption if !by_local_symbol => {
+                if self.last_trade_date.is_empty() {
+                    missing.push("last_trade_date");
+                }
+                if self.strike <= 0.0 {
+                    missing.push("strike");
+                }
+                if self.right == OptionRight::None {
+                    missing.push("right");
+                }
+            }
+            SecurityType::Future if !by_local_symbol && self.last_trade_date.is_empty() => {
+                missing.push("last_trade_date");
+            }
+            SecurityType::Bag if self.combo_legs.is_empty() => missing.push("combo_legs"),
+            _ => {}
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidContract {
+                sec_type: self.sec_type.to_string(),
+                missing,
+            })
+        }
+    }
+
+    /// The contract's identifying fields joined with `-`, e.g.
+    /// `AAPL-STK-SMART-USD`, for keying caches. Doesn't include the con ID.
+    pub(crate) fn key(&self) -> String {
+        let mut parts = vec![self.symbol.clone(), self.sec_type.to_string()];
+        parts.push(self.last_trade_date.clone());
+        if self.strike != 0.0 {
+            parts.push(self.strike.to_string());
+        }
+        if self.right != OptionRight::None {
+            parts.push(self.right.to_string());
+        }
+        parts.push(self.multiplier.clone());
+        parts.push(self.exchange.clone());
+        parts.push(self.currency.clone());
+        parts.push(self.local_symbol.clone());
+        parts.push(self.trading_class.clone());
+        parts.push(self.sec_id_type.clone());
+        parts.push(self.sec_id.clone());
+        parts.retain(|part| !part.is_empty());
+        parts.join("-")
+    }
+
+    /// Encode the combo leg count and legs (con ID, ratio, action, exchange).
+    ///
+    /// Used by data requests, which only send legs for BAG contracts.
+    pub fn encode_combo_legs(&self) -> String {
+        let mut msg = make_field(self.combo_legs.len());
+        for leg in &self.combo_legs {
+            msg.push_str(&make_field(leg.con_id));
+            msg.push_str(&make_field(leg.ratio));
+            msg.push_str(&make_field(&leg.action));
+            msg.push_str(&make_field(&leg.exchange));
+        }
+        msg
+    }
+
+    /// Encode the delta-neutral flag, followed by the underlying's con ID,
+    /// delta and price when there is one.
+    pub fn encode_delta_neutral(&self) -> String {
+        match &self.delta_neutral_contract {
+            Some(component) => format!(
+                "{}{}{}{}",
+                make_field(1),
+                make_field(component.con_id),
+                make_field(component.delta),
+                make_field(component.price)
+            ),
+            None => make_field(0),
+        }
+    }
+
+    /// Parse contract fields from an incoming message, the counterpart of
+    /// [`Contract::encode`].
+    ///
+    /// Incoming messages carry a single exchange where requests send both
+    /// the exchange and primary exchange, so the primary exchange is left
+    /// empty; callers whose message sends the primary exchange in that slot
+    /// move it over.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            con_id: fields.next_i32(),
+            symbol: fields.next_string()?.to_string(),
+            sec_type: SecurityType::from_wire(fields.next_string()?),
+            last_trade_date: fields.next_string()?.to_string(),
+            strike: fields.next_f64(),
+            right: OptionRight::from_wire(fields.next_string()?),
+            multiplier: fields.next_string()?.to_string(),
+            exchange: fields.next_string()?.to_string(),
+            currency: fields.next_string()?.to_string(),
+            local_symbol: fields.next_string()?.to_string(),
+            trading_class: fields.next_string()?.to_string(),
+            ..Default::default()
+        })
+    }
+
     /// Encode contract fields for a request message.
     ///
     /// This encodes the standard contract fields used in most requests.
     /// Server version assumed >= 68 (MIN_SERVER_VER_TRADING_CLASS).
     pub fn encode(&self) -> String {
+        self.encode_for(u32::MAX)
+    }
+
+    /// Encode contract fields for a server of `server_version`.
+    ///
+    /// Servers before [`server_versions::TRADING_CLASS`] take neither the
+    /// contract ID nor the trading class, so both are left out.
+    pub fn encode_for(&self, server_version: u32) -> String {
         let mut msg = String::new();
+        let trading_class = server_version >= server_versions::TRADING_CLASS;
 
-        // conId (server version >= 68)
-        msg.push_str(&make_field(self.con_id));
+        if trading_class {
+            msg.push_str(&make_field(self.con_id));
+        }
 
         // Core fields
         msg.push_str(&make_field(&self.symbol));
@@ -156,13 +567,120 @@ impl Contract {
         msg.push_str(&make_field(&self.currency));
         msg.push_str(&make_field(&self.local_symbol));
 
-        // tradingClass (server version >= 68)
-        msg.push_str(&make_field(&self.trading_class));
+        if trading_class {
+            msg.push_str(&make_field(&self.trading_class));
+        }
 
         msg
     }
 }
 
+/// Builds a [`Contract`] field by field, validating it in
+/// [`ContractBuilder::build`].
+#[derive(Debug, Clone)]
+pub struct ContractBuilder {
+    contract: Contract,
+}
+
+impl ContractBuilder {
+    /// Create a builder for a contract of `sec_type`.
+    pub fn new(sec_type: SecurityType) -> Self {
+        Self {
+            contract: Contract {
+                sec_type,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Set the TWS contract ID.
+    pub fn con_id(mut self, con_id: i32) -> Self {
+        self.contract.con_id = con_id;
+        self
+    }
+
+    /// Set the ticker symbol.
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.contract.symbol = symbol.to_string();
+        self
+    }
+
+    /// Set the exchange (e.g. "SMART").
+    pub fn exchange(mut self, exchange: &str) -> Self {
+        self.contract.exchange = exchange.to_string();
+        self
+    }
+
+    /// Set the primary exchange, for SMART-routed contracts.
+    pub fn primary_exchange(mut self, exchange: &str) -> Self {
+        self.contract.primary_exchange = exchange.to_string();
+        self
+    }
+
+    /// Set the currency.
+    pub fn currency(mut self, currency: &str) -> Self {
+        self.contract.currency = currency.to_string();
+        self
+    }
+
+    /// Set the expiry (`YYYYMM` or `YYYYMMDD`).
+    pub fn expiry(mut self, expiry: &str) -> Self {
+        self.contract.last_trade_date = expiry.to_string();
+        self
+    }
+
+    /// Set the option strike.
+    pub fn strike(mut self, strike: f64) -> Self {
+        self.contract.strike = strike;
+        self
+    }
+
+    /// Set the option right.
+    pub fn right(mut self, right: OptionRight) -> Self {
+        self.contract.right = right;
+        self
+    }
+
+    /// Set the contract multiplier.
+    pub fn multiplier(mut self, multiplier: &str) -> Self {
+        self.contract.multiplier = multiplier.to_string();
+        self
+    }
+
+    /// Set the local exchange symbol, e.g. an OCC option symbol.
+    pub fn local_symbol(mut self, local_symbol: &str) -> Self {
+        self.contract.local_symbol = local_symbol.to_string();
+        self
+    }
+
+    /// Set the trading class.
+    pub fn trading_class(mut self, trading_class: &str) -> Self {
+        self.contract.trading_class = trading_class.to_string();
+        self
+    }
+
+    /// Identify the contract by a security ID, e.g. `("ISIN", "US0378331005")`.
+    pub fn sec_id(mut self, sec_id_type: &str, sec_id: &str) -> Self {
+        self.contract.sec_id_type = sec_id_type.to_string();
+        self.contract.sec_id = sec_id.to_string();
+        self
+    }
+
+    /// Add a combo leg.
+    pub fn combo_leg(mut self, leg: ComboLeg) -> Self {
+        self.contract.combo_legs.push(leg);
+        self
+    }
+
+    /// Check the contract and return it.
+    ///
+    /// See [`Contract::validate`] for the fields each security type needs.
+    pub fn build(self) -> Result<Contract> {
+        self.contract.validate()?;
+        Ok(self.contract)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,6 +694,121 @@ mod tests {
         assert_eq!(c.currency, "USD");
     }
 
+    #[test]
+    fn test_futures_contracts() {
+        let es = Contract::futures("ES", "CME", "202403", "USD");
+        assert_eq!(es.sec_type, SecurityType::Future);
+        assert_eq!(es.last_trade_date, "202403");
+        assert!(es.encode().starts_with("0\0ES\0FUT\x00202403\0"));
+
+        let continuous = Contract::continuous_futures("ES", "CME", "USD");
+        assert!(continuous.encode().starts_with("0\0ES\0CONTFUT\0\0"));
+        let lookup = "FUT+CONTFUT".parse::<SecurityType>().unwrap();
+        assert_eq!(lookup, SecurityType::FutureOrContinuous);
+    }
+
+    #[test]
+    fn test_crypto_contract() {
+        let btc = Contract::crypto("BTC", "PAXOS", "USD");
+        assert_eq!(btc.sec_type, SecurityType::Crypto);
+        assert!(btc.encode().starts_with("0\0BTC\0CRYPTO\0\0\0\0\0PAXOS\0\0USD\0"));
+        assert_eq!("CRYPTO".parse::<SecurityType>().unwrap(), SecurityType::Crypto);
+    }
+
+    #[test]
+    fn test_bond_contract() {
+        let bond = Contract::bond_cusip("912828YK0");
+        assert_eq!(bond.sec_type, SecurityType::Bond);
+        assert_eq!(bond.sec_id_type, "CUSIP");
+        assert_eq!(Contract::bond_isin("US912828YK04").sec_id_type, "ISIN");
+        assert_eq!("BOND".parse::<SecurityType>().unwrap(), SecurityType::Bond);
+    }
+
+    #[test]
+    fn test_encode_delta_neutral() {
+        let mut contract = Contract::stock("AAPL", "SMART", "USD");
+        assert_eq!(contract.encode_delta_neutral(), "0\0");
+        contract.delta_neutral_contract = Some(DeltaNeutralContract::new(265598, 0.5, 185.25));
+        assert_eq!(contract.encode_delta_neutral(), "1\x00265598\x000.5\x00185.25\0");
+    }
+
+    #[test]
+    fn test_builder_validates_per_security_type() {
+        let err = Contract::builder(SecurityType::Option)
+            .symbol("AAPL")
+            .exchange("SMART")
+            .strike(190.0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid OPT contract: missing last_trade_date, right");
+
+        let option = Contract::builder(SecurityType::Option)
+            .local_symbol("AAPL  240119C00190000")
+            .exchange("SMART")
+            .build()
+            .unwrap();
+        assert_eq!(option.local_symbol, "AAPL  240119C00190000");
+
+        let future = Contract::builder(SecurityType::Future).symbol("ES").exchange("CME");
+        assert!(future.clone().build().is_err());
+        assert!(future.expiry("202403").build().is_ok());
+        assert!(ContractBuilder::new(SecurityType::Bag).build().is_err());
+        assert!(ContractBuilder::new(SecurityType::Stock).con_id(265598).build().is_ok());
+    }
+
+    #[test]
+    fn test_from_isin() {
+        let aapl = Contract::from_isin("US0378331005");
+        assert_eq!(aapl.sec_type, SecurityType::Stock);
+        assert_eq!((aapl.sec_id_type.as_str(), aapl.sec_id.as_str()), ("ISIN", "US0378331005"));
+        assert!(aapl.validate().is_ok());
+        assert_eq!(aapl.key(), "STK-SMART-ISIN-US0378331005");
+    }
+
+    #[test]
+    fn test_forex_pairs() {
+        let eurgbp = Contract::forex("EUR.GBP").unwrap();
+        assert_eq!((eurgbp.symbol.as_str(), eurgbp.currency.as_str()), ("EUR", "GBP"));
+        assert_eq!(eurgbp.sec_type, SecurityType::Cash);
+        assert_eq!(eurgbp.exchange, "IDEALPRO");
+        assert_eq!(Contract::forex("usd/jpy").unwrap().currency, "JPY");
+        assert_eq!(Contract::forex("AUDNZD").unwrap().symbol, "AUD");
+
+        for pair in ["EUR", "EUR.XYZ", "USD.USD", "EUR.USD.GBP"] {
+            assert!(matches!(Contract::forex(pair), Err(Error::InvalidCurrencyPair(_))), "{pair}");
+        }
+    }
+
+    #[test]
+    fn test_stock_overnight() {
+        let c = Contract::stock_overnight("AAPL");
+        assert_eq!(c.exchange, "OVERNIGHT");
+        assert_eq!(c.currency, "USD");
+    }
+
+    #[test]
+    fn test_parse_security_type_and_right() {
+        assert_eq!("OPT".parse::<SecurityType>().unwrap(), SecurityType::Option);
+        assert_eq!("CASH".parse::<SecurityType>().unwrap(), SecurityType::Cash);
+        assert!("XYZ".parse::<SecurityType>().is_err());
+        for code in ["FOP", "WAR", "IOPT", "FUND", "BILL", "CMDTY", "NEWS"] {
+            assert_eq!(code.parse::<SecurityType>().unwrap().as_str(), code);
+        }
+        assert!("FOREX".parse::<SecurityType>().is_err());
+        assert_eq!("C".parse::<OptionRight>().unwrap(), OptionRight::Call);
+        assert_eq!("".parse::<OptionRight>().unwrap(), OptionRight::None);
+    }
+
+    #[test]
+    fn test_parse_keeps_unknown_sec_type() {
+        let buf = b"12087792\0EUR\0SLB\0\x000\0X\0\0IDEALPRO\0USD\0EUR.USD\0EUR.USD\0";
+        let contract = Contract::parse(&mut FieldIterator::new(buf)).unwrap();
+        assert_eq!(contract.sec_type, SecurityType::Other("SLB".into()));
+        assert_eq!(contract.right, OptionRight::Other("X".into()));
+        assert!(contract.encode().contains("\0EUR\0SLB\0"));
+        assert_eq!(SecurityType::from_wire("FUT"), SecurityType::Future);
+    }
+
     #[test]
     fn test_contract_encode() {
         let c = Contract::stock("AAPL", "SMART", "USD");
@@ -0,0 +1,393 @@
+//! Contract details, from `reqContractDetails`.
+//!
+//! TWS answers a contract details request with one [`ContractDetails`] per
+//! matching contract, so a partially specified contract (e.g. a symbol and
+//! security type only) lists every match. Bonds come back in their own
+//! message, with the bond terms in [`BondDetails`].
+
+use rust_decimal::Decimal;
+
+use crate::contract::{Contract, OptionRight, SecurityType};
+use crate::error::Result;
+use crate::server_versions;
+use crate::trading_hours::TradingHours;
+use crate::wire::{FieldIterator, TagValue};
+
+/// Everything TWS knows about a contract.
+#[derive(Debug, Clone, Default)]
+pub struct ContractDetails {
+    /// The fully specified contract, including its `con_id`
+    pub contract: Contract,
+    /// Market name (e.g. "NMS")
+    pub market_name: String,
+    /// Minimum price increment
+    pub min_tick: f64,
+    /// Order types accepted for the contract
+    pub order_types: Vec<String>,
+    /// Exchanges the contract can be routed to
+    pub valid_exchanges: Vec<String>,
+    /// Factor to divide prices by to get the real price
+    pub price_magnifier: i32,
+    /// Contract ID of the underlying, for derivatives
+    pub under_con_id: i32,
+    /// Descriptive name (e.g. "APPLE INC")
+    pub long_name: String,
+    /// Contract month, for derivatives
+    pub contract_month: String,
+    pub industry: String,
+    pub category: String,
+    pub subcategory: String,
+    /// Timezone of the trading hours (e.g. "US/Eastern")
+    pub time_zone_id: String,
+    /// Trading hours, `YYYYMMDD:HHMM-YYYYMMDD:HHMM;...` or `YYYYMMDD:CLOSED`
+    pub trading_hours: String,
+    /// Liquid (regular) trading hours, in the same format
+    pub liquid_hours: String,
+    /// Economic value rule, for contracts that have one
+    pub ev_rule: String,
+    /// Economic value multiplier
+    pub ev_multiplier: f64,
+    /// Last trade time, when TWS sends one with the expiry
+    pub last_trade_time: String,
+    /// Security IDs, e.g. `ISIN`
+    pub sec_id_list: Vec<TagValue>,
+    /// Market data aggregation group
+    pub agg_group: i32,
+    /// Symbol of the underlying
+    pub under_symbol: String,
+    /// Security type of the underlying
+    pub under_sec_type: String,
+    /// Market rule IDs, one per valid exchange
+    pub market_rule_ids: Vec<i32>,
+    /// Expiration date, when different from the last trade date
+    pub real_expiration_date: String,
+    /// Stock type (e.g. "COMMON", "ETF", "ADR")
+    pub stock_type: String,
+    /// Smallest order size
+    pub min_size: Decimal,
+    /// Increment order sizes have to be a multiple of
+    pub size_increment: Decimal,
+    /// Suggested order size increment
+    pub suggested_size_increment: Decimal,
+    /// Bond terms, for bond contracts
+    pub bond: Option<BondDetails>,
+}
+
+/// The terms of a bond, from `BOND_CONTRACT_DATA`.
+#[derive(Debug, Clone, Default)]
+pub struct BondDetails {
+    pub cusip: String,
+    /// Coupon rate, in percent
+    pub coupon: f64,
+    /// Maturity date (YYYYMMDD)
+    pub maturity: String,
+    /// Issue date (YYYYMMDD)
+    pub issue_date: String,
+    /// Credit ratings
+    pub ratings: String,
+    /// Bond type (e.g. "US T-NOTE")
+    pub bond_type: String,
+    /// Coupon type (e.g. "FIXED")
+    pub coupon_type: String,
+    pub convertible: bool,
+    pub callable: bool,
+    pub putable: bool,
+    /// Description suffix (e.g. "T 2 1/4 11/15/27")
+    pub desc_append: String,
+    /// Next call/put date, for callable/putable bonds
+    pub next_option_date: String,
+    /// Next option type ("CALL" or "PUT")
+    pub next_option_type: String,
+    /// Whether the next call/put is partial
+    pub next_option_partial: bool,
+    pub notes: String,
+}
+
+impl ContractDetails {
+    /// Parse contract details from a CONTRACT_DATA message, after the
+    /// message ID. Returns the request ID with the details.
+    pub fn parse(fields: &mut FieldIterator, server_version: u32) -> Option<(i32, Self)> {
+        // The message version was dropped along with the size rules change
+        let version = if server_version < server_versions::SIZE_RULES {
+            fields.next_i32()
+        } else {
+            8
+        };
+        let req_id = if version >= 3 { fields.next_i32() } else { -1 };
+
+        let symbol = fields.next_string()?.to_string();
+        let sec_type = SecurityType::from_wire(fields.next_string()?);
+        // The expiry can carry a last trade time, e.g. "20240119 16:00"
+        let expiry = fields.next_string()?;
+        let separator = if expiry.contains('-') { '-' } else { ' ' };
+        let (last_trade_date, last_trade_time) =
+            expiry.split_once(separator).unwrap_or((expiry, ""));
+        let strike = fields.next_f64();
+        let right = OptionRight::from_wire(fields.next_string()?);
+        let exchange = fields.next_string()?.to_string();
+        let currency = fields.next_string()?.to_string();
+        let local_symbol = fields.next_string()?.to_string();
+        let mut details = Self {
+            market_name: fields.next_string()?.to_string(),
+            last_trade_time: last_trade_time.to_string(),
+            ..Default::default()
+        };
+        details.contract = Contract {
+            symbol,
+            sec_type,
+            last_trade_date: last_trade_date.to_string(),
+            strike,
+            right,
+            exchange,
+            currency,
+            local_symbol,
+            trading_class: fields.next_string()?.to_string(),
+            con_id: fields.next_i32(),
+            ..Default::default()
+        };
+        details.min_tick = fields.next_f64();
+        if (server_versions::MD_SIZE_MULTIPLIER..server_versions::SIZE_RULES)
+            .contains(&server_version)
+        {
+            let _md_size_multiplier = fields.next_i32();
+        }
+        details.contract.multiplier = fields.next_string()?.to_string();
+        details.order_types = split_list(fields.next_string()?);
+        details.valid_exchanges = split_list(fields.next_string()?);
+        details.price_magnifier = fields.next_i32();
+        if version >= 4 {
+            details.under_con_id = fields.next_i32();
+        }
+        if version >= 5 {
+            details.long_name = fields.next_string()?.to_string();
+            details.contract.primary_exchange = fields.next_string()?.to_string();
+        }
+        if version >= 6 {
+            details.contract_month = fields.next_string()?.to_string();
+            details.industry = fields.next_string()?.to_string();
+            details.category = fields.next_string()?.to_string();
+            details.subcategory = fields.next_string()?.to_string();
+            details.time_zone_id = fields.next_string()?.to_string();
+            details.trading_hours = fields.next_string()?.to_string();
+            details.liquid_hours = fields.next_string()?.to_string();
+        }
+        if version >= 8 {
+            details.ev_rule = fields.next_string()?.to_string();
+            details.ev_multiplier = fields.next_f64();
+        }
+        if version >= 7 {
+            details.sec_id_list = parse_sec_ids(fields)?;
+        }
+        if server_version >= server_versions::AGG_GROUP {
+            details.agg_group = fields.next_i32();
+        }
+        if server_version >= server_versions::UNDERLYING_INFO {
+            details.under_symbol = fields.next_string()?.to_string();
+            details.under_sec_type = fields.next_string()?.to_string();
+        }
+        if server_version >= server_versions::MARKET_RULES {
+            details.market_rule_ids = fields
+                .next_string()?
+                .split(',')
+                .filter_map(|id| id.parse().ok())
+                .collect();
+        }
+        if server_version >= server_versions::REAL_EXPIRATION_DATE {
+            details.real_expiration_date = fields.next_string()?.to_string();
+        }
+        if server_version >= server_versions::STOCK_TYPE {
+            details.stock_type = fields.next_string()?.to_string();
+        }
+        if server_version == server_versions::FRACTIONAL_SIZE_SUPPORT {
+            let _size_min_tick = fields.next_decimal();
+        }
+        if server_version >= server_versions::SIZE_RULES {
+            details.min_size = fields.next_decimal();
+            details.size_increment = fields.next_decimal();
+            details.suggested_size_increment = fields.next_decimal();
+        }
+
+        Some((req_id, details))
+    }
+
+    /// The trading hours as sessions in the contract's timezone.
+    pub fn trading_sessions(&self) -> Result<TradingHours> {
+        TradingHours::parse(&self.trading_hours, &self.time_zone_id)
+    }
+
+    /// The liquid (regular) hours as sessions in the contract's timezone.
+    pub fn liquid_sessions(&self) -> Result<TradingHours> {
+        TradingHours::parse(&self.liquid_hours, &self.time_zone_id)
+    }
+
+    /// Parse bond contract details from a BOND_CONTRACT_DATA message,
+    /// after the message ID. Returns the request ID with the details.
+    ///
+    /// Bonds have no strike, right or multiplier; their terms go in
+    /// [`ContractDetails::bond`].
+    pub fn parse_bond(fields: &mut FieldIterator, server_version: u32) -> Option<(i32, Self)> {
+        let version = if server_version < server_versions::SIZE_RULES {
+            fields.next_i32()
+        } else {
+            6
+        };
+        let req_id = if version >= 3 { fields.next_i32() } else { -1 };
+
+        let symbol = fields.next_string()?.to_string();
+        let sec_type = SecurityType::from_wire(fields.next_string()?);
+        let mut bond = BondDetails {
+            cusip: fields.next_string()?.to_string(),
+            coupon: fields.next_f64(),
+            ..Default::default()
+        };
+        // The maturity can carry a last trade time, like an expiry
+        let maturity = fields.next_string()?;
+        let (maturity, last_trade_time) = maturity.split_once(' ').unwrap_or((maturity, ""));
+        bond.maturity = maturity.to_string();
+        bond.issue_date = fields.next_string()?.to_string();
+        bond.ratings = fields.next_string()?.to_string();
+        bond.bond_type = fields.next_string()?.to_string();
+        bond.coupon_type = fields.next_string()?.to_string();
+        bond.convertible = fields.next_bool();
+        bond.callable = fields.next_bool();
+        bond.putable = fields.next_bool();
+        bond.desc_append = fields.next_string()?.to_string();
+        let exchange = fields.next_string()?.to_string();
+        let currency = fields.next_string()?.to_string();
+        let mut details = Self {
+            market_name: fields.next_string()?.to_string(),
+            last_trade_time: last_trade_time.to_string(),
+            ..Default::default()
+        };
+        details.contract = Contract {
+            symbol,
+            sec_type,
+            exchange,
+            currency,
+            trading_class: fields.next_string()?.to_string(),
+            con_id: fields.next_i32(),
+            ..Default::default()
+        };
+        details.min_tick = fields.next_f64();
+        if (server_versions::MD_SIZE_MULTIPLIER..server_versions::SIZE_RULES)
+            .contains(&server_version)
+        {
+            let _md_size_multiplier = fields.next_i32();
+        }
+        details.order_types = split_list(fields.next_string()?);
+        details.valid_exchanges = split_list(fields.next_string()?);
+        bond.next_option_date = fields.next_string()?.to_string();
+        bond.next_option_type = fields.next_string()?.to_string();
+        bond.next_option_partial = fields.next_bool();
+        bond.notes = fields.next_string()?.to_string();
+        if version >= 4 {
+            details.long_name = fields.next_string()?.to_string();
+        }
+        if version >= 6 {
+            details.ev_rule = fields.next_string()?.to_string();
+            details.ev_multiplier = fields.next_f64();
+        }
+        if version >= 5 {
+            details.sec_id_list = parse_sec_ids(fields)?;
+        }
+        if server_version >= server_versions::AGG_GROUP {
+            details.agg_group = fields.next_i32();
+        }
+        if server_version >= server_versions::MARKET_RULES {
+            details.market_rule_ids = fields
+                .next_string()?
+                .split(',')
+                .filter_map(|id| id.parse().ok())
+                .collect();
+        }
+        if server_version >= server_versions::SIZE_RULES {
+            details.min_size = fields.next_decimal();
+            details.size_increment = fields.next_decimal();
+            details.suggested_size_increment = fields.next_decimal();
+        }
+        details.bond = Some(bond);
+
+        Some((req_id, details))
+    }
+}
+
+fn parse_sec_ids(fields: &mut FieldIterator) -> Option<Vec<TagValue>> {
+    let count = fields.next_i32();
+    let mut sec_ids = Vec::new();
+    for _ in 0..count {
+        let tag = fields.next_string()?;
+        let value = fields.next_string()?;
+        sec_ids.push(TagValue::new(tag, value));
+    }
+    Some(sec_ids)
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::SecurityType;
+
+    #[test]
+    fn test_parse_contract_details() {
+        let mut raw = vec!["1000", "AAPL", "STK", "", "0", "", "SMART", "USD", "AAPL", "NMS"];
+        raw.extend(["NMS", "265598", "0.01", "", "ACTIVETIM,AD,LMT,MKT", "SMART,NASDAQ,ISLAND"]);
+        raw.extend(["1", "0"]);
+        raw.extend(["APPLE INC", "NASDAQ", "", "Technology", "Computers", "Computers"]);
+        raw.extend(["US/Eastern", "20240105:0400-20240105:2000;20240106:CLOSED"]);
+        raw.extend(["20240105:0930-20240105:1600;20240106:CLOSED", "", ""]);
+        // secIdList, aggGroup, underlying, market rules, real expiration, stock type
+        raw.extend(["1", "ISIN", "US0378331005", "1", "", "", "26,26,26", "", "COMMON"]);
+        raw.extend(["1", "1", "100"]);
+        let buf = raw.join("\0") + "\0";
+
+        let mut fields = FieldIterator::new(buf.as_bytes());
+        let (req_id, details) = ContractDetails::parse(&mut fields, 176).unwrap();
+
+        assert_eq!(req_id, 1000);
+        assert_eq!(details.contract.con_id, 265598);
+        assert_eq!(details.contract.sec_type, SecurityType::Stock);
+        assert_eq!(details.contract.primary_exchange, "NASDAQ");
+        assert_eq!(details.min_tick, 0.01);
+        assert_eq!(details.valid_exchanges, ["SMART", "NASDAQ", "ISLAND"]);
+        assert_eq!(details.long_name, "APPLE INC");
+        assert_eq!(details.time_zone_id, "US/Eastern");
+        assert!(details.liquid_hours.starts_with("20240105:0930"));
+        assert_eq!(details.liquid_sessions().unwrap().sessions.len(), 1);
+        assert_eq!(details.sec_id_list[0].value, "US0378331005");
+        assert_eq!(details.market_rule_ids, [26, 26, 26]);
+        assert_eq!(details.stock_type, "COMMON");
+        assert_eq!(details.suggested_size_increment, Decimal::from(100));
+        assert!(fields.next_string().is_none());
+    }
+
+    #[test]
+    fn test_parse_bond_contract_details() {
+        let mut raw = vec!["1000", "T", "BOND", "912828YK0", "2.25", "20271115", "20171115"];
+        raw.extend(["AA+", "US T-NOTE", "FIXED", "0", "0", "0", "T 2 1/4 11/15/27"]);
+        raw.extend(["SMART", "USD", "BOND", "", "345014823", "0.0001", "LMT", "SMART"]);
+        // next option date/type/partial, notes, long name, ev rule/multiplier
+        raw.extend(["", "", "0", "", "United States Treasury", "", ""]);
+        raw.extend(["1", "CUSIP", "912828YK0", "1", "", "1000", "1000", "1000"]);
+        let buf = raw.join("\0") + "\0";
+
+        let mut fields = FieldIterator::new(buf.as_bytes());
+        let (req_id, details) = ContractDetails::parse_bond(&mut fields, 176).unwrap();
+
+        assert_eq!(req_id, 1000);
+        assert_eq!(details.contract.sec_type, SecurityType::Bond);
+        assert_eq!(details.contract.con_id, 345014823);
+        assert_eq!(details.long_name, "United States Treasury");
+        let bond = details.bond.unwrap();
+        assert_eq!(bond.cusip, "912828YK0");
+        assert_eq!(bond.coupon, 2.25);
+        assert_eq!(bond.maturity, "20271115");
+        assert_eq!(bond.bond_type, "US T-NOTE");
+        assert!(!bond.callable);
+        assert_eq!(details.min_size, Decimal::from(1000));
+        assert!(fields.next_string().is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! TWS date/time parsing and formatting.
+//!
+//! TWS uses several shapes for the same thing:
+//! - `"20240105"` - date only (daily bars)
+//! - `"20240105 09:30:00"` - wall-clock time in the TWS login timezone
+//! - `"20240105 09:30:00 US/Eastern"` - wall-clock time in a named timezone
+//! - `"20240105-14:30:00"` - UTC (accepted in requests by newer servers)
+//! - `"1704465000"` - seconds since the Unix epoch
+//!
+//! [`DateTime`] parses all of them and formats request strings. Named
+//! timezones are kept as-is; only UTC values can be converted to a
+//! timestamp, since there is no timezone database here. With the `chrono`
+//! feature, values convert to and from `chrono` date/times, resolving
+//! timezone names with `chrono-tz`.
+
+#[cfg(feature = "chrono")]
+use chrono_tz::Tz;
+
+use crate::error::{Error, Result};
+use crate::server_versions;
+
+/// Timezone name used for UTC values.
+const UTC: &str = "UTC";
+
+/// A date and time as sent or accepted by TWS.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Timezone name ("UTC", "US/Eastern", ...), `None` for the TWS login timezone
+    pub timezone: Option<String>,
+}
+
+impl DateTime {
+    /// Create a UTC date/time.
+    pub fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            timezone: Some(UTC.to_string()),
+        }
+    }
+
+    /// Convert seconds since the Unix epoch to a UTC date/time.
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(86_400);
+        let secs = timestamp.rem_euclid(86_400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self::utc(year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+    }
+
+    /// Parse any of the date/time shapes TWS sends.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid = || Error::Protocol(format!("Invalid datetime: {s:?}"));
+
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return if s.len() == 8 {
+                let (year, month, day) = parse_date(s).ok_or_else(invalid)?;
+                Ok(Self {
+                    year,
+                    month,
+                    day,
+                    hour: 0,
+                    minute: 0,
+                    second: 0,
+                    timezone: None,
+                })
+            } else {
+                s.parse().map(Self::from_timestamp).map_err(|_| invalid())
+            };
+        }
+
+        let date = s.get(..8).ok_or_else(invalid)?;
+        let (year, month, day) = parse_date(date).ok_or_else(invalid)?;
+        let rest = &s[8..];
+
+        let (time, timezone) = if let Some(time) = rest.strip_prefix('-') {
+            (time, Some(UTC.to_string()))
+        } else {
+            // Some messages separate date and time with two spaces.
+            let rest = rest.trim_start();
+            match rest.split_once(' ') {
+                Some((time, zone)) => (time, Some(zone.trim().to_string())),
+                None => (rest, None),
+            }
+        };
+        let (hour, minute, second) = parse_time(time).ok_or_else(invalid)?;
+
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            timezone,
+        })
+    }
+
+    /// Whether the value is known to be in UTC.
+    pub fn is_utc(&self) -> bool {
+        matches!(self.timezone.as_deref(), Some("UTC" | "GMT" | "Etc/UTC" | "Etc/GMT"))
+    }
+
+    /// Seconds since the Unix epoch, if the value is in UTC.
+    pub fn timestamp(&self) -> Option<i64> {
+        if !self.is_utc() {
+            return None;
+        }
+        let days = days_from_civil(self.year, self.month, self.day);
+        Some(days * 86_400 + i64::from(self.hour * 3600 + self.minute * 60 + self.second))
+    }
+
+    /// The date as `YYYYMMDD`.
+    pub fn date_string(&self) -> String {
+        format!("{:04}{:02}{:02}", self.year, self.month, self.day)
+    }
+
+    /// Format for use in a request (e.g. a historical data end time).
+    ///
+    /// UTC values use the `YYYYMMDD-HH:MM:SS` form; others are sent as
+    /// wall-clock time with their timezone name, if any.
+    pub fn to_request_string(&self) -> String {
+        self.to_request_string_for(u32::MAX)
+    }
+
+    /// Format for use in a request to a server of `server_version`.
+    ///
+    /// Servers before [`server_versions::UTC_DATE_TIME`] only accept the
+    /// `YYYYMMDD HH:MM:SS <timezone>` form, so UTC values are sent with
+    /// their timezone name instead.
+    pub fn to_request_string_for(&self, server_version: u32) -> String {
+        if self.is_utc() && server_version >= server_versions::UTC_DATE_TIME {
+            format!(
+                "{}-{:02}:{:02}:{:02}",
+                self.date_string(),
+                self.hour,
+                self.minute,
+                self.second
+            )
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+impl std::str::FromStr for DateTime {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date_string(),
+            self.hour,
+            self.minute,
+            self.second
+        )?;
+        if let Some(timezone) = &self.timezone {
+            write!(f, " {timezone}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl DateTime {
+    /// Convert to a `chrono` date/time in the value's own timezone, or in
+    /// `login_timezone` for values without one.
+    ///
+    /// Returns `None` for unknown timezone names and wall-clock times that
+    /// don't exist in the timezone. Ambiguous times resolve to the earlier
+    /// instant.
+    pub fn to_chrono(&self, login_timezone: Tz) -> Option<chrono::DateTime<Tz>> {
+        use chrono::TimeZone;
+
+        let timezone = match self.timezone.as_deref() {
+            // GMT and the Etc/ aliases all mean UTC
+            Some(_) if self.is_utc() => chrono_tz::UTC,
+            Some(name) => name.parse().ok()?,
+            None => login_timezone,
+        };
+        let date = chrono::NaiveDate::from_ymd_opt(self.year, self.month, self.day)?;
+        let local = date.and_hms_opt(self.hour, self.minute, self.second)?;
+        timezone.from_local_datetime(&local).earliest()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for DateTime {
+    /// Convert to UTC, which requests send in the `YYYYMMDD-HH:MM:SS` form.
+    fn from(value: chrono::DateTime<Tz>) -> Self {
+        use chrono::{Datelike, Timelike};
+
+        let utc = value.naive_utc();
+        Self::utc(utc.year(), utc.month(), utc.day(), utc.hour(), utc.minute(), utc.second())
+    }
+}
+
+fn parse_date(s: &str) -> Option<(i32, u32, u32)> {
+    if s.len() != 8 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year = s[..4].parse().ok()?;
+    let month = s[4..6].parse().ok()?;
+    let day = s[6..].parse().ok()?;
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+fn parse_time(s: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = s.split(':').map(|part| part.parse::<u32>().ok());
+    let hour = parts.next()??;
+    let minute = parts.next()??;
+    let second = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some((hour, minute, second))
+}
+
+// Civil calendar conversions from Howard Hinnant's date algorithms.
+
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shapes() {
+        let zoned = DateTime::parse("20240105 09:30:00 US/Eastern").unwrap();
+        assert_eq!((zoned.year, zoned.month, zoned.day), (2024, 1, 5));
+        assert_eq!((zoned.hour, zoned.minute, zoned.second), (9, 30, 0));
+        assert_eq!(zoned.timezone.as_deref(), Some("US/Eastern"));
+        assert_eq!(zoned.timestamp(), None);
+
+        let local = DateTime::parse("20240105  09:30:00").unwrap();
+        assert_eq!(local.timezone, None);
+        assert_eq!(local.hour, 9);
+
+        let utc = DateTime::parse("20240105-14:30:00").unwrap();
+        assert_eq!(utc, DateTime::utc(2024, 1, 5, 14, 30, 0));
+
+        let epoch = DateTime::parse("1704465000").unwrap();
+        assert_eq!(epoch, utc);
+        assert_eq!(epoch.timestamp(), Some(1_704_465_000));
+
+        let date = DateTime::parse("20240105").unwrap();
+        assert_eq!(date.date_string(), "20240105");
+
+        assert!(DateTime::parse("2024-01-05").is_err());
+        assert!(DateTime::parse("20241305 09:30:00").is_err());
+        assert!(DateTime::parse("20240105 25:00:00").is_err());
+        assert!(DateTime::parse("").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let utc = DateTime::utc(2024, 1, 5, 14, 30, 0);
+        assert_eq!(utc.to_request_string(), "20240105-14:30:00");
+        assert_eq!(utc.to_request_string_for(151), "20240105 14:30:00 UTC");
+
+        let zoned = DateTime::parse("20240105 09:30:00 US/Eastern").unwrap();
+        assert_eq!(zoned.to_request_string(), "20240105 09:30:00 US/Eastern");
+        assert_eq!(zoned.to_string(), "20240105 09:30:00 US/Eastern");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_conversions() {
+        use chrono::TimeZone;
+
+        let zoned = DateTime::parse("20240105 09:30:00 US/Eastern").unwrap();
+        let converted = zoned.to_chrono(chrono_tz::UTC).unwrap();
+        assert_eq!(converted.timestamp(), 1_704_465_000);
+
+        let local = DateTime::parse("20240105 09:30:00").unwrap();
+        let converted = local.to_chrono(chrono_tz::Europe::London).unwrap();
+        assert_eq!(converted.timestamp(), 1_704_447_000);
+
+        let end = chrono_tz::US::Eastern.with_ymd_and_hms(2024, 1, 5, 16, 0, 0).unwrap();
+        assert_eq!(DateTime::from(end).to_request_string(), "20240105-21:00:00");
+    }
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        for timestamp in [0, 951_782_400, 1_704_465_000, -86_401, 4_102_444_799] {
+            assert_eq!(DateTime::from_timestamp(timestamp).timestamp(), Some(timestamp));
+        }
+        assert_eq!(DateTime::from_timestamp(951_782_400).date_string(), "20000229");
+    }
+}
@@ -0,0 +1,78 @@
+//! Paper vs. live session detection.
+
+/// Whether a session trades real money.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    /// Paper trading (simulated) account
+    Paper,
+    /// Live account
+    Live,
+    /// Not enough information to tell
+    Unknown,
+}
+
+impl Environment {
+    /// Classify a session from its managed accounts and the port it connected to.
+    ///
+    /// Paper accounts start with "DU" (individual) or "DF" (advisor master),
+    /// and take precedence when known. Otherwise the default ports are used:
+    /// 7497/4002 for paper TWS/Gateway, 7496/4001 for live.
+    pub fn detect(accounts: &[String], port: Option<u16>) -> Self {
+        if !accounts.is_empty() {
+            let paper = accounts
+                .iter()
+                .all(|account| account.starts_with("DU") || account.starts_with("DF"));
+            return if paper { Self::Paper } else { Self::Live };
+        }
+
+        match port {
+            Some(7497 | 4002) => Self::Paper,
+            Some(7496 | 4001) => Self::Live,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Paper => "paper",
+            Self::Live => "live",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the session should be treated as live (unknown counts as live).
+    pub fn is_live(&self) -> bool {
+        *self != Self::Paper
+    }
+}
+
+impl std::fmt::Display for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(|code| code.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_from_accounts() {
+        assert_eq!(Environment::detect(&accounts(&["DU123456"]), Some(7496)), Environment::Paper);
+        assert_eq!(Environment::detect(&accounts(&["DF1", "DU2"]), None), Environment::Paper);
+        assert_eq!(Environment::detect(&accounts(&["U1234567"]), Some(7497)), Environment::Live);
+        assert_eq!(Environment::detect(&accounts(&["DU1", "U2"]), None), Environment::Live);
+    }
+
+    #[test]
+    fn test_detect_from_port() {
+        assert_eq!(Environment::detect(&[], Some(4002)), Environment::Paper);
+        assert_eq!(Environment::detect(&[], Some(7496)), Environment::Live);
+        assert_eq!(Environment::detect(&[], Some(9999)), Environment::Unknown);
+        assert!(Environment::Unknown.is_live());
+    }
+}
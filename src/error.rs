@@ -14,8 +14,55 @@ pub enum Error {
     Protocol(String),
 
     /// TWS/Gateway returned an error.
-    #[error("TWS error {code}: {message}")]
-    Tws { code: i32, message: String },
+    #[error("TWS error {code}: {message}{}", context_suffix(context))]
+    Tws {
+        code: i32,
+        message: String,
+        /// The request that triggered the error, when it could be identified
+        context: Option<Box<RequestContext>>,
+    },
+
+    /// Order rejected client-side before sending.
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+
+    /// Contract missing fields its security type needs, caught before
+    /// TWS rejects it with error 200.
+    #[error("Invalid {sec_type} contract: missing {}", missing.join(", "))]
+    InvalidContract {
+        sec_type: String,
+        /// Names of the missing `Contract` fields
+        missing: Vec<&'static str>,
+    },
+
+    /// Currency pair that isn't two distinct known currencies.
+    #[error("Invalid currency pair: {0}")]
+    InvalidCurrencyPair(String),
+
+    /// Contract details didn't narrow a contract down to one match.
+    #[error("{matches} contracts match {contract}, expected exactly one")]
+    AmbiguousContract { contract: String, matches: usize },
+
+    /// Historical data request rejected client-side before sending.
+    #[error("Invalid historical data request: {0}")]
+    InvalidHistoricalRequest(String),
+
+    /// Order ID already used for another order (TWS error 103).
+    #[error("Order ID {0} was already used")]
+    DuplicateOrderId(i32),
+
+    /// Order was cancelled or went inactive before it filled.
+    #[error("Order {order_id} ended as {status} before filling")]
+    OrderNotFilled { order_id: i32, status: String },
+
+    /// Placing an OCA group failed part way; the orders already placed
+    /// are working at TWS.
+    #[error("OCA group stopped after placing orders {placed:?}: {source}")]
+    PartialOcaGroup {
+        /// IDs of the orders placed before the failure
+        placed: Vec<i32>,
+        source: Box<Error>,
+    },
 
     /// Connection not established.
     #[error("Not connected")]
@@ -26,5 +73,68 @@ pub enum Error {
     Timeout,
 }
 
+/// Describes the request that triggered a TWS error.
+///
+/// TWS error texts are often generic ("Error validating request"), so this
+/// records what was actually sent.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// Request ID the error was reported against
+    pub req_id: i32,
+    /// Kind of request (e.g. "historical_data")
+    pub kind: &'static str,
+    /// Human-readable request parameters
+    pub params: String,
+    /// Encoded request payload (only captured when enabled on the client)
+    pub payload: Option<String>,
+}
+
+impl std::fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} req {}", self.kind, self.req_id)?;
+        if !self.params.is_empty() {
+            write!(f, ": {}", self.params)?;
+        }
+        Ok(())
+    }
+}
+
+fn context_suffix(context: &Option<Box<RequestContext>>) -> String {
+    context
+        .as_ref()
+        .map(|context| format!(" ({context})"))
+        .unwrap_or_default()
+}
+
 /// Result type alias for IBKR operations.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tws_error_display() {
+        let err = Error::Tws {
+            code: 321,
+            message: "Error validating request".into(),
+            context: None,
+        };
+        assert_eq!(err.to_string(), "TWS error 321: Error validating request");
+
+        let err = Error::Tws {
+            code: 321,
+            message: "Error validating request".into(),
+            context: Some(Box::new(RequestContext {
+                req_id: 1000,
+                kind: "historical_data",
+                params: "symbol=AAPL".into(),
+                payload: None,
+            })),
+        };
+        assert_eq!(
+            err.to_string(),
+            "TWS error 321: Error validating request (historical_data req 1000: symbol=AAPL)"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Connection-level events.
+//!
+//! Messages that aren't claimed by a pending request or subscription are
+//! published as [`Event`]s, so nothing TWS sends is silently discarded.
+
+use crate::account::{AccountValue, PortfolioValue};
+use crate::market_data::TickReqParams;
+use crate::wire::FieldIterator;
+
+/// A message from TWS that no request or subscription claimed.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Next valid order ID, sent on connect
+    NextValidId(i32),
+    /// Accounts accessible by this login
+    ManagedAccounts(Vec<String>),
+    /// Account value update
+    AccountValue(AccountValue),
+    /// Time of the last account update (HH:MM)
+    AccountUpdateTime(String),
+    /// Portfolio entry update
+    PortfolioValue(Box<PortfolioValue>),
+    /// News bulletin or exchange notice
+    NewsBulletin(NewsBulletin),
+    /// An order placed in TWS was bound to an API order ID
+    OrderBound {
+        order_id: i64,
+        api_client_id: i32,
+        api_order_id: i32,
+    },
+    /// Min tick, BBO exchange and snapshot permissions for a market data
+    /// request that has no subscription (see `Subscription::tick_req_params`)
+    TickReqParams { req_id: i32, params: TickReqParams },
+    /// Live market data was refused (error 354 or 10197)
+    ///
+    /// Published in addition to failing the affected request, unless it
+    /// falls back to delayed data (see `Client::fall_back_to_delayed`).
+    /// Otherwise switching to delayed data with
+    /// `Client::set_market_data_type` and resubscribing usually works; a
+    /// competing session may also end later.
+    MarketDataUnavailable {
+        req_id: i32,
+        code: i32,
+        message: String,
+        /// Blocked by a live session logged in elsewhere (10197)
+        competing_session: bool,
+    },
+    /// TWS error or notice not matched to a pending request
+    Error(UnroutableError),
+    /// A message this crate doesn't decode yet, with its raw fields
+    Unhandled { msg_id: u32, fields: Vec<String> },
+}
+
+/// A TWS error that could not be matched to a pending request.
+///
+/// Includes connection notices sent with a request ID of -1 (e.g. market
+/// data farm status) as well as errors for requests that already timed out.
+#[derive(Debug, Clone)]
+pub struct UnroutableError {
+    pub req_id: i32,
+    pub code: i32,
+    pub message: String,
+}
+
+/// A news bulletin from TWS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewsBulletin {
+    /// Bulletin ID
+    pub id: i32,
+    /// 1 = regular bulletin, 2 = exchange no longer available, 3 = exchange available again
+    pub kind: i32,
+    /// Bulletin text
+    pub message: String,
+    /// Exchange the bulletin originated from
+    pub exchange: String,
+}
+
+impl NewsBulletin {
+    /// Parse a bulletin from message fields (after the version).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            id: fields.next_i32(),
+            kind: fields.next_i32(),
+            message: fields.next_string()?.to_string(),
+            exchange: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_news_bulletin() {
+        let buf = b"7\x002\0Exchange unavailable\0ISLAND\0";
+        let mut fields = FieldIterator::new(buf);
+        let bulletin = NewsBulletin::parse(&mut fields).unwrap();
+
+        assert_eq!(bulletin.id, 7);
+        assert_eq!(bulletin.kind, 2);
+        assert_eq!(bulletin.message, "Exchange unavailable");
+        assert_eq!(bulletin.exchange, "ISLAND");
+    }
+}
@@ -0,0 +1,298 @@
+//! Executions, commission reports and fill filtering.
+
+use rust_decimal::Decimal;
+
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::message::OutgoingMessageId;
+use crate::server_versions;
+use crate::wire::{make_field, FieldIterator};
+
+/// A single execution (fill) of an order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub order_id: i32,
+    /// Unique execution ID; corrections repeat the ID with a new suffix
+    pub exec_id: String,
+    /// Execution time, usually with a timezone name
+    pub time: DateTime,
+    pub account: String,
+    /// Exchange the execution happened on
+    pub exchange: String,
+    /// "BOT" or "SLD"
+    pub side: String,
+    pub shares: Decimal,
+    pub price: f64,
+    pub perm_id: i32,
+    pub client_id: i32,
+    /// Nonzero if the execution was an IB-initiated liquidation
+    pub liquidation: i32,
+    /// Cumulative quantity filled for the order
+    pub cum_qty: Decimal,
+    pub avg_price: f64,
+    pub order_ref: String,
+    pub ev_rule: String,
+    pub ev_multiplier: Option<f64>,
+    pub model_code: String,
+    /// 1 = added liquidity, 2 = removed liquidity, 3 = routed out
+    pub last_liquidity: i32,
+}
+
+impl Execution {
+    /// Parse an EXECUTION_DATA message (after the message ID).
+    ///
+    /// Returns the request ID (-1 for live executions), the contract and
+    /// the execution.
+    pub fn parse(fields: &mut FieldIterator, server_version: u32) -> Option<(i32, Contract, Self)> {
+        // Servers that send last liquidity dropped the version field.
+        let version = if server_version < server_versions::LAST_LIQUIDITY {
+            fields.next_i32() as u32
+        } else {
+            server_version
+        };
+        let req_id = if version >= 7 { fields.next_i32() } else { -1 };
+        let order_id = fields.next_i32();
+
+        // Every server this crate connects to sends version 10 or later,
+        // which has the full contract.
+        let contract = Contract::parse(fields)?;
+
+        let mut execution = Self {
+            order_id,
+            exec_id: fields.next_string()?.to_string(),
+            time: DateTime::parse(fields.next_string()?).ok()?,
+            account: fields.next_string()?.to_string(),
+            exchange: fields.next_string()?.to_string(),
+            side: fields.next_string()?.to_string(),
+            shares: fields.next_decimal(),
+            price: fields.next_f64(),
+            perm_id: fields.next_i32(),
+            client_id: fields.next_i32(),
+            liquidation: fields.next_i32(),
+            cum_qty: Decimal::ZERO,
+            avg_price: 0.0,
+            order_ref: String::new(),
+            ev_rule: String::new(),
+            ev_multiplier: None,
+            model_code: String::new(),
+            last_liquidity: 0,
+        };
+        if version >= 6 {
+            execution.cum_qty = fields.next_decimal();
+            execution.avg_price = fields.next_f64();
+        }
+        if version >= 8 {
+            execution.order_ref = fields.next_string().unwrap_or("").to_string();
+        }
+        if version >= 9 {
+            execution.ev_rule = fields.next_string().unwrap_or("").to_string();
+            execution.ev_multiplier = fields.next_optional_f64();
+        }
+        if server_version >= server_versions::MODELS_SUPPORT {
+            execution.model_code = fields.next_string().unwrap_or("").to_string();
+        }
+        if server_version >= server_versions::LAST_LIQUIDITY {
+            execution.last_liquidity = fields.next_i32();
+        }
+
+        Some((req_id, contract, execution))
+    }
+}
+
+/// Commission charged for an execution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommissionReport {
+    /// Execution this report belongs to
+    pub exec_id: String,
+    pub commission: f64,
+    pub currency: String,
+    /// Realized P&L, when the execution closed a position
+    pub realized_pnl: Option<f64>,
+    /// Yield, for bonds
+    pub yield_: Option<f64>,
+    /// Yield redemption date (YYYYMMDD), for bonds
+    pub yield_redemption_date: i32,
+}
+
+impl CommissionReport {
+    /// Parse a report from message fields (after the version).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            exec_id: fields.next_string()?.to_string(),
+            commission: fields.next_f64(),
+            currency: fields.next_string().unwrap_or("").to_string(),
+            realized_pnl: fields.next_optional_f64(),
+            yield_: fields.next_optional_f64(),
+            yield_redemption_date: fields.next_i32(),
+        })
+    }
+}
+
+/// An execution with its contract and, once reported, its commission.
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub contract: Contract,
+    pub execution: Execution,
+    pub commission: Option<CommissionReport>,
+}
+
+/// A live fill on the connection.
+#[derive(Debug, Clone)]
+pub enum FillEvent {
+    /// An order executed; sent as soon as TWS reports it
+    Executed(Fill),
+    /// The commission report for an earlier execution arrived
+    ///
+    /// Repeats the fill with `commission` set; don't count it as a new fill.
+    Commissioned(Fill),
+}
+
+impl FillEvent {
+    pub fn fill(&self) -> &Fill {
+        match self {
+            Self::Executed(fill) | Self::Commissioned(fill) => fill,
+        }
+    }
+}
+
+/// Selects executions by account, client and contract attributes.
+///
+/// Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionFilter {
+    /// API client ID that placed the order
+    pub client_id: Option<i32>,
+    pub account: String,
+    /// Only executions after this time ("yyyymmdd hh:mm:ss", optionally with
+    /// a time zone); ignored by [`ExecutionFilter::matches`]
+    pub time: String,
+    pub symbol: String,
+    pub sec_type: String,
+    pub exchange: String,
+    /// "BUY" or "SELL"
+    pub side: String,
+}
+
+impl ExecutionFilter {
+    /// Only executions for `account`.
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = account.to_string();
+        self
+    }
+
+    /// Only executions of orders placed by `client_id`.
+    pub fn client_id(mut self, client_id: i32) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    /// Only executions for `symbol`.
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = symbol.to_string();
+        self
+    }
+
+    /// Only executions after `time` ("yyyymmdd hh:mm:ss").
+    pub fn time(mut self, time: &str) -> Self {
+        self.time = time.to_string();
+        self
+    }
+
+    /// Only executions on `exchange`.
+    pub fn exchange(mut self, exchange: &str) -> Self {
+        self.exchange = exchange.to_string();
+        self
+    }
+
+    /// Only executions on one side, "BUY" or "SELL".
+    pub fn side(mut self, side: &str) -> Self {
+        self.side = side.to_string();
+        self
+    }
+
+    /// Encode a REQ_EXECUTIONS request for this filter.
+    pub fn encode(&self, req_id: i32) -> String {
+        let mut msg = String::new();
+
+        msg.push_str(&make_field(OutgoingMessageId::ReqExecutions.as_u32()));
+        msg.push_str(&make_field(3));
+        msg.push_str(&make_field(req_id));
+        // TWS treats client ID 0 as "any client".
+        msg.push_str(&make_field(self.client_id.unwrap_or(0)));
+        msg.push_str(&make_field(&self.account));
+        msg.push_str(&make_field(&self.time));
+        msg.push_str(&make_field(&self.symbol));
+        msg.push_str(&make_field(&self.sec_type));
+        msg.push_str(&make_field(&self.exchange));
+        msg.push_str(&make_field(&self.side));
+
+        msg
+    }
+
+    /// Whether an execution passes the filter.
+    pub fn matches(&self, contract: &Contract, execution: &Execution) -> bool {
+        let side = match execution.side.as_str() {
+            "BOT" => "BUY",
+            "SLD" => "SELL",
+            other => other,
+        };
+        let field = |filter: &str, value: &str| filter.is_empty() || filter.eq_ignore_ascii_case(value);
+
+        self.client_id.is_none_or(|id| id == execution.client_id)
+            && field(&self.account, &execution.account)
+            && field(&self.symbol, &contract.symbol)
+            && field(&self.sec_type, contract.sec_type.as_str())
+            && field(&self.exchange, &execution.exchange)
+            && field(&self.side, side)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_execution() {
+        let buf = b"-1\x0042\x00265598\0AAPL\0STK\0\x000\0\0\0SMART\0USD\0AAPL\0NMS\0\
+0000e0d5.65a1b2c3.01.01\x0020240105 09:30:01 US/Eastern\0DU123\0ISLAND\0BOT\x0012.5\x00185.25\x00991\x007\x000\x0012.5\x00185.25\0ref\0\0\0\x002\0";
+        let mut fields = FieldIterator::new(buf);
+        let (req_id, contract, execution) = Execution::parse(&mut fields, 176).unwrap();
+
+        assert_eq!(req_id, -1);
+        assert_eq!(contract.symbol, "AAPL");
+        assert_eq!(execution.order_id, 42);
+        assert_eq!(execution.exec_id, "0000e0d5.65a1b2c3.01.01");
+        assert_eq!(execution.time.to_string(), "20240105 09:30:01 US/Eastern");
+        assert_eq!(execution.time.timezone.as_deref(), Some("US/Eastern"));
+        assert_eq!(execution.shares, Decimal::new(125, 1));
+        assert_eq!(execution.client_id, 7);
+        assert_eq!(execution.order_ref, "ref");
+        assert_eq!(execution.last_liquidity, 2);
+
+        let filter = ExecutionFilter::default().account("DU123").client_id(7);
+        assert!(filter.matches(&contract, &execution));
+        assert!(!filter.clone().client_id(8).matches(&contract, &execution));
+        assert!(ExecutionFilter { side: "BUY".into(), ..filter }.matches(&contract, &execution));
+    }
+
+    #[test]
+    fn test_execution_filter_encode() {
+        let filter = ExecutionFilter::default()
+            .account("DU123")
+            .time("20240105 09:00:00")
+            .side("BUY");
+
+        assert_eq!(filter.encode(5), "7\x003\x005\x000\0DU123\x0020240105 09:00:00\0\0\0\0BUY\0");
+    }
+
+    #[test]
+    fn test_parse_commission_report() {
+        let buf = b"0000e0d5.65a1b2c3.01.01\x001.05\0USD\x001.7976931348623157E308\x001.7976931348623157E308\0\0";
+        let mut fields = FieldIterator::new(buf);
+        let report = CommissionReport::parse(&mut fields).unwrap();
+
+        assert_eq!(report.commission, 1.05);
+        assert_eq!(report.currency, "USD");
+        assert_eq!(report.realized_pnl, None);
+    }
+}
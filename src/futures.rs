@@ -0,0 +1,69 @@
+//! Futures expiry and roll helpers.
+//!
+//! A futures root (e.g. "ES") trades as a series of expiries. These pick
+//! the contract to trade on a given day, rolling to the next expiry a set
+//! number of calendar days before the front month's last trade date.
+//! `Client::front_month` applies them to the expiries TWS lists, and
+//! [`RollingFuture`] keeps a market data subscription on the front month.
+
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+
+#[cfg(feature = "client")]
+mod rolling;
+
+#[cfg(feature = "client")]
+pub use rolling::{RollingEvent, RollingFuture, RollingTicks};
+
+/// The date (YYYYMMDD) `days` calendar days before `expiry` (YYYYMMDD),
+/// on which positions should have rolled to the next expiry.
+///
+/// Returns `None` if `expiry` isn't a date.
+pub fn roll_date(expiry: &str, days: u32) -> Option<String> {
+    let mut expiry = DateTime::parse(expiry.get(..8)?).ok()?;
+    expiry.timezone = Some("UTC".to_string());
+    let rolled = expiry.timestamp()? - i64::from(days) * 86_400;
+    Some(DateTime::from_timestamp(rolled).date_string())
+}
+
+/// The contract to hold on `today` (YYYYMMDD): the earliest expiry whose
+/// roll date, `roll_days` before its last trade date, is still ahead.
+///
+/// Contracts without a full last trade date are skipped.
+pub fn front_month<'a>(
+    futures: &'a [Contract],
+    today: &str,
+    roll_days: u32,
+) -> Option<&'a Contract> {
+    futures
+        .iter()
+        .filter_map(|contract| {
+            let roll = roll_date(&contract.last_trade_date, roll_days)?;
+            (roll.as_str() > today).then_some((&contract.last_trade_date, contract))
+        })
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, contract)| contract)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_month_rolls_before_expiry() {
+        assert_eq!(roll_date("20240315", 8).as_deref(), Some("20240307"));
+        assert_eq!(roll_date("20240301", 1).as_deref(), Some("20240229"));
+        assert_eq!(roll_date("202403", 1), None);
+
+        let futures = [
+            Contract::futures("ES", "CME", "20240621", "USD"),
+            Contract::futures("ES", "CME", "20240315", "USD"),
+            Contract::futures("ES", "CME", "20231215", "USD"),
+        ];
+        let front = |today| front_month(&futures, today, 8).map(|c| c.last_trade_date.as_str());
+        assert_eq!(front("20240101"), Some("20240315"));
+        assert_eq!(front("20240306"), Some("20240315"));
+        assert_eq!(front("20240307"), Some("20240621"));
+        assert_eq!(front("20240620"), None);
+    }
+}
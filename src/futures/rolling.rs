@@ -0,0 +1,249 @@
+//! Market data that follows a futures root across expiries.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::net::TcpStream;
+
+use super::roll_date;
+use crate::client::{Client, Transport};
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+use crate::market_data::{GenericTick, Tick};
+use crate::subscription::Subscription;
+
+/// Streams market data for the front month of a futures root, rolling to
+/// the next expiry before the front month's last trade date.
+///
+/// The roll happens `roll_days` calendar days before the last trade date
+/// (see [`front_month`](super::front_month)): the next expiry is
+/// subscribed, the old subscription is cancelled and a
+/// [`RollingEvent::Roll`] is yielded between their ticks.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(client: std::sync::Arc<ibkr::Client>) -> ibkr::Result<()> {
+/// use ibkr::futures::{RollingEvent, RollingFuture};
+///
+/// let mut es = RollingFuture::new(client, "ES", "CME", "USD").roll_days(8).start().await?;
+/// while let Some(event) = es.next().await {
+///     match event {
+///         RollingEvent::Tick(tick) => println!("{}: {tick:?}", es.contract().local_symbol),
+///         RollingEvent::Roll { from, to } => {
+///             println!("rolled {} -> {}", from.last_trade_date, to.last_trade_date)
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct RollingFuture<S: Transport = TcpStream> {
+    client: Arc<Client<S>>,
+    symbol: String,
+    exchange: String,
+    currency: String,
+    roll_days: u32,
+    generic_ticks: Vec<GenericTick>,
+}
+
+impl<S: Transport> RollingFuture<S> {
+    /// Follow the `symbol` futures on `exchange`, rolling 8 days before
+    /// expiry.
+    pub fn new(client: Arc<Client<S>>, symbol: &str, exchange: &str, currency: &str) -> Self {
+        Self {
+            client,
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            currency: currency.to_string(),
+            roll_days: 8,
+            generic_ticks: Vec::new(),
+        }
+    }
+
+    /// Set how many calendar days before the last trade date to roll.
+    pub fn roll_days(mut self, days: u32) -> Self {
+        self.roll_days = days;
+        self
+    }
+
+    /// Set the optional tick groups to request for each expiry.
+    pub fn generic_ticks(mut self, generic_ticks: &[GenericTick]) -> Self {
+        self.generic_ticks = generic_ticks.to_vec();
+        self
+    }
+
+    /// Look up the front month and subscribe to its market data.
+    pub async fn start(self) -> Result<RollingTicks<S>> {
+        let contract = self.lookup().await?;
+        let ticks = self.subscribe(&contract).await?;
+        Ok(RollingTicks {
+            source: self,
+            contract,
+            ticks,
+            error: None,
+        })
+    }
+
+    async fn lookup(&self) -> Result<Contract> {
+        self.client
+            .front_month(&self.symbol, &self.exchange, &self.currency, self.roll_days)
+            .await?
+            .ok_or_else(|| {
+                Error::Protocol(format!("No {} expiry listed past its roll date", self.symbol))
+            })
+    }
+
+    async fn subscribe(&self, contract: &Contract) -> Result<Subscription<Tick>> {
+        self.client
+            .market_data(contract.clone(), &self.generic_ticks, false)
+            .await
+    }
+}
+
+/// Something that happened on a [`RollingTicks`] stream.
+#[derive(Debug, Clone)]
+pub enum RollingEvent {
+    /// A tick for the current front month
+    Tick(Tick),
+    /// The stream moved from one expiry to the next
+    Roll { from: Box<Contract>, to: Box<Contract> },
+}
+
+/// Market data for the front month of a futures root, started with
+/// [`RollingFuture::start`].
+pub struct RollingTicks<S: Transport = TcpStream> {
+    source: RollingFuture<S>,
+    contract: Contract,
+    ticks: Subscription<Tick>,
+    error: Option<Error>,
+}
+
+impl<S: Transport> RollingTicks<S> {
+    /// The expiry currently streamed.
+    pub fn contract(&self) -> &Contract {
+        &self.contract
+    }
+
+    /// Wait for the next tick or roll.
+    ///
+    /// Returns `None` once the current subscription ends or a roll fails;
+    /// see [`RollingTicks::take_error`] for why.
+    pub async fn next(&mut self) -> Option<RollingEvent> {
+        loop {
+            let until_roll = self.until_roll();
+            tokio::select! {
+                tick = self.ticks.next() => {
+                    if tick.is_none() {
+                        self.error = self.ticks.take_error();
+                    }
+                    return tick.map(RollingEvent::Tick);
+                }
+                _ = tokio::time::sleep(until_roll) => match self.roll().await {
+                    Ok(Some((from, to))) => {
+                        let (from, to) = (Box::new(from), Box::new(to));
+                        return Some(RollingEvent::Roll { from, to });
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        self.error = Some(error);
+                        return None;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Re-check the front month now, switching to it if it changed.
+    ///
+    /// Returns the old and new contract after a switch. The new expiry is
+    /// subscribed before the old one is cancelled, so no ticks are missed.
+    pub async fn roll(&mut self) -> Result<Option<(Contract, Contract)>> {
+        let front = self.source.lookup().await?;
+        if front.con_id == self.contract.con_id {
+            return Ok(None);
+        }
+        tracing::info!(
+            symbol = %front.symbol,
+            from = %self.contract.last_trade_date,
+            to = %front.last_trade_date,
+            "rolling futures subscription"
+        );
+        self.ticks = self.source.subscribe(&front).await?;
+        let from = std::mem::replace(&mut self.contract, front);
+        Ok(Some((from, self.contract.clone())))
+    }
+
+    /// Take the error that ended the stream, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Time left until the current expiry's roll date (00:00 UTC), or
+    /// practically forever without a full last trade date.
+    fn until_roll(&self) -> Duration {
+        let roll_at = roll_date(&self.contract.last_trade_date, self.source.roll_days)
+            .and_then(|date| DateTime::parse(&date).ok())
+            .and_then(|mut date| {
+                date.timezone = Some("UTC".to_string());
+                date.timestamp()
+            });
+        let Some(roll_at) = roll_at else {
+            return Duration::MAX;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        Duration::from_secs(roll_at.saturating_sub(now).max(0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{connected_client, read_frame, write_frame};
+    use tokio::io::DuplexStream;
+
+    async fn list_expiries(server: &mut DuplexStream, req_id: &str, expiries: &[(&str, &str)]) {
+        let request = read_frame(server).await;
+        assert!(request.starts_with(format!("9\x008\0{req_id}\x000\0ES\0FUT\0\0").as_bytes()));
+        for (expiry, con_id) in expiries {
+            let mut raw = vec!["10", req_id, "ES", "FUT", expiry, "0", "", "CME", "USD", ""];
+            raw.extend(["ES", "ES", con_id, "0.25", "50", "LMT", "CME", "1", "0", "", ""]);
+            raw.extend(["", "", "", "", "", "", "", "", "", "", "0", "1", "", "", "", "", ""]);
+            raw.extend(["1", "1", "1"]);
+            write_frame(server, &(raw.join("\0") + "\0")).await;
+        }
+        write_frame(server, &format!("52\x001\0{req_id}\0")).await;
+    }
+
+    #[tokio::test]
+    async fn test_rolls_to_next_expiry() {
+        let (client, mut server) = connected_client(1).await;
+        let es = RollingFuture::new(Arc::new(client), "ES", "CME", "USD");
+
+        let (es, _) = tokio::join!(es.start(), async {
+            let expiries = [("21000615", "2002"), ("20990320", "2001")];
+            list_expiries(&mut server, "1000000000", &expiries).await;
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000001\x002001\0"));
+            write_frame(&mut server, "1\x006\x001000000001\x001\x005000.25\x005\x000\0").await;
+        });
+        let mut es = es.unwrap();
+        assert_eq!(es.contract().con_id, 2001);
+        assert!(matches!(es.next().await, Some(RollingEvent::Tick(Tick::Price { .. }))));
+
+        // March has expired out of the listing; June is the new front month
+        let (rolled, _) = tokio::join!(es.roll(), async {
+            list_expiries(&mut server, "1000000002", &[("21000615", "2002")]).await;
+            let request = read_frame(&mut server).await;
+            assert!(request.starts_with(b"1\x0011\x001000000003\x002002\0"));
+            assert_eq!(read_frame(&mut server).await, b"2\x002\x001000000001\0");
+        });
+        let (from, to) = rolled.unwrap().unwrap();
+        assert_eq!((from.con_id, to.con_id), (2001, 2002));
+        assert_eq!(es.contract().last_trade_date, "21000615");
+    }
+}
@@ -0,0 +1,98 @@
+//! Foreign exchange rate types.
+
+use crate::contract::{Contract, SecurityType};
+
+/// An exchange rate between two currencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxRate {
+    /// Base currency (e.g., "EUR")
+    pub base: String,
+    /// Quote currency (e.g., "USD")
+    pub quote: String,
+    /// Units of quote currency per unit of base currency
+    pub rate: f64,
+    /// When the bid/ask snapshot the rate was taken from arrived (UTC)
+    pub time: String,
+}
+
+impl FxRate {
+    /// The rate for the opposite direction (quote per base becomes base per quote).
+    pub fn invert(&self) -> Self {
+        Self {
+            base: self.quote.clone(),
+            quote: self.base.clone(),
+            rate: 1.0 / self.rate,
+            time: self.time.clone(),
+        }
+    }
+
+    /// Combine two legs sharing a currency into a cross rate.
+    ///
+    /// `self` must quote in the currency that `other` is based on, e.g.
+    /// EUR.USD crossed with USD.JPY gives EUR.JPY. The timestamp is the
+    /// older of the two legs.
+    pub fn cross(&self, other: &FxRate) -> Self {
+        debug_assert_eq!(self.quote, other.base);
+        Self {
+            base: self.base.clone(),
+            quote: other.quote.clone(),
+            rate: self.rate * other.rate,
+            time: self.time.clone().min(other.time.clone()),
+        }
+    }
+}
+
+/// IDEALPRO cash contract for a currency pair.
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub(crate) fn idealpro_contract(base: &str, quote: &str) -> Contract {
+    Contract {
+        symbol: base.to_string(),
+        sec_type: SecurityType::Cash,
+        exchange: "IDEALPRO".to_string(),
+        currency: quote.to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(base: &str, quote: &str, rate: f64, time: &str) -> FxRate {
+        FxRate {
+            base: base.into(),
+            quote: quote.into(),
+            rate,
+            time: time.into(),
+        }
+    }
+
+    #[test]
+    fn test_invert() {
+        let usd_eur = rate("EUR", "USD", 1.25, "20240105 16:00:00").invert();
+        assert_eq!(usd_eur.base, "USD");
+        assert_eq!(usd_eur.quote, "EUR");
+        assert_eq!(usd_eur.rate, 0.8);
+    }
+
+    #[test]
+    fn test_cross() {
+        let eur_usd = rate("EUR", "USD", 1.1, "20240105 16:00:00");
+        let usd_jpy = rate("USD", "JPY", 150.0, "20240105 15:59:00");
+        let eur_jpy = eur_usd.cross(&usd_jpy);
+
+        assert_eq!(eur_jpy.base, "EUR");
+        assert_eq!(eur_jpy.quote, "JPY");
+        assert!((eur_jpy.rate - 165.0).abs() < 1e-9);
+        assert_eq!(eur_jpy.time, "20240105 15:59:00");
+    }
+
+    #[test]
+    fn test_idealpro_contract() {
+        let c = idealpro_contract("EUR", "GBP");
+        assert_eq!(c.symbol, "EUR");
+        assert_eq!(c.currency, "GBP");
+        assert_eq!(c.sec_type, SecurityType::Cash);
+        assert_eq!(c.exchange, "IDEALPRO");
+    }
+}
@@ -1,9 +1,29 @@
 //! Historical market data types.
 
-use crate::contract::Contract;
+use rust_decimal::Decimal;
+
+use crate::contract::{Contract, SecurityType};
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
 use crate::message::OutgoingMessageId;
+use crate::server_versions;
 use crate::wire::{make_field, FieldIterator};
 
+#[cfg(feature = "client")]
+mod cache;
+#[cfg(feature = "client")]
+mod downloader;
+pub mod export;
+#[cfg(feature = "client")]
+mod gapless;
+
+#[cfg(feature = "client")]
+pub use cache::HistoricalCache;
+#[cfg(feature = "client")]
+pub use downloader::{BackfillProgress, HistoricalDownloader};
+#[cfg(feature = "client")]
+pub use gapless::{GaplessBarStream, GaplessBars};
+
 /// Bar size for historical data requests.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BarSize {
@@ -15,10 +35,18 @@ pub enum BarSize {
     Min2,
     Min3,
     Min5,
+    Min10,
     Min15,
+    Min20,
     Min30,
     Hour1,
+    Hour2,
+    Hour3,
+    Hour4,
+    Hour8,
     Day1,
+    Week1,
+    Month1,
 }
 
 impl BarSize {
@@ -32,12 +60,58 @@ impl BarSize {
             Self::Min2 => "2 mins",
             Self::Min3 => "3 mins",
             Self::Min5 => "5 mins",
+            Self::Min10 => "10 mins",
             Self::Min15 => "15 mins",
+            Self::Min20 => "20 mins",
             Self::Min30 => "30 mins",
             Self::Hour1 => "1 hour",
+            Self::Hour2 => "2 hours",
+            Self::Hour3 => "3 hours",
+            Self::Hour4 => "4 hours",
+            Self::Hour8 => "8 hours",
             Self::Day1 => "1 day",
+            Self::Week1 => "1 week",
+            Self::Month1 => "1 month",
+        }
+    }
+
+    /// Longest duration, in seconds, TWS accepts in one request at this bar size.
+    pub fn max_duration_secs(&self) -> i64 {
+        const DAY: i64 = 86_400;
+        match self {
+            Self::Sec1 => 1_800,
+            Self::Sec5 => 3_600,
+            Self::Sec15 => 14_400,
+            Self::Sec30 => 28_800,
+            Self::Min1 => DAY,
+            Self::Min2 => 2 * DAY,
+            Self::Min3 | Self::Min5 => 7 * DAY,
+            Self::Min10 | Self::Min15 | Self::Min20 => 14 * DAY,
+            Self::Min30 | Self::Hour1 | Self::Hour2 | Self::Hour3 | Self::Hour4 | Self::Hour8 => {
+                30 * DAY
+            }
+            // Longer durations have to be given in years.
+            Self::Day1 | Self::Week1 | Self::Month1 => 365 * DAY,
         }
     }
+
+    /// Check that TWS accepts `duration` at this bar size in one request.
+    ///
+    /// Day and longer bars accept any duration given in years.
+    pub fn check_duration(&self, duration: Duration) -> Result<()> {
+        let max = self.max_duration_secs();
+        let secs = duration.as_secs();
+        let daily = matches!(self, Self::Day1 | Self::Week1 | Self::Month1);
+        if secs <= max || (daily && matches!(duration, Duration::Years(_))) {
+            return Ok(());
+        }
+        let requests = (secs + max - 1) / max;
+        let limit = Duration::covering(max);
+        Err(Error::InvalidHistoricalRequest(format!(
+            "{self} bars are limited to {limit} per request; split {duration} into {requests} \
+             requests of {limit} (see Client::historical_data_range)"
+        )))
+    }
 }
 
 impl std::fmt::Display for BarSize {
@@ -57,6 +131,18 @@ pub enum WhatToShow {
     BidAsk,
     HistoricalVolatility,
     OptionImpliedVolatility,
+    /// Trades adjusted for splits and dividends; only up to the present
+    AdjustedLast,
+    /// Aggregated crypto trades
+    AggTrades,
+    /// Stock borrow fee rate
+    FeeRate,
+    /// Short stock rebate rate
+    RebateRate,
+    YieldBid,
+    YieldAsk,
+    YieldBidAsk,
+    YieldLast,
 }
 
 impl WhatToShow {
@@ -69,6 +155,22 @@ impl WhatToShow {
             Self::BidAsk => "BID_ASK",
             Self::HistoricalVolatility => "HISTORICAL_VOLATILITY",
             Self::OptionImpliedVolatility => "OPTION_IMPLIED_VOLATILITY",
+            Self::AdjustedLast => "ADJUSTED_LAST",
+            Self::AggTrades => "AGGTRADES",
+            Self::FeeRate => "FEE_RATE",
+            Self::RebateRate => "REBATE_RATE",
+            Self::YieldBid => "YIELD_BID",
+            Self::YieldAsk => "YIELD_ASK",
+            Self::YieldBidAsk => "YIELD_BID_ASK",
+            Self::YieldLast => "YIELD_LAST",
+        }
+    }
+
+    /// Lowest server version accepting this data type.
+    pub fn min_server_version(&self) -> u32 {
+        match self {
+            Self::AggTrades => server_versions::CRYPTO_AGGREGATED_TRADES,
+            _ => 0,
         }
     }
 }
@@ -107,6 +209,67 @@ impl std::fmt::Display for Duration {
     }
 }
 
+impl Duration {
+    /// Length in seconds, counting months as 30 days and years as 365.
+    pub fn as_secs(&self) -> i64 {
+        const DAY: i64 = 86_400;
+        match *self {
+            Self::Seconds(n) => n.into(),
+            Self::Days(n) => i64::from(n) * DAY,
+            Self::Weeks(n) => i64::from(n) * 7 * DAY,
+            Self::Months(n) => i64::from(n) * 30 * DAY,
+            Self::Years(n) => i64::from(n) * 365 * DAY,
+        }
+    }
+
+    /// Smallest duration covering `seconds`: exact seconds up to a day,
+    /// whole days beyond that.
+    pub fn covering(seconds: i64) -> Self {
+        let seconds = seconds.max(1);
+        if seconds <= 86_400 {
+            Self::Seconds(seconds as u32)
+        } else {
+            Self::Days(((seconds + 86_399) / 86_400) as u32)
+        }
+    }
+}
+
+/// Split a time range into requests TWS will accept at `bar_size`.
+///
+/// Returns `(end, duration)` pairs in chronological order, with `end` in
+/// seconds since the Unix epoch. Durations rounded up to whole days may
+/// reach before the chunk start, so results should be trimmed and
+/// de-duplicated.
+pub fn split_range(start: i64, end: i64, bar_size: BarSize) -> Vec<(i64, Duration)> {
+    let max = bar_size.max_duration_secs();
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+    while chunk_start < end {
+        let chunk_end = (chunk_start + max).min(end);
+        chunks.push((chunk_end, Duration::covering(chunk_end - chunk_start)));
+        chunk_start = chunk_end;
+    }
+    chunks
+}
+
+/// Whether a bar falls within `start..=end` (epoch seconds).
+///
+/// Daily bars are dated without a time, so those are compared by date.
+/// Bars with unparseable dates are kept.
+pub fn bar_in_range(bar: &BarData, start: i64, end: i64) -> bool {
+    match bar.time() {
+        Ok(time) => match time.timestamp() {
+            Some(time) => (start..=end).contains(&time),
+            None => {
+                let day = time.date_string();
+                DateTime::from_timestamp(start).date_string() <= day
+                    && day <= DateTime::from_timestamp(end).date_string()
+            }
+        },
+        Err(_) => true,
+    }
+}
+
 /// Date format for returned bars.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum DateFormat {
@@ -130,10 +293,10 @@ pub struct BarData {
     pub low: f64,
     /// Closing price
     pub close: f64,
-    /// Volume
-    pub volume: f64,
-    /// Weighted average price
-    pub wap: f64,
+    /// Volume, `None` when TWS doesn't report one (e.g. midpoint bars)
+    pub volume: Option<Decimal>,
+    /// Weighted average price, `None` when TWS doesn't report one
+    pub wap: Option<Decimal>,
     /// Number of trades in the bar
     pub bar_count: i32,
 }
@@ -147,11 +310,62 @@ impl BarData {
             high: fields.next_f64(),
             low: fields.next_f64(),
             close: fields.next_f64(),
-            volume: fields.next_f64(),
-            wap: fields.next_f64(),
+            // -1 marks values that don't apply to the bar type
+            volume: fields.next_optional_decimal().filter(|volume| !volume.is_sign_negative()),
+            wap: fields.next_optional_decimal().filter(|wap| !wap.is_sign_negative()),
             bar_count: fields.next_i32(),
         })
     }
+
+    /// Parse a bar from a keepUpToDate update (HISTORICAL_DATA_UPDATE),
+    /// which orders its fields differently.
+    pub fn parse_update(fields: &mut FieldIterator) -> Option<Self> {
+        let bar_count = fields.next_i32();
+        let date = fields.next_string()?.to_string();
+        let open = fields.next_f64();
+        let close = fields.next_f64();
+        let high = fields.next_f64();
+        let low = fields.next_f64();
+        let wap = fields.next_optional_decimal().filter(|wap| !wap.is_sign_negative());
+        let volume = fields.next_optional_decimal().filter(|volume| !volume.is_sign_negative());
+        Some(Self {
+            date,
+            open,
+            high,
+            low,
+            close,
+            volume,
+            wap,
+            bar_count,
+        })
+    }
+
+    /// Parse the bar timestamp.
+    pub fn time(&self) -> Result<DateTime> {
+        DateTime::parse(&self.date)
+    }
+
+    /// The bar timestamp as a `chrono` date/time.
+    ///
+    /// Bars requested with [`DateFormat::String`] are dated in the TWS
+    /// login timezone, which has to be passed in as `login_timezone`.
+    #[cfg(feature = "chrono")]
+    pub fn datetime(
+        &self,
+        login_timezone: chrono_tz::Tz,
+    ) -> Result<chrono::DateTime<chrono_tz::Tz>> {
+        self.time()?.to_chrono(login_timezone).ok_or_else(|| {
+            crate::error::Error::Protocol(format!("Bar date {:?} has no valid time", self.date))
+        })
+    }
+}
+
+/// Historical data response.
+#[derive(Debug, Clone)]
+pub struct HistoricalDataResponse {
+    pub start: String,
+    pub end: String,
+    pub bars: Vec<BarData>,
 }
 
 /// Historical data request parameters.
@@ -200,6 +414,31 @@ impl HistoricalDataRequest {
         self
     }
 
+    /// Set the end date/time from a parsed value.
+    pub fn end_at(mut self, end: &DateTime) -> Self {
+        self.end_date_time = end.to_request_string();
+        self
+    }
+
+    /// Set the end date/time, formatted for a server of `server_version`.
+    ///
+    /// `timezone` names the timezone of an `end` without one, e.g. the
+    /// instrument's exchange timezone; with `None` such times are read in
+    /// the TWS login timezone.
+    pub fn end_at_for(
+        mut self,
+        end: &DateTime,
+        timezone: Option<&str>,
+        server_version: u32,
+    ) -> Self {
+        let mut end = end.clone();
+        if end.timezone.is_none() {
+            end.timezone = timezone.map(str::to_string);
+        }
+        self.end_date_time = end.to_request_string_for(server_version);
+        self
+    }
+
     /// Set the duration.
     pub fn duration(mut self, duration: Duration) -> Self {
         self.duration = duration;
@@ -236,20 +475,27 @@ impl HistoricalDataRequest {
         self
     }
 
-    /// Encode the request as a message payload.
+    /// Encode the request as a message payload for a server of
+    /// `server_version`.
     ///
-    /// Assumes server version >= 124 (MIN_SERVER_VER_SYNT_REALTIME_BARS).
-    pub fn encode(&self) -> String {
+    /// Servers before [`server_versions::SYNT_REALTIME_BARS`] can't keep
+    /// bars up to date, so `keep_up_to_date` is dropped for them.
+    pub fn encode(&self, server_version: u32) -> String {
         let mut msg = String::new();
 
         // Message ID
         msg.push_str(&make_field(OutgoingMessageId::ReqHistoricalData.as_u32()));
 
+        // The version field was dropped along with keepUpToDate
+        if server_version < server_versions::SYNT_REALTIME_BARS {
+            msg.push_str(&make_field(6));
+        }
+
         // Request ID
         msg.push_str(&make_field(self.req_id));
 
         // Contract fields
-        msg.push_str(&self.contract.encode());
+        msg.push_str(&self.contract.encode_for(server_version));
 
         // includeExpired
         msg.push_str(&make_field(if self.contract.include_expired { 1 } else { 0 }));
@@ -262,11 +508,19 @@ impl HistoricalDataRequest {
         msg.push_str(&make_field(self.what_to_show.as_str()));
         msg.push_str(&make_field(self.format_date as i32));
 
-        // keepUpToDate (server version >= 124)
-        msg.push_str(&make_field(if self.keep_up_to_date { 1 } else { 0 }));
+        // Combo legs, for spread price history
+        if self.contract.sec_type == SecurityType::Bag {
+            msg.push_str(&self.contract.encode_combo_legs());
+        }
+
+        if server_version >= server_versions::SYNT_REALTIME_BARS {
+            msg.push_str(&make_field(if self.keep_up_to_date { 1 } else { 0 }));
+        }
 
         // chartOptions (empty)
-        msg.push_str(&make_field(""));
+        if server_version >= server_versions::LINKING {
+            msg.push_str(&make_field(""));
+        }
 
         msg
     }
@@ -275,6 +529,7 @@ impl HistoricalDataRequest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::contract::ComboLeg;
 
     #[test]
     fn test_bar_size_str() {
@@ -283,6 +538,33 @@ mod tests {
         assert_eq!(BarSize::Day1.as_str(), "1 day");
     }
 
+    #[test]
+    fn test_bar_size_limits() {
+        const DAY: i64 = 86_400;
+        let cases = [
+            (BarSize::Min10, "10 mins", 14 * DAY),
+            (BarSize::Min20, "20 mins", 14 * DAY),
+            (BarSize::Hour2, "2 hours", 30 * DAY),
+            (BarSize::Hour3, "3 hours", 30 * DAY),
+            (BarSize::Hour4, "4 hours", 30 * DAY),
+            (BarSize::Hour8, "8 hours", 30 * DAY),
+            (BarSize::Week1, "1 week", 365 * DAY),
+            (BarSize::Month1, "1 month", 365 * DAY),
+        ];
+        for (size, name, max) in cases {
+            assert_eq!(size.as_str(), name);
+            assert_eq!(size.max_duration_secs(), max, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_what_to_show_str() {
+        assert_eq!(WhatToShow::AdjustedLast.as_str(), "ADJUSTED_LAST");
+        assert_eq!(WhatToShow::YieldBidAsk.as_str(), "YIELD_BID_ASK");
+        assert_eq!(WhatToShow::AggTrades.min_server_version(), 168);
+        assert_eq!(WhatToShow::FeeRate.min_server_version(), 0);
+    }
+
     #[test]
     fn test_duration_str() {
         assert_eq!(Duration::Days(1).as_string(), "1 D");
@@ -290,6 +572,85 @@ mod tests {
         assert_eq!(Duration::Seconds(300).as_string(), "300 S");
     }
 
+    #[test]
+    fn test_check_duration() {
+        assert!(BarSize::Sec1.check_duration(Duration::Seconds(1800)).is_ok());
+        assert!(BarSize::Min1.check_duration(Duration::Days(1)).is_ok());
+        assert!(BarSize::Hour1.check_duration(Duration::Months(1)).is_ok());
+        assert!(BarSize::Day1.check_duration(Duration::Years(10)).is_ok());
+
+        let err = BarSize::Sec1.check_duration(Duration::Days(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid historical data request: 1 sec bars are limited to 1800 S per request; \
+             split 1 D into 48 requests of 1800 S (see Client::historical_data_range)"
+        );
+        assert!(BarSize::Min1.check_duration(Duration::Weeks(1)).is_err());
+        assert!(BarSize::Day1.check_duration(Duration::Days(400)).is_err());
+    }
+
+    #[test]
+    fn test_check_duration_new_bar_sizes() {
+        let cases = [
+            (BarSize::Min10, Duration::Weeks(2), true),
+            (BarSize::Min10, Duration::Days(15), false),
+            (BarSize::Min20, Duration::Days(14), true),
+            (BarSize::Min20, Duration::Weeks(3), false),
+            (BarSize::Hour2, Duration::Months(1), true),
+            (BarSize::Hour4, Duration::Days(31), false),
+            (BarSize::Hour8, Duration::Months(2), false),
+            (BarSize::Week1, Duration::Days(365), true),
+            (BarSize::Week1, Duration::Days(400), false),
+            (BarSize::Week1, Duration::Years(5), true),
+            (BarSize::Month1, Duration::Months(13), false),
+            (BarSize::Month1, Duration::Years(20), true),
+        ];
+        for (size, duration, ok) in cases {
+            assert_eq!(size.check_duration(duration).is_ok(), ok, "{size} over {duration}");
+        }
+
+        let err = BarSize::Hour4.check_duration(Duration::Months(2)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid historical data request: 4 hours bars are limited to 30 D per request; \
+             split 2 M into 2 requests of 30 D (see Client::historical_data_range)"
+        );
+    }
+
+    #[test]
+    fn test_parse_bar_decimal_volume() {
+        let buf = b"1704465000\x0042150.5\x0042200\x0042100\x0042180\x000.3125\x0042160.125\x007\0";
+        let mut fields = FieldIterator::new(buf);
+        let bar = BarData::parse(&mut fields).unwrap();
+        assert_eq!(bar.volume, Some(Decimal::new(3125, 4)));
+        assert_eq!(bar.wap, Some(Decimal::new(42_160_125, 3)));
+
+        // Midpoint bars have no volume or average price
+        let mut fields = FieldIterator::new(b"1704465000\x001\x001\x001\x001\x00-1\x00-1\x00-1\0");
+        let bar = BarData::parse(&mut fields).unwrap();
+        assert_eq!((bar.volume, bar.wap), (None, None));
+    }
+
+    #[test]
+    fn test_split_range() {
+        // 90 minutes of 1-second bars: two half-hour chunks fit, plus one more
+        let chunks = split_range(0, 5_400, BarSize::Sec1);
+        assert_eq!(
+            chunks,
+            [
+                (1_800, Duration::Seconds(1_800)),
+                (3_600, Duration::Seconds(1_800)),
+                (5_400, Duration::Seconds(1_800)),
+            ]
+        );
+
+        // Ten days of hourly bars fit one request, rounded up to whole days
+        let chunks = split_range(0, 9 * 86_400 + 60, BarSize::Hour1);
+        assert_eq!(chunks, [(9 * 86_400 + 60, Duration::Days(10))]);
+
+        assert!(split_range(100, 100, BarSize::Day1).is_empty());
+    }
+
     #[test]
     fn test_request_encode() {
         let contract = Contract::stock("AAPL", "SMART", "USD");
@@ -297,7 +658,7 @@ mod tests {
             .duration(Duration::Days(5))
             .bar_size(BarSize::Hour1);
 
-        let encoded = request.encode();
+        let encoded = request.encode(176);
 
         // Should start with message ID
         assert!(encoded.starts_with("20\0"));
@@ -308,4 +669,64 @@ mod tests {
         // Should contain duration
         assert!(encoded.contains("5 D\0"));
     }
+
+    #[test]
+    fn test_combo_request_encodes_legs() {
+        let legs = vec![
+            ComboLeg::new(43645865, 1, "BUY", "SMART"),
+            ComboLeg::new(9408, 1, "SELL", "SMART"),
+        ];
+        let contract = Contract::combo("IBKR,MCD", "SMART", "USD", legs);
+        let encoded = HistoricalDataRequest::new(1, contract).encode(176);
+
+        assert!(encoded.contains("TRADES\x001\x002\x0043645865\x001\0BUY\0SMART\x009408\x001\0SELL\0SMART\x000\0"));
+
+        let stock = HistoricalDataRequest::new(1, Contract::stock("AAPL", "SMART", "USD"));
+        assert!(stock.encode(176).ends_with("TRADES\x001\x000\0\0"));
+    }
+
+    #[test]
+    fn test_request_encode_server_versions() {
+        let mut contract = Contract::stock("AAPL", "SMART", "USD");
+        contract.trading_class = "NMS".to_string();
+        let request = HistoricalDataRequest::new(7, contract)
+            .end_date_time("20240105 16:00:00 US/Eastern")
+            .keep_up_to_date(true);
+        let contract = "AAPL\0STK\0\0\0\0\0SMART\0\0USD\0\0";
+        let params = "0\x0020240105 16:00:00 US/Eastern\x001 hour\x001 D\x001\0TRADES\x001\0";
+
+        let current = format!("20\x007\x000\0{contract}NMS\0{params}1\0\0");
+        assert_eq!(request.encode(176), current);
+        assert_eq!(request.encode(124), current);
+
+        let before_keep_up_to_date = format!("20\x006\x007\x000\0{contract}NMS\0{params}\0");
+        assert_eq!(request.encode(123), before_keep_up_to_date);
+
+        let before_trading_class = format!("20\x006\x007\0{contract}{params}");
+        assert_eq!(request.encode(60), before_trading_class);
+    }
+
+    #[test]
+    fn test_end_at_utc() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let request = HistoricalDataRequest::new(1, contract)
+            .end_at(&DateTime::utc(2024, 1, 5, 21, 0, 0));
+
+        assert_eq!(request.end_date_time, "20240105-21:00:00");
+    }
+
+    #[test]
+    fn test_end_at_for_server_version() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let end = DateTime::utc(2024, 1, 5, 21, 0, 0);
+        let request = HistoricalDataRequest::new(1, contract.clone()).end_at_for(&end, None, 151);
+        assert_eq!(request.end_date_time, "20240105 21:00:00 UTC");
+
+        let local = DateTime::parse("20240105 16:00:00").unwrap();
+        let request = HistoricalDataRequest::new(1, contract.clone())
+            .end_at_for(&local, Some("US/Eastern"), 176);
+        assert_eq!(request.end_date_time, "20240105 16:00:00 US/Eastern");
+        let request = HistoricalDataRequest::new(1, contract).end_at_for(&local, None, 176);
+        assert_eq!(request.end_date_time, "20240105 16:00:00");
+    }
 }
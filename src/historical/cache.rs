@@ -0,0 +1,321 @@
+//! On-disk cache of historical bars.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::export::{csv_row, CSV_HEADER};
+use super::{bar_in_range, BarData, BarSize, HistoricalDownloader, WhatToShow};
+use crate::client::{Client, Transport};
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+
+/// Persists downloaded bars so repeated requests only fetch what's missing.
+///
+/// Bars are stored as CSV, one file per contract, bar size, type of data
+/// and trading hours, next to a `.ranges` file listing the time ranges
+/// already downloaded. Each chunk is saved as soon as it arrives, so an
+/// interrupted download resumes where it stopped.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(client: std::sync::Arc<ibkr::Client>) -> ibkr::Result<()> {
+/// use ibkr::historical::HistoricalCache;
+/// use ibkr::{BarSize, Contract, DateTime};
+///
+/// let cache = HistoricalCache::new("bars");
+/// let contract = Contract::stock("AAPL", "SMART", "USD");
+/// let start = DateTime::utc(2023, 1, 1, 0, 0, 0);
+/// let end = DateTime::utc(2024, 1, 1, 0, 0, 0);
+/// // Only the first call downloads anything.
+/// let bars = cache.bars(&client, &contract, BarSize::Min5, &start, &end).await?;
+/// let bars = cache.bars(&client, &contract, BarSize::Min5, &start, &end).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HistoricalCache {
+    dir: PathBuf,
+    what_to_show: WhatToShow,
+    use_rth: bool,
+}
+
+impl HistoricalCache {
+    /// Cache bars in `dir`, which is created if missing. Caches trades in
+    /// regular trading hours by default.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            what_to_show: WhatToShow::Trades,
+            use_rth: true,
+        }
+    }
+
+    /// Set the type of data to cache.
+    pub fn what_to_show(mut self, what_to_show: WhatToShow) -> Self {
+        self.what_to_show = what_to_show;
+        self
+    }
+
+    /// Set whether to only cache regular trading hours.
+    pub fn use_rth(mut self, use_rth: bool) -> Self {
+        self.use_rth = use_rth;
+        self
+    }
+
+    /// Bars for `start..=end`, which must both be UTC, downloading the
+    /// parts of the range that aren't cached yet.
+    ///
+    /// The bar in progress isn't marked as downloaded, so it is requested
+    /// again, and replaced, on the next call.
+    pub async fn bars<S: Transport>(
+        &self,
+        client: &Arc<Client<S>>,
+        contract: &Contract,
+        bar_size: BarSize,
+        start: &DateTime,
+        end: &DateTime,
+    ) -> Result<Vec<BarData>> {
+        let (Some(start), Some(end)) = (start.timestamp(), end.timestamp()) else {
+            return Err(Error::Protocol("Bar range bounds must be UTC".into()));
+        };
+        let path = self.path(contract, bar_size);
+        let ranges_path = path.with_extension("ranges");
+        let mut ranges = read_ranges(&ranges_path)?;
+
+        for (from, to) in gaps(&ranges, start, end) {
+            let mut download = HistoricalDownloader::new(
+                client.clone(),
+                contract.clone(),
+                bar_size,
+                &DateTime::from_timestamp(from),
+                &DateTime::from_timestamp(to),
+            )
+            .what_to_show(self.what_to_show)
+            .use_rth(self.use_rth);
+
+            while let Some(bars) = download.next_chunk().await? {
+                append_bars(&path, &bars)?;
+                let Some(mut until) = download.downloaded_until() else {
+                    continue;
+                };
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                if until >= now.as_secs() as i64 {
+                    match bars.last().and_then(|bar| bar.time().ok()?.timestamp()) {
+                        Some(time) => until = time - 1,
+                        None => continue,
+                    }
+                }
+                if until > from {
+                    add_range(&mut ranges, from, until);
+                    write_ranges(&ranges_path, &ranges)?;
+                }
+            }
+        }
+
+        let bars = read_bars(&path)?;
+        Ok(bars.into_iter().filter(|bar| bar_in_range(bar, start, end)).collect())
+    }
+
+    /// All cached bars for a contract and bar size, oldest first.
+    pub fn cached(&self, contract: &Contract, bar_size: BarSize) -> Result<Vec<BarData>> {
+        read_bars(&self.path(contract, bar_size))
+    }
+
+    /// Path of the bar file for a contract and bar size.
+    ///
+    /// Contracts are keyed by `con_id` when set, and by their describing
+    /// fields otherwise.
+    fn path(&self, contract: &Contract, bar_size: BarSize) -> PathBuf {
+        let contract_key = if contract.con_id != 0 {
+            contract.con_id.to_string()
+        } else {
+            contract.key()
+        };
+        let hours = if self.use_rth { "rth" } else { "all" };
+        let name = format!("{contract_key}_{bar_size}_{}_{hours}.csv", self.what_to_show);
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+            .collect();
+        self.dir.join(name)
+    }
+}
+
+/// Parts of `start..end` not covered by `ranges`, which must be sorted.
+fn gaps(ranges: &[(i64, i64)], start: i64, end: i64) -> Vec<(i64, i64)> {
+    let mut gaps = Vec::new();
+    let mut cursor = start;
+    for &(from, to) in ranges {
+        if cursor >= end {
+            break;
+        }
+        if from > cursor {
+            gaps.push((cursor, from.min(end)));
+        }
+        cursor = cursor.max(to);
+    }
+    if cursor < end {
+        gaps.push((cursor, end));
+    }
+    gaps
+}
+
+/// Add `from..to` to sorted ranges, merging overlapping ones.
+fn add_range(ranges: &mut Vec<(i64, i64)>, from: i64, to: i64) {
+    ranges.push((from, to));
+    ranges.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(ranges.len());
+    for &(from, to) in ranges.iter() {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
+        }
+    }
+    *ranges = merged;
+}
+
+fn invalid(path: &Path, line: &str) -> Error {
+    let message = format!("Bad line in {}: {line:?}", path.display());
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+fn read_ranges(path: &Path) -> Result<Vec<(i64, i64)>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut ranges = Vec::new();
+    for line in text.lines().filter(|line| !line.is_empty()) {
+        let range = line
+            .split_once(',')
+            .and_then(|(from, to)| Some((from.parse().ok()?, to.parse().ok()?)));
+        let (from, to) = range.ok_or_else(|| invalid(path, line))?;
+        add_range(&mut ranges, from, to);
+    }
+    Ok(ranges)
+}
+
+fn write_ranges(path: &Path, ranges: &[(i64, i64)]) -> Result<()> {
+    let text: String = ranges.iter().map(|(from, to)| format!("{from},{to}\n")).collect();
+    // Replace the file in one step so an interruption can't truncate it.
+    let temp = path.with_extension("ranges.tmp");
+    fs::write(&temp, text)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+fn append_bars(path: &Path, bars: &[BarData]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut text = String::new();
+    if file.metadata()?.len() == 0 {
+        text.push_str(CSV_HEADER);
+    }
+    for bar in bars {
+        text.push_str(&csv_row(bar));
+    }
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Read cached bars, oldest first. Bars downloaded more than once keep
+/// the latest copy.
+fn read_bars(path: &Path) -> Result<Vec<BarData>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut bars = BTreeMap::new();
+    for line in text.lines().skip(1).filter(|line| !line.is_empty()) {
+        let bar = parse_bar(line).ok_or_else(|| invalid(path, line))?;
+        bars.insert((bar.time().ok(), bar.date.clone()), bar);
+    }
+    Ok(bars.into_values().collect())
+}
+
+fn parse_bar(line: &str) -> Option<BarData> {
+    let mut fields = line.split(',');
+    let mut next = || fields.next();
+    let bar = BarData {
+        date: next()?.to_string(),
+        open: next()?.parse().ok()?,
+        high: next()?.parse().ok()?,
+        low: next()?.parse().ok()?,
+        close: next()?.parse().ok()?,
+        volume: next().filter(|volume| !volume.is_empty()).map(str::parse).transpose().ok()?,
+        wap: next().filter(|wap| !wap.is_empty()).map(str::parse).transpose().ok()?,
+        bar_count: next()?.parse().ok()?,
+    };
+    Some(bar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{connected_client, read_frame, write_frame};
+
+    #[test]
+    fn test_gaps() {
+        let mut ranges = Vec::new();
+        add_range(&mut ranges, 30, 40);
+        add_range(&mut ranges, 10, 20);
+        add_range(&mut ranges, 15, 25);
+        assert_eq!(ranges, vec![(10, 25), (30, 40)]);
+        assert_eq!(gaps(&ranges, 0, 50), vec![(0, 10), (25, 30), (40, 50)]);
+        assert_eq!(gaps(&ranges, 12, 35), vec![(25, 30)]);
+        assert!(gaps(&ranges, 10, 25).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cache_downloads_missing_range() {
+        let dir = std::env::temp_dir().join(format!("ibkr-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (client, mut server) = connected_client(1).await;
+        let client = Arc::new(client);
+        let cache = HistoricalCache::new(&dir);
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let start = DateTime::utc(2024, 1, 5, 14, 30, 0);
+        let middle = DateTime::utc(2024, 1, 5, 15, 0, 0);
+        let end = DateTime::utc(2024, 1, 5, 15, 20, 0);
+
+        let server = async {
+            let first = read_frame(&mut server).await;
+            assert!(first.windows(8).any(|field| field == b"\x001800 S\0"));
+            let bars = "17\x001000000000\0\0\x002\0\
+                        1704465000\x001\x001\x001\x001\x00100\x001\x001\0\
+                        1704465900\x002\x002\x002\x002\x00100\x002\x001\0";
+            write_frame(&mut server, bars).await;
+
+            // Only the part after the cached range is requested.
+            let second = read_frame(&mut server).await;
+            assert!(second.windows(8).any(|field| field == b"\x001200 S\0"));
+            let bars = "17\x001000000001\0\0\x001\0\
+                        1704467400\x003\x003\x003\x003\x00-1\x00-1\x001\0";
+            write_frame(&mut server, bars).await;
+        };
+        let requests = async {
+            let first = cache.bars(&client, &contract, BarSize::Sec1, &start, &middle);
+            assert_eq!(first.await.unwrap().len(), 2);
+            let second = cache.bars(&client, &contract, BarSize::Sec1, &start, &end);
+            second.await.unwrap()
+        };
+        let (bars, _) = tokio::join!(requests, server);
+
+        assert_eq!(bars.len(), 3);
+        assert_eq!(bars[2].close, 3.0);
+        assert_eq!(bars[2].volume, None);
+        let cached = cache.cached(&contract, BarSize::Sec1).unwrap();
+        assert_eq!(cached.len(), 3);
+        assert_eq!(cached[0].volume, Some(100.into()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! Bulk historical bar downloads within IB's pacing limits.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use super::{bar_in_range, split_range, BarData, BarSize, Duration as HistDuration, WhatToShow};
+use crate::client::{Client, Transport};
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+
+/// How far a [`Client::backfill_all`] has got.
+#[derive(Debug, Clone)]
+pub struct BackfillProgress {
+    /// Requests completed so far
+    pub requests_done: usize,
+    /// Requests needed for the whole range
+    pub requests_total: usize,
+    /// Bars downloaded so far
+    pub bars: usize,
+    /// Time of the latest bar downloaded
+    pub through: Option<DateTime>,
+}
+
+/// Downloads a long range of historical bars as a series of requests.
+///
+/// The range is split into chunks TWS accepts for the bar size, requests
+/// are throttled to stay under the pacing limit (60 requests per 10
+/// minutes by default), and pacing violations are retried after a
+/// back-off. Chunks are yielded oldest first, without duplicate bars.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(client: std::sync::Arc<ibkr::Client>) -> ibkr::Result<()> {
+/// use ibkr::historical::HistoricalDownloader;
+/// use ibkr::{BarSize, Contract, DateTime};
+///
+/// let contract = Contract::stock("AAPL", "SMART", "USD");
+/// let start = DateTime::utc(2022, 1, 1, 0, 0, 0);
+/// let end = DateTime::utc(2024, 1, 1, 0, 0, 0);
+/// let mut download = HistoricalDownloader::new(client, contract, BarSize::Min1, &start, &end);
+///
+/// while let Some(bars) = download.next_chunk().await? {
+///     println!("{} bars", bars.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct HistoricalDownloader<S: Transport = TcpStream> {
+    client: Arc<Client<S>>,
+    contract: Contract,
+    bar_size: BarSize,
+    what_to_show: WhatToShow,
+    use_rth: bool,
+    start: DateTime,
+    end: DateTime,
+    max_requests: usize,
+    window: Duration,
+    /// Remaining `(end, duration)` requests, once the range was split
+    chunks: Option<VecDeque<(i64, HistDuration)>>,
+    /// When recent requests were sent, oldest first
+    sent: VecDeque<Instant>,
+    /// Time of the last bar yielded
+    last: Option<DateTime>,
+    /// End of the last chunk downloaded, in seconds since the Unix epoch
+    downloaded: Option<i64>,
+}
+
+impl<S: Transport> HistoricalDownloader<S> {
+    /// Create a downloader for `start..=end`, which must both be UTC (see
+    /// [`DateTime::utc`]). Downloads trades in regular trading hours by
+    /// default.
+    pub fn new(
+        client: Arc<Client<S>>,
+        contract: Contract,
+        bar_size: BarSize,
+        start: &DateTime,
+        end: &DateTime,
+    ) -> Self {
+        Self {
+            client,
+            contract,
+            bar_size,
+            what_to_show: WhatToShow::Trades,
+            use_rth: true,
+            start: start.clone(),
+            end: end.clone(),
+            max_requests: 60,
+            window: Duration::from_secs(600),
+            chunks: None,
+            sent: VecDeque::new(),
+            last: None,
+            downloaded: None,
+        }
+    }
+
+    /// Set the type of data to download.
+    pub fn what_to_show(mut self, what_to_show: WhatToShow) -> Self {
+        self.what_to_show = what_to_show;
+        self
+    }
+
+    /// Set whether to only download regular trading hours.
+    pub fn use_rth(mut self, use_rth: bool) -> Self {
+        self.use_rth = use_rth;
+        self
+    }
+
+    /// Send at most `max_requests` requests in any `window`.
+    pub fn pacing(mut self, max_requests: usize, window: Duration) -> Self {
+        self.max_requests = max_requests.max(1);
+        self.window = window;
+        self
+    }
+
+    /// Number of requests left to send.
+    pub fn remaining(&self) -> Option<usize> {
+        self.chunks.as_ref().map(VecDeque::len)
+    }
+
+    /// End of the last chunk downloaded, in seconds since the Unix epoch.
+    pub(crate) fn downloaded_until(&self) -> Option<i64> {
+        self.downloaded
+    }
+
+    /// Download the next chunk of bars.
+    ///
+    /// Returns `None` once the whole range has been downloaded. A chunk
+    /// can be empty, e.g. over a weekend.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<BarData>>> {
+        let (Some(start), Some(end)) = (self.start.timestamp(), self.end.timestamp()) else {
+            return Err(Error::Protocol("Bar range bounds must be UTC".into()));
+        };
+        if self.chunks.is_none() {
+            self.client.check_what_to_show(self.what_to_show)?;
+            self.chunks = Some(split_range(start, end, self.bar_size).into());
+        }
+        let Some((chunk_end, duration)) = self.chunks.as_mut().and_then(VecDeque::pop_front)
+        else {
+            return Ok(None);
+        };
+
+        self.throttle().await;
+        let bars = self
+            .client
+            .historical_data_chunk_paced(
+                &self.contract,
+                chunk_end,
+                duration,
+                self.bar_size,
+                self.what_to_show,
+                self.use_rth,
+            )
+            .await?;
+        self.downloaded = Some(chunk_end);
+
+        // Chunks rounded up to whole days overlap the previous one.
+        let last = self.last.clone();
+        let bars: Vec<BarData> = bars
+            .into_iter()
+            .filter(|bar| last.is_none() || bar.time().ok() > last)
+            .filter(|bar| bar_in_range(bar, start, end))
+            .collect();
+        if let Some(time) = bars.last().and_then(|bar| bar.time().ok()) {
+            self.last = Some(time);
+        }
+        Ok(Some(bars))
+    }
+
+    /// Download the rest of the range.
+    pub async fn download_all(mut self) -> Result<Vec<BarData>> {
+        let mut bars = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            bars.extend(chunk);
+        }
+        Ok(bars)
+    }
+
+    /// Wait until another request fits in the pacing window.
+    async fn throttle(&mut self) {
+        let now = Instant::now();
+        while self.sent.front().is_some_and(|sent| now.duration_since(*sent) >= self.window) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max_requests
+            && let Some(oldest) = self.sent.pop_front()
+        {
+            tokio::time::sleep_until(oldest + self.window).await;
+        }
+        self.sent.push_back(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{connected_client, read_frame, write_frame};
+
+    #[tokio::test]
+    async fn test_download_in_chunks() {
+        let (client, mut server) = connected_client(1).await;
+        let start = DateTime::utc(2024, 1, 5, 14, 30, 0);
+        let end = DateTime::utc(2024, 1, 5, 15, 20, 0);
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let mut download =
+            HistoricalDownloader::new(Arc::new(client), contract, BarSize::Sec1, &start, &end);
+
+        let server = async {
+            let first = read_frame(&mut server).await;
+            assert!(first.windows(8).any(|field| field == b"\x001800 S\0"));
+            let bars = "17\x001000000000\0\0\x002\0\
+                        1704465000\x001\x001\x001\x001\x001\x001\x001\0\
+                        1704466800\x002\x002\x002\x002\x002\x002\x001\0";
+            write_frame(&mut server, bars).await;
+
+            let second = read_frame(&mut server).await;
+            assert!(second.windows(8).any(|field| field == b"\x001200 S\0"));
+            let bars = "17\x001000000001\0\0\x002\0\
+                        1704466800\x002\x002\x002\x002\x002\x002\x001\0\
+                        1704468000\x003\x003\x003\x003\x003\x003\x001\0";
+            write_frame(&mut server, bars).await;
+        };
+        let download = async {
+            let first = download.next_chunk().await.unwrap().unwrap();
+            let second = download.next_chunk().await.unwrap().unwrap();
+            assert!(download.next_chunk().await.unwrap().is_none());
+            (first, second)
+        };
+        let ((first, second), _) = tokio::join!(download, server);
+
+        assert_eq!(first.len(), 2);
+        // The bar at the chunk boundary is only yielded once.
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].close, 3.0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Writing bars to files for analysis tools.
+//!
+//! CSV is always available. Arrow IPC and Parquet, readable by pandas,
+//! polars, DuckDB and the like, need the `arrow` and `parquet` features.
+
+use std::io::Write;
+
+use rust_decimal::Decimal;
+
+use super::BarData;
+use crate::error::Result;
+
+/// CSV header row, matching [`csv_row`].
+pub(super) const CSV_HEADER: &str = "date,open,high,low,close,volume,wap,bar_count\n";
+
+/// One bar as a CSV line. Missing volumes and WAPs are left empty.
+pub(super) fn csv_row(bar: &BarData) -> String {
+    let decimal = |value: Option<Decimal>| value.map(|value| value.to_string()).unwrap_or_default();
+    format!(
+        "{},{},{},{},{},{},{},{}\n",
+        bar.date,
+        bar.open,
+        bar.high,
+        bar.low,
+        bar.close,
+        decimal(bar.volume),
+        decimal(bar.wap),
+        bar.bar_count
+    )
+}
+
+/// Write bars as CSV with a header row.
+pub fn write_csv<W: Write>(bars: &[BarData], mut writer: W) -> Result<()> {
+    writer.write_all(CSV_HEADER.as_bytes())?;
+    for bar in bars {
+        writer.write_all(csv_row(bar).as_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+mod arrow {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use arrow_array::{
+        ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray, TimestampSecondArray,
+    };
+    use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+    use rust_decimal::prelude::ToPrimitive;
+
+    use super::BarData;
+    use crate::error::{Error, Result};
+
+    pub(super) fn error(err: impl std::error::Error + Send + Sync + 'static) -> Error {
+        Error::Io(std::io::Error::other(err))
+    }
+
+    /// Arrow schema of [`to_record_batch`].
+    ///
+    /// `time` is the bar timestamp in UTC, null for bars dated without a
+    /// timezone (e.g. daily bars). `volume` and `wap` are converted to
+    /// floats.
+    pub fn bar_schema() -> SchemaRef {
+        let time = DataType::Timestamp(TimeUnit::Second, Some("UTC".into()));
+        Arc::new(Schema::new(vec![
+            Field::new("date", DataType::Utf8, false),
+            Field::new("time", time, true),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("volume", DataType::Float64, true),
+            Field::new("wap", DataType::Float64, true),
+            Field::new("bar_count", DataType::Int32, false),
+        ]))
+    }
+
+    /// Convert bars to an Arrow record batch with [`bar_schema`].
+    pub fn to_record_batch(bars: &[BarData]) -> Result<RecordBatch> {
+        let floats = |value: fn(&BarData) -> f64| {
+            Arc::new(bars.iter().map(value).collect::<Float64Array>()) as ArrayRef
+        };
+        let time = bars.iter().map(|bar| bar.time().ok()?.timestamp());
+        let volume = bars.iter().map(|bar| bar.volume.and_then(|volume| volume.to_f64()));
+        let wap = bars.iter().map(|bar| bar.wap.and_then(|wap| wap.to_f64()));
+        let columns = vec![
+            Arc::new(bars.iter().map(|bar| Some(&bar.date)).collect::<StringArray>()) as ArrayRef,
+            Arc::new(time.collect::<TimestampSecondArray>().with_timezone("UTC")),
+            floats(|bar| bar.open),
+            floats(|bar| bar.high),
+            floats(|bar| bar.low),
+            floats(|bar| bar.close),
+            Arc::new(volume.collect::<Float64Array>()),
+            Arc::new(wap.collect::<Float64Array>()),
+            Arc::new(bars.iter().map(|bar| bar.bar_count).collect::<Int32Array>()),
+        ];
+        RecordBatch::try_new(bar_schema(), columns).map_err(error)
+    }
+
+    /// Write bars as an Arrow IPC (Feather v2) file.
+    pub fn write_arrow_ipc<W: Write>(bars: &[BarData], writer: W) -> Result<()> {
+        let batch = to_record_batch(bars)?;
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(writer, &batch.schema()).map_err(error)?;
+        writer.write(&batch).map_err(error)?;
+        writer.finish().map_err(error)
+    }
+}
+
+#[cfg(feature = "arrow")]
+pub use self::arrow::{bar_schema, to_record_batch, write_arrow_ipc};
+
+/// Write bars as an uncompressed Parquet file with [`bar_schema`].
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: Write + Send>(bars: &[BarData], writer: W) -> Result<()> {
+    let batch = to_record_batch(bars)?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)
+        .map_err(self::arrow::error)?;
+    writer.write(&batch).map_err(self::arrow::error)?;
+    writer.close().map_err(self::arrow::error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<BarData> {
+        vec![
+            BarData {
+                date: "1704465000".to_string(),
+                open: 185.5,
+                high: 186.0,
+                low: 185.25,
+                close: 185.75,
+                volume: Some(Decimal::new(12505, 1)),
+                wap: Some(Decimal::new(18562, 2)),
+                bar_count: 42,
+            },
+            BarData {
+                date: "20240105".to_string(),
+                close: 185.0,
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut out = Vec::new();
+        write_csv(&bars(), &mut out).unwrap();
+        let expected = "date,open,high,low,close,volume,wap,bar_count\n\
+                        1704465000,185.5,186,185.25,185.75,1250.5,185.62,42\n\
+                        20240105,0,0,0,185,,,0\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_record_batch() {
+        use arrow_array::{Array, Float64Array, TimestampSecondArray};
+
+        let batch = to_record_batch(&bars()).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let time = batch.column(1).as_any().downcast_ref::<TimestampSecondArray>().unwrap();
+        assert_eq!(time.value(0), 1_704_465_000);
+        assert!(time.is_null(1));
+        let volume = batch.column(6).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(volume.value(0), 1250.5);
+        assert!(volume.is_null(1));
+
+        let mut out = Vec::new();
+        write_arrow_ipc(&bars(), &mut out).unwrap();
+        assert!(out.starts_with(b"ARROW1"));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_write_parquet() {
+        let mut out = Vec::new();
+        write_parquet(&bars(), &mut out).unwrap();
+        assert!(out.starts_with(b"PAR1") && out.ends_with(b"PAR1"));
+    }
+}
@@ -0,0 +1,389 @@
+//! Bar updates that carry on across reconnects without gaps.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::net::TcpStream;
+
+use super::{BarData, BarSize, Duration, WhatToShow};
+use crate::client::{Client, Transport};
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+use crate::subscription::Subscription;
+
+/// Opens a new connection once the old one has closed.
+type Reconnect<S> =
+    Box<dyn FnMut() -> Pin<Box<dyn Future<Output = Result<Arc<Client<S>>>> + Send>> + Send>;
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Keeps a [`Client::bar_updates`] stream going across reconnects.
+///
+/// When the connection closes, the stream reconnects with the callback
+/// set by [`GaplessBars::reconnect`], requests the bars missed during the
+/// outage and yields them in order before resuming live updates, so
+/// consumers see one series without gaps. Outages longer than a single
+/// request allows for the bar size are backfilled with
+/// [`Client::historical_data_range`] first.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(client: std::sync::Arc<ibkr::Client>) -> ibkr::Result<()> {
+/// use std::sync::Arc;
+///
+/// use ibkr::historical::GaplessBars;
+/// use ibkr::{BarSize, Client, Contract, Duration};
+///
+/// let contract = Contract::stock("AAPL", "SMART", "USD");
+/// let mut bars = GaplessBars::new(client, contract, Duration::Days(1), BarSize::Min1)
+///     .reconnect(|| async { Ok(Arc::new(Client::connect("127.0.0.1:4002", 1).await?)) })
+///     .start()
+///     .await?;
+/// while let Some(bar) = bars.next().await {
+///     println!("{} {}", bar.date, bar.close);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct GaplessBars<S: Transport = TcpStream> {
+    client: Arc<Client<S>>,
+    contract: Contract,
+    duration: Duration,
+    bar_size: BarSize,
+    what_to_show: WhatToShow,
+    use_rth: bool,
+    reconnect: Option<Reconnect<S>>,
+    /// Current time in epoch seconds
+    clock: fn() -> i64,
+}
+
+impl<S: Transport> GaplessBars<S> {
+    /// Stream `bar_size` bars for `contract`, starting with the bars
+    /// covering `duration` (trades, regular trading hours only).
+    pub fn new(
+        client: Arc<Client<S>>,
+        contract: Contract,
+        duration: Duration,
+        bar_size: BarSize,
+    ) -> Self {
+        Self {
+            client,
+            contract,
+            duration,
+            bar_size,
+            what_to_show: WhatToShow::Trades,
+            use_rth: true,
+            reconnect: None,
+            clock: unix_now,
+        }
+    }
+
+    /// Set the type of data to return.
+    pub fn what_to_show(mut self, what_to_show: WhatToShow) -> Self {
+        self.what_to_show = what_to_show;
+        self
+    }
+
+    /// Set whether to only return data from regular trading hours.
+    pub fn use_rth(mut self, use_rth: bool) -> Self {
+        self.use_rth = use_rth;
+        self
+    }
+
+    /// Set how to open a new connection after the current one closes.
+    ///
+    /// Without it the stream ends with the connection. If `connect`
+    /// fails, the stream ends with its error, so retries belong in it.
+    pub fn reconnect<F, Fut>(mut self, mut connect: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Arc<Client<S>>>> + Send + 'static,
+    {
+        self.reconnect = Some(Box::new(move || Box::pin(connect())));
+        self
+    }
+
+    /// Request the initial bars and subscribe to updates.
+    pub async fn start(self) -> Result<GaplessBarStream<S>> {
+        let bars = self.subscribe(self.duration).await?;
+        Ok(GaplessBarStream {
+            source: self,
+            bars,
+            backfill: VecDeque::new(),
+            last: None,
+            error: None,
+        })
+    }
+
+    async fn subscribe(&self, duration: Duration) -> Result<Subscription<BarData>> {
+        self.client
+            .bar_updates(
+                self.contract.clone(),
+                duration,
+                self.bar_size,
+                self.what_to_show,
+                self.use_rth,
+            )
+            .await
+    }
+}
+
+/// Bars from a [`GaplessBars`] stream, started with [`GaplessBars::start`].
+pub struct GaplessBarStream<S: Transport = TcpStream> {
+    source: GaplessBars<S>,
+    bars: Subscription<BarData>,
+    /// Missed bars too old for the resumed request, yielded first
+    backfill: VecDeque<BarData>,
+    /// Start of the latest bar yielded, in epoch seconds
+    last: Option<i64>,
+    error: Option<Error>,
+}
+
+impl<S: Transport> GaplessBarStream<S> {
+    /// The connection currently streaming, which changes on reconnect.
+    pub fn client(&self) -> &Arc<Client<S>> {
+        &self.source.client
+    }
+
+    /// Wait for the next bar.
+    ///
+    /// As with [`Client::bar_updates`], a bar dated the same as the one
+    /// before it replaces it. Returns `None` once the request fails or the
+    /// connection can't be reopened; see [`GaplessBarStream::take_error`]
+    /// for why.
+    pub async fn next(&mut self) -> Option<BarData> {
+        loop {
+            let bar = match self.backfill.pop_front() {
+                Some(bar) => Some(bar),
+                None => self.bars.next().await,
+            };
+            match bar {
+                Some(bar) => {
+                    // Bars requested after a reconnect overlap the ones
+                    // already yielded; the latest of those is resent as
+                    // it ended.
+                    let time = bar_time(&bar);
+                    if time.is_some() && time < self.last {
+                        continue;
+                    }
+                    self.last = time.or(self.last);
+                    return Some(bar);
+                }
+                None => match self.bars.take_error() {
+                    Some(Error::NotConnected) => {
+                        if let Err(error) = self.resume().await {
+                            self.error = Some(error);
+                            return None;
+                        }
+                    }
+                    error => {
+                        self.error = error;
+                        return None;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Take the error that ended the stream, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Reconnect and request the bars from the latest one yielded on.
+    async fn resume(&mut self) -> Result<()> {
+        let Some(reconnect) = self.source.reconnect.as_mut() else {
+            return Err(Error::NotConnected);
+        };
+        self.source.client = reconnect().await?;
+
+        let duration = match self.last {
+            Some(last) => {
+                let now = (self.source.clock)();
+                let max = self.source.bar_size.max_duration_secs();
+                if now - last > max {
+                    let start = DateTime::from_timestamp(last);
+                    let end = DateTime::from_timestamp(now - max);
+                    let missed = self
+                        .source
+                        .client
+                        .historical_data_range(
+                            self.source.contract.clone(),
+                            &start,
+                            &end,
+                            self.source.bar_size,
+                            self.source.what_to_show,
+                            self.source.use_rth,
+                        )
+                        .await?;
+                    self.backfill.extend(missed);
+                }
+                Duration::covering((now - last).min(max))
+            }
+            None => self.source.duration,
+        };
+        tracing::info!(
+            symbol = %self.source.contract.symbol,
+            %duration,
+            "resuming bar updates after reconnect"
+        );
+        self.bars = self.source.subscribe(duration).await?;
+        Ok(())
+    }
+}
+
+/// Start of a bar in epoch seconds; daily bars count from midnight UTC.
+fn bar_time(bar: &BarData) -> Option<i64> {
+    let mut time = bar.time().ok()?;
+    time.timezone.get_or_insert_with(|| "UTC".to_string());
+    time.timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::DuplexStream;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::testing::{connected_client, read_frame, write_frame};
+
+    /// Bar source whose reconnects hand the new server side to the test.
+    async fn start(
+        bar_size: BarSize,
+        clock: fn() -> i64,
+    ) -> (GaplessBarStream<DuplexStream>, DuplexStream, mpsc::UnboundedReceiver<DuplexStream>) {
+        let (client, mut server) = connected_client(1).await;
+        let (servers, reconnected) = mpsc::unbounded_channel();
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let duration = Duration::Seconds(600);
+        let mut source = GaplessBars::new(Arc::new(client), contract, duration, bar_size)
+            .reconnect(move || {
+                let servers = servers.clone();
+                async move {
+                    let (client, server) = connected_client(1).await;
+                    servers.send(server).unwrap();
+                    Ok(Arc::new(client))
+                }
+            });
+        source.clock = clock;
+        let (stream, _) = tokio::join!(source.start(), read_frame(&mut server));
+        (stream.unwrap(), server, reconnected)
+    }
+
+    fn has_field(frame: &[u8], field: &str) -> bool {
+        let field = format!("\0{field}\0");
+        frame.windows(field.len()).any(|window| window == field.as_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_backfills_after_reconnect() {
+        let (mut bars, mut server, mut reconnected) = start(BarSize::Min1, || 1_704_467_100).await;
+        let initial = "17\x001000000000\0\0\x002\0\
+                       1704466680\x001\x001\x001\x001\x0010\x001\x001\0\
+                       1704466740\x002\x002\x002\x002\x0010\x002\x001\0";
+        write_frame(&mut server, initial).await;
+        let update = "90\x001000000000\x002\x001704466740\x002\x002.5\x002.5\x002\x002.2\x0020\0";
+        write_frame(&mut server, update).await;
+
+        let closes: Vec<_> = [bars.next().await, bars.next().await, bars.next().await]
+            .into_iter()
+            .map(|bar| bar.unwrap().close)
+            .collect();
+        assert_eq!(closes, [1.0, 2.0, 2.5]);
+
+        drop(server);
+        let resumed = async {
+            let mut server = reconnected.recv().await.unwrap();
+            // The outage since the last bar started: 1704467100 - 1704466740
+            let request = read_frame(&mut server).await;
+            assert!(has_field(&request, "360 S"));
+            let missed = "17\x001000000000\0\0\x003\0\
+                          1704466680\x001\x001\x001\x001\x0010\x001\x001\0\
+                          1704466740\x002\x003\x002\x002.8\x0030\x002.5\x003\0\
+                          1704466800\x003\x003\x003\x003\x0010\x003\x001\0";
+            write_frame(&mut server, missed).await;
+            let update = "90\x001000000000\x001\x001704466860\x004\x004\x004\x004\x004\x005\0";
+            write_frame(&mut server, update).await;
+            server
+        };
+        let next = async {
+            let mut closes = Vec::new();
+            for _ in 0..3 {
+                let bar = bars.next().await.unwrap();
+                closes.push((bar.date, bar.close));
+            }
+            closes
+        };
+        let (closes, _server) = tokio::join!(next, resumed);
+
+        // The bar already seen is dropped and the one in progress replaced.
+        assert_eq!(
+            closes,
+            [
+                ("1704466740".to_string(), 2.8),
+                ("1704466800".to_string(), 3.0),
+                ("1704466860".to_string(), 4.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_outage_backfills_from_range() {
+        let (mut bars, mut server, mut reconnected) = start(BarSize::Sec5, || 1_704_470_400).await;
+        let initial = "17\x001000000000\0\0\x001\0\
+                       1704466400\x001\x001\x001\x001\x0010\x001\x001\0";
+        write_frame(&mut server, initial).await;
+        assert_eq!(bars.next().await.unwrap().date, "1704466400");
+
+        drop(server);
+        let resumed = async {
+            let mut server = reconnected.recv().await.unwrap();
+            // 4000s missed but 5 sec bars only go back an hour, so the
+            // first 400s come from a range request.
+            let range = read_frame(&mut server).await;
+            assert!(has_field(&range, "400 S"));
+            let missed = "17\x001000000000\0\0\x002\0\
+                          1704466400\x001\x002\x001\x002\x0010\x001\x001\0\
+                          1704466600\x003\x003\x003\x003\x0010\x003\x001\0";
+            write_frame(&mut server, missed).await;
+            let live = read_frame(&mut server).await;
+            assert!(has_field(&live, "3600 S"));
+            let live = "17\x001000000001\0\0\x001\0\
+                        1704470395\x004\x004\x004\x004\x0010\x004\x001\0";
+            write_frame(&mut server, live).await;
+            server
+        };
+        let next = async {
+            let mut dates = Vec::new();
+            for _ in 0..3 {
+                dates.push(bars.next().await.unwrap().date);
+            }
+            dates
+        };
+        let (dates, _server) = tokio::join!(next, resumed);
+        assert_eq!(dates, ["1704466400", "1704466600", "1704470395"]);
+    }
+
+    #[tokio::test]
+    async fn test_ends_without_reconnect() {
+        let (client, mut server) = connected_client(1).await;
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let source = GaplessBars::new(Arc::new(client), contract, Duration::Days(1), BarSize::Min1);
+        let (bars, _) = tokio::join!(source.start(), read_frame(&mut server));
+        let mut bars = bars.unwrap();
+
+        drop(server);
+        assert!(bars.next().await.is_none());
+        assert!(matches!(bars.take_error(), Some(Error::NotConnected)));
+    }
+}
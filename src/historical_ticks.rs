@@ -0,0 +1,339 @@
+//! Historical tick data types.
+//!
+//! TWS returns at most 1000 ticks per request. `Client::historical_ticks_range`
+//! pages through longer ranges using [`append_page`] to stitch the results.
+
+use rust_decimal::Decimal;
+
+use crate::contract::Contract;
+use crate::datetime::DateTime;
+use crate::message::OutgoingMessageId;
+use crate::wire::{make_field, FieldIterator};
+
+/// Most ticks TWS returns for a single request.
+pub const MAX_TICKS_PER_REQUEST: i32 = 1000;
+
+/// A midpoint tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalTick {
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    pub price: f64,
+    pub size: Decimal,
+}
+
+/// A bid/ask quote tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalTickBidAsk {
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    /// Ask is above the day's high
+    pub ask_past_high: bool,
+    /// Bid is below the day's low
+    pub bid_past_low: bool,
+    pub bid: f64,
+    pub ask: f64,
+    pub bid_size: Decimal,
+    pub ask_size: Decimal,
+}
+
+/// A trade tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalTickLast {
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    /// Trade price is outside the day's range
+    pub past_limit: bool,
+    /// Trade was reported late or out of sequence
+    pub unreported: bool,
+    pub price: f64,
+    pub size: Decimal,
+    pub exchange: String,
+    pub special_conditions: String,
+}
+
+impl HistoricalTick {
+    /// Parse a tick from message fields.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let time = fields.next_parsed()?;
+        fields.skip(1); // unused, kept for field alignment
+        Some(Self {
+            time,
+            price: fields.next_f64(),
+            size: fields.next_decimal(),
+        })
+    }
+}
+
+impl HistoricalTickBidAsk {
+    /// Parse a tick from message fields.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let time = fields.next_parsed()?;
+        let mask = fields.next_i32();
+        Some(Self {
+            time,
+            ask_past_high: mask & 1 != 0,
+            bid_past_low: mask & 2 != 0,
+            bid: fields.next_f64(),
+            ask: fields.next_f64(),
+            bid_size: fields.next_decimal(),
+            ask_size: fields.next_decimal(),
+        })
+    }
+}
+
+impl HistoricalTickLast {
+    /// Parse a tick from message fields.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let time = fields.next_parsed()?;
+        let mask = fields.next_i32();
+        Some(Self {
+            time,
+            past_limit: mask & 1 != 0,
+            unreported: mask & 2 != 0,
+            price: fields.next_f64(),
+            size: fields.next_decimal(),
+            exchange: fields.next_string().unwrap_or("").to_string(),
+            special_conditions: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// Ticks returned by one historical ticks request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoricalTicks {
+    Midpoint(Vec<HistoricalTick>),
+    BidAsk(Vec<HistoricalTickBidAsk>),
+    Last(Vec<HistoricalTickLast>),
+}
+
+impl HistoricalTicks {
+    /// Append the ticks of a later message for the same request.
+    ///
+    /// Ticks of a different kind are dropped.
+    pub fn extend(&mut self, more: HistoricalTicks) {
+        match (self, more) {
+            (Self::Midpoint(ticks), Self::Midpoint(more)) => ticks.extend(more),
+            (Self::BidAsk(ticks), Self::BidAsk(more)) => ticks.extend(more),
+            (Self::Last(ticks), Self::Last(more)) => ticks.extend(more),
+            _ => tracing::warn!("historical ticks of mixed kinds in one response"),
+        }
+    }
+}
+
+/// A kind of historical tick that can be requested.
+pub trait HistoricalTickData: Sized + Send + 'static {
+    /// `whatToShow` value requesting this kind of tick
+    const WHAT_TO_SHOW: &'static str;
+
+    /// Tick time in seconds since the Unix epoch.
+    fn time(&self) -> i64;
+
+    /// Extract ticks of this kind from a response.
+    fn from_ticks(ticks: HistoricalTicks) -> Option<Vec<Self>>;
+}
+
+impl HistoricalTickData for HistoricalTick {
+    const WHAT_TO_SHOW: &'static str = "MIDPOINT";
+
+    fn time(&self) -> i64 {
+        self.time
+    }
+
+    fn from_ticks(ticks: HistoricalTicks) -> Option<Vec<Self>> {
+        match ticks {
+            HistoricalTicks::Midpoint(ticks) => Some(ticks),
+            _ => None,
+        }
+    }
+}
+
+impl HistoricalTickData for HistoricalTickBidAsk {
+    const WHAT_TO_SHOW: &'static str = "BID_ASK";
+
+    fn time(&self) -> i64 {
+        self.time
+    }
+
+    fn from_ticks(ticks: HistoricalTicks) -> Option<Vec<Self>> {
+        match ticks {
+            HistoricalTicks::BidAsk(ticks) => Some(ticks),
+            _ => None,
+        }
+    }
+}
+
+impl HistoricalTickData for HistoricalTickLast {
+    const WHAT_TO_SHOW: &'static str = "TRADES";
+
+    fn time(&self) -> i64 {
+        self.time
+    }
+
+    fn from_ticks(ticks: HistoricalTicks) -> Option<Vec<Self>> {
+        match ticks {
+            HistoricalTicks::Last(ticks) => Some(ticks),
+            _ => None,
+        }
+    }
+}
+
+/// Historical ticks request parameters.
+#[derive(Debug, Clone)]
+pub struct HistoricalTicksRequest {
+    /// Request ID for correlation
+    pub req_id: i32,
+    /// Contract to request ticks for
+    pub contract: Contract,
+    /// First tick time (set this or `end`)
+    pub start: Option<DateTime>,
+    /// Last tick time (set this or `start`)
+    pub end: Option<DateTime>,
+    /// Number of ticks to return (at most 1000)
+    pub number_of_ticks: i32,
+    /// "TRADES", "MIDPOINT" or "BID_ASK"
+    pub what_to_show: &'static str,
+    /// Use regular trading hours only
+    pub use_rth: bool,
+    /// Ignore size-only updates for BID_ASK
+    pub ignore_size: bool,
+}
+
+impl HistoricalTicksRequest {
+    /// Create a new request for ticks of kind `T`.
+    pub fn new<T: HistoricalTickData>(req_id: i32, contract: Contract) -> Self {
+        Self {
+            req_id,
+            contract,
+            start: None,
+            end: None,
+            number_of_ticks: MAX_TICKS_PER_REQUEST,
+            what_to_show: T::WHAT_TO_SHOW,
+            use_rth: true,
+            ignore_size: false,
+        }
+    }
+
+    /// Set the start time.
+    pub fn start(mut self, start: DateTime) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Set the end time.
+    pub fn end(mut self, end: DateTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Set the number of ticks.
+    pub fn number_of_ticks(mut self, count: i32) -> Self {
+        self.number_of_ticks = count;
+        self
+    }
+
+    /// Set whether to use regular trading hours only.
+    pub fn use_rth(mut self, rth: bool) -> Self {
+        self.use_rth = rth;
+        self
+    }
+
+    /// Encode the request as a message payload for a server of
+    /// `server_version`.
+    ///
+    /// Assumes server version >= 130 (MIN_SERVER_VER_HISTORICAL_TICKS).
+    /// Times are formatted for the server, see
+    /// [`DateTime::to_request_string_for`].
+    pub fn encode(&self, server_version: u32) -> String {
+        let format = |time: &Option<DateTime>| {
+            time.as_ref()
+                .map(|time| time.to_request_string_for(server_version))
+                .unwrap_or_default()
+        };
+
+        let mut msg = String::new();
+        msg.push_str(&make_field(OutgoingMessageId::ReqHistoricalTicks.as_u32()));
+        msg.push_str(&make_field(self.req_id));
+        msg.push_str(&self.contract.encode());
+        msg.push_str(&make_field(if self.contract.include_expired { 1 } else { 0 }));
+        msg.push_str(&make_field(format(&self.start)));
+        msg.push_str(&make_field(format(&self.end)));
+        msg.push_str(&make_field(self.number_of_ticks));
+        msg.push_str(&make_field(self.what_to_show));
+        msg.push_str(&make_field(if self.use_rth { 1 } else { 0 }));
+        msg.push_str(&make_field(if self.ignore_size { 1 } else { 0 }));
+
+        // miscOptions (empty)
+        msg.push_str(&make_field(""));
+
+        msg
+    }
+}
+
+/// Append a page that was requested starting at `cursor`.
+///
+/// A page starting at `cursor` repeats the ticks from that second already
+/// returned by the previous page, so those are skipped.
+pub fn append_page<T: HistoricalTickData>(ticks: &mut Vec<T>, page: Vec<T>, cursor: i64) {
+    let mut repeated = ticks.iter().rev().take_while(|tick| tick.time() == cursor).count();
+    for tick in page {
+        if repeated > 0 && tick.time() == cursor {
+            repeated -= 1;
+            continue;
+        }
+        ticks.push(tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_versions;
+
+    fn tick(time: i64, price: f64) -> HistoricalTick {
+        HistoricalTick {
+            time,
+            price,
+            size: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_parse_last_tick() {
+        let buf = b"1704465000\x002\x00185.5\x00100\0ARCA\0 T\0";
+        let mut fields = FieldIterator::new(buf);
+        let tick = HistoricalTickLast::parse(&mut fields).unwrap();
+
+        assert_eq!(tick.time, 1_704_465_000);
+        assert!(!tick.past_limit);
+        assert!(tick.unreported);
+        assert_eq!(tick.price, 185.5);
+        assert_eq!(tick.exchange, "ARCA");
+        assert_eq!(tick.special_conditions, " T");
+    }
+
+    #[test]
+    fn test_append_page_skips_boundary_ticks() {
+        let mut ticks = vec![tick(1, 1.0), tick(2, 2.0), tick(2, 2.1)];
+        append_page(&mut ticks, vec![tick(2, 2.0), tick(2, 2.1), tick(2, 2.2), tick(3, 3.0)], 2);
+
+        let prices: Vec<f64> = ticks.iter().map(|t| t.price).collect();
+        assert_eq!(prices, [1.0, 2.0, 2.1, 2.2, 3.0]);
+    }
+
+    #[test]
+    fn test_request_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let request = HistoricalTicksRequest::new::<HistoricalTickLast>(7, contract)
+            .start(DateTime::utc(2024, 1, 5, 14, 30, 0))
+            .use_rth(false);
+        let encoded = request.encode(176);
+
+        assert!(encoded.starts_with("96\x007\0"));
+        assert!(encoded.contains("\x0020240105-14:30:00\0\x001000\0TRADES\x000\x000\0\0"));
+
+        // Servers before UTC_DATE_TIME only take the space-separated form
+        let encoded = request.encode(server_versions::UTC_DATE_TIME - 1);
+        assert!(encoded.contains("\x0020240105 14:30:00 UTC\0\x001000\0TRADES\0"));
+    }
+}
@@ -2,42 +2,136 @@
 //!
 //! A clean, idiomatic Rust interface to Interactive Brokers' TWS API.
 //!
-//! # Example
-//!
-//! ```no_run
-//! use ibkr::{Client, Contract, BarSize, Duration, WhatToShow};
-//!
-//! #[tokio::main]
-//! async fn main() -> ibkr::Result<()> {
-//!     let client = Client::connect("127.0.0.1:7496", 1).await?;
+#![cfg_attr(
+    feature = "client",
+    doc = r#"
+# Example
+
+```no_run
+use ibkr::{Client, Contract, BarSize, Duration, WhatToShow};
+
+#[tokio::main]
+async fn main() -> ibkr::Result<()> {
+    let client = Client::connect("127.0.0.1:7496", 1).await?;
+
+    let contract = Contract::stock("AAPL", "SMART", "USD");
+    let bars = client.historical_data(
+        contract,
+        Duration::Days(5),
+        BarSize::Day1,
+        WhatToShow::Trades,
+        true,
+    ).await?;
+
+    for bar in bars {
+        println!("{}: O:{} H:{} L:{} C:{}",
+            bar.date, bar.open, bar.high, bar.low, bar.close);
+    }
+    Ok(())
+}
+```
+"#
+)]
 //!
-//!     let contract = Contract::stock("AAPL", "SMART", "USD");
-//!     let bars = client.historical_data(
-//!         contract,
-//!         Duration::Days(5),
-//!         BarSize::Day1,
-//!         WhatToShow::Trades,
-//!         true,
-//!     ).await?;
+//! # Features
 //!
-//!     for bar in bars {
-//!         println!("{}: O:{} H:{} L:{} C:{}",
-//!             bar.date, bar.open, bar.high, bar.low, bar.close);
-//!     }
-//!     Ok(())
-//! }
-//! ```
+//! - `client` (default): the tokio-based [`Client`], [`ClientPool`] and
+//!   subscriptions. Without it the crate is a runtime-agnostic protocol
+//!   core; see [`codec`] for driving a connection from any event loop.
+//! - `serde`: `Serialize`/`Deserialize` for snapshot types.
+//! - `chrono`: conversions between [`DateTime`] and `chrono` date/times,
+//!   with timezone names resolved by `chrono-tz`.
+//! - `arrow` / `parquet`: writing bars as Arrow IPC or Parquet files, see
+//!   [`historical::export`].
 
+pub mod account;
+#[cfg(feature = "client")]
 pub mod client;
+pub mod codec;
+pub mod condition;
+pub mod conid_cache;
 pub mod contract;
+pub mod contract_details;
+pub mod datetime;
+pub mod environment;
 pub mod error;
+pub mod event;
+pub mod execution;
+pub mod futures;
+pub mod fx;
 pub mod historical;
+pub mod historical_ticks;
+pub mod market_data;
+pub mod market_depth;
+pub mod market_rule;
 pub mod message;
+pub mod option_chain;
+pub mod order;
+pub mod pnl;
+#[cfg(feature = "client")]
+pub mod pool;
+pub mod recorder;
+pub mod scanner;
+pub mod server_versions;
+#[cfg(feature = "client")]
+pub mod subscription;
+pub mod trading_hours;
 pub mod wire;
 
-pub use client::Client;
-pub use contract::{Contract, OptionRight, SecurityType};
-pub use error::{Error, Result};
+#[cfg(all(test, feature = "client"))]
+mod testing;
+
+pub use account::{
+    AccountAliases, AccountSummaryTag, AccountSummaryValue, AccountValue, MarginSummary,
+    PortfolioValue, Position,
+};
+#[cfg(feature = "client")]
+pub use client::{Client, Transport};
+pub use codec::{ConnectOptions, ConnectionInfo};
+pub use condition::{Condition, Conjunction, OrderCondition};
+pub use conid_cache::ConIdCache;
+pub use contract::{
+    ComboLeg, Contract, ContractBuilder, DeltaNeutralContract, OptionRight, SecurityType,
+};
+pub use contract_details::{BondDetails, ContractDetails};
+pub use datetime::DateTime;
+pub use environment::Environment;
+pub use error::{Error, RequestContext, Result};
+pub use event::{Event, NewsBulletin, UnroutableError};
+pub use execution::{CommissionReport, Execution, ExecutionFilter, Fill, FillEvent};
+pub use fx::FxRate;
 pub use historical::{BarData, BarSize, Duration, WhatToShow};
+pub use historical_ticks::{HistoricalTick, HistoricalTickBidAsk, HistoricalTickLast};
+pub use market_data::{
+    Auction, EtfNav, GenericTick, Halted, MarketDataType, Quote, Shortable, SmartComponent, Tick,
+    TickAttrib, TickReqParams, Trade,
+};
+pub use market_depth::{
+    DepthExchange, DepthOperation, DepthRow, DepthSide, DepthUpdate, OrderBook,
+};
+pub use market_rule::{round_to_tick, MarketRule, PriceIncrement};
 pub use message::{IncomingMessageId, OutgoingMessageId};
-pub use wire::{extract_message, make_field, make_message, parse_fields, FieldIterator};
+pub use option_chain::{OptionChainRow, OptionParams};
+pub use order::{
+    AdaptivePriority, Algo, DeltaNeutralOrder, ExerciseAction, MarginImpact, OcaType, OpenOrder,
+    Order, OrderAction, OrderBuilder, OrderDefaults, OrderEvent, OrderState, OrderStatus, OrderType,
+    PeggedBenchmark, ReferencePriceType, RiskAversion, TimeInForce, TwapStrategyType,
+    VolatilityType,
+};
+pub use pnl::{ContractPnl, PnlSingle, PnlSnapshot};
+#[cfg(feature = "client")]
+pub use futures::{RollingEvent, RollingFuture, RollingTicks};
+#[cfg(feature = "client")]
+pub use historical::{
+    BackfillProgress, GaplessBarStream, GaplessBars, HistoricalCache, HistoricalDownloader,
+};
+#[cfg(feature = "client")]
+pub use pnl::PnlSnapshotter;
+#[cfg(feature = "client")]
+pub use pool::ClientPool;
+pub use rust_decimal::Decimal;
+pub use scanner::{ScannerData, ScannerRow, ScannerSubscription};
+#[cfg(feature = "client")]
+pub use subscription::{Fills, OrderTracker, SharedTicks, Subscription};
+pub use trading_hours::{Session, TradingHours};
+pub use wire::{extract_message, make_field, make_message, parse_fields, FieldIterator, TagValue};
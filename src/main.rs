@@ -4,7 +4,11 @@ use ibkr::{BarSize, Client, Contract, Duration, WhatToShow};
 async fn main() -> ibkr::Result<()> {
     println!("Connecting to TWS...");
     let client = Client::connect("127.0.0.1:7496", 1).await?;
-    println!("Connected to TWS v{}", client.server_version());
+    println!(
+        "Connected to TWS v{} ({} session)",
+        client.server_version(),
+        client.environment()
+    );
 
     // Request historical data for AMZN
     println!("\nRequesting AMZN daily bars for the past week...");
@@ -22,8 +26,13 @@ async fn main() -> ibkr::Result<()> {
     println!("Received {} bars:", bars.len());
     for bar in &bars {
         println!(
-            "  {} O:{:.2} H:{:.2} L:{:.2} C:{:.2} V:{:.0}",
-            bar.date, bar.open, bar.high, bar.low, bar.close, bar.volume
+            "  {} O:{:.2} H:{:.2} L:{:.2} C:{:.2} V:{}",
+            bar.date,
+            bar.open,
+            bar.high,
+            bar.low,
+            bar.close,
+            bar.volume.unwrap_or_default()
         );
     }
 
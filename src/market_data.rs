@@ -0,0 +1,811 @@
+//! Market data types.
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::contract::{Contract, SecurityType};
+use crate::message::OutgoingMessageId;
+use crate::wire::{make_field, FieldIterator};
+
+/// Which market data TWS should send for subsequent requests.
+///
+/// Delayed data needs no market data subscription, so it's the usual
+/// fallback when a live subscription is rejected (errors 354 and 10197).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarketDataType {
+    /// Real-time streaming data (requires a subscription)
+    #[default]
+    Realtime = 1,
+    /// Last recorded data when the market is closed
+    Frozen = 2,
+    /// Data delayed by 15-20 minutes
+    Delayed = 3,
+    /// Delayed data, frozen when the market is closed
+    DelayedFrozen = 4,
+}
+
+impl MarketDataType {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(Self::Realtime),
+            2 => Some(Self::Frozen),
+            3 => Some(Self::Delayed),
+            4 => Some(Self::DelayedFrozen),
+            _ => None,
+        }
+    }
+
+    /// Whether ticks of this type lag the market.
+    pub fn is_delayed(&self) -> bool {
+        matches!(self, Self::Delayed | Self::DelayedFrozen)
+    }
+
+    /// Whether ticks of this type are the last values recorded before the
+    /// market closed.
+    pub fn is_frozen(&self) -> bool {
+        matches!(self, Self::Frozen | Self::DelayedFrozen)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Realtime => "REALTIME",
+            Self::Frozen => "FROZEN",
+            Self::Delayed => "DELAYED",
+            Self::DelayedFrozen => "DELAYED_FROZEN",
+        }
+    }
+}
+
+impl std::fmt::Display for MarketDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Optional tick groups a market data request can ask for, by their
+/// generic tick ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GenericTick {
+    /// Call and put option volume (tick types 29, 30)
+    OptionVolume = 100,
+    /// Call and put open interest (27, 28)
+    OptionOpenInterest = 101,
+    /// 30-day historical volatility (23)
+    HistoricalVolatility = 104,
+    /// Average option volume (87)
+    AverageOptionVolume = 105,
+    /// Implied volatility of the underlying (24)
+    OptionImpliedVolatility = 106,
+    /// Index future premium (31)
+    IndexFuturePremium = 162,
+    /// 13/26/52-week highs and lows and average volume (15-21)
+    MiscStats = 165,
+    /// Mark price (37)
+    MarkPrice = 221,
+    /// Auction volume, price and imbalance (34-36, 61)
+    AuctionValues = 225,
+    /// Last trade details as one string (48)
+    RtVolume = 233,
+    /// Shortable indicator and shortable shares (46, 89)
+    Shortable = 236,
+    /// Fundamental ratios (47)
+    FundamentalRatios = 258,
+    /// Trade count, trade rate and volume rate (54-56)
+    TradeCount = 293,
+    TradeRate = 294,
+    VolumeRate = 295,
+    /// Last regular-hours trade (57)
+    LastRthTrade = 318,
+    /// Like RTVolume, without unreportable trades (77)
+    RtTradeVolume = 375,
+    /// Real-time historical volatility (58)
+    RtHistoricalVolatility = 411,
+    /// Dividend summary (59)
+    Dividends = 456,
+    /// Bond factor multiplier (60)
+    BondFactorMultiplier = 460,
+    /// ETF NAV bid and ask (94, 95)
+    EtfNavBidAsk = 576,
+    /// ETF NAV last (96)
+    EtfNavLast = 577,
+    /// ETF NAV close and prior close (92, 93)
+    EtfNavClose = 578,
+    /// Futures open interest (86)
+    FuturesOpenInterest = 588,
+    /// Short-term volume over 3, 5 and 10 minutes (63-65)
+    ShortTermVolume = 595,
+    /// ETF NAV high and low (98, 99)
+    EtfNavHighLow = 614,
+    /// ETF NAV frozen last (97)
+    EtfNavFrozenLast = 623,
+}
+
+impl GenericTick {
+    const ALL: [Self; 27] = [
+        Self::OptionVolume,
+        Self::OptionOpenInterest,
+        Self::HistoricalVolatility,
+        Self::AverageOptionVolume,
+        Self::OptionImpliedVolatility,
+        Self::IndexFuturePremium,
+        Self::MiscStats,
+        Self::MarkPrice,
+        Self::AuctionValues,
+        Self::RtVolume,
+        Self::Shortable,
+        Self::FundamentalRatios,
+        Self::TradeCount,
+        Self::TradeRate,
+        Self::VolumeRate,
+        Self::LastRthTrade,
+        Self::RtTradeVolume,
+        Self::RtHistoricalVolatility,
+        Self::Dividends,
+        Self::BondFactorMultiplier,
+        Self::EtfNavBidAsk,
+        Self::EtfNavLast,
+        Self::EtfNavClose,
+        Self::FuturesOpenInterest,
+        Self::ShortTermVolume,
+        Self::EtfNavHighLow,
+        Self::EtfNavFrozenLast,
+    ];
+
+    pub fn from_i32(value: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|tick| *tick as i32 == value)
+    }
+
+    /// Tick types TWS sends for this group.
+    pub fn tick_types(&self) -> &'static [i32] {
+        match self {
+            Self::OptionVolume => &[29, 30],
+            Self::OptionOpenInterest => &[27, 28],
+            Self::HistoricalVolatility => &[23],
+            Self::AverageOptionVolume => &[87],
+            Self::OptionImpliedVolatility => &[24],
+            Self::IndexFuturePremium => &[31],
+            Self::MiscStats => &[15, 16, 17, 18, 19, 20, 21],
+            Self::MarkPrice => &[37],
+            Self::AuctionValues => &[34, 35, 36, 61],
+            Self::RtVolume => &[48],
+            Self::Shortable => &[46, 89],
+            Self::FundamentalRatios => &[47],
+            Self::TradeCount => &[54],
+            Self::TradeRate => &[55],
+            Self::VolumeRate => &[56],
+            Self::LastRthTrade => &[57],
+            Self::RtTradeVolume => &[77],
+            Self::RtHistoricalVolatility => &[58],
+            Self::Dividends => &[59],
+            Self::BondFactorMultiplier => &[60],
+            Self::EtfNavBidAsk => &[94, 95],
+            Self::EtfNavLast => &[96],
+            Self::EtfNavClose => &[92, 93],
+            Self::FuturesOpenInterest => &[86],
+            Self::ShortTermVolume => &[63, 64, 65],
+            Self::EtfNavHighLow => &[98, 99],
+            Self::EtfNavFrozenLast => &[97],
+        }
+    }
+
+    /// The group a tick type is requested with, if it isn't sent by default.
+    pub fn for_tick_type(tick_type: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|tick| tick.tick_types().contains(&tick_type))
+    }
+
+    /// Format a list of groups as the comma-separated string TWS expects.
+    pub fn list(ticks: &[Self]) -> String {
+        ticks.iter().map(|tick| (*tick as i32).to_string()).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Market data request parameters.
+#[derive(Debug, Clone)]
+pub struct MarketDataRequest {
+    /// Request ID for correlation
+    pub req_id: i32,
+    /// Contract to request data for
+    pub contract: Contract,
+    /// Optional tick groups to stream besides the default ticks
+    pub generic_ticks: Vec<GenericTick>,
+    /// Send one snapshot and end the request instead of streaming
+    pub snapshot: bool,
+    /// Regulatory snapshot (incurs a fee per request for US stocks)
+    pub regulatory_snapshot: bool,
+}
+
+impl MarketDataRequest {
+    /// Create a streaming request for a contract.
+    pub fn new(req_id: i32, contract: Contract) -> Self {
+        Self {
+            req_id,
+            contract,
+            generic_ticks: Vec::new(),
+            snapshot: false,
+            regulatory_snapshot: false,
+        }
+    }
+
+    /// Request a single snapshot.
+    pub fn snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Set the optional tick groups.
+    pub fn generic_ticks(mut self, ticks: &[GenericTick]) -> Self {
+        self.generic_ticks = ticks.to_vec();
+        self
+    }
+
+    /// Encode the request as a message payload.
+    pub fn encode(&self) -> String {
+        let mut msg = String::new();
+
+        msg.push_str(&make_field(OutgoingMessageId::ReqMktData.as_u32()));
+        msg.push_str(&make_field(11)); // version
+        msg.push_str(&make_field(self.req_id));
+        msg.push_str(&self.contract.encode());
+        if self.contract.sec_type == SecurityType::Bag {
+            msg.push_str(&self.contract.encode_combo_legs());
+        }
+        msg.push_str(&self.contract.encode_delta_neutral());
+        msg.push_str(&make_field(GenericTick::list(&self.generic_ticks)));
+        msg.push_str(&make_field(if self.snapshot { 1 } else { 0 }));
+        msg.push_str(&make_field(if self.regulatory_snapshot { 1 } else { 0 }));
+
+        // mktDataOptions (empty)
+        msg.push_str(&make_field(""));
+
+        msg
+    }
+}
+
+// Tick types making up a quote, with their delayed equivalents.
+const BID_SIZE: [i32; 2] = [0, 69];
+const BID: [i32; 2] = [1, 66];
+const ASK: [i32; 2] = [2, 67];
+const ASK_SIZE: [i32; 2] = [3, 70];
+const LAST: [i32; 2] = [4, 68];
+const LAST_SIZE: [i32; 2] = [5, 71];
+const HIGH: [i32; 2] = [6, 72];
+const LOW: [i32; 2] = [7, 73];
+const VOLUME: [i32; 2] = [8, 74];
+const CLOSE: [i32; 2] = [9, 75];
+const OPEN: [i32; 2] = [14, 76];
+
+/// Top-of-book quote assembled from market data ticks.
+///
+/// Fields TWS didn't send (or sent as -1, meaning unavailable) are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Quote {
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub last: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    /// Previous session's close
+    pub close: Option<f64>,
+    pub open: Option<f64>,
+    pub bid_size: Option<Decimal>,
+    pub ask_size: Option<Decimal>,
+    pub last_size: Option<Decimal>,
+    pub volume: Option<Decimal>,
+    /// Market data type the values came from
+    pub data_type: MarketDataType,
+}
+
+impl Quote {
+    /// Midpoint of the bid and ask, when both are present.
+    pub fn midpoint(&self) -> Option<f64> {
+        Some((self.bid? + self.ask?) / 2.0)
+    }
+
+    /// Record a price tick. Unknown tick types are ignored.
+    pub fn apply_price(&mut self, tick_type: i32, price: f64) {
+        let price = Some(price).filter(|price| *price != -1.0);
+        let field = match tick_type {
+            t if BID.contains(&t) => &mut self.bid,
+            t if ASK.contains(&t) => &mut self.ask,
+            t if LAST.contains(&t) => &mut self.last,
+            t if HIGH.contains(&t) => &mut self.high,
+            t if LOW.contains(&t) => &mut self.low,
+            t if CLOSE.contains(&t) => &mut self.close,
+            t if OPEN.contains(&t) => &mut self.open,
+            _ => return,
+        };
+        *field = price;
+    }
+
+    /// Record a size tick. Unknown tick types are ignored.
+    pub fn apply_size(&mut self, tick_type: i32, size: Decimal) {
+        let field = match tick_type {
+            t if BID_SIZE.contains(&t) => &mut self.bid_size,
+            t if ASK_SIZE.contains(&t) => &mut self.ask_size,
+            t if LAST_SIZE.contains(&t) => &mut self.last_size,
+            t if VOLUME.contains(&t) => &mut self.volume,
+            _ => return,
+        };
+        *field = Some(size);
+    }
+}
+
+/// Price tick attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickAttrib {
+    /// The price can be executed against automatically
+    pub can_auto_execute: bool,
+    /// Bid is below the day's low or ask above the day's high
+    pub past_limit: bool,
+    /// Price is from the pre-open session
+    pub pre_open: bool,
+}
+
+impl TickAttrib {
+    /// Decode the attribute bit mask sent with a price tick.
+    pub fn from_mask(mask: i32) -> Self {
+        Self {
+            can_auto_execute: mask & 1 != 0,
+            past_limit: mask & 2 != 0,
+            pre_open: mask & 4 != 0,
+        }
+    }
+}
+
+/// An update from a streaming market data request.
+///
+/// `tick_type` is the TWS tick type ID, e.g. 1 for bid or 48 for RTVolume.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tick {
+    /// A price, with `-1.0` meaning unavailable
+    Price {
+        tick_type: i32,
+        price: f64,
+        attrib: TickAttrib,
+    },
+    Size { tick_type: i32, size: Decimal },
+    /// A text value, e.g. RTVolume or the last trade timestamp
+    String { tick_type: i32, value: String },
+    /// A numeric value, e.g. shortable shares or the halted flag
+    Generic { tick_type: i32, value: f64 },
+    /// Market data type the following ticks come from
+    DataType(MarketDataType),
+    /// All ticks of a snapshot request have been sent
+    SnapshotEnd,
+}
+
+// String tick types carrying trades in RTVolume format
+const RT_VOLUME: i32 = 48;
+const RT_TRADE_VOLUME: i32 = 77;
+// Ticks requested with GenericTick::Shortable
+const SHORTABLE: i32 = 46;
+const SHORTABLE_SHARES: i32 = 89;
+// Ticks requested with GenericTick::AuctionValues
+const AUCTION_VOLUME: i32 = 34;
+const AUCTION_PRICE: i32 = 35;
+const AUCTION_IMBALANCE: i32 = 36;
+const REGULATORY_IMBALANCE: i32 = 61;
+// Trading halt status, live and delayed
+const HALTED: [i32; 2] = [49, 90];
+
+/// How easily a contract can be borrowed for a short sale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortable {
+    /// At least 1000 shares are available to short
+    Available,
+    /// Shares may be located on request
+    Locate,
+    NotAvailable,
+}
+
+impl Shortable {
+    /// Classify the value of a shortable tick.
+    pub fn from_value(value: f64) -> Self {
+        if value > 2.5 {
+            Self::Available
+        } else if value > 1.5 {
+            Self::Locate
+        } else {
+            Self::NotAvailable
+        }
+    }
+}
+
+/// An opening or closing auction value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Auction {
+    /// Shares that would trade at the indicative price
+    Volume(Decimal),
+    /// Indicative auction price
+    Price(f64),
+    /// Shares unmatched at the indicative price
+    Imbalance(Decimal),
+    /// Imbalance reported by the exchange to regulators
+    RegulatoryImbalance(Decimal),
+}
+
+/// Trading halt status of a contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halted {
+    NotHalted,
+    /// Halted by the exchange or a regulator
+    Halted,
+    /// Paused by a volatility trading pause
+    VolatilityPause,
+}
+
+impl Halted {
+    pub fn from_value(value: f64) -> Option<Self> {
+        match value as i32 {
+            0 => Some(Self::NotHalted),
+            1 => Some(Self::Halted),
+            2 => Some(Self::VolatilityPause),
+            _ => None,
+        }
+    }
+}
+
+/// An ETF net asset value tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EtfNav {
+    Close(f64),
+    PriorClose(f64),
+    /// Indicative NAV bid
+    Bid(f64),
+    /// Indicative NAV ask
+    Ask(f64),
+    /// Indicative NAV
+    Last(f64),
+    /// Indicative NAV when the market closed
+    FrozenLast(f64),
+    High(f64),
+    Low(f64),
+}
+
+impl EtfNav {
+    fn from_price(tick_type: i32, price: f64) -> Option<Self> {
+        match tick_type {
+            92 => Some(Self::Close(price)),
+            93 => Some(Self::PriorClose(price)),
+            94 => Some(Self::Bid(price)),
+            95 => Some(Self::Ask(price)),
+            96 => Some(Self::Last(price)),
+            97 => Some(Self::FrozenLast(price)),
+            98 => Some(Self::High(price)),
+            99 => Some(Self::Low(price)),
+            _ => None,
+        }
+    }
+}
+
+/// A trade reported by an RTVolume or RTTradeVolume tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    /// Trade price; `None` for volume-only updates
+    pub price: Option<f64>,
+    pub size: Option<Decimal>,
+    /// Milliseconds since the Unix epoch
+    pub time: i64,
+    /// Day's volume including this trade
+    pub total_volume: Decimal,
+    /// Day's volume-weighted average price
+    pub vwap: f64,
+    /// Whether the trade was filled by a single market maker
+    pub single_trade_flag: bool,
+}
+
+impl Trade {
+    /// Parse the `price;size;time;totalVolume;vwap;singleTrade` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';');
+        let mut next = || parts.next().map(str::trim);
+        Some(Self {
+            price: next()?.parse().ok(),
+            size: next()?.parse().ok(),
+            time: next()?.parse().ok()?,
+            total_volume: next()?.parse().ok()?,
+            vwap: next()?.parse().ok()?,
+            single_trade_flag: next()? == "true",
+        })
+    }
+}
+
+impl Tick {
+    /// TWS tick type ID, for ticks that carry one.
+    pub fn tick_type(&self) -> Option<i32> {
+        match self {
+            Self::Price { tick_type, .. }
+            | Self::Size { tick_type, .. }
+            | Self::String { tick_type, .. }
+            | Self::Generic { tick_type, .. } => Some(*tick_type),
+            Self::DataType(_) | Self::SnapshotEnd => None,
+        }
+    }
+
+    /// The trade in an RTVolume or RTTradeVolume tick.
+    pub fn trade(&self) -> Option<Trade> {
+        match self {
+            Self::String {
+                tick_type: RT_VOLUME | RT_TRADE_VOLUME,
+                value,
+            } => Trade::parse(value),
+            _ => None,
+        }
+    }
+
+    /// The auction value in an auction tick.
+    pub fn auction(&self) -> Option<Auction> {
+        match *self {
+            Self::Size {
+                tick_type: AUCTION_VOLUME,
+                size,
+            } => Some(Auction::Volume(size)),
+            Self::Price {
+                tick_type: AUCTION_PRICE,
+                price,
+                ..
+            } => Some(Auction::Price(price)),
+            Self::Size {
+                tick_type: AUCTION_IMBALANCE,
+                size,
+            } => Some(Auction::Imbalance(size)),
+            Self::Size {
+                tick_type: REGULATORY_IMBALANCE,
+                size,
+            } => Some(Auction::RegulatoryImbalance(size)),
+            _ => None,
+        }
+    }
+
+    /// Halt status, from a halted tick.
+    pub fn halted(&self) -> Option<Halted> {
+        match self {
+            Self::Generic { tick_type, value } if HALTED.contains(tick_type) => {
+                Halted::from_value(*value)
+            }
+            _ => None,
+        }
+    }
+
+    /// The net asset value in an ETF NAV tick.
+    pub fn etf_nav(&self) -> Option<EtfNav> {
+        match self {
+            Self::Price {
+                tick_type, price, ..
+            } => EtfNav::from_price(*tick_type, *price),
+            _ => None,
+        }
+    }
+
+    /// Borrow availability, from a shortable tick.
+    pub fn shortable(&self) -> Option<Shortable> {
+        match self {
+            Self::Generic {
+                tick_type: SHORTABLE,
+                value,
+            } => Some(Shortable::from_value(*value)),
+            _ => None,
+        }
+    }
+
+    /// Number of shares available to short, from a shortable shares tick.
+    pub fn shortable_shares(&self) -> Option<Decimal> {
+        match self {
+            Self::Size {
+                tick_type: SHORTABLE_SHARES,
+                size,
+            } => Some(*size),
+            Self::Generic {
+                tick_type: SHORTABLE_SHARES,
+                value,
+            } => Decimal::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    /// The generic tick group this tick was requested with, if any.
+    pub fn generic_tick(&self) -> Option<GenericTick> {
+        self.tick_type().and_then(GenericTick::for_tick_type)
+    }
+}
+
+/// Parameters TWS reports at the start of a market data request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickReqParams {
+    /// Minimum price increment for the contract
+    pub min_tick: f64,
+    /// Exchange code to look up with `reqSmartComponents` for SMART quotes
+    pub bbo_exchange: String,
+    /// Bitmask of market data types available as snapshots
+    pub snapshot_permissions: i32,
+}
+
+impl TickReqParams {
+    /// Parse parameters from message fields (after the request ID).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            min_tick: fields.next_parsed()?,
+            bbo_exchange: fields.next_string().unwrap_or("").to_string(),
+            snapshot_permissions: fields.next_i32(),
+        })
+    }
+}
+
+/// An exchange taking part in SMART routing, from `reqSmartComponents`.
+///
+/// Bid, ask and last exchange ticks list exchanges by their single-letter
+/// code; [`TickReqParams::bbo_exchange`] names the map to decode them with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartComponent {
+    /// Exchange name (e.g. "NYSE")
+    pub exchange: String,
+    /// Letter the exchange appears as in exchange ticks
+    pub exchange_letter: char,
+}
+
+impl SmartComponent {
+    /// Parse a SMART_COMPONENTS message (after the request ID) into
+    /// components by bit number.
+    pub fn parse_map(fields: &mut FieldIterator) -> Option<HashMap<i32, Self>> {
+        let count = fields.next_i32().max(0) as usize;
+        let mut components = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let bit = fields.next_i32();
+            let component = Self {
+                exchange: fields.next_string()?.to_string(),
+                exchange_letter: fields.next_string()?.chars().next().unwrap_or(' '),
+            };
+            components.insert(bit, component);
+        }
+        Some(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::ComboLeg;
+
+    #[test]
+    fn test_parse_tick_req_params() {
+        let buf = b"0.01\x009c0001\x003\0";
+        let mut fields = FieldIterator::new(buf);
+        let params = TickReqParams::parse(&mut fields).unwrap();
+
+        assert_eq!(params.min_tick, 0.01);
+        assert_eq!(params.bbo_exchange, "9c0001");
+        assert_eq!(params.snapshot_permissions, 3);
+    }
+
+    #[test]
+    fn test_parse_smart_components() {
+        let buf = b"2\x000\0NYSE\0N\x001\0ARCA\0P\0";
+        let components = SmartComponent::parse_map(&mut FieldIterator::new(buf)).unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[&1].exchange, "ARCA");
+        assert_eq!(components[&0].exchange_letter, 'N');
+    }
+
+    #[test]
+    fn test_quote_from_ticks() {
+        let mut quote = Quote::default();
+        quote.apply_price(1, 185.5);
+        quote.apply_price(67, 185.7);
+        quote.apply_price(4, -1.0);
+        quote.apply_size(0, Decimal::new(25, 1));
+        quote.apply_price(45, 1.0);
+
+        assert_eq!(quote.bid, Some(185.5));
+        assert_eq!(quote.ask, Some(185.7));
+        assert_eq!(quote.last, None);
+        assert_eq!(quote.bid_size, Some(Decimal::new(25, 1)));
+        assert!((quote.midpoint().unwrap() - 185.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tick_attrib_mask() {
+        let attrib = TickAttrib::from_mask(5);
+        assert!(attrib.can_auto_execute);
+        assert!(!attrib.past_limit);
+        assert!(attrib.pre_open);
+    }
+
+    #[test]
+    fn test_generic_ticks() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let ticks = [GenericTick::RtVolume, GenericTick::Shortable];
+        let encoded = MarketDataRequest::new(5, contract).generic_ticks(&ticks).encode();
+        assert!(encoded.ends_with("\0USD\0\0\x000\x00233,236\x000\x000\0\0"));
+
+        let tick = Tick::Generic {
+            tick_type: 46,
+            value: 3.0,
+        };
+        assert_eq!(tick.generic_tick(), Some(GenericTick::Shortable));
+        assert_eq!(GenericTick::from_i32(165), Some(GenericTick::MiscStats));
+        assert_eq!(GenericTick::for_tick_type(1), None);
+    }
+
+    #[test]
+    fn test_rt_volume_trade() {
+        let tick = Tick::String {
+            tick_type: 48,
+            value: "185.5;100;1704465000123;5000;185.4012;true".into(),
+        };
+        let trade = tick.trade().unwrap();
+        assert_eq!(trade.price, Some(185.5));
+        assert_eq!(trade.size, Some(Decimal::from(100)));
+        assert_eq!(trade.time, 1704465000123);
+        assert_eq!(trade.total_volume, Decimal::from(5000));
+        assert!(trade.single_trade_flag);
+
+        let volume_only = Trade::parse(";;1704465000456;5100;185.41;false").unwrap();
+        assert_eq!(volume_only.price, None);
+        assert!(Trade::parse("garbage").is_none());
+    }
+
+    #[test]
+    fn test_shortable_ticks() {
+        let shortable = |value| Tick::Generic {
+            tick_type: 46,
+            value,
+        };
+        assert_eq!(shortable(3.0).shortable(), Some(Shortable::Available));
+        assert_eq!(shortable(2.0).shortable(), Some(Shortable::Locate));
+        assert_eq!(shortable(1.0).shortable(), Some(Shortable::NotAvailable));
+
+        let shares = Tick::Size {
+            tick_type: 89,
+            size: Decimal::from(250_000),
+        };
+        assert_eq!(shares.shortable_shares(), Some(Decimal::from(250_000)));
+        assert_eq!(shares.shortable(), None);
+    }
+
+    #[test]
+    fn test_auction_halted_and_nav_ticks() {
+        let imbalance = Tick::Size {
+            tick_type: 36,
+            size: Decimal::from(-5000),
+        };
+        assert_eq!(imbalance.auction(), Some(Auction::Imbalance(Decimal::from(-5000))));
+
+        let halted = Tick::Generic {
+            tick_type: 49,
+            value: 2.0,
+        };
+        assert_eq!(halted.halted(), Some(Halted::VolatilityPause));
+        assert_eq!(halted.auction(), None);
+
+        let nav = Tick::Price {
+            tick_type: 96,
+            price: 421.37,
+            attrib: TickAttrib::default(),
+        };
+        assert_eq!(nav.etf_nav(), Some(EtfNav::Last(421.37)));
+        assert_eq!(nav.halted(), None);
+    }
+
+    #[test]
+    fn test_snapshot_request_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let encoded = MarketDataRequest::new(5, contract).snapshot(true).encode();
+
+        assert!(encoded.starts_with("1\x0011\x005\x000\0AAPL\0STK\0"));
+        assert!(encoded.ends_with("\0USD\0\0\x000\0\x001\x000\0\0"));
+    }
+
+    #[test]
+    fn test_combo_request_encodes_legs() {
+        let legs = vec![
+            ComboLeg::new(43645865, 1, "BUY", "SMART"),
+            ComboLeg::new(9408, 1, "SELL", "SMART"),
+        ];
+        let contract = Contract::combo("IBKR,MCD", "SMART", "USD", legs);
+        let encoded = MarketDataRequest::new(5, contract).encode();
+
+        let legs = "USD\0\0\x002\x0043645865\x001\0BUY\0SMART\x009408\x001\0SELL\0SMART\0";
+        assert!(encoded.contains(&format!("{legs}0\0")));
+    }
+}
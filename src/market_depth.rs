@@ -0,0 +1,295 @@
+//! Level 2 market depth.
+//!
+//! [`DepthUpdate`]s describe changes to numbered rows of each side of the
+//! book; [`OrderBook`] applies them to keep a local copy.
+
+use rust_decimal::Decimal;
+
+use crate::server_versions;
+use crate::wire::FieldIterator;
+
+/// What a depth update does to its row of the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthOperation {
+    /// A new row at `position`; rows below shift down
+    Insert = 0,
+    /// The row at `position` changed
+    Update = 1,
+    /// The row at `position` is gone; rows below shift up
+    Delete = 2,
+}
+
+impl DepthOperation {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Insert),
+            1 => Some(Self::Update),
+            2 => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Side of the book a depth update applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthSide {
+    Ask = 0,
+    Bid = 1,
+}
+
+impl DepthSide {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Ask),
+            1 => Some(Self::Bid),
+            _ => None,
+        }
+    }
+}
+
+/// A venue that offers market depth, from `reqMktDepthExchanges`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthExchange {
+    pub exchange: String,
+    pub sec_type: String,
+    /// Primary listing exchange, when TWS reports one
+    pub listing_exchange: String,
+    /// "Deep" for L2 data, "Deep2" for aggregated L2
+    pub service_data_type: String,
+    /// Market depth aggregation group
+    pub agg_group: Option<i32>,
+}
+
+impl DepthExchange {
+    /// Parse one entry of a MKT_DEPTH_EXCHANGES message.
+    pub fn parse(fields: &mut FieldIterator, server_version: u32) -> Option<Self> {
+        let exchange = fields.next_string()?.to_string();
+        let sec_type = fields.next_string()?.to_string();
+        if server_version < server_versions::SERVICE_DATA_TYPE {
+            let service_data_type = if fields.next_bool() { "Deep2" } else { "Deep" };
+            return Some(Self {
+                exchange,
+                sec_type,
+                listing_exchange: String::new(),
+                service_data_type: service_data_type.to_string(),
+                agg_group: None,
+            });
+        }
+        Some(Self {
+            exchange,
+            sec_type,
+            listing_exchange: fields.next_string()?.to_string(),
+            service_data_type: fields.next_string()?.to_string(),
+            agg_group: fields.next_parsed().filter(|group| *group != i32::MAX),
+        })
+    }
+}
+
+/// One change to the order book, from MARKET_DEPTH or MARKET_DEPTH_L2.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthUpdate {
+    /// Row of the book, 0 being the top
+    pub position: i32,
+    /// Market maker or exchange quoting the row; empty for MARKET_DEPTH
+    pub market_maker: String,
+    pub operation: DepthOperation,
+    pub side: DepthSide,
+    pub price: f64,
+    pub size: Decimal,
+    /// Whether the row comes from aggregated SMART depth
+    pub is_smart_depth: bool,
+}
+
+impl DepthUpdate {
+    /// Parse a MARKET_DEPTH message (after the request ID).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            position: fields.next_i32(),
+            market_maker: String::new(),
+            operation: DepthOperation::from_i32(fields.next_i32())?,
+            side: DepthSide::from_i32(fields.next_i32())?,
+            price: fields.next_f64(),
+            size: fields.next_decimal(),
+            is_smart_depth: false,
+        })
+    }
+
+    /// Parse a MARKET_DEPTH_L2 message (after the request ID).
+    pub fn parse_l2(fields: &mut FieldIterator, server_version: u32) -> Option<Self> {
+        Some(Self {
+            position: fields.next_i32(),
+            market_maker: fields.next_string()?.to_string(),
+            operation: DepthOperation::from_i32(fields.next_i32())?,
+            side: DepthSide::from_i32(fields.next_i32())?,
+            price: fields.next_f64(),
+            size: fields.next_decimal(),
+            is_smart_depth: server_version >= server_versions::SMART_DEPTH && fields.next_bool(),
+        })
+    }
+}
+
+/// One row of an [`OrderBook`] side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthRow {
+    pub price: f64,
+    pub size: Decimal,
+    /// Market maker or exchange quoting the row; empty without L2 data
+    pub market_maker: String,
+}
+
+/// A local order book maintained from market depth updates.
+///
+/// Rows are kept in TWS's order, best price first, so several rows can
+/// share a price when market makers quote it separately.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderBook {
+    bids: Vec<DepthRow>,
+    asks: Vec<DepthRow>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a depth update to the book.
+    ///
+    /// Updates to rows past the end of a side are treated as inserts, and
+    /// deletes of missing rows are ignored.
+    pub fn apply(&mut self, update: &DepthUpdate) {
+        let rows = match update.side {
+            DepthSide::Bid => &mut self.bids,
+            DepthSide::Ask => &mut self.asks,
+        };
+        let position = usize::try_from(update.position).unwrap_or(0);
+        let row = DepthRow {
+            price: update.price,
+            size: update.size,
+            market_maker: update.market_maker.clone(),
+        };
+        match update.operation {
+            DepthOperation::Insert => rows.insert(position.min(rows.len()), row),
+            DepthOperation::Update => match rows.get_mut(position) {
+                Some(existing) => *existing = row,
+                None => rows.push(row),
+            },
+            DepthOperation::Delete => {
+                if position < rows.len() {
+                    rows.remove(position);
+                }
+            }
+        }
+    }
+
+    /// Bid rows, highest price first.
+    pub fn bids(&self) -> &[DepthRow] {
+        &self.bids
+    }
+
+    /// Ask rows, lowest price first.
+    pub fn asks(&self) -> &[DepthRow] {
+        &self.asks
+    }
+
+    pub fn best_bid(&self) -> Option<&DepthRow> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&DepthRow> {
+        self.asks.first()
+    }
+
+    /// Midpoint of the best bid and ask.
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()?.price + self.best_ask()?.price) / 2.0)
+    }
+
+    /// Best ask minus best bid.
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Total size quoted at `price` on one side.
+    pub fn size_at(&self, side: DepthSide, price: f64) -> Decimal {
+        let rows = match side {
+            DepthSide::Bid => &self.bids,
+            DepthSide::Ask => &self.asks,
+        };
+        rows.iter().filter(|row| row.price == price).map(|row| row.size).sum()
+    }
+
+    /// Size imbalance over the top `rows` rows of each side.
+    ///
+    /// Ranges from -1 (only asks) to 1 (only bids); `None` when both
+    /// sides are empty.
+    pub fn imbalance(&self, rows: usize) -> Option<Decimal> {
+        let bid: Decimal = self.bids.iter().take(rows).map(|row| row.size).sum();
+        let ask: Decimal = self.asks.iter().take(rows).map(|row| row.size).sum();
+        let total = bid + ask;
+        (!total.is_zero()).then(|| (bid - ask) / total)
+    }
+
+    /// Remove all rows, e.g. after the depth request was restarted.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_depth_updates() {
+        let mut fields = FieldIterator::new(b"0\x001\x001\x00185.5\x00300\0");
+        let update = DepthUpdate::parse(&mut fields).unwrap();
+        assert_eq!(update.operation, DepthOperation::Update);
+        assert_eq!(update.side, DepthSide::Bid);
+        assert_eq!(update.size, Decimal::from(300));
+
+        let mut fields = FieldIterator::new(b"2\0ARCA\x000\x000\x00185.6\x00100\x001\0");
+        let update = DepthUpdate::parse_l2(&mut fields, 176).unwrap();
+        assert_eq!(update.position, 2);
+        assert_eq!(update.market_maker, "ARCA");
+        assert_eq!(update.operation, DepthOperation::Insert);
+        assert_eq!(update.side, DepthSide::Ask);
+        assert!(update.is_smart_depth);
+    }
+
+    #[test]
+    fn test_parse_depth_exchange() {
+        let mut fields = FieldIterator::new(b"ISLAND\0STK\0NASDAQ\0Deep2\x002147483647\0");
+        let exchange = DepthExchange::parse(&mut fields, 176).unwrap();
+        assert_eq!(exchange.exchange, "ISLAND");
+        assert_eq!(exchange.service_data_type, "Deep2");
+        assert_eq!(exchange.agg_group, None);
+    }
+
+    #[test]
+    fn test_order_book_updates() {
+        let update = |position, operation, side, price, size: i64| DepthUpdate {
+            position,
+            market_maker: String::new(),
+            operation,
+            side,
+            price,
+            size: Decimal::from(size),
+            is_smart_depth: false,
+        };
+        let mut book = OrderBook::new();
+        book.apply(&update(0, DepthOperation::Insert, DepthSide::Bid, 100.0, 300));
+        book.apply(&update(0, DepthOperation::Insert, DepthSide::Bid, 100.1, 100));
+        book.apply(&update(0, DepthOperation::Insert, DepthSide::Ask, 100.2, 200));
+        assert_eq!(book.best_bid().unwrap().price, 100.1);
+        assert_eq!(book.bids()[1].price, 100.0);
+        assert_eq!(book.spread().map(|spread| (spread * 10.0).round()), Some(1.0));
+        assert_eq!(book.imbalance(1), Some(Decimal::new(-1, 0) / Decimal::from(3)));
+
+        book.apply(&update(1, DepthOperation::Update, DepthSide::Bid, 100.0, 500));
+        assert_eq!(book.size_at(DepthSide::Bid, 100.0), Decimal::from(500));
+        book.apply(&update(0, DepthOperation::Delete, DepthSide::Bid, 0.0, 0));
+        assert_eq!(book.best_bid().unwrap().price, 100.0);
+        assert_eq!(book.imbalance(5), Some(Decimal::from(3) / Decimal::from(7)));
+    }
+}
@@ -0,0 +1,81 @@
+//! Market rules: the minimum price increments of a venue.
+//!
+//! Contract details list one market rule ID per valid exchange
+//! (`ContractDetails::market_rule_ids`); `reqMarketRule` returns the rule's
+//! price increment table. Limit prices off the increment are rejected, so
+//! generated prices should go through [`round_to_tick`].
+
+use crate::wire::FieldIterator;
+
+/// The price increment that applies from `low_edge` upwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceIncrement {
+    pub low_edge: f64,
+    pub increment: f64,
+}
+
+/// A market rule's price increments, in ascending order of `low_edge`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketRule {
+    pub id: i32,
+    pub increments: Vec<PriceIncrement>,
+}
+
+impl MarketRule {
+    /// Parse a MARKET_RULE message (after the message ID).
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let id = fields.next_i32();
+        let count = fields.next_i32().max(0) as usize;
+        let mut increments = Vec::with_capacity(count);
+        for _ in 0..count {
+            increments.push(PriceIncrement {
+                low_edge: fields.next_parsed()?,
+                increment: fields.next_parsed()?,
+            });
+        }
+        Some(Self { id, increments })
+    }
+
+    /// The price increment for prices around `price`.
+    pub fn increment_at(&self, price: f64) -> Option<f64> {
+        self.increments
+            .iter()
+            .take_while(|row| row.low_edge <= price.abs())
+            .last()
+            .or(self.increments.first())
+            .map(|row| row.increment)
+    }
+}
+
+/// Round `price` to the nearest valid tick under `rule`.
+///
+/// Prices are returned unchanged if the rule has no increments.
+pub fn round_to_tick(price: f64, rule: &MarketRule) -> f64 {
+    let Some(increment) = rule.increment_at(price).filter(|increment| *increment > 0.0) else {
+        return price;
+    };
+    let rounded = (price / increment).round() * increment;
+    // Trim float noise (e.g. 0.30000000000000004) to the increment's decimals
+    let decimals = increment.to_string().split_once('.').map_or(0, |(_, d)| d.len());
+    let scale = 10f64.powi(decimals as i32);
+    (rounded * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_round() {
+        let buf = "26\x002\x000\x000.01\x001\x000.05\0";
+        let rule = MarketRule::parse(&mut FieldIterator::new(buf.as_bytes())).unwrap();
+        assert_eq!(rule.id, 26);
+        assert_eq!(rule.increments[1], PriceIncrement { low_edge: 1.0, increment: 0.05 });
+
+        assert_eq!(rule.increment_at(0.5), Some(0.01));
+        assert_eq!(round_to_tick(0.123, &rule), 0.12);
+        assert_eq!(round_to_tick(10.03, &rule), 10.05);
+        assert_eq!(round_to_tick(10.42, &rule), 10.4);
+        assert_eq!(round_to_tick(0.3, &rule), 0.3);
+    }
+}
@@ -6,14 +6,74 @@
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum OutgoingMessageId {
+    /// Request streaming or snapshot market data
+    ReqMktData = 1,
+    /// Cancel market data
+    CancelMktData = 2,
+    /// Place or modify an order
+    PlaceOrder = 3,
+    /// Request open orders placed by this client
+    ReqOpenOrders = 5,
     /// Request account data subscription
     ReqAccountData = 6,
+    /// Request executions matching a filter
+    ReqExecutions = 7,
+    /// Request the next valid order ID
+    ReqIds = 8,
+    /// Request contract details
+    ReqContractData = 9,
+    /// Request market depth
+    ReqMktDepth = 10,
+    /// Cancel market depth
+    CancelMktDepth = 11,
+    /// Bind orders entered in TWS to this client (client ID 0 only)
+    ReqAutoOpenOrders = 15,
+    /// Request open orders from all clients and TWS
+    ReqAllOpenOrders = 16,
+    /// Request financial advisor configuration
+    ReqFa = 18,
     /// Request historical bar data
     ReqHistoricalData = 20,
+    /// Exercise an option or let it lapse
+    ExerciseOptions = 21,
+    /// Request market scanner subscription
+    ReqScannerSubscription = 22,
+    /// Cancel market scanner subscription
+    CancelScannerSubscription = 23,
+    /// Request market scanner parameters (XML)
+    ReqScannerParameters = 24,
     /// Cancel historical data request
     CancelHistoricalData = 25,
+    /// Request current server time
+    ReqCurrentTime = 49,
+    /// Select real-time, frozen or delayed market data
+    ReqMarketDataType = 59,
+    /// Request position subscription
+    ReqPositions = 61,
+    /// Request account summary subscription
+    ReqAccountSummary = 62,
+    /// Cancel account summary subscription
+    CancelAccountSummary = 63,
+    /// Cancel position subscription
+    CancelPositions = 64,
     /// Start API connection
     StartApi = 71,
+    /// Request the expiries and strikes of an underlying's options
+    ReqSecDefOptParams = 78,
+    /// Request the exchanges offering market depth
+    ReqMktDepthExchanges = 82,
+    /// Request the exchanges behind a SMART BBO exchange code
+    ReqSmartComponents = 83,
+    /// Request the timestamp of the earliest available historical data
+    ReqHeadTimestamp = 87,
+    /// Request a market rule's price increments
+    ReqMarketRule = 91,
+    /// Request daily P&L for a single position
+    ReqPnlSingle = 94,
+    /// Cancel single position P&L subscription
+    CancelPnlSingle = 95,
+    /// Request historical ticks
+    ReqHistoricalTicks = 96,
 }
 
 impl OutgoingMessageId {
@@ -32,22 +92,100 @@ impl std::fmt::Display for OutgoingMessageId {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum IncomingMessageId {
+    /// Market data price tick
+    TickPrice = 1,
+    /// Market data size tick
+    TickSize = 2,
+    /// Order status update
+    OrderStatus = 3,
     /// Error message
     Error = 4,
+    /// Open order
+    OpenOrder = 5,
     /// Account value update
     AccountValue = 6,
     /// Portfolio value update
     PortfolioValue = 7,
-    /// Account download end marker
-    AccountDownloadEnd = 8,
+    /// Account update time
+    AccountUpdateTime = 8,
     /// Next valid order ID
     NextValidId = 9,
+    /// Contract details
+    ContractData = 10,
+    /// Execution details
+    ExecutionData = 11,
+    /// Market depth update
+    MarketDepth = 12,
+    /// Market depth update with the quoting market maker
+    MarketDepthL2 = 13,
+    /// News bulletin
+    NewsBulletins = 14,
     /// Managed accounts list
     ManagedAccounts = 15,
+    /// Financial advisor configuration (XML)
+    ReceiveFa = 16,
     /// Historical bar data
     HistoricalData = 17,
+    /// Bond contract details
+    BondContractData = 18,
+    /// Market scanner parameters (XML)
+    ScannerParameters = 19,
+    /// Market scanner results
+    ScannerData = 20,
+    /// Market data generic numeric tick
+    TickGeneric = 45,
+    /// Market data string tick
+    TickString = 46,
+    /// Current server time
+    CurrentTime = 49,
+    /// Contract details end marker
+    ContractDataEnd = 52,
+    /// Open orders end marker
+    OpenOrderEnd = 53,
+    /// Account download end marker
+    AccountDownloadEnd = 54,
+    /// Execution details end marker
+    ExecutionDataEnd = 55,
+    /// Market data snapshot end marker
+    TickSnapshotEnd = 57,
+    /// Market data type in effect for a request
+    MarketDataType = 58,
+    /// Commission report for an execution
+    CommissionReport = 59,
+    /// Position data
+    PositionData = 61,
+    /// Position end marker
+    PositionEnd = 62,
+    /// Account summary value
+    AccountSummary = 63,
+    /// Account summary end marker
+    AccountSummaryEnd = 64,
+    /// Expiries and strikes of an underlying's options on one exchange
+    SecurityDefinitionOptionParameter = 75,
+    /// End of option parameters
+    SecurityDefinitionOptionParameterEnd = 76,
+    /// Exchanges offering market depth
+    MktDepthExchanges = 80,
+    /// Market data request parameters (min tick, BBO exchange)
+    TickReqParams = 81,
+    /// Exchanges behind a SMART BBO exchange code
+    SmartComponents = 82,
+    /// Earliest available historical data timestamp
+    HeadTimestamp = 88,
     /// Historical data update (for keepUpToDate)
     HistoricalDataUpdate = 90,
+    /// Market rule price increments
+    MarketRule = 93,
+    /// Daily P&L for a single position
+    PnlSingle = 95,
+    /// Historical midpoint ticks
+    HistoricalTicks = 96,
+    /// Historical bid/ask ticks
+    HistoricalTicksBidAsk = 97,
+    /// Historical trade ticks
+    HistoricalTicksLast = 98,
+    /// Manual TWS order bound to an API order ID
+    OrderBound = 100,
     /// Historical data end marker
     HistoricalDataEnd = 108,
 }
@@ -55,14 +193,53 @@ pub enum IncomingMessageId {
 impl IncomingMessageId {
     pub fn from_u32(value: u32) -> Option<Self> {
         match value {
+            1 => Some(Self::TickPrice),
+            2 => Some(Self::TickSize),
+            3 => Some(Self::OrderStatus),
             4 => Some(Self::Error),
+            5 => Some(Self::OpenOrder),
             6 => Some(Self::AccountValue),
             7 => Some(Self::PortfolioValue),
-            8 => Some(Self::AccountDownloadEnd),
+            8 => Some(Self::AccountUpdateTime),
             9 => Some(Self::NextValidId),
+            10 => Some(Self::ContractData),
+            11 => Some(Self::ExecutionData),
+            12 => Some(Self::MarketDepth),
+            13 => Some(Self::MarketDepthL2),
+            14 => Some(Self::NewsBulletins),
             15 => Some(Self::ManagedAccounts),
+            16 => Some(Self::ReceiveFa),
             17 => Some(Self::HistoricalData),
+            18 => Some(Self::BondContractData),
+            19 => Some(Self::ScannerParameters),
+            20 => Some(Self::ScannerData),
+            45 => Some(Self::TickGeneric),
+            46 => Some(Self::TickString),
+            49 => Some(Self::CurrentTime),
+            52 => Some(Self::ContractDataEnd),
+            53 => Some(Self::OpenOrderEnd),
+            54 => Some(Self::AccountDownloadEnd),
+            55 => Some(Self::ExecutionDataEnd),
+            57 => Some(Self::TickSnapshotEnd),
+            58 => Some(Self::MarketDataType),
+            59 => Some(Self::CommissionReport),
+            61 => Some(Self::PositionData),
+            62 => Some(Self::PositionEnd),
+            63 => Some(Self::AccountSummary),
+            64 => Some(Self::AccountSummaryEnd),
+            75 => Some(Self::SecurityDefinitionOptionParameter),
+            76 => Some(Self::SecurityDefinitionOptionParameterEnd),
+            80 => Some(Self::MktDepthExchanges),
+            81 => Some(Self::TickReqParams),
+            82 => Some(Self::SmartComponents),
+            88 => Some(Self::HeadTimestamp),
             90 => Some(Self::HistoricalDataUpdate),
+            93 => Some(Self::MarketRule),
+            95 => Some(Self::PnlSingle),
+            96 => Some(Self::HistoricalTicks),
+            97 => Some(Self::HistoricalTicksBidAsk),
+            98 => Some(Self::HistoricalTicksLast),
+            100 => Some(Self::OrderBound),
             108 => Some(Self::HistoricalDataEnd),
             _ => None,
         }
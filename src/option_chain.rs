@@ -0,0 +1,76 @@
+//! Option chain parameters, from `reqSecDefOptParams`.
+//!
+//! TWS answers with one [`OptionParams`] per exchange and trading class an
+//! underlying's options trade under, listing every expiry and strike in
+//! use. Not every strike exists for every expiry; `Client::option_chain`
+//! resolves the actual contracts through contract details.
+
+use crate::contract::Contract;
+use crate::market_data::Quote;
+use crate::wire::FieldIterator;
+
+/// Expiries and strikes of an underlying's options on one exchange.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptionParams {
+    /// Exchange (e.g. "SMART", "CBOE")
+    pub exchange: String,
+    pub underlying_con_id: i32,
+    /// Trading class (e.g. "SPX" or "SPXW")
+    pub trading_class: String,
+    pub multiplier: String,
+    /// Expiries (YYYYMMDD)
+    pub expirations: Vec<String>,
+    pub strikes: Vec<f64>,
+}
+
+impl OptionParams {
+    /// Parse a SECURITY_DEFINITION_OPTION_PARAMETER message, after the
+    /// message ID. Returns the request ID with the parameters.
+    pub fn parse(fields: &mut FieldIterator) -> Option<(i32, Self)> {
+        let req_id = fields.next_i32();
+        let mut params = Self {
+            exchange: fields.next_string()?.to_string(),
+            underlying_con_id: fields.next_i32(),
+            trading_class: fields.next_string()?.to_string(),
+            multiplier: fields.next_string()?.to_string(),
+            ..Default::default()
+        };
+        for _ in 0..fields.next_i32().max(0) {
+            params.expirations.push(fields.next_string()?.to_string());
+        }
+        for _ in 0..fields.next_i32().max(0) {
+            params.strikes.push(fields.next_parsed()?);
+        }
+        Some((req_id, params))
+    }
+}
+
+/// An option in a chain with a snapshot quote.
+#[derive(Debug, Clone)]
+pub struct OptionChainRow {
+    /// The fully specified option contract
+    pub contract: Contract,
+    /// Snapshot quote, or `None` if it couldn't be fetched
+    pub quote: Option<Quote>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_option_params() {
+        let raw = ["1000", "SMART", "265598", "AAPL", "100", "2", "20240119", "20240216"];
+        let raw = [&raw[..], &["3", "185", "190", "195"]].concat();
+        let buf = raw.join("\0") + "\0";
+
+        let mut fields = FieldIterator::new(buf.as_bytes());
+        let (req_id, params) = OptionParams::parse(&mut fields).unwrap();
+        assert_eq!(req_id, 1000);
+        assert_eq!(params.exchange, "SMART");
+        assert_eq!(params.underlying_con_id, 265598);
+        assert_eq!(params.expirations, ["20240119", "20240216"]);
+        assert_eq!(params.strikes, [185.0, 190.0, 195.0]);
+        assert!(fields.next_string().is_none());
+    }
+}
@@ -0,0 +1,2073 @@
+//! Orders and order placement.
+
+use rust_decimal::Decimal;
+
+use crate::condition::OrderCondition;
+use crate::contract::{Contract, DeltaNeutralContract, SecurityType};
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+use crate::message::OutgoingMessageId;
+use crate::server_versions;
+use crate::wire::{make_field, make_optional_field, FieldIterator, TagValue};
+
+/// Side of an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderAction {
+    #[default]
+    Buy,
+    Sell,
+    /// Short sale, for institutional accounts that must flag them
+    SShort,
+}
+
+impl OrderAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buy => "BUY",
+            Self::Sell => "SELL",
+            Self::SShort => "SSHORT",
+        }
+    }
+}
+
+impl std::str::FromStr for OrderAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "BUY" => Ok(Self::Buy),
+            "SELL" => Ok(Self::Sell),
+            "SSHORT" => Ok(Self::SShort),
+            _ => Err(Error::Protocol(format!("Unknown order action: {s}"))),
+        }
+    }
+}
+
+impl std::fmt::Display for OrderAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// `Order::compete_against_best_offset` that competes up to the midpoint.
+pub const COMPETE_AGAINST_BEST_OFFSET_UP_TO_MID: f64 = f64::INFINITY;
+
+/// Order type, as sent in `placeOrder`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OrderType {
+    #[default]
+    Market,
+    Limit,
+    Stop,
+    StopLimit,
+    /// Stop that follows the price by `aux_price` or `trailing_percent`
+    TrailingStop,
+    /// Trailing stop that sends a limit order when triggered
+    TrailingStopLimit,
+    MarketIfTouched,
+    LimitIfTouched,
+    MarketOnClose,
+    LimitOnClose,
+    /// Pegged to primary
+    Relative,
+    PeggedToMidpoint,
+    PeggedToMarket,
+    PeggedToBenchmark,
+    /// Pegged to the best competing price (IBKRATS)
+    PeggedToBest,
+    Volatility,
+    MarketToLimit,
+    /// Any other type code TWS accepts
+    Other(String),
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Market => "MKT",
+            Self::Limit => "LMT",
+            Self::Stop => "STP",
+            Self::StopLimit => "STP LMT",
+            Self::TrailingStop => "TRAIL",
+            Self::TrailingStopLimit => "TRAIL LIMIT",
+            Self::MarketIfTouched => "MIT",
+            Self::LimitIfTouched => "LIT",
+            Self::MarketOnClose => "MOC",
+            Self::LimitOnClose => "LOC",
+            Self::Relative => "REL",
+            Self::PeggedToMidpoint => "PEG MID",
+            Self::PeggedToMarket => "PEG MKT",
+            Self::PeggedToBenchmark => "PEG BENCH",
+            Self::PeggedToBest => "PEG BEST",
+            Self::Volatility => "VOL",
+            Self::MarketToLimit => "MTL",
+            Self::Other(code) => code,
+        }
+    }
+}
+
+impl std::str::FromStr for OrderType {
+    type Err = Error;
+
+    /// Never fails: unknown codes become [`OrderType::Other`].
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "MKT" => Self::Market,
+            "LMT" => Self::Limit,
+            "STP" => Self::Stop,
+            "STP LMT" => Self::StopLimit,
+            "TRAIL" => Self::TrailingStop,
+            "TRAIL LIMIT" => Self::TrailingStopLimit,
+            "MIT" => Self::MarketIfTouched,
+            "LIT" => Self::LimitIfTouched,
+            "MOC" => Self::MarketOnClose,
+            "LOC" => Self::LimitOnClose,
+            "REL" => Self::Relative,
+            "PEG MID" => Self::PeggedToMidpoint,
+            "PEG MKT" => Self::PeggedToMarket,
+            "PEG BENCH" => Self::PeggedToBenchmark,
+            "PEG BEST" => Self::PeggedToBest,
+            "VOL" => Self::Volatility,
+            "MTL" => Self::MarketToLimit,
+            other => Self::Other(other.to_string()),
+        })
+    }
+}
+
+impl std::fmt::Display for OrderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// How long an order stays working.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeInForce {
+    /// Until the end of the trading day
+    #[default]
+    Day,
+    /// Good till canceled
+    Gtc,
+    /// Immediate or cancel: fill what's possible, cancel the rest
+    Ioc,
+    /// Fill or kill: fill completely at once or cancel
+    Fok,
+    /// Good till the order's `good_till_date`
+    Gtd,
+    /// At the opening auction (market-on-open / limit-on-open)
+    Opg,
+}
+
+impl TimeInForce {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "DAY",
+            Self::Gtc => "GTC",
+            Self::Ioc => "IOC",
+            Self::Fok => "FOK",
+            Self::Gtd => "GTD",
+            Self::Opg => "OPG",
+        }
+    }
+}
+
+impl std::str::FromStr for TimeInForce {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "DAY" => Ok(Self::Day),
+            "GTC" => Ok(Self::Gtc),
+            "IOC" => Ok(Self::Ioc),
+            "FOK" => Ok(Self::Fok),
+            "GTD" => Ok(Self::Gtd),
+            "OPG" => Ok(Self::Opg),
+            _ => Err(Error::Protocol(format!("Unknown time in force: {s}"))),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What happens to the rest of a one-cancels-all group when one order fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcaType {
+    /// Cancel the other orders; overfills are prevented by blocking
+    CancelWithBlock = 1,
+    /// Reduce the other orders' size by the filled amount, with blocking
+    ReduceWithBlock = 2,
+    /// Reduce the other orders' size without blocking (overfills possible)
+    ReduceNonBlock = 3,
+}
+
+impl OcaType {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(Self::CancelWithBlock),
+            2 => Some(Self::ReduceWithBlock),
+            3 => Some(Self::ReduceNonBlock),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with an option position at exercise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExerciseAction {
+    Exercise = 1,
+    /// Let the option expire unexercised
+    Lapse = 2,
+}
+
+/// How urgently the Adaptive algo works an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdaptivePriority {
+    /// Fills quickly, paying up when needed
+    Urgent,
+    #[default]
+    Normal,
+    /// Waits for better prices, risking no fill
+    Patient,
+}
+
+impl AdaptivePriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Urgent => "Urgent",
+            Self::Normal => "Normal",
+            Self::Patient => "Patient",
+        }
+    }
+}
+
+/// How aggressively the Arrival Price algo trades against the benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RiskAversion {
+    GetDone,
+    Aggressive,
+    #[default]
+    Neutral,
+    Passive,
+}
+
+impl RiskAversion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::GetDone => "Get Done",
+            Self::Aggressive => "Aggressive",
+            Self::Neutral => "Neutral",
+            Self::Passive => "Passive",
+        }
+    }
+}
+
+/// Which prices the TWAP algo trades at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TwapStrategyType {
+    #[default]
+    Marketable,
+    MatchingMidpoint,
+    MatchingSameSide,
+    MatchingLast,
+}
+
+impl TwapStrategyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Marketable => "Marketable",
+            Self::MatchingMidpoint => "Matching Midpoint",
+            Self::MatchingSameSide => "Matching Same Side",
+            Self::MatchingLast => "Matching Last",
+        }
+    }
+}
+
+/// An IB algo strategy and its parameters.
+///
+/// The constructors cover the common algos; [`Algo::new`] and
+/// [`Algo::param`] reach any other strategy. Times are "hh:mm:ss" with a
+/// time zone, e.g. "09:45:00 US/Eastern".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Algo {
+    /// Strategy name as TWS knows it (e.g. "Adaptive", "Vwap")
+    pub strategy: String,
+    pub params: Vec<TagValue>,
+}
+
+impl Algo {
+    /// Create an algo with no parameters.
+    pub fn new(strategy: &str) -> Self {
+        Self {
+            strategy: strategy.to_string(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Adaptive: works the order between the bid and ask.
+    pub fn adaptive(priority: AdaptivePriority) -> Self {
+        Self::new("Adaptive").param("adaptivePriority", priority.as_str())
+    }
+
+    /// VWAP: trades towards the volume-weighted average price, taking at
+    /// most `max_pct_vol` (0.01 to 0.5) of the volume.
+    pub fn vwap(max_pct_vol: f64) -> Self {
+        Self::new("Vwap").param("maxPctVol", max_pct_vol)
+    }
+
+    /// TWAP: spreads the order evenly over the time window.
+    pub fn twap(strategy_type: TwapStrategyType) -> Self {
+        Self::new("Twap").param("strategyType", strategy_type.as_str())
+    }
+
+    /// Arrival Price: trades towards the price when the order was
+    /// submitted, taking at most `max_pct_vol` (0.1 to 0.5) of the volume.
+    pub fn arrival_price(max_pct_vol: f64, risk_aversion: RiskAversion) -> Self {
+        Self::new("ArrivalPx")
+            .param("maxPctVol", max_pct_vol)
+            .param("riskAversion", risk_aversion.as_str())
+    }
+
+    /// Set a parameter, replacing any earlier value for `tag`.
+    pub fn param(mut self, tag: &str, value: impl std::fmt::Display) -> Self {
+        self.params.retain(|param| param.tag != tag);
+        self.params.push(TagValue::new(tag, value));
+        self
+    }
+
+    /// Start working the order at `time`.
+    pub fn start_time(self, time: &str) -> Self {
+        self.param("startTime", time)
+    }
+
+    /// Stop working the order at `time`.
+    pub fn end_time(self, time: &str) -> Self {
+        self.param("endTime", time)
+    }
+
+    /// Keep working the order after the end time if it isn't done.
+    pub fn allow_past_end_time(self, allow: bool) -> Self {
+        self.param("allowPastEndTime", if allow { 1 } else { 0 })
+    }
+
+    /// Never take liquidity (VWAP).
+    pub fn no_take_liq(self, no_take_liq: bool) -> Self {
+        self.param("noTakeLiq", if no_take_liq { 1 } else { 0 })
+    }
+
+    /// Try to finish by the end time regardless of the volume cap
+    /// (Arrival Price).
+    pub fn force_completion(self, force: bool) -> Self {
+        self.param("forceCompletion", if force { 1 } else { 0 })
+    }
+}
+
+/// Period a VOL order's volatility is quoted over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolatilityType {
+    Daily = 1,
+    Annual = 2,
+}
+
+impl VolatilityType {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(Self::Daily),
+            2 => Some(Self::Annual),
+            _ => None,
+        }
+    }
+}
+
+/// Underlying price a VOL order's option price is computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferencePriceType {
+    /// Average of the national best bid and offer
+    Average = 1,
+    /// NBB for buy orders, NBO for sell orders
+    BidOrAsk = 2,
+}
+
+impl ReferencePriceType {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            1 => Some(Self::Average),
+            2 => Some(Self::BidOrAsk),
+            _ => None,
+        }
+    }
+}
+
+/// Order TWS sends in the underlying to delta-hedge a filled VOL order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeltaNeutralOrder {
+    /// Hedge order type, e.g. "MKT" or "LMT"
+    pub order_type: String,
+    pub aux_price: Option<f64>,
+    /// Contract ID of the underlying; 0 lets TWS pick it
+    pub con_id: i32,
+    pub settling_firm: String,
+    pub clearing_account: String,
+    pub clearing_intent: String,
+    /// "O" to open, "C" to close (institutional accounts)
+    pub open_close: String,
+    pub short_sale: bool,
+    pub short_sale_slot: i32,
+    pub designated_location: String,
+}
+
+impl DeltaNeutralOrder {
+    /// Create a hedge order of `order_type`.
+    pub fn new(order_type: &str) -> Self {
+        Self {
+            order_type: order_type.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Settings of a pegged-to-benchmark (PEG BENCH) order.
+///
+/// The order's price starts at `starting_price` and moves by
+/// `pegged_change_amount` for every `reference_change_amount` the reference
+/// contract moves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeggedBenchmark {
+    /// Contract ID of the benchmark
+    pub reference_con_id: i32,
+    /// Exchange the benchmark price is taken from
+    pub reference_exchange: String,
+    pub starting_price: f64,
+    pub pegged_change_amount: f64,
+    /// Move the order's price down (rather than up) as the benchmark rises
+    pub decrease: bool,
+    pub reference_change_amount: f64,
+    /// Benchmark price range (lower, upper); the order is canceled if the
+    /// benchmark trades outside it
+    pub reference_range: Option<(f64, f64)>,
+}
+
+/// An order to send with `placeOrder`.
+///
+/// Only the commonly used attributes are modelled; everything else is
+/// sent with TWS's defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub action: OrderAction,
+    /// Number of shares or contracts; fractional where IB allows it.
+    /// Zero for cash quantity orders
+    pub total_quantity: Decimal,
+    pub order_type: OrderType,
+    /// Limit price, for limit-style orders
+    pub lmt_price: Option<f64>,
+    /// Stop or trigger price, for stop-style orders; the trailing amount
+    /// for trailing stops
+    pub aux_price: Option<f64>,
+    /// Trailing amount in percent, for trailing stops (instead of `aux_price`)
+    pub trailing_percent: Option<f64>,
+    /// Initial stop price of a trailing stop; `None` lets TWS compute it
+    pub trail_stop_price: Option<f64>,
+    /// Amount of currency to trade instead of a quantity
+    pub cash_qty: Option<f64>,
+    /// Time in force; `None` uses the TWS default
+    pub tif: Option<TimeInForce>,
+    /// Expiry for GTD orders; without a timezone TWS reads it in the
+    /// login timezone
+    pub good_till_date: Option<DateTime>,
+    /// Hold the order in TWS until this time
+    pub good_after_time: Option<DateTime>,
+    /// Allow the order to trigger or fill outside regular trading hours
+    pub outside_rth: bool,
+    /// One-cancels-all group name; orders sharing it cancel or reduce
+    /// each other as they fill
+    pub oca_group: String,
+    /// How the group reacts to a fill; `None` uses the TWS default
+    pub oca_type: Option<OcaType>,
+    /// Account to place the order in (required for multi-account logins)
+    pub account: String,
+    /// Free-form reference, echoed back on executions
+    pub order_ref: String,
+    /// Send the order to the market; `false` only stages it in TWS
+    pub transmit: bool,
+    /// Order ID of the parent, for child orders of a bracket; 0 for none
+    pub parent_id: i32,
+    /// Cancel the parent when this child order is cancelled, including
+    /// when TWS cancels it (server version 162+, ignored before)
+    pub auto_cancel_parent: bool,
+    /// Quantity shown on the book (iceberg order); `None` shows it all
+    pub display_size: Option<i32>,
+    /// Keep the order off the book entirely (NASDAQ-routed orders only)
+    pub hidden: bool,
+    /// Only compute margin and commission; TWS doesn't place the order
+    pub what_if: bool,
+    /// IB algo to route the order through
+    pub algo: Option<Algo>,
+    /// Benchmark settings, for PEG BENCH orders
+    pub pegged_benchmark: Option<PeggedBenchmark>,
+    /// Minimum quantity per fill, for IBKRATS orders
+    pub min_trade_qty: Option<i32>,
+    /// Smallest competing order to peg to, for PEG BEST orders
+    pub min_compete_size: Option<i32>,
+    /// Offset from the best competing price, for PEG BEST orders
+    pub compete_against_best_offset: Option<f64>,
+    /// Offset from the midpoint when the spread is a whole number of
+    /// ticks, for PEG MID orders (and PEG BEST up to the midpoint)
+    pub mid_offset_at_whole: Option<f64>,
+    /// Offset from the midpoint when the spread is a half tick
+    pub mid_offset_at_half: Option<f64>,
+    /// Target implied volatility in percent, for VOL orders
+    pub volatility: Option<f64>,
+    pub volatility_type: Option<VolatilityType>,
+    /// Recompute a VOL order's price as the underlying moves
+    pub continuous_update: bool,
+    pub reference_price_type: Option<ReferencePriceType>,
+    /// Hedge sent in the underlying when a VOL order fills
+    pub delta_neutral: Option<DeltaNeutralOrder>,
+    /// Conditions that submit the order (or cancel it, see
+    /// `conditions_cancel_order`) once met
+    pub conditions: Vec<OrderCondition>,
+    /// Also evaluate conditions outside regular trading hours
+    pub conditions_ignore_rth: bool,
+    /// Cancel the order when the conditions are met instead of submitting it
+    pub conditions_cancel_order: bool,
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Self {
+            action: OrderAction::Buy,
+            total_quantity: Decimal::ZERO,
+            order_type: OrderType::Market,
+            lmt_price: None,
+            aux_price: None,
+            trailing_percent: None,
+            trail_stop_price: None,
+            cash_qty: None,
+            tif: None,
+            good_till_date: None,
+            good_after_time: None,
+            outside_rth: false,
+            oca_group: String::new(),
+            oca_type: None,
+            account: String::new(),
+            order_ref: String::new(),
+            transmit: true,
+            parent_id: 0,
+            auto_cancel_parent: false,
+            display_size: None,
+            hidden: false,
+            what_if: false,
+            algo: None,
+            pegged_benchmark: None,
+            min_trade_qty: None,
+            min_compete_size: None,
+            compete_against_best_offset: None,
+            mid_offset_at_whole: None,
+            mid_offset_at_half: None,
+            volatility: None,
+            volatility_type: None,
+            continuous_update: false,
+            reference_price_type: None,
+            delta_neutral: None,
+            conditions: Vec::new(),
+            conditions_ignore_rth: false,
+            conditions_cancel_order: false,
+        }
+    }
+}
+
+impl Order {
+    /// Create an order of any type.
+    pub fn new(action: OrderAction, quantity: Decimal, order_type: OrderType) -> Self {
+        Self {
+            action,
+            total_quantity: quantity,
+            order_type,
+            ..Default::default()
+        }
+    }
+
+    /// Create a market order.
+    pub fn market(action: OrderAction, quantity: Decimal) -> Self {
+        Self::new(action, quantity, OrderType::Market)
+    }
+
+    /// Create a limit order.
+    pub fn limit(action: OrderAction, quantity: Decimal, price: f64) -> Self {
+        Self {
+            lmt_price: Some(price),
+            ..Self::new(action, quantity, OrderType::Limit)
+        }
+    }
+
+    /// Create a market order for an amount of currency rather than a
+    /// quantity, e.g. buy $500 worth of a stock.
+    pub fn cash(action: OrderAction, amount: f64) -> Self {
+        Self {
+            cash_qty: Some(amount),
+            ..Self::new(action, Decimal::ZERO, OrderType::Market)
+        }
+    }
+
+    /// Create a stop order, sent as a market order once the price
+    /// touches `stop`.
+    pub fn stop(action: OrderAction, quantity: Decimal, stop: f64) -> Self {
+        Self {
+            aux_price: Some(stop),
+            ..Self::new(action, quantity, OrderType::Stop)
+        }
+    }
+
+    /// Create a trailing stop (TRAIL) order whose stop follows the price
+    /// at a distance of `trailing_percent`.
+    pub fn trailing_stop(action: OrderAction, quantity: Decimal, trailing_percent: f64) -> Self {
+        Self {
+            trailing_percent: Some(trailing_percent),
+            ..Self::new(action, quantity, OrderType::TrailingStop)
+        }
+    }
+
+    /// Create a market-if-touched (MIT) order, sent as a market order once
+    /// the price touches `trigger`.
+    pub fn market_if_touched(action: OrderAction, quantity: Decimal, trigger: f64) -> Self {
+        Self {
+            aux_price: Some(trigger),
+            ..Self::new(action, quantity, OrderType::MarketIfTouched)
+        }
+    }
+
+    /// Create a limit-if-touched (LIT) order, sent as a limit order at
+    /// `price` once the price touches `trigger`.
+    pub fn limit_if_touched(
+        action: OrderAction,
+        quantity: Decimal,
+        trigger: f64,
+        price: f64,
+    ) -> Self {
+        Self {
+            lmt_price: Some(price),
+            aux_price: Some(trigger),
+            ..Self::new(action, quantity, OrderType::LimitIfTouched)
+        }
+    }
+
+    /// Create a market-on-close (MOC) order.
+    pub fn market_on_close(action: OrderAction, quantity: Decimal) -> Self {
+        Self::new(action, quantity, OrderType::MarketOnClose)
+    }
+
+    /// Create a limit-on-close (LOC) order.
+    pub fn limit_on_close(action: OrderAction, quantity: Decimal, price: f64) -> Self {
+        Self {
+            lmt_price: Some(price),
+            ..Self::new(action, quantity, OrderType::LimitOnClose)
+        }
+    }
+
+    /// Create a volatility (VOL) order for an option, priced from
+    /// `volatility` (in percent) and the underlying's price.
+    pub fn volatility(
+        action: OrderAction,
+        quantity: Decimal,
+        volatility: f64,
+        volatility_type: VolatilityType,
+    ) -> Self {
+        Self {
+            volatility: Some(volatility),
+            volatility_type: Some(volatility_type),
+            reference_price_type: Some(ReferencePriceType::Average),
+            ..Self::new(action, quantity, OrderType::Volatility)
+        }
+    }
+
+    /// Delta-hedge the order in the underlying once it fills.
+    pub fn delta_neutral(mut self, hedge: DeltaNeutralOrder) -> Self {
+        self.delta_neutral = Some(hedge);
+        self
+    }
+
+    /// Create a pegged-to-primary (REL) order.
+    ///
+    /// Pegs to the bid (buy) or ask (sell) plus an aggressive `offset`;
+    /// `price_cap` limits how far the price may move.
+    pub fn pegged_primary(
+        action: OrderAction,
+        quantity: Decimal,
+        offset: f64,
+        price_cap: Option<f64>,
+    ) -> Self {
+        Self {
+            lmt_price: price_cap,
+            aux_price: Some(offset),
+            ..Self::new(action, quantity, OrderType::Relative)
+        }
+    }
+
+    /// Create a pegged-to-midpoint (PEG MID) order.
+    ///
+    /// Pegs to the midpoint minus (buy) or plus (sell) `offset`.
+    pub fn pegged_mid(
+        action: OrderAction,
+        quantity: Decimal,
+        offset: f64,
+        price_cap: Option<f64>,
+    ) -> Self {
+        Self {
+            lmt_price: price_cap,
+            aux_price: Some(offset),
+            ..Self::new(action, quantity, OrderType::PeggedToMidpoint)
+        }
+    }
+
+    /// Create a pegged-to-best (PEG BEST) order for IBKRATS.
+    ///
+    /// Pegs to the best competing order of at least `min_compete_size`,
+    /// improved by `offset`; [`COMPETE_AGAINST_BEST_OFFSET_UP_TO_MID`]
+    /// competes up to the midpoint (see [`Order::mid_offsets`]).
+    pub fn pegged_best(
+        action: OrderAction,
+        quantity: Decimal,
+        min_compete_size: i32,
+        offset: f64,
+    ) -> Self {
+        Self {
+            min_compete_size: Some(min_compete_size),
+            compete_against_best_offset: Some(offset),
+            ..Self::new(action, quantity, OrderType::PeggedToBest)
+        }
+    }
+
+    /// Set the midpoint offsets used when the spread is a whole number of
+    /// ticks or a half tick wide.
+    pub fn mid_offsets(mut self, at_whole: f64, at_half: f64) -> Self {
+        self.mid_offset_at_whole = Some(at_whole);
+        self.mid_offset_at_half = Some(at_half);
+        self
+    }
+
+    /// Create a pegged-to-benchmark (PEG BENCH) order.
+    pub fn pegged_benchmark(
+        action: OrderAction,
+        quantity: Decimal,
+        benchmark: PeggedBenchmark,
+    ) -> Self {
+        Self {
+            pegged_benchmark: Some(benchmark),
+            ..Self::new(action, quantity, OrderType::PeggedToBenchmark)
+        }
+    }
+
+    /// Set the time in force.
+    pub fn tif(mut self, tif: TimeInForce) -> Self {
+        self.tif = Some(tif);
+        self
+    }
+
+    /// Make the order good till `date`.
+    pub fn good_till(mut self, date: DateTime) -> Self {
+        self.tif = Some(TimeInForce::Gtd);
+        self.good_till_date = Some(date);
+        self
+    }
+
+    /// Hold the order until `time`.
+    pub fn good_after(mut self, time: DateTime) -> Self {
+        self.good_after_time = Some(time);
+        self
+    }
+
+    /// Let the order trigger or fill outside regular trading hours.
+    pub fn outside_rth(mut self) -> Self {
+        self.outside_rth = true;
+        self
+    }
+
+    /// Put the order in a one-cancels-all group.
+    pub fn oca(mut self, group: &str, oca_type: OcaType) -> Self {
+        self.oca_group = group.to_string();
+        self.oca_type = Some(oca_type);
+        self
+    }
+
+    /// Set the account.
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = account.to_string();
+        self
+    }
+
+    /// Route the order through an IB algo.
+    pub fn algo(mut self, algo: Algo) -> Self {
+        self.algo = Some(algo);
+        self
+    }
+
+    /// Show only `size` of the order on the book at a time.
+    pub fn display_size(mut self, size: i32) -> Self {
+        self.display_size = Some(size);
+        self
+    }
+
+    /// Hide the order from the book.
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    /// Add a condition; the order is held in TWS until the conditions are met.
+    pub fn condition(mut self, condition: OrderCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Set the order reference.
+    pub fn order_ref(mut self, order_ref: &str) -> Self {
+        self.order_ref = order_ref.to_string();
+        self
+    }
+
+    /// Start building an order of any type.
+    pub fn builder(action: OrderAction, quantity: Decimal, order_type: OrderType) -> OrderBuilder {
+        OrderBuilder::new(action, quantity, order_type)
+    }
+
+    /// Check the order for mistakes TWS would reject it for.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::InvalidOrder(reason.to_string()));
+
+        match self.cash_qty {
+            Some(amount) if amount <= 0.0 => return invalid("cash quantity must be positive"),
+            Some(_) if !self.total_quantity.is_zero() => {
+                return invalid("order can't have both a quantity and a cash quantity");
+            }
+            Some(_) => {}
+            None if self.total_quantity <= Decimal::ZERO => {
+                return invalid("quantity must be positive");
+            }
+            None => {}
+        }
+        let order_type = &self.order_type;
+        let needs_limit = matches!(
+            order_type,
+            OrderType::Limit
+                | OrderType::StopLimit
+                | OrderType::LimitIfTouched
+                | OrderType::LimitOnClose
+        );
+        if needs_limit && self.lmt_price.is_none() {
+            return invalid(&format!("{order_type} order needs a limit price"));
+        }
+        let needs_trigger = matches!(
+            order_type,
+            OrderType::Stop
+                | OrderType::StopLimit
+                | OrderType::MarketIfTouched
+                | OrderType::LimitIfTouched
+        );
+        if needs_trigger && self.aux_price.is_none() {
+            return invalid(&format!("{order_type} order needs a trigger price"));
+        }
+        let trailing = matches!(order_type, OrderType::TrailingStop | OrderType::TrailingStopLimit);
+        if !trailing && (self.trailing_percent.is_some() || self.trail_stop_price.is_some()) {
+            return invalid(&format!("{order_type} order can't have trailing stop settings"));
+        }
+        if trailing && self.aux_price.is_some() == self.trailing_percent.is_some() {
+            return invalid("trailing stop needs either a trailing amount or a trailing percent");
+        }
+        if self.display_size.is_some_and(|size| size <= 0) {
+            return invalid("display size must be positive");
+        }
+        if self.hidden && self.display_size.is_some() {
+            return invalid("a hidden order can't also have a display size");
+        }
+        let no_aux = matches!(
+            order_type,
+            OrderType::Market
+                | OrderType::Limit
+                | OrderType::MarketOnClose
+                | OrderType::LimitOnClose
+        );
+        if no_aux && self.aux_price.is_some() {
+            return invalid("aux price is only used by stop, touched and pegged order types");
+        }
+        let no_limit = matches!(
+            order_type,
+            OrderType::Market
+                | OrderType::Stop
+                | OrderType::TrailingStop
+                | OrderType::MarketIfTouched
+                | OrderType::MarketOnClose
+        );
+        if no_limit && self.lmt_price.is_some() {
+            return invalid(&format!("{order_type} order can't have a limit price"));
+        }
+        let day_only = self.tif.is_none_or(|tif| tif == TimeInForce::Day);
+        let on_close = matches!(order_type, OrderType::MarketOnClose | OrderType::LimitOnClose);
+        if on_close && !day_only {
+            return invalid("on-close orders are DAY orders");
+        }
+        if (*order_type == OrderType::PeggedToBenchmark) != self.pegged_benchmark.is_some() {
+            return invalid("benchmark settings go with PEG BENCH orders only");
+        }
+        let vol_set = self.volatility.is_some() && self.volatility_type.is_some();
+        if *order_type == OrderType::Volatility && !vol_set {
+            return invalid("VOL order needs a volatility and volatility type");
+        }
+        if self.auto_cancel_parent && self.parent_id == 0 {
+            return invalid("auto cancel parent needs a parent order");
+        }
+        if self.delta_neutral.as_ref().is_some_and(|hedge| hedge.order_type.is_empty()) {
+            return invalid("delta-neutral hedge needs an order type");
+        }
+        if let (Some(after), Some(till)) = (&self.good_after_time, &self.good_till_date)
+            && after.timezone == till.timezone
+            && after >= till
+        {
+            return invalid("good after time must be before the good till date");
+        }
+        match self.tif {
+            Some(TimeInForce::Gtd) if self.good_till_date.is_none() => {
+                invalid("GTD order needs a good till date")
+            }
+            Some(TimeInForce::Opg)
+                if !matches!(order_type, OrderType::Market | OrderType::Limit) =>
+            {
+                invalid("OPG is only valid for MKT and LMT orders")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reject on-close orders (MOC, LOC) placed at or after `cutoff`.
+    ///
+    /// Exchanges stop accepting on-close orders some minutes before the
+    /// close (15:50 ET on NYSE), and TWS would hold a late order for the
+    /// next day's close. `now` and `cutoff` must be in the same timezone.
+    /// Other order types always pass.
+    pub fn validate_close_cutoff(&self, now: &DateTime, cutoff: &DateTime) -> Result<()> {
+        if !matches!(self.order_type, OrderType::MarketOnClose | OrderType::LimitOnClose) {
+            return Ok(());
+        }
+        if now.timezone != cutoff.timezone {
+            return Err(Error::InvalidOrder("close cutoff needs times in one timezone".into()));
+        }
+        if now >= cutoff {
+            return Err(Error::InvalidOrder(format!(
+                "{} order past the close cutoff {cutoff}",
+                self.order_type
+            )));
+        }
+        Ok(())
+    }
+
+    /// Encode a placeOrder message for `contract`.
+    ///
+    /// Assumes server version >= 151 (MIN_SERVER_VER_PRICE_MGMT_ALGO).
+    pub fn encode(&self, order_id: i32, contract: &Contract, server_version: u32) -> String {
+        let mut msg = String::new();
+
+        msg.push_str(&make_field(OutgoingMessageId::PlaceOrder.as_u32()));
+        msg.push_str(&make_field(order_id));
+        msg.push_str(&contract.encode());
+        msg.push_str(&make_field(&contract.sec_id_type));
+        msg.push_str(&make_field(&contract.sec_id));
+
+        // Main order fields
+        msg.push_str(&make_field(self.action));
+        // Trailing zeros are dropped, so 10.50 goes out as "10.5"
+        msg.push_str(&make_field(self.total_quantity.normalize()));
+        msg.push_str(&make_field(&self.order_type));
+        msg.push_str(&make_optional_field(self.lmt_price));
+        msg.push_str(&make_optional_field(self.aux_price));
+
+        // Extended order fields: tif, ocaGroup, account, openClose, origin
+        // (customer), orderRef, transmit, parentId, blockOrder, sweepToFill,
+        // displaySize, triggerMethod, outsideRth, hidden
+        msg.push_str(&make_field(self.tif.map_or("", |tif| tif.as_str())));
+        msg.push_str(&make_field(&self.oca_group));
+        msg.push_str(&make_field(&self.account));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(&self.order_ref));
+        msg.push_str(&make_field(if self.transmit { 1 } else { 0 }));
+        msg.push_str(&make_field(self.parent_id));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(0));
+        // TWS reads a display size of 0 as the full quantity
+        msg.push_str(&make_field(self.display_size.unwrap_or(0)));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(if self.outside_rth { 1 } else { 0 }));
+        msg.push_str(&make_field(if self.hidden { 1 } else { 0 }));
+
+        if contract.sec_type == SecurityType::Bag {
+            msg.push_str(&make_field(contract.combo_legs.len()));
+            for leg in &contract.combo_legs {
+                msg.push_str(&make_field(leg.con_id));
+                msg.push_str(&make_field(leg.ratio));
+                msg.push_str(&make_field(&leg.action));
+                msg.push_str(&make_field(&leg.exchange));
+                // openClose, shortSaleSlot, designatedLocation, exemptCode
+                msg.push_str(&make_field(0));
+                msg.push_str(&make_field(0));
+                msg.push_str(&make_field(""));
+                msg.push_str(&make_field(-1));
+            }
+            // No per-leg prices or smart combo routing params
+            msg.push_str(&make_field(0));
+            msg.push_str(&make_field(0));
+        }
+
+        // Deprecated sharesAllocation, discretionaryAmt, goodAfterTime,
+        // goodTillDate, faGroup, faMethod, faPercentage, faProfile, modelCode
+        let request_string = |time: &Option<DateTime>| {
+            time.as_ref()
+                .map_or_else(String::new, |time| time.to_request_string_for(server_version))
+        };
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(request_string(&self.good_after_time)));
+        msg.push_str(&make_field(request_string(&self.good_till_date)));
+        for _ in 0..5 {
+            msg.push_str(&make_field(""));
+        }
+
+        // shortSaleSlot, designatedLocation, exemptCode, ocaType
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(-1));
+        msg.push_str(&make_field(self.oca_type.map_or(0, |oca_type| oca_type as i32)));
+
+        // rule80A, settlingFirm, allOrNone, minQty, percentOffset,
+        // eTradeOnly, firmQuoteOnly, nbboPriceCap, auctionStrategy,
+        // startingPrice, stockRefPrice, delta, stockRangeLower,
+        // stockRangeUpper, overridePercentageConstraints
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        let benchmark = self.pegged_benchmark.as_ref();
+        let range = benchmark.and_then(|benchmark| benchmark.reference_range);
+        msg.push_str(&make_optional_field(benchmark.map(|benchmark| benchmark.starting_price)));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_optional_field(range.map(|(lower, _)| lower)));
+        msg.push_str(&make_optional_field(range.map(|(_, upper)| upper)));
+        msg.push_str(&make_field(0));
+
+        // Volatility, with the delta-neutral hedge order if there is one
+        msg.push_str(&make_optional_field(self.volatility));
+        msg.push_str(&make_optional_field(self.volatility_type.map(|kind| kind as i32)));
+        match &self.delta_neutral {
+            Some(hedge) => {
+                msg.push_str(&make_field(&hedge.order_type));
+                msg.push_str(&make_optional_field(hedge.aux_price));
+                msg.push_str(&make_field(hedge.con_id));
+                msg.push_str(&make_field(&hedge.settling_firm));
+                msg.push_str(&make_field(&hedge.clearing_account));
+                msg.push_str(&make_field(&hedge.clearing_intent));
+                msg.push_str(&make_field(&hedge.open_close));
+                msg.push_str(&make_field(if hedge.short_sale { 1 } else { 0 }));
+                msg.push_str(&make_field(hedge.short_sale_slot));
+                msg.push_str(&make_field(&hedge.designated_location));
+            }
+            None => {
+                msg.push_str(&make_field(""));
+                msg.push_str(&make_field(""));
+            }
+        }
+        msg.push_str(&make_field(if self.continuous_update { 1 } else { 0 }));
+        msg.push_str(&make_optional_field(self.reference_price_type.map(|kind| kind as i32)));
+
+        msg.push_str(&make_optional_field(self.trail_stop_price));
+        msg.push_str(&make_optional_field(self.trailing_percent));
+
+        // Scale: initLevelSize, subsLevelSize, priceIncrement, then
+        // scaleTable, activeStartTime, activeStopTime
+        for _ in 0..6 {
+            msg.push_str(&make_field(""));
+        }
+
+        // hedgeType, optOutSmartRouting, clearingAccount, clearingIntent,
+        // notHeld, then the delta-neutral contract
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&contract.encode_delta_neutral());
+
+        // algoStrategy and its parameters, algoId
+        match &self.algo {
+            Some(algo) => {
+                msg.push_str(&make_field(&algo.strategy));
+                msg.push_str(&make_field(algo.params.len()));
+                for param in &algo.params {
+                    msg.push_str(&make_field(&param.tag));
+                    msg.push_str(&make_field(&param.value));
+                }
+            }
+            None => msg.push_str(&make_field("")),
+        }
+        msg.push_str(&make_field(""));
+
+        // whatIf, miscOptions, solicited, randomizeSize, randomizePrice
+        msg.push_str(&make_field(if self.what_if { 1 } else { 0 }));
+        msg.push_str(&make_field(""));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(0));
+
+        if let Some(benchmark) = benchmark {
+            msg.push_str(&make_field(benchmark.reference_con_id));
+            msg.push_str(&make_field(if benchmark.decrease { 1 } else { 0 }));
+            msg.push_str(&make_field(benchmark.pegged_change_amount));
+            msg.push_str(&make_field(benchmark.reference_change_amount));
+            msg.push_str(&make_field(&benchmark.reference_exchange));
+        }
+
+        msg.push_str(&make_field(self.conditions.len()));
+        if !self.conditions.is_empty() {
+            for condition in &self.conditions {
+                msg.push_str(&condition.encode());
+            }
+            msg.push_str(&make_field(if self.conditions_ignore_rth { 1 } else { 0 }));
+            msg.push_str(&make_field(if self.conditions_cancel_order { 1 } else { 0 }));
+        }
+
+        // adjustedOrderType, triggerPrice, lmtPriceOffset, adjustedStopPrice,
+        // adjustedStopLimitPrice, adjustedTrailingAmount, adjustableTrailingUnit
+        for _ in 0..6 {
+            msg.push_str(&make_field(""));
+        }
+        msg.push_str(&make_field(0));
+
+        // extOperator, softDollarTier name and value
+        for _ in 0..3 {
+            msg.push_str(&make_field(""));
+        }
+        msg.push_str(&make_optional_field(self.cash_qty));
+        // MiFID II decision maker/algo and execution trader/algo
+        for _ in 0..4 {
+            msg.push_str(&make_field(""));
+        }
+
+        // dontUseAutoPriceForHedge, isOmsContainer,
+        // discretionaryUpToLimitPrice, usePriceMgmtAlgo
+        msg.push_str(&make_field(1));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(0));
+        msg.push_str(&make_field(""));
+
+        if server_version >= server_versions::DURATION {
+            msg.push_str(&make_field(""));
+        }
+        if server_version >= server_versions::POST_TO_ATS {
+            msg.push_str(&make_field(""));
+        }
+        if server_version >= server_versions::AUTO_CANCEL_PARENT {
+            msg.push_str(&make_field(if self.auto_cancel_parent { 1 } else { 0 }));
+        }
+        if server_version >= server_versions::ADVANCED_ORDER_REJECT {
+            msg.push_str(&make_field(""));
+        }
+        if server_version >= server_versions::MANUAL_ORDER_TIME {
+            msg.push_str(&make_field(""));
+        }
+        if server_version >= server_versions::PEGBEST_PEGMID_OFFSETS {
+            if contract.exchange == "IBKRATS" {
+                msg.push_str(&make_optional_field(self.min_trade_qty));
+            }
+            let mut mid_offsets = self.order_type == OrderType::PeggedToMidpoint;
+            if self.order_type == OrderType::PeggedToBest {
+                let offset = self.compete_against_best_offset;
+                msg.push_str(&make_optional_field(self.min_compete_size));
+                // TWS spells the up-to-midpoint offset "Infinity"
+                msg.push_str(&make_optional_field(offset.map(|offset| match offset {
+                    COMPETE_AGAINST_BEST_OFFSET_UP_TO_MID => "Infinity".to_string(),
+                    offset => offset.to_string(),
+                })));
+                mid_offsets = offset == Some(COMPETE_AGAINST_BEST_OFFSET_UP_TO_MID);
+            }
+            if mid_offsets {
+                msg.push_str(&make_optional_field(self.mid_offset_at_whole));
+                msg.push_str(&make_optional_field(self.mid_offset_at_half));
+            }
+        }
+
+        msg
+    }
+}
+
+/// Settings shared by many orders, e.g. one profile for paper trading and
+/// one for live.
+///
+/// Builders started from [`OrderDefaults::builder`] begin with these
+/// values; the builder's setters override them order by order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderDefaults {
+    pub account: Option<String>,
+    pub tif: Option<TimeInForce>,
+    pub outside_rth: Option<bool>,
+    pub transmit: Option<bool>,
+    pub algo: Option<Algo>,
+}
+
+impl OrderDefaults {
+    /// Defaults that leave every field at the TWS default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Place orders in `account`.
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    /// Use `tif` as the time in force.
+    pub fn tif(mut self, tif: TimeInForce) -> Self {
+        self.tif = Some(tif);
+        self
+    }
+
+    /// Allow orders to trigger or fill outside regular trading hours.
+    pub fn outside_rth(mut self, outside_rth: bool) -> Self {
+        self.outside_rth = Some(outside_rth);
+        self
+    }
+
+    /// Send orders to the market, or only stage them in TWS.
+    pub fn transmit(mut self, transmit: bool) -> Self {
+        self.transmit = Some(transmit);
+        self
+    }
+
+    /// Route orders through `algo`.
+    pub fn algo(mut self, algo: Algo) -> Self {
+        self.algo = Some(algo);
+        self
+    }
+
+    /// Start building an order from these defaults.
+    pub fn builder(
+        &self,
+        action: OrderAction,
+        quantity: Decimal,
+        order_type: OrderType,
+    ) -> OrderBuilder {
+        let mut order = Order::new(action, quantity, order_type);
+        if let Some(account) = &self.account {
+            order.account = account.clone();
+        }
+        order.tif = self.tif.or(order.tif);
+        order.outside_rth = self.outside_rth.unwrap_or(order.outside_rth);
+        order.transmit = self.transmit.unwrap_or(order.transmit);
+        order.algo = self.algo.clone().or(order.algo);
+        OrderBuilder { order }
+    }
+}
+
+/// Builds an [`Order`] field by field, validating it in [`OrderBuilder::build`].
+///
+/// Every field not set keeps the TWS default.
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    order: Order,
+}
+
+impl OrderBuilder {
+    /// Create a builder for an order of any type.
+    pub fn new(action: OrderAction, quantity: Decimal, order_type: OrderType) -> Self {
+        Self {
+            order: Order::new(action, quantity, order_type),
+        }
+    }
+
+    /// Set the limit price.
+    pub fn lmt_price(mut self, price: f64) -> Self {
+        self.order.lmt_price = Some(price);
+        self
+    }
+
+    /// Set the stop or trigger price.
+    pub fn aux_price(mut self, price: f64) -> Self {
+        self.order.aux_price = Some(price);
+        self
+    }
+
+    /// Set the time in force.
+    pub fn tif(mut self, tif: TimeInForce) -> Self {
+        self.order.tif = Some(tif);
+        self
+    }
+
+    /// Make the order good till `date`.
+    pub fn good_till(mut self, date: DateTime) -> Self {
+        self.order = self.order.good_till(date);
+        self
+    }
+
+    /// Hold the order until `time`.
+    pub fn good_after(mut self, time: DateTime) -> Self {
+        self.order.good_after_time = Some(time);
+        self
+    }
+
+    /// Set whether the order may trigger or fill outside regular hours.
+    pub fn outside_rth(mut self, outside_rth: bool) -> Self {
+        self.order.outside_rth = outside_rth;
+        self
+    }
+
+    /// Put the order in a one-cancels-all group.
+    pub fn oca(mut self, group: &str, oca_type: OcaType) -> Self {
+        self.order = self.order.oca(group, oca_type);
+        self
+    }
+
+    /// Set the account.
+    pub fn account(mut self, account: &str) -> Self {
+        self.order.account = account.to_string();
+        self
+    }
+
+    /// Set the order reference.
+    pub fn order_ref(mut self, order_ref: &str) -> Self {
+        self.order.order_ref = order_ref.to_string();
+        self
+    }
+
+    /// Set whether the order is sent to the market or only staged in TWS.
+    pub fn transmit(mut self, transmit: bool) -> Self {
+        self.order.transmit = transmit;
+        self
+    }
+
+    /// Attach the order to a parent order.
+    pub fn parent_id(mut self, parent_id: i32) -> Self {
+        self.order.parent_id = parent_id;
+        self
+    }
+
+    /// Set whether cancelling this child order also cancels its parent.
+    pub fn auto_cancel_parent(mut self, auto_cancel_parent: bool) -> Self {
+        self.order.auto_cancel_parent = auto_cancel_parent;
+        self
+    }
+
+    /// Show only `size` of the order on the book at a time.
+    pub fn display_size(mut self, size: i32) -> Self {
+        self.order.display_size = Some(size);
+        self
+    }
+
+    /// Set whether the order is hidden from the book.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.order.hidden = hidden;
+        self
+    }
+
+    /// Route the order through an IB algo.
+    pub fn algo(mut self, algo: Algo) -> Self {
+        self.order.algo = Some(algo);
+        self
+    }
+
+    /// Add a condition.
+    pub fn condition(mut self, condition: OrderCondition) -> Self {
+        self.order.conditions.push(condition);
+        self
+    }
+
+    /// Set whether conditions are also evaluated outside regular hours.
+    pub fn conditions_ignore_rth(mut self, ignore_rth: bool) -> Self {
+        self.order.conditions_ignore_rth = ignore_rth;
+        self
+    }
+
+    /// Set whether met conditions cancel the order instead of submitting it.
+    pub fn conditions_cancel_order(mut self, cancel: bool) -> Self {
+        self.order.conditions_cancel_order = cancel;
+        self
+    }
+
+    /// Set whether the order is only a what-if preview.
+    pub fn what_if(mut self, what_if: bool) -> Self {
+        self.order.what_if = what_if;
+        self
+    }
+
+    /// Validate and return the order.
+    pub fn build(self) -> Result<Order> {
+        self.order.validate()?;
+        Ok(self.order)
+    }
+}
+
+/// Status update for an order (ORDER_STATUS).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderStatus {
+    pub order_id: i32,
+    /// e.g. "PreSubmitted", "Submitted", "Filled", "Cancelled", "Inactive"
+    pub status: String,
+    pub filled: Decimal,
+    pub remaining: Decimal,
+    pub avg_fill_price: f64,
+    pub perm_id: i32,
+    pub parent_id: i32,
+    pub last_fill_price: f64,
+    pub client_id: i32,
+    /// Why the order is held (e.g. "locate" for shorts awaiting a borrow)
+    pub why_held: String,
+    /// Price cap TWS applied to a market order
+    pub mkt_cap_price: Option<f64>,
+}
+
+impl OrderStatus {
+    /// Parse an ORDER_STATUS message (after the message ID).
+    pub fn parse(fields: &mut FieldIterator, server_version: u32) -> Option<Self> {
+        // The version field was dropped when the market cap price was added.
+        if server_version < server_versions::MARKET_CAP_PRICE {
+            fields.skip(1);
+        }
+        Some(Self {
+            order_id: fields.next_i32(),
+            status: fields.next_string()?.to_string(),
+            filled: fields.next_decimal(),
+            remaining: fields.next_decimal(),
+            avg_fill_price: fields.next_f64(),
+            perm_id: fields.next_i32(),
+            parent_id: fields.next_i32(),
+            last_fill_price: fields.next_f64(),
+            client_id: fields.next_i32(),
+            why_held: fields.next_string().unwrap_or("").to_string(),
+            mkt_cap_price: fields.next_optional_f64(),
+        })
+    }
+
+    /// Whether the order can't fill any further.
+    pub fn is_done(&self) -> bool {
+        matches!(self.status.as_str(), "Filled" | "Cancelled" | "ApiCancelled" | "Inactive")
+    }
+}
+
+/// Order state reported with an open order.
+///
+/// Margin and commission figures are only filled in for what-if orders;
+/// values TWS didn't compute are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderState {
+    pub status: String,
+    pub init_margin_before: Option<f64>,
+    pub maint_margin_before: Option<f64>,
+    pub equity_with_loan_before: Option<f64>,
+    pub init_margin_change: Option<f64>,
+    pub maint_margin_change: Option<f64>,
+    pub equity_with_loan_change: Option<f64>,
+    pub init_margin_after: Option<f64>,
+    pub maint_margin_after: Option<f64>,
+    pub equity_with_loan_after: Option<f64>,
+    pub commission: Option<f64>,
+    pub min_commission: Option<f64>,
+    pub max_commission: Option<f64>,
+    pub commission_currency: String,
+    pub warning_text: String,
+}
+
+impl OrderState {
+    /// Parse the order state block of an open order.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        Some(Self {
+            status: fields.next_string()?.to_string(),
+            init_margin_before: fields.next_optional_f64(),
+            maint_margin_before: fields.next_optional_f64(),
+            equity_with_loan_before: fields.next_optional_f64(),
+            init_margin_change: fields.next_optional_f64(),
+            maint_margin_change: fields.next_optional_f64(),
+            equity_with_loan_change: fields.next_optional_f64(),
+            init_margin_after: fields.next_optional_f64(),
+            maint_margin_after: fields.next_optional_f64(),
+            equity_with_loan_after: fields.next_optional_f64(),
+            commission: fields.next_optional_f64(),
+            min_commission: fields.next_optional_f64(),
+            max_commission: fields.next_optional_f64(),
+            commission_currency: fields.next_string().unwrap_or("").to_string(),
+            warning_text: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// Margin and commission impact of an order, from a what-if preview.
+///
+/// Values TWS didn't compute are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarginImpact {
+    pub init_margin_before: Option<f64>,
+    pub init_margin_change: Option<f64>,
+    pub init_margin_after: Option<f64>,
+    pub maint_margin_before: Option<f64>,
+    pub maint_margin_change: Option<f64>,
+    pub maint_margin_after: Option<f64>,
+    pub equity_with_loan_before: Option<f64>,
+    pub equity_with_loan_change: Option<f64>,
+    pub equity_with_loan_after: Option<f64>,
+    pub commission: Option<f64>,
+    pub min_commission: Option<f64>,
+    pub max_commission: Option<f64>,
+    pub commission_currency: String,
+    /// Warnings TWS would show when placing the order
+    pub warning_text: String,
+}
+
+impl MarginImpact {
+    /// Take the margin and commission figures from a what-if order state.
+    pub fn from_state(state: OrderState) -> Self {
+        Self {
+            init_margin_before: state.init_margin_before,
+            init_margin_change: state.init_margin_change,
+            init_margin_after: state.init_margin_after,
+            maint_margin_before: state.maint_margin_before,
+            maint_margin_change: state.maint_margin_change,
+            maint_margin_after: state.maint_margin_after,
+            equity_with_loan_before: state.equity_with_loan_before,
+            equity_with_loan_change: state.equity_with_loan_change,
+            equity_with_loan_after: state.equity_with_loan_after,
+            commission: state.commission,
+            min_commission: state.min_commission,
+            max_commission: state.max_commission,
+            commission_currency: state.commission_currency,
+            warning_text: state.warning_text,
+        }
+    }
+}
+
+/// A working order as reported by TWS (OPEN_ORDER).
+#[derive(Debug, Clone)]
+pub struct OpenOrder {
+    pub order_id: i32,
+    /// API client that placed the order (0 for TWS-entered orders)
+    pub client_id: i32,
+    pub perm_id: i32,
+    pub parent_id: i32,
+    pub contract: Contract,
+    /// The order's modelled attributes
+    pub order: Order,
+    pub state: OrderState,
+}
+
+impl OpenOrder {
+    /// Parse an OPEN_ORDER message (after the message ID).
+    ///
+    /// Assumes server version >= 151, like [`Order::encode`]. Fields after
+    /// the cash quantity aren't modelled and are not read.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let order_id = fields.next_i32();
+        let mut contract = Contract::parse(fields)?;
+
+        let mut order = Order {
+            action: fields.next_string()?.parse().unwrap_or_default(),
+            total_quantity: fields.next_decimal(),
+            order_type: fields.next_string()?.parse().unwrap_or_default(),
+            lmt_price: fields.next_optional_f64(),
+            aux_price: fields.next_optional_f64(),
+            tif: fields.next_string()?.parse().ok(),
+            oca_group: fields.next_string()?.to_string(),
+            account: fields.next_string()?.to_string(),
+            ..Default::default()
+        };
+        // openClose, origin
+        fields.skip(2);
+        order.order_ref = fields.next_string()?.to_string();
+        let client_id = fields.next_i32();
+        let perm_id = fields.next_i32();
+        order.outside_rth = fields.next_bool();
+        order.hidden = fields.next_bool();
+        // discretionaryAmt
+        fields.skip(1);
+        order.good_after_time = fields.next_string()?.parse().ok();
+        // sharesAllocation, faGroup, faMethod, faPercentage, faProfile,
+        // modelCode
+        fields.skip(6);
+        order.good_till_date = fields.next_string()?.parse().ok();
+        // rule80A, percentOffset, settlingFirm, shortSaleSlot,
+        // designatedLocation, exemptCode, auctionStrategy
+        fields.skip(7);
+        let starting_price = fields.next_f64();
+        // stockRefPrice, delta
+        fields.skip(2);
+        let range_lower = fields.next_optional_f64();
+        let range_upper = fields.next_optional_f64();
+        order.display_size = Some(fields.next_i32()).filter(|size| *size > 0);
+        // blockOrder, sweepToFill, allOrNone, minQty
+        fields.skip(4);
+        order.oca_type = OcaType::from_i32(fields.next_i32());
+        // eTradeOnly, firmQuoteOnly, nbboPriceCap
+        fields.skip(3);
+        order.parent_id = fields.next_i32();
+
+        // triggerMethod
+        fields.skip(1);
+        order.volatility = fields.next_optional_f64();
+        order.volatility_type = VolatilityType::from_i32(fields.next_i32());
+        let delta_neutral_order_type = fields.next_string()?;
+        let delta_neutral_aux_price = fields.next_optional_f64();
+        if !delta_neutral_order_type.is_empty() {
+            let hedge = DeltaNeutralOrder {
+                order_type: delta_neutral_order_type.to_string(),
+                aux_price: delta_neutral_aux_price,
+                con_id: fields.next_i32(),
+                settling_firm: fields.next_string()?.to_string(),
+                clearing_account: fields.next_string()?.to_string(),
+                clearing_intent: fields.next_string()?.to_string(),
+                open_close: fields.next_string()?.to_string(),
+                short_sale: fields.next_bool(),
+                short_sale_slot: fields.next_i32(),
+                designated_location: fields.next_string()?.to_string(),
+            };
+            // TWS reports "None" for orders without a hedge
+            order.delta_neutral = Some(hedge).filter(|hedge| hedge.order_type != "None");
+        }
+        order.continuous_update = fields.next_bool();
+        order.reference_price_type = ReferencePriceType::from_i32(fields.next_i32());
+        order.trail_stop_price = fields.next_optional_f64();
+        order.trailing_percent = fields.next_optional_f64();
+        // basisPoints, basisPointsType, comboLegsDescrip
+        fields.skip(3);
+
+        // Combo legs (8 fields each), leg prices, smart combo routing params
+        let legs = fields.next_i32().max(0) as usize;
+        fields.skip(legs * 8);
+        let leg_prices = fields.next_i32().max(0) as usize;
+        fields.skip(leg_prices);
+        let routing_params = fields.next_i32().max(0) as usize;
+        fields.skip(routing_params * 2);
+
+        // scaleInitLevelSize, scaleSubsLevelSize, then the price increment,
+        // which is followed by seven more fields when set
+        fields.skip(2);
+        if fields.next_optional_f64().is_some_and(|increment| increment > 0.0) {
+            fields.skip(7);
+        }
+        if !fields.next_string()?.is_empty() {
+            // hedgeParam
+            fields.skip(1);
+        }
+        // optOutSmartRouting, clearingAccount, clearingIntent, notHeld
+        fields.skip(4);
+        if fields.next_bool() {
+            contract.delta_neutral_contract = Some(DeltaNeutralContract {
+                con_id: fields.next_i32(),
+                delta: fields.next_f64(),
+                price: fields.next_f64(),
+            });
+        }
+        let strategy = fields.next_string()?;
+        if !strategy.is_empty() {
+            let mut algo = Algo::new(strategy);
+            for _ in 0..fields.next_i32().max(0) {
+                let tag = fields.next_string()?;
+                algo.params.push(TagValue::new(tag, fields.next_string()?));
+            }
+            order.algo = Some(algo);
+        }
+        // solicited
+        fields.skip(1);
+        order.what_if = fields.next_bool();
+        let state = OrderState::parse(fields)?;
+
+        // randomizeSize, randomizePrice
+        fields.skip(2);
+        if order.order_type == OrderType::PeggedToBenchmark {
+            order.pegged_benchmark = Some(PeggedBenchmark {
+                reference_con_id: fields.next_i32(),
+                decrease: fields.next_bool(),
+                pegged_change_amount: fields.next_f64(),
+                reference_change_amount: fields.next_f64(),
+                reference_exchange: fields.next_string()?.to_string(),
+                starting_price,
+                reference_range: range_lower.zip(range_upper),
+            });
+        }
+        let conditions = fields.next_i32().max(0);
+        if conditions > 0 {
+            for _ in 0..conditions {
+                order.conditions.push(OrderCondition::parse(fields)?);
+            }
+            order.conditions_ignore_rth = fields.next_bool();
+            order.conditions_cancel_order = fields.next_bool();
+        }
+
+        // adjustedOrderType, triggerPrice, trailStopPrice, lmtPriceOffset,
+        // adjustedStopPrice, adjustedStopLimitPrice, adjustedTrailingAmount,
+        // adjustableTrailingUnit, softDollarTier name, value and display name
+        fields.skip(11);
+        order.cash_qty = fields.next_optional_f64();
+
+        Some(Self {
+            order_id,
+            client_id,
+            perm_id,
+            parent_id: order.parent_id,
+            contract,
+            order,
+            state,
+        })
+    }
+}
+
+/// An order lifecycle message.
+#[derive(Debug, Clone)]
+pub enum OrderEvent {
+    /// An order was placed, changed or reported on request
+    Open(Box<OpenOrder>),
+    /// An order's status or fill progress changed
+    Status(OrderStatus),
+}
+
+impl OrderEvent {
+    pub fn order_id(&self) -> i32 {
+        match self {
+            Self::Open(open) => open.order_id,
+            Self::Status(status) => status.order_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_order_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::market(OrderAction::Buy, Decimal::from(100)).account("DU123");
+        let encoded = order.encode(7, &contract, 176);
+
+        assert!(encoded.starts_with("3\x007\x000\0AAPL\0STK\0"));
+        assert!(encoded.contains("\0USD\0\0\0\0\0BUY\x00100\0MKT\0\0\0\0\0DU123\0\x000\0\x001\x000\0"));
+        // dontUseAutoPriceForHedge onwards, with the version-gated tail
+        assert!(encoded.ends_with("\x001\x000\x000\0\0\0\x000\0\0\0"));
+        assert_eq!(encoded.matches('\0').count(), 115);
+    }
+
+    #[test]
+    fn test_algo_order_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let algo = Algo::vwap(0.1).start_time("09:45:00 US/Eastern").no_take_liq(true);
+        let order = Order::limit(OrderAction::Buy, Decimal::from(500), 185.0).algo(algo);
+
+        let encoded = order.encode(9, &contract, 176);
+        assert!(encoded.contains("\x000\0Vwap\x003\0maxPctVol\x000.1\0"));
+        assert!(encoded.contains("\0startTime\x0009:45:00 US/Eastern\0noTakeLiq\x001\0\0"));
+        assert_eq!(encoded.matches('\0').count(), 115 + 7);
+
+        let adaptive = Algo::adaptive(AdaptivePriority::Patient)
+            .param("adaptivePriority", "Urgent");
+        assert_eq!(adaptive.params, vec![TagValue::new("adaptivePriority", "Urgent")]);
+    }
+
+    #[test]
+    fn test_limit_order_tif() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let till = DateTime::parse("20240105 16:00:00 US/Eastern").unwrap();
+        let order = Order::limit(OrderAction::Sell, Decimal::from(10), 190.5)
+            .good_till(till)
+            .good_after(DateTime::utc(2024, 1, 5, 14, 30, 0))
+            .outside_rth();
+        assert!(order.validate().is_ok());
+
+        let encoded = order.encode(8, &contract, 176);
+        assert!(encoded.contains("SELL\x0010\0LMT\x00190.5\0\0GTD\0"));
+        assert!(encoded.contains("\x000\x001\x000\0"));
+        assert!(encoded.contains("\0\x000\x0020240105-14:30:00\x0020240105 16:00:00 US/Eastern\0"));
+
+        assert!(Order { good_till_date: None, ..order.clone() }.validate().is_err());
+        let early_till = DateTime::utc(2024, 1, 5, 14, 0, 0);
+        assert!(order.clone().good_till(early_till).validate().is_err());
+        assert!(Order::market(OrderAction::Buy, Decimal::ZERO).validate().is_err());
+        let stop = Order::stop(OrderAction::Buy, Decimal::ONE, 180.0);
+        assert!(stop.tif(TimeInForce::Opg).validate().is_err());
+        assert_eq!("IOC".parse::<TimeInForce>().unwrap(), TimeInForce::Ioc);
+    }
+
+    #[test]
+    fn test_good_after_and_till_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::limit(OrderAction::Buy, Decimal::from(10), 180.0)
+            .good_till(DateTime::parse("20240105 16:00:00").unwrap())
+            .good_after(DateTime::utc(2024, 1, 5, 14, 30, 0));
+
+        // Without a timezone TWS reads the time in the login timezone
+        let encoded = order.encode(8, &contract, 176);
+        assert!(encoded.contains("\0\x000\x0020240105-14:30:00\x0020240105 16:00:00\0"));
+        // Servers before UTC_DATE_TIME need the timezone name instead
+        let encoded = order.encode(8, &contract, 162);
+        assert!(encoded.contains("\0\x000\x0020240105 14:30:00 UTC\x0020240105 16:00:00\0"));
+    }
+
+    #[test]
+    fn test_trailing_stop_order() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::trailing_stop(OrderAction::Sell, Decimal::from(100), 2.5);
+        assert!(order.validate().is_ok());
+        assert!(order.encode(9, &contract, 176).contains("\0\x002.5\0\0\0\0\0\0\0\0"));
+
+        let mut limit = Order::limit(OrderAction::Buy, Decimal::from(100), 185.0);
+        limit.trailing_percent = Some(2.5);
+        assert!(matches!(limit.validate(), Err(Error::InvalidOrder(_))));
+        let both = Order { aux_price: Some(1.0), ..order };
+        assert!(both.validate().is_err());
+
+        assert_eq!("TRAIL LIMIT".parse::<OrderType>().unwrap(), OrderType::TrailingStopLimit);
+        assert_eq!("BOX TOP".parse::<OrderType>().unwrap().as_str(), "BOX TOP");
+        assert!("HOLD".parse::<OrderAction>().is_err());
+    }
+
+    #[test]
+    fn test_oca_order_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::limit(OrderAction::Sell, Decimal::from(10), 200.0)
+            .oca("exit-1", OcaType::CancelWithBlock);
+        let encoded = order.encode(9, &contract, 176);
+
+        assert!(encoded.contains("LMT\x00200\0\0\0exit-1\0"));
+        assert!(encoded.contains("\x000\0\0-1\x001\0\0\x000\0"));
+    }
+
+    #[test]
+    fn test_order_builder() {
+        let order = Order::builder(OrderAction::Buy, Decimal::from(100), OrderType::Stop)
+            .aux_price(180.0)
+            .tif(TimeInForce::Gtc)
+            .account("DU123")
+            .build()
+            .unwrap();
+        assert_eq!(order.aux_price, Some(180.0));
+        assert_eq!(order.tif, Some(TimeInForce::Gtc));
+        assert!(order.transmit);
+
+        let plain_market = Order::builder(OrderAction::Buy, Decimal::from(100), OrderType::Market)
+            .aux_price(180.0);
+        assert!(matches!(plain_market.build(), Err(Error::InvalidOrder(_))));
+        let hidden_iceberg = Order::builder(OrderAction::Buy, Decimal::from(100), OrderType::Limit)
+            .lmt_price(180.0)
+            .display_size(10)
+            .hidden(true);
+        assert!(hidden_iceberg.build().is_err());
+    }
+
+    #[test]
+    fn test_order_defaults() {
+        let paper = OrderDefaults::new()
+            .account("DU123")
+            .tif(TimeInForce::Gtc)
+            .outside_rth(true)
+            .transmit(false);
+        let order = paper
+            .builder(OrderAction::Buy, Decimal::from(100), OrderType::Limit)
+            .lmt_price(180.0)
+            .tif(TimeInForce::Day)
+            .build()
+            .unwrap();
+        assert_eq!(order.account, "DU123");
+        assert_eq!(order.tif, Some(TimeInForce::Day));
+        assert!(order.outside_rth);
+        assert!(!order.transmit);
+
+        let order = OrderDefaults::new()
+            .builder(OrderAction::Buy, Decimal::from(100), OrderType::Market)
+            .build()
+            .unwrap();
+        assert_eq!(order, Order::market(OrderAction::Buy, Decimal::from(100)));
+    }
+
+    #[test]
+    fn test_child_order_auto_cancel_parent() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::builder(OrderAction::Sell, Decimal::from(100), OrderType::Stop)
+            .aux_price(180.0)
+            .parent_id(41)
+            .auto_cancel_parent(true)
+            .build()
+            .unwrap();
+
+        let encoded = order.encode(42, &contract, 176);
+        assert!(encoded.contains("\0\x001\x0041\x000\x000\x000\0"));
+        assert!(encoded.ends_with("\0\x001\0\0\0"));
+        assert!(!order.encode(42, &contract, 161).ends_with("\0\x001\0\0\0"));
+
+        let orphan = Order { parent_id: 0, ..order };
+        assert!(orphan.validate().is_err());
+    }
+
+    #[test]
+    fn test_pegged_orders() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::pegged_mid(OrderAction::Buy, Decimal::from(100), 0.01, Some(185.0));
+        let encoded = order.clone().mid_offsets(0.01, 0.005).encode(11, &contract, 176);
+        assert!(encoded.contains("\0PEG MID\x00185\x000.01\0"));
+        // autoCancelParent, advancedErrorOverride, manualOrderTime, mid offsets
+        assert!(encoded.ends_with("\x000\0\0\x000.01\x000.005\0"));
+        assert!(order.encode(11, &contract, 169).ends_with("\x000\0\0\0"));
+        assert!(order.validate().is_ok());
+
+        let ats = Contract::stock("AAPL", "IBKRATS", "USD");
+        let best = Order::pegged_best(OrderAction::Buy, Decimal::from(100), 100, 0.02);
+        assert!(best.encode(13, &ats, 176).ends_with("\0\0\0\x00100\x000.02\0"));
+        let best = Order::pegged_best(
+            OrderAction::Buy,
+            Decimal::from(100),
+            100,
+            COMPETE_AGAINST_BEST_OFFSET_UP_TO_MID,
+        );
+        let encoded = best.mid_offsets(0.01, 0.005).encode(13, &ats, 176);
+        assert!(encoded.ends_with("\x00100\0Infinity\x000.01\x000.005\0"));
+
+        let benchmark = PeggedBenchmark {
+            reference_con_id: 756733,
+            reference_exchange: "ARCA".into(),
+            starting_price: 185.0,
+            pegged_change_amount: 0.5,
+            decrease: false,
+            reference_change_amount: 1.0,
+            reference_range: Some((390.0, 410.0)),
+        };
+        let order = Order::pegged_benchmark(OrderAction::Buy, Decimal::from(100), benchmark);
+        let encoded = order.encode(12, &contract, 176);
+        assert!(encoded.contains("\x00185\0\0\x00390\x00410\x000\0"));
+        assert!(encoded.contains("\x00756733\x000\x000.5\x001\0ARCA\x000\0"));
+        let unset = Order::new(OrderAction::Buy, Decimal::from(100), OrderType::PeggedToBenchmark);
+        assert!(unset.validate().is_err());
+    }
+
+    #[test]
+    fn test_touched_and_close_orders() {
+        let quantity = Decimal::from(100);
+        assert!(Order::market_if_touched(OrderAction::Buy, quantity, 180.0).validate().is_ok());
+        let lit = Order::limit_if_touched(OrderAction::Buy, quantity, 180.0, 180.5);
+        assert!(lit.validate().is_ok());
+        let no_prices = Order::new(OrderAction::Buy, quantity, OrderType::LimitIfTouched);
+        assert!(no_prices.validate().is_err());
+        let loc = Order::limit_on_close(OrderAction::Sell, quantity, 190.0);
+        assert!(loc.tif(TimeInForce::Gtc).validate().is_err());
+
+        let moc = Order::market_on_close(OrderAction::Sell, quantity);
+        let cutoff = DateTime::parse("20240105 15:50:00 US/Eastern").unwrap();
+        let before = DateTime::parse("20240105 15:49:59 US/Eastern").unwrap();
+        let after = DateTime::parse("20240105 15:50:00 US/Eastern").unwrap();
+        assert!(moc.validate_close_cutoff(&before, &cutoff).is_ok());
+        assert!(moc.validate_close_cutoff(&after, &cutoff).is_err());
+        let market = Order::market(OrderAction::Sell, quantity);
+        assert!(market.validate_close_cutoff(&after, &cutoff).is_ok());
+    }
+
+    #[test]
+    fn test_volatility_order_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let quantity = Decimal::from(5);
+        let order = Order::volatility(OrderAction::Buy, quantity, 25.5, VolatilityType::Annual)
+            .delta_neutral(DeltaNeutralOrder::new("MKT"));
+        assert!(order.validate().is_ok());
+
+        let encoded = order.encode(13, &contract, 176);
+        // volatility, volatilityType, then the hedge order
+        assert!(encoded.contains("\x0025.5\x002\0MKT\0\x000\0\0\0\0\x000\x000\0"));
+        // continuousUpdate, referencePriceType, trailStopPrice
+        assert!(encoded.contains("\0MKT\0\x000\0\0\0\0\x000\x000\0\x000\x001\0\0"));
+        let no_volatility = Order::new(OrderAction::Buy, Decimal::from(5), OrderType::Volatility);
+        assert!(no_volatility.validate().is_err());
+    }
+
+    #[test]
+    fn test_fractional_and_cash_orders() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let fractional = Order::market(OrderAction::Buy, Decimal::new(1050, 2));
+        assert!(fractional.encode(14, &contract, 176).contains("\0BUY\x0010.5\0MKT\0"));
+
+        let cash = Order::cash(OrderAction::Buy, 500.0);
+        assert!(cash.validate().is_ok());
+        assert!(cash.encode(15, &contract, 176).contains("\0BUY\x000\0MKT\0"));
+        // adjustableTrailingUnit, extOperator, softDollarTier, cashQty, MiFID II
+        let cash_qty = "\x000\0\0\0\x00500\0\0\0\0\x001\0";
+        assert!(cash.encode(15, &contract, 176).contains(cash_qty));
+        assert!(Order { total_quantity: Decimal::ONE, ..cash }.validate().is_err());
+    }
+
+    #[test]
+    fn test_iceberg_order_encode() {
+        let contract = Contract::stock("AAPL", "SMART", "USD");
+        let order = Order::limit(OrderAction::Buy, Decimal::from(5000), 185.0).display_size(100);
+        // transmit, parentId, blockOrder, sweepToFill, displaySize,
+        // triggerMethod, outsideRth, hidden
+        let extended = "\0\x001\x000\x000\x000\x00100\x000\x000\x000\0";
+        assert!(order.encode(10, &contract, 176).contains(extended));
+
+        let hidden = Order::limit(OrderAction::Buy, Decimal::from(5000), 185.0).hidden();
+        let extended = "\0\x001\x000\x000\x000\x000\x000\x000\x001\0";
+        assert!(hidden.encode(10, &contract, 176).contains(extended));
+        assert!(order.display_size(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_order_status() {
+        let buf = b"12\0Filled\x00100\x000\x00150.25\x001234\x000\x00150.25\x007\0\0\0";
+        let mut fields = FieldIterator::new(buf);
+        let status = OrderStatus::parse(&mut fields, 176).unwrap();
+
+        assert_eq!(status.order_id, 12);
+        assert_eq!(status.filled, Decimal::from(100));
+        assert_eq!(status.client_id, 7);
+        assert_eq!(status.mkt_cap_price, None);
+        assert!(status.is_done());
+    }
+
+    #[test]
+    fn test_parse_open_order() {
+        let mut raw = vec!["12", "265598", "AAPL", "STK", "", "0", "", "", "SMART", "USD", "AAPL", "NMS"];
+        raw.extend(["BUY", "100", "LMT", "150.25", "", "DAY", "exit", "DU123", "", "0", "ref", "7", "1234"]);
+        // outsideRth through modelCode, then goodTillDate
+        raw.extend(["0", "0", "0", "", "", "", "", "", "", "", ""]);
+        // rule80A through minQty, then ocaType
+        raw.extend(["", "", "", "0", "", "-1", "0", "", "", "", "", "", "", "0", "0", "0", "", "3"]);
+        // eTradeOnly, firmQuoteOnly, nbboPriceCap, parentId
+        raw.extend(["0", "0", "", "0"]);
+        // Volatility and trailing fields, no combo legs or routing params
+        raw.extend(["0", "", "0", "", "", "0", "0", "", "", "", "", "", "0", "0", "0"]);
+        // Scale, hedge, routing, clearing, notHeld, delta neutral, algo, solicited, whatIf
+        raw.extend(["", "", "", "", "0", "", "", "0", "1", "756733", "0.5", "410.1", "", "0", "1"]);
+        raw.extend(["PreSubmitted", "", "", "", "3756.25", "", "", "", "", "", "1.7976931348623157E308", "", "", "", ""]);
+        // randomizeSize, randomizePrice, one price condition, ignoreRth, cancelOrder
+        raw.extend(["0", "0", "1", "1", "a", "1", "180", "265598", "SMART", "2", "1", "0"]);
+        // Adjusted order fields, soft dollar tier, cashQty
+        raw.extend(["", "", "", "", "", "", "", "0", "", "", "", "1.7976931348623157E308"]);
+        let buf = raw.join("\0") + "\0";
+
+        let mut fields = FieldIterator::new(buf.as_bytes());
+        let open = OpenOrder::parse(&mut fields).unwrap();
+
+        assert_eq!(open.order_id, 12);
+        assert_eq!(open.client_id, 7);
+        assert_eq!(open.perm_id, 1234);
+        assert_eq!(open.contract.symbol, "AAPL");
+        let component = open.contract.delta_neutral_contract.unwrap();
+        assert_eq!(component, DeltaNeutralContract::new(756733, 0.5, 410.1));
+        assert_eq!(open.order.lmt_price, Some(150.25));
+        assert_eq!(open.order.tif, Some(TimeInForce::Day));
+        assert_eq!(open.order.oca_type, Some(OcaType::ReduceNonBlock));
+        assert_eq!(open.state.status, "PreSubmitted");
+        assert!(open.order.what_if);
+        assert_eq!(open.order.conditions.len(), 1);
+        assert!(open.order.conditions_ignore_rth);
+
+        let impact = MarginImpact::from_state(open.state);
+        assert_eq!(impact.init_margin_change, Some(3756.25));
+        assert_eq!(impact.commission, None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Profit and loss types and daily per-contract snapshotting.
+
+use rust_decimal::Decimal;
+
+use crate::server_versions;
+use crate::wire::FieldIterator;
+
+#[cfg(feature = "client")]
+mod snapshotter;
+
+#[cfg(feature = "client")]
+pub use snapshotter::{PnlSnapshots, PnlSnapshotter};
+
+/// Daily P&L update for a single position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PnlSingle {
+    /// Current position size
+    pub position: Decimal,
+    /// P&L since the start of the trading day
+    pub daily_pnl: Option<f64>,
+    /// Unrealized P&L (server version >= 129)
+    pub unrealized_pnl: Option<f64>,
+    /// Realized P&L (server version >= 135)
+    pub realized_pnl: Option<f64>,
+    /// Current market value of the position
+    pub value: f64,
+}
+
+impl PnlSingle {
+    /// Parse an update from message fields (after the request ID).
+    pub fn parse(fields: &mut FieldIterator, server_version: u32) -> Option<Self> {
+        let position = fields.next_parsed()?;
+        let daily_pnl = fields.next_optional_f64();
+        let unrealized_pnl = if server_version >= server_versions::UNREALIZED_PNL {
+            fields.next_optional_f64()
+        } else {
+            None
+        };
+        let realized_pnl = if server_version >= server_versions::REALIZED_PNL {
+            fields.next_optional_f64()
+        } else {
+            None
+        };
+
+        Some(Self {
+            position,
+            daily_pnl,
+            unrealized_pnl,
+            realized_pnl,
+            value: fields.next_f64(),
+        })
+    }
+}
+
+/// P&L for one contract at a point in time.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContractPnl {
+    pub con_id: i32,
+    pub symbol: String,
+    pub position: Decimal,
+    pub daily_pnl: Option<f64>,
+    pub unrealized_pnl: Option<f64>,
+    pub realized_pnl: Option<f64>,
+    pub value: f64,
+}
+
+/// Per-contract P&L for every position in an account, sampled at one time.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PnlSnapshot {
+    pub account: String,
+    /// Trading date the sample belongs to (YYYYMMDD)
+    pub date: String,
+    /// When the sample was taken (seconds since the Unix epoch)
+    pub timestamp: i64,
+    pub contracts: Vec<ContractPnl>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pnl_single() {
+        let buf = b"100\x0012.5\x001.7976931348623157E308\x000\x0015000\0";
+        let mut fields = FieldIterator::new(buf);
+        let pnl = PnlSingle::parse(&mut fields, 176).unwrap();
+
+        assert_eq!(pnl.position, Decimal::from(100));
+        assert_eq!(pnl.daily_pnl, Some(12.5));
+        assert_eq!(pnl.unrealized_pnl, None);
+        assert_eq!(pnl.realized_pnl, Some(0.0));
+        assert_eq!(pnl.value, 15000.0);
+    }
+}
@@ -0,0 +1,341 @@
+//! Background sampler turning P&L updates into end-of-day snapshots.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+
+use super::{ContractPnl, PnlSingle, PnlSnapshot};
+use crate::account::Position;
+use crate::client::{Client, Transport};
+use crate::datetime::DateTime;
+use crate::error::Result;
+
+/// Samples per-contract P&L for an account and produces end-of-day snapshots.
+///
+/// Subscribes to P&L updates for every open position, records a sample at
+/// the configured frequency, and emits the last sample of each trading date
+/// once the date rolls over. Positions are reloaded at each rollover.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(client: std::sync::Arc<ibkr::Client>) -> ibkr::Result<()> {
+/// use std::time::Duration;
+/// use ibkr::pnl::PnlSnapshotter;
+///
+/// let mut snapshots = PnlSnapshotter::new(client, "U1234567")
+///     .frequency(Duration::from_secs(60))
+///     .utc_offset(-5 * 3600)
+///     .start()
+///     .await?;
+///
+/// while let Some(eod) = snapshots.next_end_of_day().await {
+///     println!("{}: {} contracts", eod.date, eod.contracts.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct PnlSnapshotter<S: Transport = TcpStream> {
+    client: Arc<Client<S>>,
+    account: String,
+    model_code: String,
+    frequency: Duration,
+    utc_offset: i64,
+    /// Current Unix time, replaced in tests
+    clock: fn() -> i64,
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+impl<S: Transport> PnlSnapshotter<S> {
+    /// Create a snapshotter for an account (samples every minute, UTC dates).
+    pub fn new(client: Arc<Client<S>>, account: &str) -> Self {
+        Self {
+            client,
+            account: account.to_string(),
+            model_code: String::new(),
+            frequency: Duration::from_secs(60),
+            utc_offset: 0,
+            clock: unix_now,
+        }
+    }
+
+    /// Set how often P&L is sampled.
+    pub fn frequency(mut self, frequency: Duration) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the model code to restrict P&L to (empty for none).
+    pub fn model_code(mut self, model_code: &str) -> Self {
+        self.model_code = model_code.to_string();
+        self
+    }
+
+    /// Set the offset from UTC, in seconds, used to assign samples to dates.
+    pub fn utc_offset(mut self, seconds: i64) -> Self {
+        self.utc_offset = seconds;
+        self
+    }
+
+    /// Start sampling in a background task.
+    ///
+    /// Fails if the initial position load fails; later errors are logged
+    /// and retried on the next sample.
+    pub async fn start(self) -> Result<PnlSnapshots> {
+        let positions = self.load_positions().await?;
+        let latest = Arc::new(Mutex::new(None));
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let task = tokio::spawn(self.run(positions, latest.clone(), tx));
+
+        Ok(PnlSnapshots {
+            latest,
+            end_of_day: rx,
+            task,
+        })
+    }
+
+    async fn load_positions(&self) -> Result<Vec<Position>> {
+        let positions = self.client.positions().await?;
+        Ok(positions
+            .into_iter()
+            .filter(|p| p.account == self.account && !p.position.is_zero())
+            .collect())
+    }
+
+    async fn run(
+        self,
+        mut positions: Vec<Position>,
+        latest: Arc<Mutex<Option<PnlSnapshot>>>,
+        end_of_day: mpsc::UnboundedSender<PnlSnapshot>,
+    ) {
+        let mut interval = tokio::time::interval(self.frequency);
+        let mut current_date: Option<String> = None;
+
+        loop {
+            // Owned by this task, so aborting it cancels every feed too.
+            let values = Arc::new(Mutex::new(HashMap::new()));
+            let mut feeds = self.subscribe_all(&positions, &values).await;
+
+            loop {
+                interval.tick().await;
+                let snapshot = self.sample(&positions, &values).await;
+
+                // Date rolled over: the previous sample closes that day.
+                let rolled = current_date
+                    .as_ref()
+                    .is_some_and(|date| *date != snapshot.date);
+                if rolled
+                    && let Some(previous) = latest.lock().await.take()
+                    && end_of_day.send(previous).is_err()
+                {
+                    return;
+                }
+                current_date = Some(snapshot.date.clone());
+                *latest.lock().await = Some(snapshot);
+
+                if rolled {
+                    break;
+                }
+            }
+
+            feeds.shutdown().await;
+            match self.load_positions().await {
+                Ok(reloaded) => positions = reloaded,
+                Err(error) => tracing::warn!(%error, "failed to reload positions for P&L"),
+            }
+        }
+    }
+
+    /// Subscribe to P&L for each position, keeping the latest value per contract.
+    async fn subscribe_all(
+        &self,
+        positions: &[Position],
+        values: &Arc<Mutex<HashMap<i32, PnlSingle>>>,
+    ) -> JoinSet<()> {
+        let mut feeds = JoinSet::new();
+        for position in positions {
+            let con_id = position.contract.con_id;
+            let subscription = self
+                .client
+                .pnl_single(&self.account, &self.model_code, con_id)
+                .await;
+            let mut subscription = match subscription {
+                Ok(subscription) => subscription,
+                Err(error) => {
+                    tracing::warn!(con_id, %error, "failed to subscribe to P&L");
+                    continue;
+                }
+            };
+
+            let values = values.clone();
+            feeds.spawn(async move {
+                while let Some(update) = subscription.next().await {
+                    values.lock().await.insert(con_id, update);
+                }
+            });
+        }
+        feeds
+    }
+
+    async fn sample(
+        &self,
+        positions: &[Position],
+        values: &Mutex<HashMap<i32, PnlSingle>>,
+    ) -> PnlSnapshot {
+        let timestamp = (self.clock)();
+
+        let values = values.lock().await;
+        let contracts = positions
+            .iter()
+            .filter_map(|position| {
+                let pnl = values.get(&position.contract.con_id)?;
+                Some(ContractPnl {
+                    con_id: position.contract.con_id,
+                    symbol: position.contract.symbol.clone(),
+                    position: pnl.position,
+                    daily_pnl: pnl.daily_pnl,
+                    unrealized_pnl: pnl.unrealized_pnl,
+                    realized_pnl: pnl.realized_pnl,
+                    value: pnl.value,
+                })
+            })
+            .collect();
+
+        PnlSnapshot {
+            account: self.account.clone(),
+            date: DateTime::from_timestamp(timestamp + self.utc_offset).date_string(),
+            timestamp,
+            contracts,
+        }
+    }
+}
+
+/// Handle to a running [`PnlSnapshotter`]. Dropping it stops sampling and
+/// cancels its P&L subscriptions.
+pub struct PnlSnapshots {
+    latest: Arc<Mutex<Option<PnlSnapshot>>>,
+    end_of_day: mpsc::UnboundedReceiver<PnlSnapshot>,
+    task: JoinHandle<()>,
+}
+
+impl PnlSnapshots {
+    /// The most recent sample for the current trading date.
+    pub async fn latest(&self) -> Option<PnlSnapshot> {
+        self.latest.lock().await.clone()
+    }
+
+    /// Wait for the next end-of-day snapshot.
+    pub async fn next_end_of_day(&mut self) -> Option<PnlSnapshot> {
+        self.end_of_day.recv().await
+    }
+}
+
+impl Drop for PnlSnapshots {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{connected_client, read_frame, write_frame};
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use tokio::io::DuplexStream;
+
+    /// 2024-01-05 14:30:00 UTC, moved forward to roll the date
+    static NOW: AtomicI64 = AtomicI64::new(1_704_465_000);
+
+    fn test_clock() -> i64 {
+        NOW.load(Ordering::SeqCst)
+    }
+
+    async fn send_positions(server: &mut DuplexStream, con_ids: &[&str]) {
+        assert_eq!(read_frame(server).await, b"61\x001\0");
+        write_positions(server, con_ids).await;
+    }
+
+    async fn write_positions(server: &mut DuplexStream, con_ids: &[&str]) {
+        for con_id in con_ids {
+            let position = format!(
+                "61\x003\0DU123\0{con_id}\0AAPL\0STK\0\x000\0\0\0SMART\0USD\0AAPL\0NMS\0\
+                 100\x00185.5\0"
+            );
+            write_frame(server, &position).await;
+        }
+        write_frame(server, "62\x001\0").await;
+    }
+
+    /// Read `count` frames in any order, skipping position cancels.
+    /// Cancels are sent from their own tasks, so they race other requests.
+    async fn read_frames(server: &mut DuplexStream, count: usize) -> HashSet<Vec<u8>> {
+        let mut frames = HashSet::new();
+        while frames.len() < count {
+            let frame = read_frame(server).await;
+            if frame != b"64\x001\0" {
+                frames.insert(frame);
+            }
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_samples_and_rolls_over() {
+        let (client, mut server) = connected_client(1).await;
+        let mut snapshotter = PnlSnapshotter::new(Arc::new(client), "DU123")
+            .frequency(Duration::from_millis(10));
+        snapshotter.clock = test_clock;
+
+        let (snapshots, _) = tokio::join!(
+            snapshotter.start(),
+            send_positions(&mut server, &["265598", "272093"])
+        );
+        let mut snapshots = snapshots.unwrap();
+        assert_eq!(
+            read_frames(&mut server, 2).await,
+            HashSet::from([
+                b"94\x001000000001\0DU123\0\x00265598\0".to_vec(),
+                b"94\x001000000002\0DU123\0\x00272093\0".to_vec(),
+            ])
+        );
+        write_frame(&mut server, "95\x001000000001\x00100\x00120.5\x00300\x000\x0018550\0").await;
+        write_frame(&mut server, "95\x001000000002\x00100\x00-40\x0075\x000\x0041000\0").await;
+        while snapshots.latest().await.is_none_or(|latest| latest.contracts.len() < 2) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // The next day's first sample closes the 5th, then the feeds are
+        // cancelled and positions reloaded.
+        NOW.fetch_add(86_400, Ordering::SeqCst);
+        let eod = snapshots.next_end_of_day().await.unwrap();
+        assert_eq!(eod.date, "20240105");
+        let daily: Vec<_> = eod.contracts.iter().map(|pnl| (pnl.con_id, pnl.daily_pnl)).collect();
+        assert_eq!(daily, [(265598, Some(120.5)), (272093, Some(-40.0))]);
+        let reloaded = HashSet::from([
+            b"95\x001000000001\0".to_vec(),
+            b"95\x001000000002\0".to_vec(),
+            b"61\x001\0".to_vec(),
+        ]);
+        assert_eq!(read_frames(&mut server, 3).await, reloaded);
+        write_positions(&mut server, &["265598"]).await;
+        let resubscribed = HashSet::from([b"94\x001000000004\0DU123\0\x00265598\0".to_vec()]);
+        assert_eq!(read_frames(&mut server, 1).await, resubscribed);
+
+        // Dropping the handle cancels the feeds with the sampler
+        drop(snapshots);
+        let cancelled = HashSet::from([b"95\x001000000004\0".to_vec()]);
+        assert_eq!(read_frames(&mut server, 1).await, cancelled);
+    }
+}
@@ -0,0 +1,135 @@
+//! Connection pool across multiple client IDs.
+//!
+//! TWS paces requests per session, so spreading read-only requests over
+//! several connections raises throughput. Order flow must stay on a single
+//! connection, since orders are tied to the client ID that placed them.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+
+use crate::client::{Client, Transport};
+use crate::contract::Contract;
+use crate::error::Result;
+use crate::fx::FxRate;
+use crate::historical::{BarData, BarSize, Duration as HistDuration, WhatToShow};
+
+/// A set of connections with distinct client IDs.
+///
+/// The first connection is designated for order flow; read-only requests
+/// are spread round-robin over the others (or all of them, if the pool has
+/// a single connection). Disconnected clients are skipped.
+pub struct ClientPool<S: Transport = TcpStream> {
+    clients: Vec<Arc<Client<S>>>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    /// Open `size` connections using client IDs `first_client_id..first_client_id + size`.
+    ///
+    /// The connection with `first_client_id` is the order connection.
+    pub async fn connect(addr: &str, first_client_id: i32, size: usize) -> Result<Self> {
+        let mut clients = Vec::with_capacity(size);
+        for client_id in (first_client_id..).take(size.max(1)) {
+            clients.push(Client::connect(addr, client_id).await?);
+        }
+        Ok(Self::new(clients))
+    }
+}
+
+impl<S: Transport> ClientPool<S> {
+    /// Build a pool from connected clients. The first one handles orders.
+    ///
+    /// # Panics
+    /// Panics if `clients` is empty.
+    pub fn new(clients: Vec<Client<S>>) -> Self {
+        assert!(!clients.is_empty(), "ClientPool needs at least one client");
+        Self {
+            clients: clients.into_iter().map(Arc::new).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of connections in the pool.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Whether the pool has no connections (never true for a constructed pool).
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The connection designated for order flow.
+    pub fn orders(&self) -> &Arc<Client<S>> {
+        &self.clients[0]
+    }
+
+    /// The next connection for a read-only request.
+    pub fn read_only(&self) -> &Arc<Client<S>> {
+        let readers = if self.clients.len() > 1 {
+            &self.clients[1..]
+        } else {
+            &self.clients[..]
+        };
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..readers.len())
+            .map(|offset| &readers[(start + offset) % readers.len()])
+            .find(|client| client.is_connected())
+            .unwrap_or(&readers[start % readers.len()])
+    }
+
+    /// Request historical data on the next read-only connection.
+    ///
+    /// See [`Client::historical_data`].
+    pub async fn historical_data(
+        &self,
+        contract: Contract,
+        duration: HistDuration,
+        bar_size: BarSize,
+        what_to_show: WhatToShow,
+        use_rth: bool,
+    ) -> Result<Vec<BarData>> {
+        self.read_only()
+            .historical_data(contract, duration, bar_size, what_to_show, use_rth)
+            .await
+    }
+
+    /// Get an exchange rate on the next read-only connection.
+    ///
+    /// See [`Client::fx_rate`].
+    pub async fn fx_rate(&self, base: &str, quote: &str) -> Result<FxRate> {
+        self.read_only().fx_rate(base, quote).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::connected_client;
+
+    #[tokio::test]
+    async fn test_read_only_round_robin_skips_orders_and_disconnected() {
+        let (orders, _orders_server) = connected_client(1).await;
+        let (first, first_server) = connected_client(2).await;
+        let (second, _second_server) = connected_client(3).await;
+        let pool = ClientPool::new(vec![orders, first, second]);
+
+        let a = pool.read_only().clone();
+        let b = pool.read_only().clone();
+        assert!(!Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, pool.orders()));
+        assert!(!Arc::ptr_eq(&b, pool.orders()));
+
+        // Once a reader disconnects, every read goes to the remaining one
+        drop(first_server);
+        while pool.clients[1].is_connected() {
+            tokio::task::yield_now().await;
+        }
+        for _ in 0..3 {
+            assert!(Arc::ptr_eq(pool.read_only(), &pool.clients[2]));
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Recording streamed data to disk.
+//!
+//! A [`Recorder`] appends [`Record`]s (market data ticks, depth updates)
+//! to newline-delimited JSON or CSV files, optionally rotating to a new
+//! file each day or once a file reaches a size limit. Each line is prefixed
+//! with the time it was recorded, in milliseconds since the Unix epoch.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rust_decimal::Decimal;
+
+use crate::datetime::DateTime;
+use crate::error::Result;
+use crate::market_data::Tick;
+use crate::market_depth::DepthUpdate;
+
+/// File format written by a [`Recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// One JSON object per line (`.jsonl`)
+    #[default]
+    Json,
+    /// Comma-separated values with a header row (`.csv`)
+    Csv,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "jsonl",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// When a [`Recorder`] starts a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// Append to `<prefix>.<ext>` forever
+    #[default]
+    Never,
+    /// One file per UTC date, `<prefix>-YYYYMMDD.<ext>`
+    Daily,
+    /// Numbered files, `<prefix>-N.<ext>`, of at most this many bytes
+    Size(u64),
+}
+
+/// A single field of a [`Record`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Decimal(Decimal),
+    Text(String),
+}
+
+impl Value {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Self::Int(value) => out.push_str(&value.to_string()),
+            // JSON has no NaN or infinity
+            Self::Float(value) if !value.is_finite() => out.push_str("null"),
+            Self::Float(value) => out.push_str(&value.to_string()),
+            Self::Decimal(value) => out.push_str(&value.to_string()),
+            Self::Text(value) => {
+                out.push('"');
+                for c in value.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+        }
+    }
+
+    fn write_csv(&self, out: &mut String) {
+        match self {
+            Self::Null => {}
+            Self::Text(value) if value.contains([',', '"', '\n', '\r']) => {
+                out.push('"');
+                out.push_str(&value.replace('"', "\"\""));
+                out.push('"');
+            }
+            Self::Text(value) => out.push_str(value),
+            Self::Bool(value) => out.push_str(if *value { "1" } else { "0" }),
+            Self::Int(value) => out.push_str(&value.to_string()),
+            Self::Float(value) => out.push_str(&value.to_string()),
+            Self::Decimal(value) => out.push_str(&value.to_string()),
+        }
+    }
+}
+
+/// A streamed item that can be written by a [`Recorder`].
+pub trait Record {
+    /// Field names, in the order [`Record::values`] returns them
+    const FIELDS: &'static [&'static str];
+
+    /// Field values, one per name in [`Record::FIELDS`].
+    fn values(&self) -> Vec<Value>;
+}
+
+impl Record for Tick {
+    const FIELDS: &'static [&'static str] = &["kind", "tick_type", "value"];
+
+    fn values(&self) -> Vec<Value> {
+        let (kind, value) = match self {
+            Self::Price { price, .. } => ("price", Value::Float(*price)),
+            Self::Size { size, .. } => ("size", Value::Decimal(*size)),
+            Self::String { value, .. } => ("string", Value::Text(value.clone())),
+            Self::Generic { value, .. } => ("generic", Value::Float(*value)),
+            Self::DataType(data_type) => ("data_type", Value::Int(*data_type as i64)),
+            Self::SnapshotEnd => ("snapshot_end", Value::Null),
+        };
+        let tick_type = self.tick_type().map_or(Value::Null, |tick_type| {
+            Value::Int(tick_type.into())
+        });
+        vec![Value::Text(kind.to_string()), tick_type, value]
+    }
+}
+
+impl Record for DepthUpdate {
+    const FIELDS: &'static [&'static str] = &[
+        "position",
+        "market_maker",
+        "operation",
+        "side",
+        "price",
+        "size",
+        "is_smart_depth",
+    ];
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int(self.position.into()),
+            Value::Text(self.market_maker.clone()),
+            Value::Int(self.operation as i64),
+            Value::Int(self.side as i64),
+            Value::Float(self.price),
+            Value::Decimal(self.size),
+            Value::Bool(self.is_smart_depth),
+        ]
+    }
+}
+
+/// Appends records to files in a directory.
+///
+/// Writes are buffered; call [`Recorder::flush`] to push them to disk
+/// (dropping the recorder also flushes).
+///
+/// # Example
+///
+/// ```no_run
+/// # fn run(ticks: Vec<ibkr::Tick>) -> ibkr::Result<()> {
+/// use ibkr::recorder::{Format, Recorder, Rotation};
+///
+/// let mut recorder = Recorder::new("ticks", "AAPL")
+///     .format(Format::Csv)
+///     .rotation(Rotation::Daily);
+///
+/// // e.g. ticks from `Client::market_data`
+/// for tick in &ticks {
+///     recorder.record(tick)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder {
+    dir: PathBuf,
+    prefix: String,
+    format: Format,
+    rotation: Rotation,
+    file: Option<BufWriter<File>>,
+    /// Path of the open file
+    path: PathBuf,
+    /// Bytes in the open file
+    written: u64,
+    /// Records written to the open file
+    rows: u64,
+}
+
+impl Recorder {
+    /// Record to files named after `prefix` in `dir`, which is created if
+    /// missing.
+    pub fn new(dir: impl Into<PathBuf>, prefix: &str) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: prefix.to_string(),
+            format: Format::default(),
+            rotation: Rotation::default(),
+            file: None,
+            path: PathBuf::new(),
+            written: 0,
+            rows: 0,
+        }
+    }
+
+    /// Set the file format (newline-delimited JSON by default).
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set when to start a new file (never by default).
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Path of the file currently being written, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|_| self.path.as_path())
+    }
+
+    /// Append a record, stamped with the current time.
+    pub fn record<R: Record>(&mut self, record: &R) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.record_at(now.as_millis() as i64, record)
+    }
+
+    /// Append a record stamped with `time`, in milliseconds since the Unix
+    /// epoch, e.g. when replaying recorded data.
+    pub fn record_at<R: Record>(&mut self, time: i64, record: &R) -> Result<()> {
+        let mut line = String::new();
+        let values = record.values();
+        match self.format {
+            Format::Json => {
+                line.push_str(&format!("{{\"time\":{time}"));
+                for (name, value) in R::FIELDS.iter().zip(&values) {
+                    line.push_str(&format!(",\"{name}\":"));
+                    value.write_json(&mut line);
+                }
+                line.push('}');
+            }
+            Format::Csv => {
+                line.push_str(&time.to_string());
+                for value in &values {
+                    line.push(',');
+                    value.write_csv(&mut line);
+                }
+            }
+        }
+        line.push('\n');
+
+        let path = self.path_for(time, line.len() as u64);
+        if self.file.is_none() || path != self.path {
+            self.open(path, R::FIELDS)?;
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+            self.written += line.len() as u64;
+            self.rows += 1;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered records to disk.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// File the next line of `len` bytes, recorded at `time`, belongs in.
+    fn path_for(&self, time: i64, len: u64) -> PathBuf {
+        let ext = self.format.extension();
+        let name = match self.rotation {
+            Rotation::Never => format!("{}.{ext}", self.prefix),
+            Rotation::Daily => {
+                let date = DateTime::from_timestamp(time.div_euclid(1000)).date_string();
+                format!("{}-{date}.{ext}", self.prefix)
+            }
+            Rotation::Size(limit) => {
+                let mut index = self.index_of_open().unwrap_or(0);
+                // A file always takes at least one line, however long.
+                if self.rows > 0 && self.written + len > limit {
+                    index += 1;
+                }
+                format!("{}-{index}.{ext}", self.prefix)
+            }
+        };
+        self.dir.join(name)
+    }
+
+    /// Index of the open size-rotated file.
+    fn index_of_open(&self) -> Option<u32> {
+        self.file.as_ref()?;
+        let stem = self.path.file_stem()?.to_str()?;
+        stem.strip_prefix(&self.prefix)?.strip_prefix('-')?.parse().ok()
+    }
+
+    fn open(&mut self, mut path: PathBuf, fields: &[&str]) -> Result<()> {
+        self.flush()?;
+        fs::create_dir_all(&self.dir)?;
+        // Size-rotated files are never appended to across recorders, so a
+        // restarted recorder moves past the files already on disk.
+        if let Rotation::Size(_) = self.rotation {
+            let mut index = self.index_of_open().map_or(0, |index| index + 1);
+            loop {
+                let name = format!("{}-{index}.{}", self.prefix, self.format.extension());
+                path = self.dir.join(name);
+                if !path.exists() {
+                    break;
+                }
+                index += 1;
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut written = file.metadata()?.len();
+        if written == 0 && self.format == Format::Csv {
+            let header = format!("time,{}\n", fields.join(","));
+            file.write_all(header.as_bytes())?;
+            written = header.len() as u64;
+        }
+        self.file = Some(BufWriter::new(file));
+        self.path = path;
+        self.written = written;
+        self.rows = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let name = format!("ibkr-recorder-{}-{name}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_record_json_daily() {
+        let dir = temp_dir("json");
+        let mut recorder = Recorder::new(&dir, "AAPL").rotation(Rotation::Daily);
+        let price = Tick::Price {
+            tick_type: 4,
+            price: 185.5,
+            attrib: Default::default(),
+        };
+        recorder.record_at(1_704_465_000_000, &price).unwrap();
+        let text = Tick::String {
+            tick_type: 45,
+            value: "say \"hi\"".to_string(),
+        };
+        recorder.record_at(1_704_551_400_000, &text).unwrap();
+        recorder.flush().unwrap();
+
+        let first = fs::read_to_string(dir.join("AAPL-20240105.jsonl")).unwrap();
+        let line = "{\"time\":1704465000000,\"kind\":\"price\",\"tick_type\":4,\"value\":185.5}\n";
+        assert_eq!(first, line);
+        let second = fs::read_to_string(dir.join("AAPL-20240106.jsonl")).unwrap();
+        assert!(second.ends_with("\"value\":\"say \\\"hi\\\"\"}\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_csv_size_rotation() {
+        let dir = temp_dir("csv");
+        let mut recorder = Recorder::new(&dir, "depth")
+            .format(Format::Csv)
+            .rotation(Rotation::Size(100));
+        let update = DepthUpdate {
+            position: 0,
+            market_maker: "NSDQ".to_string(),
+            operation: crate::market_depth::DepthOperation::Insert,
+            side: crate::market_depth::DepthSide::Bid,
+            price: 185.5,
+            size: Decimal::from(200),
+            is_smart_depth: false,
+        };
+        for _ in 0..3 {
+            recorder.record_at(1_704_465_000_000, &update).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let first = fs::read_to_string(dir.join("depth-0.csv")).unwrap();
+        let header = "time,position,market_maker,operation,side,price,size,is_smart_depth\n";
+        let row = "1704465000000,0,NSDQ,0,1,185.5,200,0\n";
+        assert_eq!(first, format!("{header}{row}"));
+        assert_eq!(recorder.path(), Some(dir.join("depth-2.csv").as_path()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Market scanner types.
+
+use crate::contract::{Contract, OptionRight, SecurityType};
+use crate::market_data::Quote;
+use crate::message::OutgoingMessageId;
+use crate::server_versions;
+use crate::wire::{make_field, make_optional_field, make_tag_values_field, FieldIterator, TagValue};
+
+/// Parameters of a market scanner subscription.
+///
+/// Valid instruments, locations, scan codes and filter tags are listed in
+/// the XML returned by `Client::scanner_parameters`.
+#[derive(Debug, Clone, Default)]
+pub struct ScannerSubscription {
+    /// Number of rows to return (-1 for the TWS default of 50)
+    pub number_of_rows: i32,
+    /// Instrument type (e.g., "STK")
+    pub instrument: String,
+    /// Location code (e.g., "STK.US.MAJOR")
+    pub location_code: String,
+    /// Scan code (e.g., "TOP_PERC_GAIN")
+    pub scan_code: String,
+    pub above_price: Option<f64>,
+    pub below_price: Option<f64>,
+    pub above_volume: Option<i32>,
+    pub market_cap_above: Option<f64>,
+    pub market_cap_below: Option<f64>,
+    pub moody_rating_above: String,
+    pub moody_rating_below: String,
+    pub sp_rating_above: String,
+    pub sp_rating_below: String,
+    pub maturity_date_above: String,
+    pub maturity_date_below: String,
+    pub coupon_rate_above: Option<f64>,
+    pub coupon_rate_below: Option<f64>,
+    pub exclude_convertible: bool,
+    pub average_option_volume_above: Option<i32>,
+    pub scanner_setting_pairs: String,
+    /// "ALL", "CORP" or "ADR"
+    pub stock_type_filter: String,
+    /// Filters from the scanner parameters XML (e.g. "changePercAbove")
+    pub filters: Vec<TagValue>,
+    /// Scanner subscription options
+    pub options: Vec<TagValue>,
+}
+
+impl ScannerSubscription {
+    /// Create a scanner subscription.
+    pub fn new(instrument: &str, location_code: &str, scan_code: &str) -> Self {
+        Self {
+            number_of_rows: -1,
+            instrument: instrument.to_string(),
+            location_code: location_code.to_string(),
+            scan_code: scan_code.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the number of rows.
+    pub fn rows(mut self, rows: i32) -> Self {
+        self.number_of_rows = rows;
+        self
+    }
+
+    /// Only include results priced above `price`.
+    pub fn above_price(mut self, price: f64) -> Self {
+        self.above_price = Some(price);
+        self
+    }
+
+    /// Only include results priced below `price`.
+    pub fn below_price(mut self, price: f64) -> Self {
+        self.below_price = Some(price);
+        self
+    }
+
+    /// Only include results trading more than `volume`.
+    pub fn above_volume(mut self, volume: i32) -> Self {
+        self.above_volume = Some(volume);
+        self
+    }
+
+    /// Set the stock type filter ("ALL", "CORP" or "ADR").
+    pub fn stock_type_filter(mut self, filter: &str) -> Self {
+        self.stock_type_filter = filter.to_string();
+        self
+    }
+
+    /// Add a filter tag, e.g. `.filter("changePercAbove", 5)`.
+    pub fn filter(mut self, tag: &str, value: impl std::fmt::Display) -> Self {
+        self.filters.push(TagValue::new(tag, value));
+        self
+    }
+
+    /// Add a subscription option.
+    pub fn option(mut self, tag: &str, value: impl std::fmt::Display) -> Self {
+        self.options.push(TagValue::new(tag, value));
+        self
+    }
+
+    /// Encode the subscription request as a message payload.
+    pub fn encode(&self, req_id: i32, server_version: u32) -> String {
+        let mut msg = String::new();
+
+        msg.push_str(&make_field(OutgoingMessageId::ReqScannerSubscription.as_u32()));
+        // The version field was dropped along with the generic options change
+        if server_version < server_versions::SCANNER_GENERIC_OPTS {
+            msg.push_str(&make_field(4));
+        }
+        msg.push_str(&make_field(req_id));
+        msg.push_str(&make_field(self.number_of_rows));
+        msg.push_str(&make_field(&self.instrument));
+        msg.push_str(&make_field(&self.location_code));
+        msg.push_str(&make_field(&self.scan_code));
+        msg.push_str(&make_optional_field(self.above_price));
+        msg.push_str(&make_optional_field(self.below_price));
+        msg.push_str(&make_optional_field(self.above_volume));
+        msg.push_str(&make_optional_field(self.market_cap_above));
+        msg.push_str(&make_optional_field(self.market_cap_below));
+        msg.push_str(&make_field(&self.moody_rating_above));
+        msg.push_str(&make_field(&self.moody_rating_below));
+        msg.push_str(&make_field(&self.sp_rating_above));
+        msg.push_str(&make_field(&self.sp_rating_below));
+        msg.push_str(&make_field(&self.maturity_date_above));
+        msg.push_str(&make_field(&self.maturity_date_below));
+        msg.push_str(&make_optional_field(self.coupon_rate_above));
+        msg.push_str(&make_optional_field(self.coupon_rate_below));
+        msg.push_str(&make_field(if self.exclude_convertible { 1 } else { 0 }));
+        msg.push_str(&make_optional_field(self.average_option_volume_above));
+        msg.push_str(&make_field(&self.scanner_setting_pairs));
+        msg.push_str(&make_field(&self.stock_type_filter));
+
+        if server_version >= server_versions::SCANNER_GENERIC_OPTS {
+            msg.push_str(&make_tag_values_field(&self.filters));
+        }
+        msg.push_str(&make_tag_values_field(&self.options));
+
+        msg
+    }
+}
+
+/// One row of scanner results.
+#[derive(Debug, Clone)]
+pub struct ScannerData {
+    /// Position in the scan, starting at 0
+    pub rank: i32,
+    pub contract: Contract,
+    pub market_name: String,
+    pub distance: String,
+    pub benchmark: String,
+    pub projection: String,
+    /// Combo legs description, for combo scans
+    pub legs: String,
+}
+
+impl ScannerData {
+    /// Parse a row from message fields.
+    pub fn parse(fields: &mut FieldIterator) -> Option<Self> {
+        let rank = fields.next_i32();
+        let mut contract = Contract {
+            con_id: fields.next_i32(),
+            symbol: fields.next_string()?.to_string(),
+            sec_type: SecurityType::from_wire(fields.next_string()?),
+            last_trade_date: fields.next_string()?.to_string(),
+            strike: fields.next_f64(),
+            right: OptionRight::from_wire(fields.next_string()?),
+            exchange: fields.next_string()?.to_string(),
+            currency: fields.next_string()?.to_string(),
+            local_symbol: fields.next_string()?.to_string(),
+            ..Default::default()
+        };
+        let market_name = fields.next_string()?.to_string();
+        contract.trading_class = fields.next_string()?.to_string();
+
+        Some(Self {
+            rank,
+            contract,
+            market_name,
+            distance: fields.next_string().unwrap_or("").to_string(),
+            benchmark: fields.next_string().unwrap_or("").to_string(),
+            projection: fields.next_string().unwrap_or("").to_string(),
+            legs: fields.next_string().unwrap_or("").to_string(),
+        })
+    }
+
+    /// Contract to request market data for; scanner rows may omit the exchange.
+    pub fn quote_contract(&self) -> Contract {
+        let mut contract = self.contract.clone();
+        if contract.exchange.is_empty() {
+            contract.exchange = "SMART".to_string();
+        }
+        contract
+    }
+}
+
+/// A scanner row with a snapshot quote for its contract.
+#[derive(Debug, Clone)]
+pub struct ScannerRow {
+    pub data: ScannerData,
+    /// Snapshot quote, or `None` if it couldn't be fetched
+    pub quote: Option<Quote>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_with_filters() {
+        let subscription = ScannerSubscription::new("STK", "STK.US.MAJOR", "TOP_PERC_GAIN")
+            .rows(10)
+            .above_price(5.0)
+            .filter("changePercAbove", 5)
+            .filter("avgVolumeAbove", 100000);
+        let encoded = subscription.encode(9, 176);
+
+        assert!(encoded.starts_with("22\x009\x0010\0STK\0STK.US.MAJOR\0TOP_PERC_GAIN\x005\0\0"));
+        assert!(encoded.ends_with("\0changePercAbove=5;avgVolumeAbove=100000;\0\0"));
+
+        // Older servers take a version field and no filter list
+        let encoded = subscription.encode(9, 140);
+        assert!(encoded.starts_with("22\x004\x009\0"));
+        assert!(!encoded.contains("changePercAbove"));
+    }
+
+    #[test]
+    fn test_parse_row() {
+        let buf = b"0\x00265598\0AAPL\0STK\0\x000\0\0SMART\0USD\0AAPL\0NMS\0NMS\0\0\0\0\0";
+        let mut fields = FieldIterator::new(buf);
+        let row = ScannerData::parse(&mut fields).unwrap();
+
+        assert_eq!(row.rank, 0);
+        assert_eq!(row.contract.con_id, 265598);
+        assert_eq!(row.contract.symbol, "AAPL");
+        assert_eq!(row.market_name, "NMS");
+        assert_eq!(row.contract.trading_class, "NMS");
+    }
+}
@@ -0,0 +1,66 @@
+//! Minimum server versions for version-dependent message fields.
+//!
+//! Mirrors the constants in the reference client's `server_versions.py`.
+
+/// Contract ID and trading class in contract fields
+pub const TRADING_CLASS: u32 = 68;
+/// Chart options on historical data and real-time bar requests
+pub const LINKING: u32 = 70;
+/// Model code on executions and positions
+pub const MODELS_SUPPORT: u32 = 103;
+/// Market data size multiplier in contract details
+pub const MD_SIZE_MULTIPLIER: u32 = 110;
+/// Aggregation group in contract details
+pub const AGG_GROUP: u32 = 121;
+/// Underlying symbol and security type in contract details
+pub const UNDERLYING_INFO: u32 = 122;
+/// Keep-up-to-date historical bars (and the request version dropped)
+pub const SYNT_REALTIME_BARS: u32 = 124;
+/// Market rule IDs in contract details
+pub const MARKET_RULES: u32 = 126;
+/// Unrealized P&L in PnL/PnLSingle messages
+pub const UNREALIZED_PNL: u32 = 129;
+/// Market cap price on order status (and the order status version dropped)
+pub const MARKET_CAP_PRICE: u32 = 131;
+/// Real expiration date in contract details
+pub const REAL_EXPIRATION_DATE: u32 = 134;
+/// Realized P&L in PnL/PnLSingle messages
+pub const REALIZED_PNL: u32 = 135;
+/// Last liquidity on executions (and the execution message version dropped)
+pub const LAST_LIQUIDITY: u32 = 136;
+/// Generic filter options in scanner subscriptions
+pub const SCANNER_GENERIC_OPTS: u32 = 143;
+/// Listing exchange and service data type in market depth exchanges
+pub const SERVICE_DATA_TYPE: u32 = 143;
+/// SMART aggregated market depth
+pub const SMART_DEPTH: u32 = 146;
+/// Price management algo on orders; the PLACE_ORDER layout `Order::encode`
+/// writes
+pub const PRICE_MGMT_ALGO: u32 = 151;
+/// Stock type in contract details
+pub const STOCK_TYPE: u32 = 152;
+/// Order duration (for IOC-style time in force)
+pub const DURATION: u32 = 158;
+/// Post-to-ATS order attribute
+pub const POST_TO_ATS: u32 = 160;
+/// Auto-cancel parent order attribute
+pub const AUTO_CANCEL_PARENT: u32 = 162;
+/// UTC date/times in requests as `yyyymmdd-hh:mm:ss`
+pub const UTC_DATE_TIME: u32 = 163;
+/// Fractional sizes, and the size minimum tick in contract details
+pub const FRACTIONAL_SIZE_SUPPORT: u32 = 163;
+/// Minimum size and size increments in contract details (and the contract
+/// data message version dropped)
+pub const SIZE_RULES: u32 = 164;
+/// Advanced error override on orders
+pub const ADVANCED_ORDER_REJECT: u32 = 166;
+/// AGGTRADES historical data for crypto
+pub const CRYPTO_AGGREGATED_TRADES: u32 = 168;
+/// Manual order time on orders
+pub const MANUAL_ORDER_TIME: u32 = 169;
+/// Pegged-to-best/midpoint offsets and IBKRATS minimum trade quantity
+pub const PEGBEST_PEGMID_OFFSETS: u32 = 170;
+/// Bond issuer ID in contract details requests
+pub const BOND_ISSUERID: u32 = 176;
+/// Manual order time on exercise options requests
+pub const MANUAL_ORDER_TIME_EXERCISE_OPTIONS: u32 = 177;
@@ -0,0 +1,365 @@
+//! Streaming subscriptions.
+//!
+//! Requests that keep producing data (e.g. account summary updates) return a
+//! [`Subscription`]. The reader task forwards decoded messages into it, and
+//! dropping it cancels the request with TWS.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::AbortHandle;
+
+use rust_decimal::Decimal;
+
+use crate::account::{AccountSummaryValue, Position};
+use crate::contract_details::ContractDetails;
+use crate::error::{Error, Result};
+use crate::execution::{CommissionReport, ExecutionFilter, Fill, FillEvent};
+use crate::historical::BarData;
+use crate::market_data::{MarketDataType, Tick, TickReqParams};
+use crate::market_depth::DepthUpdate;
+use crate::option_chain::OptionParams;
+use crate::order::{OpenOrder, OrderStatus};
+use crate::pnl::PnlSingle;
+use crate::scanner::ScannerData;
+
+/// Internal message for streaming subscriptions.
+pub(crate) enum StreamMessage {
+    AccountSummary(AccountSummaryValue),
+    AccountSummaryEnd,
+    Position(Box<Position>),
+    PositionEnd,
+    OpenOrder(Box<OpenOrder>),
+    OpenOrderEnd,
+    ContractDetails(Box<ContractDetails>),
+    ContractDetailsEnd,
+    OptionParams(Box<OptionParams>),
+    OptionParamsEnd,
+    Execution(Box<Fill>),
+    Commission(CommissionReport),
+    ExecutionEnd,
+    PnlSingle(PnlSingle),
+    ScannerData(Vec<ScannerData>),
+    TickPrice {
+        tick_type: i32,
+        price: f64,
+        attr_mask: i32,
+    },
+    TickSize { tick_type: i32, size: Decimal },
+    TickString { tick_type: i32, value: String },
+    TickGeneric { tick_type: i32, value: f64 },
+    MarketDataType(MarketDataType),
+    TickSnapshotEnd,
+    TickReqParams(TickReqParams),
+    MarketDepth(DepthUpdate),
+    Bar(BarData),
+    Error(Error),
+}
+
+/// A stream of updates for a long-lived request.
+///
+/// Yields items until the request is cancelled, fails, or the connection
+/// closes. If the stream ended because of an error, it can be retrieved with
+/// [`Subscription::take_error`]. Dropping the subscription cancels the
+/// request with TWS.
+pub struct Subscription<T> {
+    req_id: i32,
+    receiver: mpsc::UnboundedReceiver<StreamMessage>,
+    decode: fn(StreamMessage) -> Option<T>,
+    error: Option<Error>,
+    tick_req_params: Option<TickReqParams>,
+    cancel: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl<T> Subscription<T> {
+    pub(crate) fn new(
+        req_id: i32,
+        receiver: mpsc::UnboundedReceiver<StreamMessage>,
+        decode: fn(StreamMessage) -> Option<T>,
+        cancel: Box<dyn FnOnce() + Send + Sync>,
+    ) -> Self {
+        Self {
+            req_id,
+            receiver,
+            decode,
+            error: None,
+            tick_req_params: None,
+            cancel: Some(cancel),
+        }
+    }
+
+    /// Request ID used for this subscription.
+    pub fn req_id(&self) -> i32 {
+        self.req_id
+    }
+
+    /// Wait for the next item.
+    ///
+    /// Returns `None` once the subscription has ended.
+    pub async fn next(&mut self) -> Option<T> {
+        std::future::poll_fn(|cx| self.poll_item(cx)).await
+    }
+
+    /// Mark the request as finished by TWS, so dropping sends no cancel.
+    pub(crate) fn complete(&mut self) {
+        self.cancel = None;
+    }
+
+    /// Min tick, BBO exchange and snapshot permissions of a market data
+    /// request.
+    ///
+    /// TWS sends these ahead of the first tick, so they are available once
+    /// [`Subscription::next`] has returned an item.
+    pub fn tick_req_params(&self) -> Option<&TickReqParams> {
+        self.tick_req_params.as_ref()
+    }
+
+    /// Take the error that ended the subscription, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    fn poll_item(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(StreamMessage::Error(error))) => {
+                    self.error = Some(error);
+                    self.receiver.close();
+                }
+                Poll::Ready(Some(StreamMessage::TickReqParams(params))) => {
+                    self.tick_req_params = Some(params);
+                }
+                Poll::Ready(Some(message)) => {
+                    // TWS ends snapshots itself; there's nothing to cancel.
+                    if matches!(message, StreamMessage::TickSnapshotEnd) {
+                        self.cancel = None;
+                    }
+                    if let Some(item) = (self.decode)(message) {
+                        return Poll::Ready(Some(item));
+                    }
+                }
+                Poll::Ready(None) => {
+                    // The route is only dropped without an error when the
+                    // connection goes away.
+                    if self.error.is_none() && self.cancel.is_some() {
+                        self.error = Some(Error::NotConnected);
+                    }
+                    self.cancel = None;
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_item(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel();
+        }
+    }
+}
+
+/// Handle to an order placed with [`Client::place_order`](crate::Client::place_order).
+///
+/// Streams the order's status updates; the stream ends once the order is
+/// filled, cancelled or inactive, or the connection closes.
+pub struct OrderTracker {
+    order_id: i32,
+    latest: watch::Receiver<Option<OrderStatus>>,
+    updates: mpsc::UnboundedReceiver<OrderStatus>,
+    rejection: oneshot::Receiver<Error>,
+}
+
+impl OrderTracker {
+    pub(crate) fn new(
+        order_id: i32,
+        latest: watch::Receiver<Option<OrderStatus>>,
+        updates: mpsc::UnboundedReceiver<OrderStatus>,
+        rejection: oneshot::Receiver<Error>,
+    ) -> Self {
+        Self {
+            order_id,
+            latest,
+            updates,
+            rejection,
+        }
+    }
+
+    /// Order ID the order was placed under.
+    pub fn order_id(&self) -> i32 {
+        self.order_id
+    }
+
+    /// Most recent status TWS reported, or `None` before the first one.
+    pub fn status(&self) -> Option<OrderStatus> {
+        self.latest.borrow().clone()
+    }
+
+    /// Wait for the next status update.
+    ///
+    /// Returns `None` once the order is done, TWS has rejected it or the
+    /// connection has closed.
+    pub async fn next(&mut self) -> Option<OrderStatus> {
+        self.updates.recv().await
+    }
+
+    /// Wait until the order is completely filled.
+    ///
+    /// Fails with [`Error::OrderNotFilled`] if the order is cancelled or
+    /// goes inactive first, with the TWS error if TWS rejects it, with
+    /// [`Error::NotConnected`] if the connection closes, and with
+    /// [`Error::Timeout`] after `timeout`.
+    pub async fn await_filled(&mut self, timeout: Duration) -> Result<OrderStatus> {
+        let order_id = self.order_id;
+        let wait = async {
+            if let Some(status) = self.status().filter(|status| status.is_done()) {
+                return Ok(status);
+            }
+            while let Some(status) = self.next().await {
+                if status.is_done() {
+                    return Ok(status);
+                }
+            }
+            // The rejection is sent before the updates channel closes
+            Err(self.rejection.try_recv().unwrap_or(Error::NotConnected))
+        };
+        let status = tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout)??;
+        if status.status != "Filled" {
+            return Err(Error::OrderNotFilled {
+                order_id,
+                status: status.status,
+            });
+        }
+        Ok(status)
+    }
+}
+
+impl Stream for OrderTracker {
+    type Item = OrderStatus;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<OrderStatus>> {
+        self.get_mut().updates.poll_recv(cx)
+    }
+}
+
+/// Live fills on the connection, narrowed by an [`ExecutionFilter`].
+///
+/// Only fills that happen after the stream was created are delivered.
+pub struct Fills {
+    receiver: broadcast::Receiver<FillEvent>,
+    filter: ExecutionFilter,
+}
+
+impl Fills {
+    pub(crate) fn new(receiver: broadcast::Receiver<FillEvent>, filter: ExecutionFilter) -> Self {
+        Self { receiver, filter }
+    }
+
+    /// Wait for the next matching fill event.
+    ///
+    /// Returns `None` once the connection has closed.
+    pub async fn next(&mut self) -> Option<FillEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    let fill = event.fill();
+                    if self.filter.matches(&fill.contract, &fill.execution) {
+                        return Some(event);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "fill stream fell behind, events dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// A market data request shared by every [`SharedTicks`] for the same
+/// contract and tick groups.
+///
+/// A task forwards the request's ticks into a broadcast channel; dropping
+/// the last handle stops it, which cancels the request.
+pub(crate) struct SharedFeed {
+    pub(crate) req_id: i32,
+    receiver: broadcast::Receiver<Tick>,
+    forward: AbortHandle,
+}
+
+impl SharedFeed {
+    pub(crate) fn new(
+        req_id: i32,
+        receiver: broadcast::Receiver<Tick>,
+        forward: AbortHandle,
+    ) -> Self {
+        Self {
+            req_id,
+            receiver,
+            forward,
+        }
+    }
+
+    /// A new consumer, receiving ticks from now on.
+    pub(crate) fn subscribe(self: &Arc<Self>) -> SharedTicks {
+        SharedTicks {
+            receiver: self.receiver.resubscribe(),
+            feed: self.clone(),
+        }
+    }
+}
+
+impl Drop for SharedFeed {
+    fn drop(&mut self) {
+        self.forward.abort();
+    }
+}
+
+/// Ticks from a market data request shared with other consumers, from
+/// [`Client::shared_market_data`](crate::Client::shared_market_data).
+///
+/// The request is cancelled once every handle for it has been dropped.
+pub struct SharedTicks {
+    receiver: broadcast::Receiver<Tick>,
+    feed: Arc<SharedFeed>,
+}
+
+impl SharedTicks {
+    /// Request ID of the underlying market data request.
+    pub fn req_id(&self) -> i32 {
+        self.feed.req_id
+    }
+
+    /// Wait for the next tick.
+    ///
+    /// Returns `None` once the request has ended or the connection has
+    /// closed. A consumer that falls too far behind skips the ticks it
+    /// missed.
+    pub async fn next(&mut self) -> Option<Tick> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(tick) => return Some(tick),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "shared tick stream fell behind, ticks dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
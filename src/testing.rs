@@ -0,0 +1,38 @@
+//! Test helpers: an in-memory TWS peer for driving a `Client`.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use crate::client::Client;
+
+/// Read one length-prefixed frame sent by the client.
+pub(crate) async fn read_frame(server: &mut DuplexStream) -> Vec<u8> {
+    let mut len = [0u8; 4];
+    server.read_exact(&mut len).await.unwrap();
+    let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+    server.read_exact(&mut buf).await.unwrap();
+    buf
+}
+
+/// Send one length-prefixed frame to the client.
+pub(crate) async fn write_frame(server: &mut DuplexStream, payload: &str) {
+    server.write_all(&(payload.len() as u32).to_be_bytes()).await.unwrap();
+    server.write_all(payload.as_bytes()).await.unwrap();
+}
+
+/// Answer the connection handshake as a server at `server_version`.
+pub(crate) async fn accept(server: &mut DuplexStream, server_version: u32) {
+    let mut prefix = [0u8; 4];
+    server.read_exact(&mut prefix).await.unwrap();
+    assert_eq!(&prefix, b"API\0");
+    read_frame(server).await;
+    write_frame(server, &format!("{server_version}\x0020240105 09:30:00 EST\0")).await;
+    // START_API
+    read_frame(server).await;
+}
+
+/// Connect a client to an in-memory server that has completed the handshake.
+pub(crate) async fn connected_client(client_id: i32) -> (Client<DuplexStream>, DuplexStream) {
+    let (client_side, mut server) = tokio::io::duplex(64 * 1024);
+    let (client, _) = tokio::join!(Client::connect_with(client_side, client_id), accept(&mut server, 176));
+    (client.unwrap(), server)
+}
@@ -0,0 +1,163 @@
+//! Trading and liquid hours from contract details.
+//!
+//! TWS sends a contract's hours as `;`-separated days, each either
+//! `YYYYMMDD:HHMM-YYYYMMDD:HHMM` (sessions can run past midnight, and a day
+//! can list several, separated by `,`) or `YYYYMMDD:CLOSED`. Older servers
+//! leave the date off the times: `20090507:0700-1830,1830-2330`. Times are
+//! wall-clock times in the contract's `time_zone_id`.
+
+#[cfg(feature = "chrono")]
+use chrono_tz::Tz;
+
+use crate::datetime::DateTime;
+use crate::error::{Error, Result};
+
+/// One trading session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// Trading day the session belongs to (YYYYMMDD)
+    pub date: String,
+    /// Session open, in the contract's timezone
+    pub open: DateTime,
+    /// Session close, in the contract's timezone
+    pub close: DateTime,
+}
+
+/// A contract's sessions, from `ContractDetails::trading_hours` or
+/// `liquid_hours`. Closed days have no sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TradingHours {
+    /// Timezone the session times are in (e.g. "US/Eastern")
+    pub time_zone: String,
+    pub sessions: Vec<Session>,
+}
+
+impl TradingHours {
+    /// Parse a trading or liquid hours string in `time_zone`.
+    pub fn parse(hours: &str, time_zone: &str) -> Result<Self> {
+        let invalid = || Error::Protocol(format!("Invalid trading hours: {hours:?}"));
+        let timezone = Some(time_zone.to_string()).filter(|zone| !zone.is_empty());
+
+        let mut sessions = Vec::new();
+        for day in hours.split(';').filter(|day| !day.is_empty()) {
+            let (date, spans) = day.split_once(':').ok_or_else(invalid)?;
+            if spans == "CLOSED" {
+                continue;
+            }
+            for span in spans.split(',') {
+                let (open, close) = span.split_once('-').ok_or_else(invalid)?;
+                let time = |point: &str| {
+                    let (day, hhmm) = point.split_once(':').unwrap_or((date, point));
+                    let time = format!("{day} {}:{}", hhmm.get(..2)?, hhmm.get(2..)?);
+                    let mut time = DateTime::parse(&time).ok()?;
+                    time.timezone = timezone.clone();
+                    Some(time)
+                };
+                sessions.push(Session {
+                    date: date.to_string(),
+                    open: time(open).ok_or_else(invalid)?,
+                    close: time(close).ok_or_else(invalid)?,
+                });
+            }
+        }
+        Ok(Self {
+            time_zone: time_zone.to_string(),
+            sessions,
+        })
+    }
+
+    /// Whether a session is open at `time`.
+    ///
+    /// Times without a timezone, or in the sessions' own, are compared as
+    /// wall-clock times. Others are converted with the `chrono` feature;
+    /// without it, or for unknown timezone names, this returns `None`.
+    pub fn is_open_at(&self, time: &DateTime) -> Option<bool> {
+        let time = self.local(time)?;
+        Some(self.find(&time).is_some())
+    }
+
+    /// The session open at `time`, if any. See [`TradingHours::is_open_at`].
+    pub fn session_at(&self, time: &DateTime) -> Option<&Session> {
+        self.find(&self.local(time)?)
+    }
+
+    fn find(&self, local: &DateTime) -> Option<&Session> {
+        let at = wall_clock(local);
+        self.sessions
+            .iter()
+            .find(|session| wall_clock(&session.open) <= at && at < wall_clock(&session.close))
+    }
+
+    /// `time` as a wall-clock time in the sessions' timezone.
+    fn local(&self, time: &DateTime) -> Option<DateTime> {
+        match time.timezone.as_deref() {
+            Some(zone) if zone != self.time_zone => self.to_local(time),
+            _ => Some(time.clone()),
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    fn to_local(&self, time: &DateTime) -> Option<DateTime> {
+        use chrono::{Datelike, Timelike};
+
+        let zone: Tz = self.time_zone.parse().ok()?;
+        let local = time.to_chrono(zone)?.with_timezone(&zone);
+        Some(DateTime {
+            year: local.year(),
+            month: local.month(),
+            day: local.day(),
+            hour: local.hour(),
+            minute: local.minute(),
+            second: local.second(),
+            timezone: Some(self.time_zone.clone()),
+        })
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    fn to_local(&self, _time: &DateTime) -> Option<DateTime> {
+        None
+    }
+}
+
+fn wall_clock(time: &DateTime) -> (i32, u32, u32, u32, u32, u32) {
+    (time.year, time.month, time.day, time.hour, time.minute, time.second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trading_hours() {
+        let hours = "20240105:0930-20240105:1600;20240106:CLOSED;20240107:1700-20240108:1600";
+        let hours = TradingHours::parse(hours, "US/Eastern").unwrap();
+        assert_eq!(hours.sessions.len(), 2);
+        assert_eq!(hours.sessions[0].open.to_string(), "20240105 09:30:00 US/Eastern");
+        assert_eq!(hours.sessions[1].date, "20240107");
+        assert_eq!(hours.sessions[1].close.to_string(), "20240108 16:00:00 US/Eastern");
+
+        let at = |s: &str| DateTime::parse(s).unwrap();
+        assert_eq!(hours.is_open_at(&at("20240105 10:00:00 US/Eastern")), Some(true));
+        assert_eq!(hours.is_open_at(&at("20240105 16:00:00")), Some(false));
+        assert_eq!(hours.is_open_at(&at("20240108 02:00:00")), Some(true));
+        let session = hours.session_at(&at("20240108 02:00:00")).unwrap();
+        assert_eq!(session.date, "20240107");
+
+        let old = TradingHours::parse("20090507:0700-1830,1830-2330;20090508:CLOSED", "EST")
+            .unwrap();
+        assert_eq!(old.sessions.len(), 2);
+        assert_eq!(old.sessions[1].close.to_string(), "20090507 23:30:00 EST");
+        assert!(TradingHours::parse("20090507:0700", "EST").is_err());
+    }
+
+    #[test]
+    fn test_is_open_at_other_timezone() {
+        let hours = TradingHours::parse("20240105:0930-20240105:1600", "US/Eastern").unwrap();
+        let open = hours.is_open_at(&DateTime::utc(2024, 1, 5, 14, 45, 0));
+        if cfg!(feature = "chrono") {
+            assert_eq!(open, Some(true));
+        } else {
+            assert_eq!(open, None);
+        }
+    }
+}
@@ -6,11 +6,49 @@
 
 use std::io::{self, Write};
 
+use rust_decimal::Decimal;
+
+/// Values at or above this are TWS's "unset" double (`Double.MAX_VALUE`).
+const UNSET_DOUBLE_THRESHOLD: f64 = f64::MAX / 2.0;
+
 /// Create a null-terminated field from a value.
 pub fn make_field<T: std::fmt::Display>(value: T) -> String {
     format!("{value}\0")
 }
 
+/// Create a field from an optional value; `None` is sent as an empty field.
+pub fn make_optional_field<T: std::fmt::Display>(value: Option<T>) -> String {
+    match value {
+        Some(value) => make_field(value),
+        None => make_field(""),
+    }
+}
+
+/// A tag/value pair, as used for scanner filters and algo parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagValue {
+    pub tag: String,
+    pub value: String,
+}
+
+impl TagValue {
+    pub fn new(tag: &str, value: impl std::fmt::Display) -> Self {
+        Self {
+            tag: tag.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Encode a tag/value list as a single "tag=value;" field.
+pub fn make_tag_values_field(values: &[TagValue]) -> String {
+    let joined: String = values
+        .iter()
+        .map(|tv| format!("{}={};", tv.tag, tv.value))
+        .collect();
+    make_field(joined)
+}
+
 /// Build a complete message payload from fields.
 ///
 /// Each field is converted to a null-terminated string.
@@ -45,12 +83,17 @@ pub fn extract_message(buf: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
 }
 
 /// Parse a message buffer into null-separated fields.
+///
+/// Empty fields are preserved: TWS sends unset strings as empty fields, and
+/// dropping them would shift every field after them.
 pub fn parse_fields(buf: &[u8]) -> Vec<&str> {
-    std::str::from_utf8(buf)
-        .unwrap_or("")
-        .split('\0')
-        .filter(|s| !s.is_empty())
-        .collect()
+    let payload = std::str::from_utf8(buf).unwrap_or("");
+    // Every field is null-terminated, so the final split piece is not a field.
+    let payload = payload.strip_suffix('\0').unwrap_or(payload);
+    if payload.is_empty() {
+        return Vec::new();
+    }
+    payload.split('\0').collect()
 }
 
 /// Iterator for parsing fields from a message.
@@ -63,12 +106,10 @@ pub struct FieldIterator<'a> {
 
 impl<'a> FieldIterator<'a> {
     pub fn new(buf: &'a [u8]) -> Self {
-        let fields = std::str::from_utf8(buf)
-            .unwrap_or("")
-            .split('\0')
-            .filter(|s| !s.is_empty())
-            .collect();
-        Self { fields, position: 0 }
+        Self {
+            fields: parse_fields(buf),
+            position: 0,
+        }
     }
 
     /// Get the next field as a string.
@@ -97,6 +138,27 @@ impl<'a> FieldIterator<'a> {
         self.next_parsed().unwrap_or(0.0)
     }
 
+    /// Get the next field as f64, treating empty fields and the TWS unset
+    /// sentinel (`Double.MAX_VALUE`) as `None`.
+    pub fn next_optional_f64(&mut self) -> Option<f64> {
+        self.next_parsed::<f64>()
+            .filter(|value| *value < UNSET_DOUBLE_THRESHOLD)
+    }
+
+    /// Get the next field as a Decimal, defaulting to zero for empty/invalid.
+    ///
+    /// Used for sizes and quantities, which can be fractional (crypto,
+    /// fractional shares) and would lose precision as f64.
+    pub fn next_decimal(&mut self) -> Decimal {
+        self.next_parsed().unwrap_or(Decimal::ZERO)
+    }
+
+    /// Get the next field as a Decimal, treating empty fields and the TWS
+    /// unset sentinel (too large for a Decimal) as `None`.
+    pub fn next_optional_decimal(&mut self) -> Option<Decimal> {
+        self.next_parsed()
+    }
+
     /// Get the next field as bool (0 = false, anything else = true).
     pub fn next_bool(&mut self) -> bool {
         self.next_i32() != 0
@@ -118,6 +180,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_make_field() {
         assert_eq!(make_field("hello"), "hello\0");
         assert_eq!(make_field(42), "42\0");
@@ -148,6 +211,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::octal_escapes)]
     fn test_field_iterator() {
         let buf = b"17\0123\045.5\0hello\0";
         let mut iter = FieldIterator::new(buf);
@@ -158,4 +222,37 @@ mod tests {
         assert_eq!(iter.next_string(), Some("hello"));
         assert_eq!(iter.next_string(), None);
     }
+
+    #[test]
+    fn test_next_optional_f64() {
+        let buf = b"1.5\0\x001.7976931348623157E308\0";
+        let mut iter = FieldIterator::new(buf);
+
+        assert_eq!(iter.next_optional_f64(), Some(1.5));
+        assert_eq!(iter.next_optional_f64(), None);
+        assert_eq!(iter.next_optional_f64(), None);
+    }
+
+    #[test]
+    fn test_next_decimal_keeps_fractional_sizes() {
+        let buf = b"0.00012345\0\x00100\0";
+        let mut iter = FieldIterator::new(buf);
+
+        assert_eq!(iter.next_decimal().to_string(), "0.00012345");
+        assert_eq!(iter.next_decimal(), Decimal::ZERO);
+        assert_eq!(iter.next_decimal(), Decimal::from(100));
+    }
+
+    #[test]
+    fn test_field_iterator_keeps_empty_fields() {
+        let buf = b"61\x003\0DU123\0\0AAPL\0";
+        let mut iter = FieldIterator::new(buf);
+
+        assert_eq!(iter.next_i32(), 61);
+        assert_eq!(iter.next_i32(), 3);
+        assert_eq!(iter.next_string(), Some("DU123"));
+        assert_eq!(iter.next_string(), Some(""));
+        assert_eq!(iter.next_string(), Some("AAPL"));
+        assert_eq!(iter.next_string(), None);
+    }
 }